@@ -0,0 +1,11 @@
+#![no_main]
+
+use istok_core::codec::{capsule, h3_datagram};
+use libfuzzer_sys::fuzz_target;
+
+// Invariant: capsule and HTTP Datagram decoding must never panic on arbitrary input.
+// Any byte sequence must produce Ok or Err, never a panic.
+fuzz_target!(|data: &[u8]| {
+    let _ = capsule::next_datagram(data);
+    let _ = h3_datagram::decode(data);
+});
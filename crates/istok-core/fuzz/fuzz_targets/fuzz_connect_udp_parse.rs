@@ -0,0 +1,14 @@
+#![no_main]
+
+use istok_core::codec::connect_udp;
+use libfuzzer_sys::fuzz_target;
+
+// Invariant: CONNECT-UDP path parsing, host decoding and datagram payload
+// splitting must never panic on arbitrary input.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(target) = connect_udp::parse_udp_path(data) {
+        let mut host = [0u8; 256];
+        let _ = connect_udp::percent_decode(target.host, &mut host);
+    }
+    let _ = connect_udp::decode_payload(data);
+});
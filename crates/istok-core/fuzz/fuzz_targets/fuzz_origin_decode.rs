@@ -0,0 +1,15 @@
+#![no_main]
+
+use istok_core::codec::origin;
+use libfuzzer_sys::fuzz_target;
+
+// Invariant: ORIGIN payload decoding never panics, and a payload that
+// decodes cleanly re-encodes to the same bytes.
+fuzz_target!(|data: &[u8]| {
+    let Ok(origins) = origin::entries(data).collect::<Result<Vec<&[u8]>, _>>() else {
+        return;
+    };
+    let mut out = vec![0u8; origin::encoded_len(&origins)];
+    let n = origin::encode_origin(&origins, &mut out).expect("decoded origins re-encode");
+    assert_eq!(&out[..n], data);
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use istok_core::codec::priority;
+use libfuzzer_sys::fuzz_target;
+
+// Invariant: Priority Field Value parsing and PRIORITY_UPDATE payload
+// decoding must never panic on arbitrary input.
+fuzz_target!(|data: &[u8]| {
+    let _ = priority::parse_priority_field(data);
+    if let Ok(update) = priority::decode_priority_update(data) {
+        let _ = priority::parse_priority_field(update.field_value);
+    }
+});
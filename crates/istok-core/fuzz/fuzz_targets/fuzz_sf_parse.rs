@@ -0,0 +1,21 @@
+#![no_main]
+
+use istok_core::sf;
+use libfuzzer_sys::fuzz_target;
+
+// Invariant: Structured Field parsing must never panic, and anything that
+// parses must serialize and reparse to the same value.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(item) = sf::parse_item(data) {
+        let text = sf::serialize_item(&item).expect("parsed item serializes");
+        assert_eq!(sf::parse_item(text.as_bytes()), Ok(item));
+    }
+    if let Ok(list) = sf::parse_list(data) {
+        let text = sf::serialize_list(&list).expect("parsed list serializes");
+        assert_eq!(sf::parse_list(text.as_bytes()), Ok(list));
+    }
+    if let Ok(dict) = sf::parse_dictionary(data) {
+        let text = sf::serialize_dictionary(&dict).expect("parsed dictionary serializes");
+        assert_eq!(sf::parse_dictionary(text.as_bytes()), Ok(dict));
+    }
+});
@@ -0,0 +1,243 @@
+//! Shared, immutable byte buffers.
+//!
+//! A `Bytes` is a window into a reference-counted allocation. Cloning,
+//! slicing and splitting only move offsets, so a payload can be queued,
+//! cut at flow-control boundaries and handed to the transport without
+//! copying it.
+//!
+//! Invariants:
+//! - `start <= end <= buf.len()`; an empty `Bytes` may have no allocation.
+//! - The bytes behind a `Bytes` never change once it is created.
+//! - Offsets past the end are clamped rather than panicking.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::{Bound, Deref, RangeBounds};
+
+/// Cheaply cloneable view into shared, immutable bytes.
+#[derive(Clone, Default)]
+pub struct Bytes {
+    buf: Option<Arc<Vec<u8>>>,
+    start: usize,
+    end: usize,
+}
+
+impl Bytes {
+    /// An empty buffer; does not allocate.
+    pub const fn new() -> Self {
+        Self {
+            buf: None,
+            start: 0,
+            end: 0,
+        }
+    }
+
+    /// A buffer holding a copy of `data`.
+    pub fn copy_from_slice(data: &[u8]) -> Self {
+        Self::from(data.to_vec())
+    }
+
+    /// Number of bytes in view.
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Whether the view is empty.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// The bytes in view.
+    pub fn as_slice(&self) -> &[u8] {
+        match &self.buf {
+            Some(buf) => buf.get(self.start..self.end).unwrap_or_default(),
+            None => &[],
+        }
+    }
+
+    /// The bytes in `range`, sharing this buffer's allocation.
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> Self {
+        let len = self.len();
+        let from = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let to = match range.end_bound() {
+            Bound::Included(&n) => n.saturating_add(1),
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => len,
+        };
+        let to = to.min(len);
+        let from = from.min(to);
+        Self {
+            buf: self.buf.clone(),
+            start: self.start + from,
+            end: self.start + to,
+        }
+    }
+
+    /// Split off and return the first `at` bytes; `self` keeps the rest.
+    pub fn split_to(&mut self, at: usize) -> Self {
+        let front = self.slice(..at);
+        self.start = front.end;
+        front
+    }
+
+    /// Split off and return everything from `at` on; `self` keeps the front.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        let back = self.slice(at..);
+        self.end = back.start;
+        back
+    }
+
+    /// The bytes as a `Vec`, without copying when this is the only
+    /// reference to the whole allocation.
+    pub fn into_vec(self) -> Vec<u8> {
+        self.try_into_vec()
+            .unwrap_or_else(|bytes| bytes.as_slice().to_vec())
+    }
+
+    /// The underlying allocation, if this is the only reference to all of
+    /// it, e.g. to hand a written buffer back to a pool.
+    pub fn try_into_vec(self) -> Result<Vec<u8>, Self> {
+        let Some(buf) = self.buf else {
+            return Ok(Vec::new());
+        };
+        if self.start != 0 || self.end != buf.len() {
+            return Err(Self {
+                buf: Some(buf),
+                ..self
+            });
+        }
+        Arc::try_unwrap(buf).map_err(|buf| Self {
+            buf: Some(buf),
+            ..self
+        })
+    }
+}
+
+impl From<Vec<u8>> for Bytes {
+    /// Takes over `vec` without copying it.
+    fn from(vec: Vec<u8>) -> Self {
+        let end = vec.len();
+        Self {
+            buf: Some(Arc::new(vec)),
+            start: 0,
+            end,
+        }
+    }
+}
+
+impl From<&[u8]> for Bytes {
+    fn from(data: &[u8]) -> Self {
+        Self::copy_from_slice(data)
+    }
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl AsRef<[u8]> for Bytes {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl PartialEq for Bytes {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Eq for Bytes {}
+
+impl PartialEq<[u8]> for Bytes {
+    fn eq(&self, other: &[u8]) -> bool {
+        self.as_slice() == other
+    }
+}
+
+impl PartialEq<Vec<u8>> for Bytes {
+    fn eq(&self, other: &Vec<u8>) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl fmt::Debug for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_slice(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_and_slices_share_the_allocation() {
+        let bytes = Bytes::from(alloc::vec![1, 2, 3, 4, 5]);
+        let clone = bytes.clone();
+        let middle = bytes.slice(1..4);
+
+        assert_eq!(clone.as_ptr(), bytes.as_ptr());
+        assert_eq!(middle.as_ptr(), bytes[1..].as_ptr());
+        assert_eq!(middle, [2, 3, 4][..]);
+        assert_eq!(middle.slice(1..), [3, 4][..]);
+    }
+
+    #[test]
+    fn split_to_and_split_off() {
+        let mut bytes = Bytes::from(alloc::vec![1, 2, 3, 4, 5]);
+        let front = bytes.split_to(2);
+        assert_eq!(front, [1, 2][..]);
+        assert_eq!(bytes, [3, 4, 5][..]);
+
+        let back = bytes.split_off(1);
+        assert_eq!(bytes, [3][..]);
+        assert_eq!(back, [4, 5][..]);
+        assert_eq!(back.as_ptr(), front[..].as_ptr().wrapping_add(3));
+    }
+
+    #[test]
+    fn offsets_past_the_end_are_clamped() {
+        let mut bytes = Bytes::from(alloc::vec![1, 2, 3]);
+        assert!(bytes.slice(5..).is_empty());
+        assert_eq!(bytes.slice(2..9), [3][..]);
+
+        let all = bytes.split_to(10);
+        assert_eq!(all, [1, 2, 3][..]);
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn into_vec_reuses_a_unique_allocation() {
+        let vec = alloc::vec![7u8; 32];
+        let ptr = vec.as_ptr();
+        let back = Bytes::from(vec).into_vec();
+        assert_eq!(back.as_ptr(), ptr);
+
+        let shared = Bytes::from(alloc::vec![1, 2, 3]);
+        let clone = shared.clone();
+        assert_eq!(shared.into_vec(), [1, 2, 3]);
+        assert_eq!(clone.slice(1..).into_vec(), [2, 3]);
+        assert!(Bytes::new().into_vec().is_empty());
+    }
+
+    #[test]
+    fn try_into_vec_needs_the_whole_unique_allocation() {
+        let bytes = Bytes::from(alloc::vec![1, 2, 3]);
+        let clone = bytes.clone();
+        let bytes = bytes.try_into_vec().expect_err("shared");
+        drop(clone);
+        let tail = bytes.slice(1..);
+        assert!(tail.try_into_vec().is_err(), "sliced");
+        assert_eq!(bytes.try_into_vec(), Ok(alloc::vec![1, 2, 3]));
+    }
+}
@@ -0,0 +1,326 @@
+//! Capsule Protocol codec (RFC 9297 §3).
+//!
+//! Capsules travel inside the payload of DATA frames on a request stream whose
+//! HTTP upgrade negotiated the Capsule Protocol. The capsule stream is the
+//! concatenation of those DATA payloads; a capsule may span several frames.
+//!
+//! Invariants:
+//! - Capsule header is encoded as `type(varint)` + `length(varint)`.
+//! - `decode_capsule` is allocation-free and returns a borrowed value slice.
+//! - An incomplete capsule yields `BufferTooSmall` so callers can buffer more input.
+//! - `next_datagram` skips capsule types it does not understand (RFC 9297 §3.2).
+//! - WebTransport session capsules (draft-ietf-webtrans-http3 §5) are decoded
+//!   into dedicated variants; a malformed one is `InvalidCapsule`.
+
+use core::fmt;
+
+use crate::codec::varint;
+use crate::h3::consts;
+
+/// Maximum reason length in a CLOSE_WEBTRANSPORT_SESSION capsule.
+pub const MAX_CLOSE_REASON_LEN: usize = 1024;
+
+/// Type and value length that start every capsule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapsuleHeader {
+    /// Capsule type, e.g. `consts::CAPSULE_TYPE_DATAGRAM`.
+    pub ty: u64,
+    /// Length of the value that follows the header.
+    pub len: u64,
+}
+
+/// A decoded capsule borrowing its value from the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capsule<'a> {
+    /// DATAGRAM capsule (type 0x00): carries an HTTP Datagram payload without
+    /// the Quarter Stream ID prefix, since the stream is implied.
+    Datagram(&'a [u8]),
+    /// CLOSE_WEBTRANSPORT_SESSION: application error code + UTF-8 reason.
+    CloseWebTransportSession { code: u32, reason: &'a [u8] },
+    /// DRAIN_WEBTRANSPORT_SESSION: the peer asks for a graceful wind-down.
+    DrainWebTransportSession,
+    /// Any capsule type this codec does not interpret.
+    Unknown { ty: u64, value: &'a [u8] },
+}
+
+/// Why a capsule could not be decoded or encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Input ends before the capsule header or value is complete.
+    BufferTooSmall,
+    /// Capsule length does not fit in `usize`.
+    LengthTooLarge,
+    /// A known capsule type carries a value that violates its format.
+    InvalidCapsule,
+    /// A header varint is malformed or too large to encode.
+    VarInt(varint::VarIntError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BufferTooSmall => write!(f, "buffer too small"),
+            Error::LengthTooLarge => write!(f, "capsule length too large"),
+            Error::InvalidCapsule => write!(f, "invalid capsule value"),
+            Error::VarInt(inner) => write!(f, "varint error: {inner}"),
+        }
+    }
+}
+
+impl From<varint::VarIntError> for Error {
+    fn from(value: varint::VarIntError) -> Self {
+        match value {
+            varint::VarIntError::BufferTooSmall => Self::BufferTooSmall,
+            other => Self::VarInt(other),
+        }
+    }
+}
+
+/// Decode a capsule header from `input`.
+///
+/// Returns `(header, bytes_consumed)`.
+pub fn decode_capsule_header(input: &[u8]) -> Result<(CapsuleHeader, usize), Error> {
+    let (ty, ty_len) = varint::decode(input)?;
+    let (len, len_len) = varint::decode(&input[ty_len..])?;
+
+    Ok((CapsuleHeader { ty, len }, ty_len + len_len))
+}
+
+/// Encode a capsule header into `out`.
+///
+/// Returns `bytes_written`.
+pub fn encode_capsule_header(h: CapsuleHeader, out: &mut [u8]) -> Result<usize, Error> {
+    let total = varint::encoded_len(h.ty)? + varint::encoded_len(h.len)?;
+    if out.len() < total {
+        return Err(Error::BufferTooSmall);
+    }
+
+    let ty_written = varint::encode(h.ty, out)?;
+    let len_written = varint::encode(h.len, &mut out[ty_written..])?;
+
+    Ok(ty_written + len_written)
+}
+
+/// Decode one full capsule from `input`.
+///
+/// Returns `(capsule, bytes_consumed)`.
+pub fn decode_capsule(input: &[u8]) -> Result<(Capsule<'_>, usize), Error> {
+    let (header, header_len) = decode_capsule_header(input)?;
+
+    let value_len = usize::try_from(header.len).map_err(|_| Error::LengthTooLarge)?;
+    if input.len().saturating_sub(header_len) < value_len {
+        return Err(Error::BufferTooSmall);
+    }
+
+    let end = header_len + value_len;
+    let value = &input[header_len..end];
+    let capsule = match header.ty {
+        consts::CAPSULE_TYPE_DATAGRAM => Capsule::Datagram(value),
+        consts::CAPSULE_TYPE_CLOSE_WEBTRANSPORT_SESSION => {
+            if value.len() < 4 || value.len() - 4 > MAX_CLOSE_REASON_LEN {
+                return Err(Error::InvalidCapsule);
+            }
+            let code = u32::from_be_bytes([value[0], value[1], value[2], value[3]]);
+            Capsule::CloseWebTransportSession {
+                code,
+                reason: &value[4..],
+            }
+        }
+        consts::CAPSULE_TYPE_DRAIN_WEBTRANSPORT_SESSION => {
+            if !value.is_empty() {
+                return Err(Error::InvalidCapsule);
+            }
+            Capsule::DrainWebTransportSession
+        }
+        ty => Capsule::Unknown { ty, value },
+    };
+
+    Ok((capsule, end))
+}
+
+/// Encode a complete CLOSE_WEBTRANSPORT_SESSION capsule into `out`.
+///
+/// Returns `bytes_written`.
+pub fn encode_close_webtransport_session(
+    code: u32,
+    reason: &[u8],
+    out: &mut [u8],
+) -> Result<usize, Error> {
+    if reason.len() > MAX_CLOSE_REASON_LEN {
+        return Err(Error::InvalidCapsule);
+    }
+
+    let value_len = 4 + reason.len();
+    let header_len = encode_capsule_header(
+        CapsuleHeader {
+            ty: consts::CAPSULE_TYPE_CLOSE_WEBTRANSPORT_SESSION,
+            len: value_len as u64,
+        },
+        out,
+    )?;
+    let end = header_len + value_len;
+    if out.len() < end {
+        return Err(Error::BufferTooSmall);
+    }
+
+    out[header_len..header_len + 4].copy_from_slice(&code.to_be_bytes());
+    out[header_len + 4..end].copy_from_slice(reason);
+    Ok(end)
+}
+
+/// Find the next DATAGRAM capsule in `input`, skipping any other capsule types.
+///
+/// Returns `Ok(Some((payload, bytes_consumed)))` when a DATAGRAM capsule was
+/// found; `bytes_consumed` covers the skipped capsules as well. Returns
+/// `Ok(None)` when `input` holds only complete non-DATAGRAM capsules, in which
+/// case all of `input` may be discarded. A trailing incomplete capsule yields
+/// `BufferTooSmall`.
+pub fn next_datagram(input: &[u8]) -> Result<Option<(&[u8], usize)>, Error> {
+    let mut pos = 0;
+    while pos < input.len() {
+        let (capsule, consumed) = decode_capsule(&input[pos..])?;
+        pos += consumed;
+        if let Capsule::Datagram(payload) = capsule {
+            return Ok(Some((payload, pos)));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capsule(ty: u64, value: &[u8], out: &mut [u8]) -> usize {
+        let header_len = encode_capsule_header(
+            CapsuleHeader {
+                ty,
+                len: value.len() as u64,
+            },
+            out,
+        )
+        .expect("capsule header encodes");
+        out[header_len..header_len + value.len()].copy_from_slice(value);
+        header_len + value.len()
+    }
+
+    #[test]
+    fn roundtrip_datagram_capsule() {
+        let mut buf = [0u8; 16];
+        let written = capsule(consts::CAPSULE_TYPE_DATAGRAM, &[1, 2, 3], &mut buf);
+
+        let (decoded, consumed) = decode_capsule(&buf[..written]).expect("decodes");
+        assert_eq!(decoded, Capsule::Datagram(&[1, 2, 3]));
+        assert_eq!(consumed, written);
+    }
+
+    #[test]
+    fn unknown_capsule_is_surfaced_by_decode_capsule() {
+        let mut buf = [0u8; 16];
+        let written = capsule(0x1234, &[9], &mut buf);
+
+        let (decoded, consumed) = decode_capsule(&buf[..written]).expect("decodes");
+        assert_eq!(
+            decoded,
+            Capsule::Unknown {
+                ty: 0x1234,
+                value: &[9]
+            }
+        );
+        assert_eq!(consumed, written);
+    }
+
+    #[test]
+    fn next_datagram_skips_unknown_capsules() {
+        let mut buf = [0u8; 32];
+        let mut len = capsule(0x29, &[0xee; 4], &mut buf);
+        len += capsule(0x4242, &[], &mut buf[len..]);
+        let skipped = len;
+        len += capsule(consts::CAPSULE_TYPE_DATAGRAM, &[0x42], &mut buf[len..]);
+
+        let (payload, consumed) = next_datagram(&buf[..len])
+            .expect("decodes")
+            .expect("datagram found");
+        assert_eq!(payload, &[0x42]);
+        assert_eq!(consumed, len);
+        assert!(consumed > skipped);
+    }
+
+    #[test]
+    fn next_datagram_none_when_only_unknown_capsules() {
+        let mut buf = [0u8; 16];
+        let len = capsule(0x29, &[1, 2], &mut buf);
+        assert_eq!(next_datagram(&buf[..len]), Ok(None));
+        assert_eq!(next_datagram(&[]), Ok(None));
+    }
+
+    #[test]
+    fn roundtrip_close_webtransport_session() {
+        let mut buf = [0u8; 32];
+        let written = encode_close_webtransport_session(0x0102_0304, b"bye", &mut buf)
+            .expect("close capsule encodes");
+
+        let (decoded, consumed) = decode_capsule(&buf[..written]).expect("decodes");
+        assert_eq!(
+            decoded,
+            Capsule::CloseWebTransportSession {
+                code: 0x0102_0304,
+                reason: b"bye"
+            }
+        );
+        assert_eq!(consumed, written);
+    }
+
+    #[test]
+    fn drain_webtransport_session() {
+        let mut buf = [0u8; 16];
+        let written = capsule(
+            consts::CAPSULE_TYPE_DRAIN_WEBTRANSPORT_SESSION,
+            &[],
+            &mut buf,
+        );
+        let (decoded, _) = decode_capsule(&buf[..written]).expect("decodes");
+        assert_eq!(decoded, Capsule::DrainWebTransportSession);
+    }
+
+    #[test]
+    fn malformed_webtransport_capsules() {
+        let mut buf = [0u8; 16];
+        let written = capsule(
+            consts::CAPSULE_TYPE_CLOSE_WEBTRANSPORT_SESSION,
+            &[0, 0, 1],
+            &mut buf,
+        );
+        assert_eq!(decode_capsule(&buf[..written]), Err(Error::InvalidCapsule));
+
+        let written = capsule(
+            consts::CAPSULE_TYPE_DRAIN_WEBTRANSPORT_SESSION,
+            &[0],
+            &mut buf,
+        );
+        assert_eq!(decode_capsule(&buf[..written]), Err(Error::InvalidCapsule));
+    }
+
+    #[test]
+    fn truncated_value_is_buffer_too_small() {
+        let mut buf = [0u8; 16];
+        let len = capsule(consts::CAPSULE_TYPE_DATAGRAM, &[1, 2, 3], &mut buf);
+        assert_eq!(decode_capsule(&buf[..len - 1]), Err(Error::BufferTooSmall));
+    }
+
+    #[test]
+    fn truncated_header_is_buffer_too_small() {
+        // Length varint announces 8 bytes but only the first is present.
+        assert_eq!(decode_capsule(&[0x00, 0xc0]), Err(Error::BufferTooSmall));
+        assert_eq!(decode_capsule(&[]), Err(Error::BufferTooSmall));
+    }
+
+    #[test]
+    fn encode_header_buffer_too_small() {
+        let mut buf = [0u8; 1];
+        assert_eq!(
+            encode_capsule_header(CapsuleHeader { ty: 0, len: 64 }, &mut buf),
+            Err(Error::BufferTooSmall)
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! CONNECT-UDP request target and datagram payload codec (RFC 9298).
+//!
+//! Invariants:
+//! - Only the default URI template path
+//!   `/.well-known/masque/udp/{target_host}/{target_port}/` is recognized.
+//! - `target_host` is returned still percent-encoded; `percent_decode` writes
+//!   the decoded form into a caller-supplied buffer. No allocation.
+//! - `target_port` is a decimal integer in `1..=65535` without a sign.
+//! - Datagram payloads start with a Context ID varint; Context ID 0 carries
+//!   a raw UDP payload (RFC 9298 §4).
+
+use core::fmt;
+
+use crate::codec::varint;
+
+/// Path prefix of the default CONNECT-UDP URI template.
+pub const UDP_PATH_PREFIX: &[u8] = b"/.well-known/masque/udp/";
+
+/// Context ID whose datagrams carry UDP payloads.
+pub const CONTEXT_ID_UDP: u64 = 0;
+
+/// Target of a CONNECT-UDP request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UdpTarget<'a> {
+    /// Percent-encoded host name or IP literal.
+    pub host: &'a [u8],
+    /// UDP port in `1..=65535`.
+    pub port: u16,
+}
+
+/// Why a CONNECT-UDP path or datagram payload could not be handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Path does not match the URI template.
+    InvalidPath,
+    /// `target_host` is empty or has a bad percent-encoding.
+    InvalidHost,
+    /// `target_port` is not a decimal in `1..=65535`.
+    InvalidPort,
+    /// Output buffer is too small for the percent-decoded host.
+    BufferTooSmall,
+    /// The Context ID varint is malformed or too large to encode.
+    VarInt(varint::VarIntError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidPath => write!(f, "path does not match connect-udp template"),
+            Error::InvalidHost => write!(f, "invalid target host"),
+            Error::InvalidPort => write!(f, "invalid target port"),
+            Error::BufferTooSmall => write!(f, "buffer too small"),
+            Error::VarInt(inner) => write!(f, "varint error: {inner}"),
+        }
+    }
+}
+
+impl From<varint::VarIntError> for Error {
+    fn from(value: varint::VarIntError) -> Self {
+        Self::VarInt(value)
+    }
+}
+
+/// Parse a `:path` against the default CONNECT-UDP URI template.
+pub fn parse_udp_path(path: &[u8]) -> Result<UdpTarget<'_>, Error> {
+    let rest = path
+        .strip_prefix(UDP_PATH_PREFIX)
+        .and_then(|rest| rest.strip_suffix(b"/"))
+        .ok_or(Error::InvalidPath)?;
+
+    let mut parts = rest.split(|&b| b == b'/');
+    let (Some(host), Some(port), None) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(Error::InvalidPath);
+    };
+
+    if host.is_empty() || !valid_percent_encoding(host) {
+        return Err(Error::InvalidHost);
+    }
+
+    Ok(UdpTarget {
+        host,
+        port: parse_port(port)?,
+    })
+}
+
+/// Decode a percent-encoded host into `out`.
+///
+/// Returns `bytes_written`.
+pub fn percent_decode(input: &[u8], out: &mut [u8]) -> Result<usize, Error> {
+    let mut pos = 0;
+    let mut written = 0;
+    while pos < input.len() {
+        let byte = if input[pos] == b'%' {
+            let hi = input.get(pos + 1).copied().and_then(hex_value);
+            let lo = input.get(pos + 2).copied().and_then(hex_value);
+            let (Some(hi), Some(lo)) = (hi, lo) else {
+                return Err(Error::InvalidHost);
+            };
+            pos += 3;
+            (hi << 4) | lo
+        } else {
+            pos += 1;
+            input[pos - 1]
+        };
+
+        let slot = out.get_mut(written).ok_or(Error::BufferTooSmall)?;
+        *slot = byte;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Split an HTTP Datagram payload into `(context_id, rest)`.
+pub fn decode_payload(payload: &[u8]) -> Result<(u64, &[u8]), Error> {
+    let (context_id, consumed) = varint::decode(payload)?;
+    Ok((context_id, &payload[consumed..]))
+}
+
+/// Write the Context ID prefix for a UDP payload into `out`.
+///
+/// Returns `bytes_written`.
+pub fn encode_udp_payload_header(out: &mut [u8]) -> Result<usize, Error> {
+    Ok(varint::encode(CONTEXT_ID_UDP, out)?)
+}
+
+fn parse_port(input: &[u8]) -> Result<u16, Error> {
+    if input.is_empty() || input.len() > 5 {
+        return Err(Error::InvalidPort);
+    }
+    let mut port: u32 = 0;
+    for &b in input {
+        if !b.is_ascii_digit() {
+            return Err(Error::InvalidPort);
+        }
+        port = port * 10 + u32::from(b - b'0');
+    }
+    match u16::try_from(port) {
+        Ok(port) if port != 0 => Ok(port),
+        _ => Err(Error::InvalidPort),
+    }
+}
+
+fn valid_percent_encoding(input: &[u8]) -> bool {
+    let mut pos = 0;
+    while pos < input.len() {
+        if input[pos] == b'%' {
+            let ok = input.get(pos + 1).copied().and_then(hex_value).is_some()
+                && input.get(pos + 2).copied().and_then(hex_value).is_some();
+            if !ok {
+                return false;
+            }
+            pos += 3;
+        } else {
+            pos += 1;
+        }
+    }
+    true
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_default_template() {
+        let target = parse_udp_path(b"/.well-known/masque/udp/192.0.2.6/443/").unwrap();
+        assert_eq!(
+            target,
+            UdpTarget {
+                host: b"192.0.2.6",
+                port: 443
+            }
+        );
+    }
+
+    #[test]
+    fn ipv6_host_is_percent_decoded() {
+        let target = parse_udp_path(b"/.well-known/masque/udp/2001%3adb8%3A%3A42/53/").unwrap();
+        let mut out = [0u8; 32];
+        let n = percent_decode(target.host, &mut out).unwrap();
+        assert_eq!(&out[..n], b"2001:db8::42");
+        assert_eq!(target.port, 53);
+    }
+
+    #[test]
+    fn rejects_paths_off_template() {
+        for path in [
+            &b"/"[..],
+            b"/.well-known/masque/udp/host/443",
+            b"/.well-known/masque/udp/host/",
+            b"/.well-known/masque/udp/host/443/extra/",
+            b"/.well-known/masque/ip/host/443/",
+        ] {
+            assert_eq!(parse_udp_path(path), Err(Error::InvalidPath), "{path:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_bad_host() {
+        assert_eq!(
+            parse_udp_path(b"/.well-known/masque/udp//443/"),
+            Err(Error::InvalidHost)
+        );
+        assert_eq!(
+            parse_udp_path(b"/.well-known/masque/udp/a%zz/443/"),
+            Err(Error::InvalidHost)
+        );
+        assert_eq!(
+            parse_udp_path(b"/.well-known/masque/udp/a%4/443/"),
+            Err(Error::InvalidHost)
+        );
+    }
+
+    #[test]
+    fn port_boundaries() {
+        let port = |p: &[u8]| {
+            let mut path = alloc::vec::Vec::from(UDP_PATH_PREFIX);
+            path.extend_from_slice(b"h/");
+            path.extend_from_slice(p);
+            path.push(b'/');
+            parse_udp_path(&path).map(|t| t.port)
+        };
+        assert_eq!(port(b"1"), Ok(1));
+        assert_eq!(port(b"65535"), Ok(65535));
+        assert_eq!(port(b"0"), Err(Error::InvalidPort));
+        assert_eq!(port(b"65536"), Err(Error::InvalidPort));
+        assert_eq!(port(b"100000"), Err(Error::InvalidPort));
+        assert_eq!(port(b"+80"), Err(Error::InvalidPort));
+        assert_eq!(port(b""), Err(Error::InvalidPort));
+    }
+
+    #[test]
+    fn percent_decode_buffer_too_small() {
+        let mut out = [0u8; 2];
+        assert_eq!(percent_decode(b"abc", &mut out), Err(Error::BufferTooSmall));
+    }
+
+    #[test]
+    fn roundtrip_udp_payload() {
+        let mut buf = [0u8; 8];
+        let n = encode_udp_payload_header(&mut buf).unwrap();
+        buf[n..n + 2].copy_from_slice(b"hi");
+
+        let (context_id, payload) = decode_payload(&buf[..n + 2]).unwrap();
+        assert_eq!(context_id, CONTEXT_ID_UDP);
+        assert_eq!(payload, b"hi");
+    }
+
+    #[test]
+    fn empty_payload_is_malformed() {
+        assert_eq!(
+            decode_payload(&[]),
+            Err(Error::VarInt(varint::VarIntError::BufferTooSmall))
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! HTTP Datagram payload codec (RFC 9297 §2.1).
+//!
+//! Invariants:
+//! - Each QUIC DATAGRAM payload starts with a Quarter Stream ID varint, which is
+//!   the associated client-initiated bidirectional stream ID divided by four.
+//! - Quarter Stream IDs above `MAX_QUARTER_STREAM_ID` are rejected; they cannot
+//!   name a valid QUIC stream.
+//! - `decode` is allocation-free and returns a borrowed payload slice.
+
+use core::fmt;
+
+use crate::codec::varint;
+
+/// Largest Quarter Stream ID that maps to a valid QUIC stream ID (2^60 - 1).
+pub const MAX_QUARTER_STREAM_ID: u64 = varint::VARINT_MAX >> 2;
+
+/// Why an HTTP Datagram could not be decoded or encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// `out` cannot hold the Quarter Stream ID.
+    BufferTooSmall,
+    /// Quarter Stream ID exceeds `MAX_QUARTER_STREAM_ID`.
+    QuarterStreamIdTooLarge,
+    /// Stream ID is not a client-initiated bidirectional stream.
+    InvalidStreamId,
+    /// The Quarter Stream ID varint is truncated or malformed.
+    VarInt(varint::VarIntError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BufferTooSmall => write!(f, "buffer too small"),
+            Error::QuarterStreamIdTooLarge => write!(f, "quarter stream id too large"),
+            Error::InvalidStreamId => {
+                write!(f, "stream id is not client-initiated bidirectional")
+            }
+            Error::VarInt(inner) => write!(f, "varint error: {inner}"),
+        }
+    }
+}
+
+impl From<varint::VarIntError> for Error {
+    fn from(value: varint::VarIntError) -> Self {
+        Self::VarInt(value)
+    }
+}
+
+/// Decode an HTTP Datagram from a QUIC DATAGRAM payload.
+///
+/// Returns `(stream_id, payload)` where `stream_id` is the request stream the
+/// datagram is associated with.
+pub fn decode(input: &[u8]) -> Result<(u64, &[u8]), Error> {
+    let (quarter, consumed) = varint::decode(input)?;
+    if quarter > MAX_QUARTER_STREAM_ID {
+        return Err(Error::QuarterStreamIdTooLarge);
+    }
+
+    Ok((quarter << 2, &input[consumed..]))
+}
+
+/// Encode the Quarter Stream ID prefix for `stream_id` into `out`.
+///
+/// Returns `bytes_written`; the caller appends the datagram payload.
+pub fn encode_header(stream_id: u64, out: &mut [u8]) -> Result<usize, Error> {
+    if stream_id & 0x03 != 0 {
+        return Err(Error::InvalidStreamId);
+    }
+
+    let quarter = stream_id >> 2;
+    let len = varint::encoded_len(quarter)?;
+    if out.len() < len {
+        return Err(Error::BufferTooSmall);
+    }
+
+    Ok(varint::encode(quarter, out)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_stream_ids() {
+        let payload = [0xca, 0xfe];
+        for stream_id in [0u64, 4, 252, 256, 1 << 20, MAX_QUARTER_STREAM_ID << 2] {
+            let mut buf = [0u8; 16];
+            let header_len = encode_header(stream_id, &mut buf).expect("encodes");
+            buf[header_len..header_len + payload.len()].copy_from_slice(&payload);
+
+            let (decoded_id, decoded_payload) =
+                decode(&buf[..header_len + payload.len()]).expect("decodes");
+            assert_eq!(decoded_id, stream_id);
+            assert_eq!(decoded_payload, payload);
+        }
+    }
+
+    #[test]
+    fn quarter_stream_id_is_stream_id_divided_by_four() {
+        let mut buf = [0u8; 8];
+        let written = encode_header(8, &mut buf).expect("encodes");
+        assert_eq!(&buf[..written], &[0x02]);
+    }
+
+    #[test]
+    fn empty_payload_is_valid() {
+        let (id, payload) = decode(&[0x01]).expect("decodes");
+        assert_eq!(id, 4);
+        assert!(payload.is_empty());
+    }
+
+    #[test]
+    fn malformed_empty_input() {
+        assert_eq!(
+            decode(&[]),
+            Err(Error::VarInt(varint::VarIntError::BufferTooSmall))
+        );
+    }
+
+    #[test]
+    fn malformed_quarter_stream_id_too_large() {
+        let mut buf = [0u8; 8];
+        let written = varint::encode(MAX_QUARTER_STREAM_ID + 1, &mut buf).expect("encodes");
+        assert_eq!(decode(&buf[..written]), Err(Error::QuarterStreamIdTooLarge));
+    }
+
+    #[test]
+    fn encode_rejects_non_request_stream() {
+        let mut buf = [0u8; 8];
+        assert_eq!(encode_header(2, &mut buf), Err(Error::InvalidStreamId));
+        assert_eq!(encode_header(3, &mut buf), Err(Error::InvalidStreamId));
+    }
+
+    #[test]
+    fn encode_buffer_too_small() {
+        let mut buf = [0u8; 1];
+        assert_eq!(encode_header(256, &mut buf), Err(Error::BufferTooSmall));
+    }
+}
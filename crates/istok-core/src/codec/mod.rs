@@ -1,3 +1,8 @@
+pub mod capsule;
+pub mod connect_udp;
+pub mod h3_datagram;
 pub mod h3_frame;
+pub mod origin;
 pub mod prefix_int;
+pub mod priority;
 pub mod varint;
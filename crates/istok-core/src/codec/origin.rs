@@ -0,0 +1,137 @@
+//! ORIGIN frame codec (RFC 9412).
+//!
+//! The payload is a sequence of Origin-Entry fields: a 16-bit Origin-Len in
+//! network byte order followed by that many bytes of ASCII serialized origin
+//! (`scheme "://" host [ ":" port ]`).
+//!
+//! Invariants:
+//! - An empty payload is valid and carries no origins.
+//! - A truncated entry makes the whole payload `Truncated`; entries before
+//!   it are still yielded by `entries`, so callers must not commit origins
+//!   until the iterator finished without error.
+//! - Origins are returned borrowed and are not validated beyond their
+//!   length prefix. No allocation.
+
+use core::fmt;
+
+/// ORIGIN frame payload errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// An Origin-Entry runs past the end of the payload.
+    Truncated,
+    /// An origin longer than 65535 bytes cannot be encoded.
+    OriginTooLong,
+    BufferTooSmall,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Truncated => write!(f, "truncated origin entry"),
+            Error::OriginTooLong => write!(f, "origin too long"),
+            Error::BufferTooSmall => write!(f, "buffer too small"),
+        }
+    }
+}
+
+/// Iterator over the origins in an ORIGIN frame payload.
+#[derive(Debug, Clone)]
+pub struct Entries<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Result<&'a [u8], Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        let entry = match self.rest {
+            [hi, lo, rest @ ..] => {
+                let len = usize::from(u16::from_be_bytes([*hi, *lo]));
+                rest.split_at_checked(len)
+            }
+            _ => None,
+        };
+        match entry {
+            Some((origin, rest)) => {
+                self.rest = rest;
+                Some(Ok(origin))
+            }
+            None => {
+                self.rest = &[];
+                Some(Err(Error::Truncated))
+            }
+        }
+    }
+}
+
+/// Iterate over the origins in an ORIGIN frame payload.
+pub fn entries(payload: &[u8]) -> Entries<'_> {
+    Entries { rest: payload }
+}
+
+/// Encode `origins` as an ORIGIN frame payload into `out`.
+///
+/// Returns `bytes_written`.
+pub fn encode_origin(origins: &[&[u8]], out: &mut [u8]) -> Result<usize, Error> {
+    let mut pos = 0;
+    for origin in origins {
+        let len = u16::try_from(origin.len()).map_err(|_| Error::OriginTooLong)?;
+        let end = pos + 2 + origin.len();
+        let dst = out.get_mut(pos..end).ok_or(Error::BufferTooSmall)?;
+        dst[..2].copy_from_slice(&len.to_be_bytes());
+        dst[2..].copy_from_slice(origin);
+        pos = end;
+    }
+    Ok(pos)
+}
+
+/// Bytes `encode_origin` writes for `origins`.
+pub fn encoded_len(origins: &[&[u8]]) -> usize {
+    origins.iter().map(|origin| 2 + origin.len()).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn roundtrip() {
+        let origins: [&[u8]; 3] = [b"https://a.example", b"https://b.example:8443", b""];
+        let mut out = [0u8; 64];
+        let n = encode_origin(&origins, &mut out).unwrap();
+        assert_eq!(n, encoded_len(&origins));
+        assert_eq!(&out[..19], b"\x00\x11https://a.example");
+
+        let decoded: Result<Vec<&[u8]>, Error> = entries(&out[..n]).collect();
+        assert_eq!(decoded.unwrap(), origins);
+    }
+
+    #[test]
+    fn empty_payload_has_no_origins() {
+        assert_eq!(entries(&[]).count(), 0);
+        assert_eq!(encode_origin(&[], &mut []), Ok(0));
+    }
+
+    #[test]
+    fn truncated_entries() {
+        for payload in [&b"\x00"[..], b"\x00\x05abc", b"\x00\x01a\x00"] {
+            let last = entries(payload).last();
+            assert_eq!(last, Some(Err(Error::Truncated)), "{payload:?}");
+        }
+    }
+
+    #[test]
+    fn encode_errors() {
+        let mut out = [0u8; 8];
+        assert_eq!(
+            encode_origin(&[b"https://a.example"], &mut out),
+            Err(Error::BufferTooSmall)
+        );
+        let long = alloc::vec![b'a'; 65536];
+        assert_eq!(encode_origin(&[&long], &mut out), Err(Error::OriginTooLong));
+    }
+}
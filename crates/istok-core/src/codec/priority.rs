@@ -0,0 +1,312 @@
+//! Extensible Priorities codec (RFC 9218).
+//!
+//! The `priority` request header and the PRIORITY_UPDATE frame both carry a
+//! Priority Field Value: a Structured Fields Dictionary (RFC 8941 §3.2).
+//!
+//! Invariants:
+//! - `parse_priority_field` parses the value with `crate::sf`, so it needs
+//!   the `alloc` feature. It accepts any valid Dictionary and only
+//!   interprets `u` (Integer `0..=7`) and `i` (Boolean). Unknown keys,
+//!   out-of-range values and values of unexpected types are ignored
+//!   (RFC 9218 §4); a syntax error is `Malformed`.
+//! - Members may appear more than once; the last occurrence wins.
+//! - PRIORITY_UPDATE payloads are `element_id(varint)` + field value; the
+//!   value is returned borrowed. Encoding and PRIORITY_UPDATE framing do
+//!   not allocate.
+
+use core::fmt;
+
+use crate::codec::varint;
+
+/// Default urgency when `u` is absent (RFC 9218 §4.1).
+pub const DEFAULT_URGENCY: u8 = 3;
+/// Lowest priority urgency value.
+pub const MAX_URGENCY: u8 = 7;
+
+/// Priority parameters of a request or push.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Priority {
+    /// `0` is most urgent, `7` least.
+    pub urgency: u8,
+    /// Whether the response can be used as it arrives, so bandwidth may be
+    /// shared with other incremental responses of the same urgency.
+    pub incremental: bool,
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Self {
+            urgency: DEFAULT_URGENCY,
+            incremental: false,
+        }
+    }
+}
+
+/// A decoded PRIORITY_UPDATE frame payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriorityUpdate<'a> {
+    /// Request stream id (type 0xF0700) or Push ID (type 0xF0701).
+    pub element_id: u64,
+    /// Unparsed Priority Field Value.
+    pub field_value: &'a [u8],
+}
+
+/// PRIORITY_UPDATE and Priority field value errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Field value is not a valid Structured Fields Dictionary.
+    Malformed,
+    /// The output buffer cannot hold the encoded frame.
+    BufferTooSmall,
+    /// A frame varint is truncated or out of range.
+    VarInt(varint::VarIntError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Malformed => write!(f, "malformed priority field value"),
+            Error::BufferTooSmall => write!(f, "buffer too small"),
+            Error::VarInt(inner) => write!(f, "varint error: {inner}"),
+        }
+    }
+}
+
+impl From<varint::VarIntError> for Error {
+    fn from(value: varint::VarIntError) -> Self {
+        Self::VarInt(value)
+    }
+}
+
+/// Parse a Priority Field Value.
+#[cfg(feature = "alloc")]
+pub fn parse_priority_field(input: &[u8]) -> Result<Priority, Error> {
+    use crate::sf::{self, BareItem, Item, ListEntry};
+
+    let dict = sf::parse_dictionary(input).map_err(|_| Error::Malformed)?;
+    let mut priority = Priority::default();
+    let bare = |key| match dict.get(key) {
+        Some(ListEntry::Item(Item { bare, .. })) => Some(bare),
+        _ => None,
+    };
+    if let Some(&BareItem::Integer(u)) = bare("u")
+        && let Ok(u) = u8::try_from(u)
+        && u <= MAX_URGENCY
+    {
+        priority.urgency = u;
+    }
+    if let Some(&BareItem::Boolean(i)) = bare("i") {
+        priority.incremental = i;
+    }
+    Ok(priority)
+}
+
+/// Serialize `priority` as a Priority Field Value, omitting defaults.
+///
+/// Returns `bytes_written` (`0` for the default priority).
+pub fn encode_priority_field(priority: Priority, out: &mut [u8]) -> Result<usize, Error> {
+    if priority.urgency > MAX_URGENCY {
+        return Err(Error::Malformed);
+    }
+    let mut buf = [0u8; 8];
+    let mut len = 0;
+    if priority.urgency != DEFAULT_URGENCY {
+        buf[..3].copy_from_slice(&[b'u', b'=', b'0' + priority.urgency]);
+        len = 3;
+    }
+    if priority.incremental {
+        if len > 0 {
+            buf[len..len + 2].copy_from_slice(b", ");
+            len += 2;
+        }
+        buf[len] = b'i';
+        len += 1;
+    }
+    let dst = out.get_mut(..len).ok_or(Error::BufferTooSmall)?;
+    dst.copy_from_slice(&buf[..len]);
+    Ok(len)
+}
+
+/// Decode a PRIORITY_UPDATE frame payload.
+pub fn decode_priority_update(payload: &[u8]) -> Result<PriorityUpdate<'_>, Error> {
+    let (element_id, consumed) = varint::decode(payload)?;
+    Ok(PriorityUpdate {
+        element_id,
+        field_value: &payload[consumed..],
+    })
+}
+
+/// Encode a PRIORITY_UPDATE frame payload into `out`.
+///
+/// Returns `bytes_written`.
+pub fn encode_priority_update(update: &PriorityUpdate<'_>, out: &mut [u8]) -> Result<usize, Error> {
+    let id_len = varint::encode(update.element_id, out)?;
+    let end = id_len + update.field_value.len();
+    let dst = out.get_mut(id_len..end).ok_or(Error::BufferTooSmall)?;
+    dst.copy_from_slice(update.field_value);
+    Ok(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "alloc")]
+    fn parse(input: &[u8]) -> Result<Priority, Error> {
+        parse_priority_field(input)
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn empty_value_is_default() {
+        assert_eq!(parse(b""), Ok(Priority::default()));
+        assert_eq!(parse(b"  "), Ok(Priority::default()));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn parses_urgency_and_incremental() {
+        assert_eq!(
+            parse(b"u=5, i"),
+            Ok(Priority {
+                urgency: 5,
+                incremental: true
+            })
+        );
+        assert_eq!(
+            parse(b"i=?0,u=0"),
+            Ok(Priority {
+                urgency: 0,
+                incremental: false
+            })
+        );
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn last_member_wins() {
+        assert_eq!(parse(b"u=1, u=6").map(|p| p.urgency), Ok(6));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn ignores_unknown_and_out_of_range_members() {
+        for input in [
+            &b"u=8"[..],
+            b"u=-1",
+            b"u=1.5",
+            b"u=\"1\"",
+            b"i=1",
+            b"i=tok",
+            b"x=(1 2);p, y=:AQID:, z=?1;q=\"s\"",
+        ] {
+            assert_eq!(parse(input), Ok(Priority::default()), "{input:?}");
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn member_parameters_are_skipped() {
+        assert_eq!(
+            parse(b"u=2;x=1;y, i;z").map(|p| (p.urgency, p.incremental)),
+            Ok((2, true))
+        );
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn rejects_malformed_dictionaries() {
+        for input in [
+            &b"U=1"[..],
+            b"u=",
+            b"u=1,",
+            b"u=1 i",
+            b"u=?2",
+            b"u=\"open",
+            b"u=:not base64!:",
+            b"u=1234567890123456",
+            b"u=1.2345",
+            b"u=(1",
+            b",u=1",
+        ] {
+            assert_eq!(parse(input), Err(Error::Malformed), "{input:?}");
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn roundtrip_priority_field() {
+        let mut buf = [0u8; 16];
+        for urgency in 0..=MAX_URGENCY {
+            for incremental in [false, true] {
+                let priority = Priority {
+                    urgency,
+                    incremental,
+                };
+                let n = encode_priority_field(priority, &mut buf).unwrap();
+                assert_eq!(parse(&buf[..n]), Ok(priority));
+            }
+        }
+        assert_eq!(encode_priority_field(Priority::default(), &mut buf), Ok(0));
+    }
+
+    #[test]
+    fn encode_priority_field_boundaries() {
+        let p = Priority {
+            urgency: 0,
+            incremental: true,
+        };
+        let mut small = [0u8; 5];
+        assert_eq!(
+            encode_priority_field(p, &mut small),
+            Err(Error::BufferTooSmall)
+        );
+        let mut exact = [0u8; 6];
+        assert_eq!(encode_priority_field(p, &mut exact), Ok(6));
+        assert_eq!(&exact, b"u=0, i");
+
+        let bad = Priority {
+            urgency: 8,
+            incremental: false,
+        };
+        assert_eq!(
+            encode_priority_field(bad, &mut exact),
+            Err(Error::Malformed)
+        );
+    }
+
+    #[test]
+    fn roundtrip_priority_update() {
+        let update = PriorityUpdate {
+            element_id: 4,
+            field_value: b"u=1",
+        };
+        let mut buf = [0u8; 16];
+        let n = encode_priority_update(&update, &mut buf).unwrap();
+        assert_eq!(&buf[..n], &[0x04, b'u', b'=', b'1']);
+        assert_eq!(decode_priority_update(&buf[..n]), Ok(update));
+    }
+
+    #[test]
+    fn priority_update_boundaries() {
+        assert_eq!(
+            decode_priority_update(&[]),
+            Err(Error::VarInt(varint::VarIntError::BufferTooSmall))
+        );
+        let update = PriorityUpdate {
+            element_id: 0,
+            field_value: b"",
+        };
+        assert_eq!(decode_priority_update(&[0x00]), Ok(update));
+
+        let mut small = [0u8; 2];
+        let update = PriorityUpdate {
+            element_id: 0,
+            field_value: b"u=1",
+        };
+        assert_eq!(
+            encode_priority_update(&update, &mut small),
+            Err(Error::BufferTooSmall)
+        );
+    }
+}
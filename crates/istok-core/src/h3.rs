@@ -1,2 +1,4 @@
 pub mod consts;
+pub mod grease;
 pub mod settings;
+pub mod validate;
@@ -6,16 +6,60 @@ pub const STREAM_TYPE_CONTROL: u64 = 0x00;
 pub const STREAM_TYPE_PUSH: u64 = 0x01;
 pub const STREAM_TYPE_QPACK_ENCODER: u64 = 0x02;
 pub const STREAM_TYPE_QPACK_DECODER: u64 = 0x03;
+/// WebTransport unidirectional stream (draft-ietf-webtrans-http3 §4.2).
+pub const STREAM_TYPE_WEBTRANSPORT: u64 = 0x54;
 
 /// Frame types
 pub const FRAME_TYPE_DATA: u64 = 0x00;
 pub const FRAME_TYPE_HEADERS: u64 = 0x01;
+pub const FRAME_TYPE_CANCEL_PUSH: u64 = 0x03;
 pub const FRAME_TYPE_SETTINGS: u64 = 0x04;
+pub const FRAME_TYPE_PUSH_PROMISE: u64 = 0x05;
 pub const FRAME_TYPE_GOAWAY: u64 = 0x07;
-// Add more as needed (CANCEL_PUSH=0x03, MAX_PUSH_ID=0x0D, etc.)
+/// ORIGIN, sent by servers on the control stream (RFC 9412 §2).
+pub const FRAME_TYPE_ORIGIN: u64 = 0x0c;
+pub const FRAME_TYPE_MAX_PUSH_ID: u64 = 0x0d;
+/// Signal value opening a WebTransport bidirectional stream, sent in place of
+/// a frame type (draft-ietf-webtrans-http3 §4.3).
+pub const FRAME_TYPE_WEBTRANSPORT_STREAM: u64 = 0x41;
+/// PRIORITY_UPDATE for a request stream / push (RFC 9218 §7.2).
+pub const FRAME_TYPE_PRIORITY_UPDATE_REQUEST: u64 = 0xf_0700;
+pub const FRAME_TYPE_PRIORITY_UPDATE_PUSH: u64 = 0xf_0701;
+/// HTTP/2 frame types with no HTTP/3 meaning; receiving one is
+/// `H3_FRAME_UNEXPECTED` (RFC 9114 §7.2.8).
+pub const FRAME_TYPES_HTTP2_RESERVED: [u64; 4] = [0x02, 0x06, 0x08, 0x09];
+
+/// SETTINGS identifiers
+pub const SETTINGS_MAX_FIELD_SECTION_SIZE: u64 = 0x06;
+pub const SETTINGS_ENABLE_CONNECT_PROTOCOL: u64 = 0x08;
+pub const SETTINGS_H3_DATAGRAM: u64 = 0x33;
+pub const SETTINGS_WEBTRANSPORT_MAX_SESSIONS: u64 = 0xc671_706a;
+
+/// Capsule types (RFC 9297, draft-ietf-webtrans-http3)
+pub const CAPSULE_TYPE_DATAGRAM: u64 = 0x00;
+pub const CAPSULE_TYPE_CLOSE_WEBTRANSPORT_SESSION: u64 = 0x2843;
+pub const CAPSULE_TYPE_DRAIN_WEBTRANSPORT_SESSION: u64 = 0x78ae;
 
 /// Common error codes (subset; expand later)
 pub const H3_NO_ERROR: u64 = 0x0100;
 pub const H3_GENERAL_PROTOCOL_ERROR: u64 = 0x0101;
-pub const H3_FRAME_UNEXPECTED: u64 = 0x0103;
+pub const H3_INTERNAL_ERROR: u64 = 0x0102;
+pub const H3_STREAM_CREATION_ERROR: u64 = 0x0103;
+/// A control or QPACK stream was closed (RFC 9114 §6.2.1, RFC 9204 §4.2).
+pub const H3_CLOSED_CRITICAL_STREAM: u64 = 0x0104;
+pub const H3_FRAME_UNEXPECTED: u64 = 0x0105;
 pub const H3_FRAME_ERROR: u64 = 0x0106;
+pub const H3_EXCESSIVE_LOAD: u64 = 0x0107;
+pub const H3_ID_ERROR: u64 = 0x0108;
+pub const H3_SETTINGS_ERROR: u64 = 0x0109;
+pub const H3_REQUEST_REJECTED: u64 = 0x010b;
+pub const H3_REQUEST_CANCELLED: u64 = 0x010c;
+pub const H3_REQUEST_INCOMPLETE: u64 = 0x010d;
+pub const H3_MESSAGE_ERROR: u64 = 0x010e;
+pub const H3_CONNECT_ERROR: u64 = 0x010f;
+pub const H3_DATAGRAM_ERROR: u64 = 0x33;
+pub const H3_QPACK_DECOMPRESSION_FAILED: u64 = 0x0200;
+pub const H3_QPACK_ENCODER_STREAM_ERROR: u64 = 0x0201;
+pub const H3_QPACK_DECODER_STREAM_ERROR: u64 = 0x0202;
+pub const H3_WEBTRANSPORT_BUFFERED_STREAM_REJECTED: u64 = 0x3994_bd84;
+pub const H3_WEBTRANSPORT_SESSION_GONE: u64 = 0x170d_7b68;
@@ -0,0 +1,107 @@
+//! GREASE values for HTTP/3 (RFC 9114 §6.2.3, §7.2.4.1, §7.2.8).
+//!
+//! Stream types, frame types and setting identifiers of the form
+//! `0x1f * N + 0x21` are reserved: receivers must ignore them, so senders
+//! may emit them to exercise that path. `Rng` picks the values; it is a
+//! seeded SplitMix64 so that runs with the same seed are byte-identical.
+//!
+//! Invariants:
+//! - `reserved(n)` is a valid varint for every `n <= MAX_N`, and
+//!   `Rng::reserved` only returns such values.
+//! - `Rng` is not cryptographic; it only needs to be unpredictable enough
+//!   that peers cannot special-case the values.
+//! - No allocation.
+
+use crate::codec::varint::VARINT_MAX;
+
+/// Largest `N` whose reserved value still fits in a varint.
+pub const MAX_N: u64 = (VARINT_MAX - 0x21) / 0x1f;
+
+/// The `n`th reserved value.
+pub const fn reserved(n: u64) -> u64 {
+    0x1f * n + 0x21
+}
+
+/// Whether `value` is a reserved (GREASE) stream type, frame type or
+/// setting identifier.
+pub const fn is_reserved(value: u64) -> bool {
+    value >= 0x21 && (value - 0x21).is_multiple_of(0x1f)
+}
+
+/// Deterministic pseudo-random source for GREASE values.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Generator seeded with `seed`; equal seeds give equal sequences.
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Next value of the sequence (SplitMix64).
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform-enough value in `0..bound`; `0` when `bound` is `0`.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        match bound {
+            0 => 0,
+            _ => self.next_u64() % bound,
+        }
+    }
+
+    /// A random reserved value.
+    pub fn reserved(&mut self) -> u64 {
+        reserved(self.below(MAX_N + 1))
+    }
+
+    /// Overwrite `out` with pseudo-random bytes.
+    pub fn fill(&mut self, out: &mut [u8]) {
+        for chunk in out.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::varint;
+
+    #[test]
+    fn reserved_values() {
+        assert_eq!(reserved(0), 0x21);
+        assert_eq!(reserved(1), 0x40);
+        assert!(is_reserved(0x21) && is_reserved(0x40) && is_reserved(reserved(MAX_N)));
+        assert!(!is_reserved(0x00) && !is_reserved(0x20) && !is_reserved(0x41));
+        assert!(varint::encoded_len(reserved(MAX_N)).is_ok());
+        assert!(varint::encoded_len(reserved(MAX_N + 1)).is_err());
+    }
+
+    #[test]
+    fn rng_is_deterministic_per_seed() {
+        let (mut a, mut b, mut c) = (Rng::new(7), Rng::new(7), Rng::new(8));
+        for _ in 0..64 {
+            let value = a.reserved();
+            assert!(is_reserved(value));
+            assert!(varint::encoded_len(value).is_ok());
+            assert_eq!(value, b.reserved());
+        }
+        assert_ne!(a.next_u64(), c.next_u64());
+        assert_eq!(a.below(0), 0);
+        assert!(a.below(3) < 3);
+
+        let (mut x, mut y) = ([0u8; 11], [0u8; 11]);
+        Rng::new(1).fill(&mut x);
+        Rng::new(1).fill(&mut y);
+        assert_eq!(x, y);
+    }
+}
@@ -1,29 +1,70 @@
 //! SETTINGS frame payload encoding/decoding.
 //!
 //! RFC 9114 defines SETTINGS as a sequence of (identifier, value) varint pairs.
+//!
+//! Invariants:
+//! - Unknown identifiers are skipped on decode (RFC 9114 §7.2.4).
+//! - A known identifier that appears twice is rejected as `DuplicateSetting`.
+//! - Identifiers reserved for HTTP/2 (0x02..=0x05) are rejected as `ReservedSetting`.
 
 use core::fmt;
 
-/// SETTINGS payload encoding errors.
+use crate::codec::varint;
+use crate::h3::consts;
+
+/// SETTINGS payload encoding/decoding errors.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Error {
     BufferTooSmall,
+    /// Payload ends in the middle of an (identifier, value) pair.
+    Truncated,
+    /// A known setting identifier appeared more than once.
+    DuplicateSetting(u64),
+    /// An HTTP/2 setting identifier that HTTP/3 forbids.
+    ReservedSetting(u64),
+    /// A known setting carries a value outside its permitted range.
+    InvalidValue {
+        id: u64,
+        value: u64,
+    },
+    VarInt(varint::VarIntError),
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Error::BufferTooSmall => write!(f, "buffer too small"),
+            Error::Truncated => write!(f, "truncated setting pair"),
+            Error::DuplicateSetting(id) => write!(f, "duplicate setting {id:#x}"),
+            Error::ReservedSetting(id) => write!(f, "reserved HTTP/2 setting {id:#x}"),
+            Error::InvalidValue { id, value } => {
+                write!(f, "invalid value {value} for setting {id:#x}")
+            }
+            Error::VarInt(inner) => write!(f, "varint error: {inner}"),
         }
     }
 }
 
+impl From<varint::VarIntError> for Error {
+    fn from(value: varint::VarIntError) -> Self {
+        Self::VarInt(value)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct Settings {
-    // Start minimal; expand later.
-    // Examples:
-    // pub max_field_section_size: Option<u64>,
-    // pub enable_connect_protocol: Option<u64>,
+    /// `SETTINGS_MAX_FIELD_SECTION_SIZE` (RFC 9114 §7.2.4.1): largest field
+    /// section, in RFC 9114 §4.2.2 units, the sender is willing to accept.
+    pub max_field_section_size: Option<u64>,
+    /// `SETTINGS_ENABLE_CONNECT_PROTOCOL` (RFC 9220 §3): `Some(1)` allows
+    /// extended CONNECT with a `:protocol` pseudo-header. Only 0 and 1 are valid.
+    pub enable_connect_protocol: Option<u64>,
+    /// `SETTINGS_H3_DATAGRAM` (RFC 9297 §2.1.1): `Some(1)` advertises support
+    /// for HTTP Datagrams. Only 0 and 1 are valid.
+    pub h3_datagram: Option<u64>,
+    /// `SETTINGS_WEBTRANSPORT_MAX_SESSIONS` (draft-ietf-webtrans-http3 §3.1):
+    /// number of concurrent WebTransport sessions the sender accepts.
+    pub webtransport_max_sessions: Option<u64>,
 }
 
 impl Settings {
@@ -31,19 +72,127 @@ impl Settings {
         Self::default()
     }
 
+    /// Returns `true` when these settings advertise HTTP Datagram support.
+    pub fn datagrams_enabled(&self) -> bool {
+        self.h3_datagram == Some(1)
+    }
+
+    /// Returns `true` when these settings allow extended CONNECT.
+    pub fn extended_connect_enabled(&self) -> bool {
+        self.enable_connect_protocol == Some(1)
+    }
+
+    /// Returns `true` when these settings advertise WebTransport: extended
+    /// CONNECT, HTTP Datagrams and at least one session.
+    pub fn webtransport_enabled(&self) -> bool {
+        self.extended_connect_enabled()
+            && self.datagrams_enabled()
+            && self.webtransport_max_sessions.is_some_and(|n| n > 0)
+    }
+
+    /// Number of bytes `encode_payload` will write.
+    pub fn encoded_len(&self) -> Result<usize, Error> {
+        let mut total = 0;
+        for (id, value) in self.pairs().into_iter().flatten() {
+            total += varint::encoded_len(id)? + varint::encoded_len(value)?;
+        }
+        Ok(total)
+    }
+
     /// Encodes this SETTINGS payload into `out`.
     ///
-    /// Minimal M0 behavior: empty settings are valid and encode to 0 bytes.
+    /// Empty settings are valid and encode to 0 bytes. Settings that are
+    /// `None` are omitted from the payload.
     pub fn encode_payload(&self, out: &mut [u8]) -> Result<usize, Error> {
-        let _ = self;
-        let _ = out;
-        Ok(0)
+        if out.len() < self.encoded_len()? {
+            return Err(Error::BufferTooSmall);
+        }
+
+        let mut written = 0;
+        for (id, value) in self.pairs().into_iter().flatten() {
+            written += varint::encode(id, &mut out[written..])?;
+            written += varint::encode(value, &mut out[written..])?;
+        }
+        Ok(written)
+    }
+
+    /// Decodes a complete SETTINGS payload.
+    ///
+    /// `payload` must contain exactly the frame payload; a trailing partial
+    /// pair is reported as `Truncated`.
+    pub fn decode_payload(payload: &[u8]) -> Result<Self, Error> {
+        let mut settings = Self::new();
+        let mut pos = 0;
+
+        while pos < payload.len() {
+            let (id, id_len) = varint::decode(&payload[pos..]).map_err(truncated)?;
+            pos += id_len;
+            let (value, value_len) = varint::decode(&payload[pos..]).map_err(truncated)?;
+            pos += value_len;
+
+            match id {
+                0x02..=0x05 => return Err(Error::ReservedSetting(id)),
+                consts::SETTINGS_MAX_FIELD_SECTION_SIZE => {
+                    if settings.max_field_section_size.is_some() {
+                        return Err(Error::DuplicateSetting(id));
+                    }
+                    settings.max_field_section_size = Some(value);
+                }
+                consts::SETTINGS_ENABLE_CONNECT_PROTOCOL => {
+                    set_flag(&mut settings.enable_connect_protocol, id, value)?;
+                }
+                consts::SETTINGS_H3_DATAGRAM => {
+                    set_flag(&mut settings.h3_datagram, id, value)?;
+                }
+                consts::SETTINGS_WEBTRANSPORT_MAX_SESSIONS => {
+                    if settings.webtransport_max_sessions.is_some() {
+                        return Err(Error::DuplicateSetting(id));
+                    }
+                    settings.webtransport_max_sessions = Some(value);
+                }
+                // Unknown and GREASE identifiers MUST be ignored.
+                _ => {}
+            }
+        }
+
+        Ok(settings)
+    }
+
+    fn pairs(&self) -> [Option<(u64, u64)>; 4] {
+        [
+            self.max_field_section_size
+                .map(|v| (consts::SETTINGS_MAX_FIELD_SECTION_SIZE, v)),
+            self.enable_connect_protocol
+                .map(|v| (consts::SETTINGS_ENABLE_CONNECT_PROTOCOL, v)),
+            self.h3_datagram.map(|v| (consts::SETTINGS_H3_DATAGRAM, v)),
+            self.webtransport_max_sessions
+                .map(|v| (consts::SETTINGS_WEBTRANSPORT_MAX_SESSIONS, v)),
+        ]
+    }
+}
+
+/// Store a boolean (0/1) setting, rejecting duplicates and other values.
+fn set_flag(slot: &mut Option<u64>, id: u64, value: u64) -> Result<(), Error> {
+    if slot.is_some() {
+        return Err(Error::DuplicateSetting(id));
+    }
+    if value > 1 {
+        return Err(Error::InvalidValue { id, value });
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+fn truncated(err: varint::VarIntError) -> Error {
+    match err {
+        varint::VarIntError::BufferTooSmall => Error::Truncated,
+        other => Error::VarInt(other),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Settings;
+    use super::*;
 
     #[test]
     fn empty_settings_encode_to_zero_bytes() {
@@ -67,4 +216,121 @@ mod tests {
         assert_eq!(written, 0);
         assert_eq!(out, [0xAAu8; 8]);
     }
+
+    #[test]
+    fn roundtrip_h3_datagram() {
+        let settings = Settings {
+            h3_datagram: Some(1),
+            ..Settings::default()
+        };
+        let mut out = [0u8; 8];
+        let written = settings.encode_payload(&mut out).expect("encodes");
+        assert_eq!(&out[..written], &[0x33, 0x01]);
+
+        let decoded = Settings::decode_payload(&out[..written]).expect("decodes");
+        assert_eq!(decoded, settings);
+        assert!(decoded.datagrams_enabled());
+    }
+
+    #[test]
+    fn roundtrip_max_field_section_size() {
+        let settings = Settings {
+            max_field_section_size: Some(8192),
+            h3_datagram: Some(1),
+            ..Settings::default()
+        };
+        let mut out = [0u8; 8];
+        let written = settings.encode_payload(&mut out).expect("encodes");
+        assert_eq!(&out[..written], &[0x06, 0x60, 0x00, 0x33, 0x01]);
+        assert_eq!(Settings::decode_payload(&out[..written]), Ok(settings));
+        assert_eq!(
+            Settings::decode_payload(&[0x06, 0x01, 0x06, 0x01]),
+            Err(Error::DuplicateSetting(
+                consts::SETTINGS_MAX_FIELD_SECTION_SIZE
+            ))
+        );
+    }
+
+    #[test]
+    fn decode_skips_unknown_identifiers() {
+        // GREASE id 0x21 (2-byte varint 0x4021) with value 7, then H3_DATAGRAM=0.
+        let payload = [0x40, 0x21, 0x07, 0x33, 0x00];
+        let decoded = Settings::decode_payload(&payload).expect("decodes");
+        assert_eq!(decoded.h3_datagram, Some(0));
+        assert!(!decoded.datagrams_enabled());
+    }
+
+    #[test]
+    fn decode_rejects_duplicate_known_setting() {
+        let payload = [0x33, 0x01, 0x33, 0x01];
+        assert_eq!(
+            Settings::decode_payload(&payload),
+            Err(Error::DuplicateSetting(consts::SETTINGS_H3_DATAGRAM))
+        );
+    }
+
+    #[test]
+    fn decode_rejects_http2_identifiers() {
+        assert_eq!(
+            Settings::decode_payload(&[0x02, 0x00]),
+            Err(Error::ReservedSetting(0x02))
+        );
+    }
+
+    #[test]
+    fn decode_rejects_out_of_range_datagram_value() {
+        assert_eq!(
+            Settings::decode_payload(&[0x33, 0x02]),
+            Err(Error::InvalidValue {
+                id: consts::SETTINGS_H3_DATAGRAM,
+                value: 2
+            })
+        );
+    }
+
+    #[test]
+    fn roundtrip_webtransport_settings() {
+        let settings = Settings {
+            enable_connect_protocol: Some(1),
+            h3_datagram: Some(1),
+            webtransport_max_sessions: Some(1),
+            ..Settings::default()
+        };
+        let mut out = [0u8; 16];
+        let written = settings.encode_payload(&mut out).expect("encodes");
+        assert_eq!(written, settings.encoded_len().expect("len"));
+
+        let decoded = Settings::decode_payload(&out[..written]).expect("decodes");
+        assert_eq!(decoded, settings);
+        assert!(decoded.webtransport_enabled());
+    }
+
+    #[test]
+    fn webtransport_requires_connect_protocol_and_datagrams() {
+        let settings = Settings {
+            enable_connect_protocol: Some(1),
+            h3_datagram: Some(0),
+            webtransport_max_sessions: Some(4),
+            ..Settings::default()
+        };
+        assert!(!settings.webtransport_enabled());
+    }
+
+    #[test]
+    fn decode_truncated_pair() {
+        assert_eq!(Settings::decode_payload(&[0x33]), Err(Error::Truncated));
+    }
+
+    #[test]
+    fn encode_buffer_too_small() {
+        let settings = Settings {
+            h3_datagram: Some(1),
+            ..Settings::default()
+        };
+        let mut out = [0u8; 1];
+        assert_eq!(
+            settings.encode_payload(&mut out),
+            Err(Error::BufferTooSmall)
+        );
+    }
 }
@@ -0,0 +1,510 @@
+//! Request message validation (RFC 9114 §4.1–4.3).
+//!
+//! `RequestValidator` is fed field lines in order, typically from the
+//! `qpack::decode` visitor, and reports whether the request is malformed.
+//! `BodyLength` then checks the DATA frames of a message, in either
+//! direction, against its `content-length`. Malformed messages are reset
+//! with `H3_MESSAGE_ERROR` (RFC 9114 §4.1.2).
+//!
+//! Invariants:
+//! - Only the first violation is reported; later field lines are ignored.
+//! - Field names must be non-empty lowercase tokens; field values must not
+//!   contain NUL, CR or LF.
+//! - Pseudo-headers precede regular fields, appear at most once, and are
+//!   limited to the request set (`:method`, `:scheme`, `:authority`,
+//!   `:path`, `:protocol`).
+//! - Connection-specific fields are rejected; `te` may only be `trailers`.
+//! - CONNECT without `:protocol` carries `:authority` and no `:scheme` or
+//!   `:path`; every other request carries `:method`, `:scheme` and a
+//!   non-empty `:path`, plus `:authority` or `host` for `http`/`https`.
+//! - `content-length` must be a decimal number; repeated or comma-separated
+//!   values must all agree (RFC 9110 §8.6).
+//! - A body may not exceed its declared length, nor end short of it.
+//! - `:status` is exactly three digits in 100..=599; 1xx responses are
+//!   interim and precede the final response (RFC 9114 §4.1).
+//! - No allocation.
+
+use core::fmt;
+
+/// Why a message is malformed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Empty field name.
+    EmptyName,
+    /// Field name with an uppercase letter (RFC 9114 §4.2).
+    UppercaseName,
+    /// Field name with a character outside the token set.
+    InvalidName,
+    /// Field value containing NUL, CR or LF.
+    InvalidValue,
+    /// `connection`, `keep-alive`, `proxy-connection`, `transfer-encoding`
+    /// or `upgrade` (RFC 9114 §4.2).
+    ConnectionSpecific,
+    /// `te` with a value other than `trailers`.
+    InvalidTe,
+    /// Pseudo-header after a regular field (RFC 9114 §4.3).
+    PseudoAfterRegular,
+    /// Pseudo-header that is not defined for requests, including `:status`.
+    UnknownPseudo,
+    /// The same pseudo-header appeared twice.
+    DuplicatePseudo,
+    /// A mandatory pseudo-header is absent.
+    MissingPseudo,
+    /// CONNECT carrying `:scheme` or `:path` (RFC 9114 §4.4).
+    ConnectWithSchemeOrPath,
+    /// `:path` present but empty.
+    EmptyPath,
+    /// `content-length` that is not a number, or disagrees with another one.
+    InvalidContentLength,
+    /// More DATA than `content-length` declared.
+    BodyTooLong,
+    /// The message ended before `content-length` bytes of DATA.
+    BodyTooShort,
+    /// `:status` that is not a three-digit code in 100..=599.
+    InvalidStatus,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::EmptyName => write!(f, "empty field name"),
+            Error::UppercaseName => write!(f, "uppercase field name"),
+            Error::InvalidName => write!(f, "invalid field name"),
+            Error::InvalidValue => write!(f, "invalid field value"),
+            Error::ConnectionSpecific => write!(f, "connection-specific field"),
+            Error::InvalidTe => write!(f, "te field other than trailers"),
+            Error::PseudoAfterRegular => write!(f, "pseudo-header after regular field"),
+            Error::UnknownPseudo => write!(f, "pseudo-header not valid in a request"),
+            Error::DuplicatePseudo => write!(f, "duplicate pseudo-header"),
+            Error::MissingPseudo => write!(f, "missing mandatory pseudo-header"),
+            Error::ConnectWithSchemeOrPath => write!(f, "CONNECT with :scheme or :path"),
+            Error::EmptyPath => write!(f, "empty :path"),
+            Error::InvalidContentLength => write!(f, "invalid content-length"),
+            Error::BodyTooLong => write!(f, "body longer than content-length"),
+            Error::BodyTooShort => write!(f, "body shorter than content-length"),
+            Error::InvalidStatus => write!(f, "invalid :status"),
+        }
+    }
+}
+
+const METHOD: u8 = 1 << 0;
+const SCHEME: u8 = 1 << 1;
+const AUTHORITY: u8 = 1 << 2;
+const PATH: u8 = 1 << 3;
+const PROTOCOL: u8 = 1 << 4;
+
+/// Streaming checker for one request header section.
+#[derive(Debug, Clone, Default)]
+pub struct RequestValidator {
+    seen: u8,
+    regular_seen: bool,
+    connect: bool,
+    /// `:scheme` is `http` or `https`, which require an authority.
+    authority_required: bool,
+    has_host: bool,
+    content_length: Option<u64>,
+    error: Option<Error>,
+}
+
+impl RequestValidator {
+    /// A checker that has seen no field line yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check one field line.
+    pub fn field(&mut self, name: &[u8], value: &[u8]) {
+        if self.error.is_none()
+            && let Err(e) = self.check_field(name, value)
+        {
+            self.error = Some(e);
+        }
+    }
+
+    /// Declared `content-length`, if any. Only meaningful once `finish`
+    /// succeeded.
+    pub fn content_length(&self) -> Option<u64> {
+        self.content_length
+    }
+
+    /// Check the section as a whole once every field line has been seen.
+    pub fn finish(&self) -> Result<(), Error> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        if self.seen & METHOD == 0 {
+            return Err(Error::MissingPseudo);
+        }
+        if self.connect && self.seen & PROTOCOL == 0 {
+            if self.seen & (SCHEME | PATH) != 0 {
+                return Err(Error::ConnectWithSchemeOrPath);
+            }
+            if self.seen & AUTHORITY == 0 {
+                return Err(Error::MissingPseudo);
+            }
+            return Ok(());
+        }
+        if self.seen & (SCHEME | PATH) != SCHEME | PATH {
+            return Err(Error::MissingPseudo);
+        }
+        if self.authority_required && self.seen & AUTHORITY == 0 && !self.has_host {
+            return Err(Error::MissingPseudo);
+        }
+        Ok(())
+    }
+
+    fn check_field(&mut self, name: &[u8], value: &[u8]) -> Result<(), Error> {
+        if value.iter().any(|&b| matches!(b, 0x00 | b'\r' | b'\n')) {
+            return Err(Error::InvalidValue);
+        }
+
+        if let Some(pseudo) = name.strip_prefix(b":") {
+            if self.regular_seen {
+                return Err(Error::PseudoAfterRegular);
+            }
+            let bit = match pseudo {
+                b"method" => METHOD,
+                b"scheme" => SCHEME,
+                b"authority" => AUTHORITY,
+                b"path" => PATH,
+                b"protocol" => PROTOCOL,
+                _ => return Err(Error::UnknownPseudo),
+            };
+            if self.seen & bit != 0 {
+                return Err(Error::DuplicatePseudo);
+            }
+            self.seen |= bit;
+            match bit {
+                METHOD => self.connect = value == b"CONNECT",
+                SCHEME => self.authority_required = matches!(value, b"http" | b"https"),
+                PATH if value.is_empty() => return Err(Error::EmptyPath),
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        check_name(name)?;
+        self.regular_seen = true;
+        match name {
+            b"connection" | b"keep-alive" | b"proxy-connection" | b"transfer-encoding"
+            | b"upgrade" => Err(Error::ConnectionSpecific),
+            b"te" if value != b"trailers" => Err(Error::InvalidTe),
+            b"host" => {
+                self.has_host = true;
+                Ok(())
+            }
+            b"content-length" => {
+                let length = parse_content_length(value)?;
+                if self.content_length.is_some_and(|seen| seen != length) {
+                    return Err(Error::InvalidContentLength);
+                }
+                self.content_length = Some(length);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Parse a `content-length` value. A list of identical values such as
+/// `42, 42` is accepted as `42`.
+pub fn parse_content_length(value: &[u8]) -> Result<u64, Error> {
+    let mut length = None;
+    for part in value.split(|&b| b == b',') {
+        let digits = part.trim_ascii();
+        if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+            return Err(Error::InvalidContentLength);
+        }
+        let parsed = digits.iter().try_fold(0u64, |acc, &d| {
+            acc.checked_mul(10)?.checked_add(u64::from(d - b'0'))
+        });
+        match (parsed, length) {
+            (None, _) => return Err(Error::InvalidContentLength),
+            (Some(n), Some(seen)) if n != seen => return Err(Error::InvalidContentLength),
+            (Some(n), _) => length = Some(n),
+        }
+    }
+    length.ok_or(Error::InvalidContentLength)
+}
+
+/// Parse a `:status` value (RFC 9110 §15).
+pub fn parse_status(value: &[u8]) -> Result<u16, Error> {
+    let [a, b, c] = value else {
+        return Err(Error::InvalidStatus);
+    };
+    if ![a, b, c].iter().all(|d| d.is_ascii_digit()) {
+        return Err(Error::InvalidStatus);
+    }
+    let status = u16::from(a - b'0') * 100 + u16::from(b - b'0') * 10 + u16::from(c - b'0');
+    match status {
+        100..=599 => Ok(status),
+        _ => Err(Error::InvalidStatus),
+    }
+}
+
+/// Whether `status` is an interim (1xx) response, which is followed by
+/// more responses on the same stream.
+pub fn is_interim(status: u16) -> bool {
+    (100..200).contains(&status)
+}
+
+/// DATA payload bytes of one message, checked against its declared
+/// `content-length` (RFC 9114 §4.1.2). Without a declared length any body
+/// is accepted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BodyLength {
+    declared: Option<u64>,
+    received: u64,
+}
+
+impl BodyLength {
+    /// Nothing received yet of a body declared `declared` bytes long, or
+    /// of any length when `None`.
+    pub fn new(declared: Option<u64>) -> Self {
+        Self {
+            declared,
+            received: 0,
+        }
+    }
+
+    /// The `content-length` the message declared.
+    pub fn declared(&self) -> Option<u64> {
+        self.declared
+    }
+
+    /// Total DATA payload bytes counted so far.
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// Count a DATA frame of `len` payload bytes. Fails as soon as the body
+    /// would exceed the declared length, before the bytes are relayed.
+    pub fn on_data(&mut self, len: u64) -> Result<(), Error> {
+        let received = self.received.checked_add(len).ok_or(Error::BodyTooLong)?;
+        if self.declared.is_some_and(|declared| received > declared) {
+            return Err(Error::BodyTooLong);
+        }
+        self.received = received;
+        Ok(())
+    }
+
+    /// The message ended (FIN): the body must have reached the declared length.
+    pub fn on_end(&self) -> Result<(), Error> {
+        match self.declared {
+            Some(declared) if self.received != declared => Err(Error::BodyTooShort),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn check_name(name: &[u8]) -> Result<(), Error> {
+    if name.is_empty() {
+        return Err(Error::EmptyName);
+    }
+    for &b in name {
+        if b.is_ascii_uppercase() {
+            return Err(Error::UppercaseName);
+        }
+        if !(b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)) {
+            return Err(Error::InvalidName);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(fields: &[(&[u8], &[u8])]) -> Result<(), Error> {
+        let mut v = RequestValidator::new();
+        for (name, value) in fields {
+            v.field(name, value);
+        }
+        v.finish()
+    }
+
+    const GET: [(&[u8], &[u8]); 4] = [
+        (b":method", b"GET"),
+        (b":scheme", b"https"),
+        (b":authority", b"example.com"),
+        (b":path", b"/"),
+    ];
+
+    #[test]
+    fn accepts_well_formed_requests() {
+        assert_eq!(validate(&GET), Ok(()));
+
+        let mut with_fields = GET.to_vec();
+        with_fields.extend([
+            (&b"te"[..], &b"trailers"[..]),
+            (b"accept", b"*/*"),
+            (b"x-empty", b""),
+        ]);
+        assert_eq!(validate(&with_fields), Ok(()));
+
+        let host_instead = [
+            (&b":method"[..], &b"GET"[..]),
+            (b":scheme", b"https"),
+            (b":path", b"/"),
+            (b"host", b"example.com"),
+        ];
+        assert_eq!(validate(&host_instead), Ok(()));
+
+        let connect = [
+            (&b":method"[..], &b"CONNECT"[..]),
+            (b":authority", b"example.com:443"),
+        ];
+        assert_eq!(validate(&connect), Ok(()));
+
+        let extended = [
+            (&b":method"[..], &b"CONNECT"[..]),
+            (b":protocol", b"webtransport"),
+            (b":scheme", b"https"),
+            (b":authority", b"example.com"),
+            (b":path", b"/chat"),
+        ];
+        assert_eq!(validate(&extended), Ok(()));
+    }
+
+    #[test]
+    fn rejects_bad_field_names_and_values() {
+        let with = |name: &'static [u8], value: &'static [u8]| {
+            let mut fields = GET.to_vec();
+            fields.push((name, value));
+            validate(&fields)
+        };
+        assert_eq!(with(b"Accept", b"*/*"), Err(Error::UppercaseName));
+        assert_eq!(with(b"", b"x"), Err(Error::EmptyName));
+        assert_eq!(with(b"bad name", b"x"), Err(Error::InvalidName));
+        assert_eq!(with(b"x-split", b"a\r\nb: c"), Err(Error::InvalidValue));
+        assert_eq!(with(b"x-nul", b"a\0"), Err(Error::InvalidValue));
+        for name in [
+            &b"connection"[..],
+            b"keep-alive",
+            b"proxy-connection",
+            b"transfer-encoding",
+            b"upgrade",
+        ] {
+            assert_eq!(with(name, b"x"), Err(Error::ConnectionSpecific));
+        }
+        assert_eq!(with(b"te", b"gzip"), Err(Error::InvalidTe));
+    }
+
+    #[test]
+    fn rejects_pseudo_header_misuse() {
+        let mut after = GET.to_vec();
+        after.insert(1, (b"accept", b"*/*"));
+        assert_eq!(validate(&after), Err(Error::PseudoAfterRegular));
+
+        let mut duplicate = GET.to_vec();
+        duplicate.push((b":path", b"/again"));
+        assert_eq!(validate(&duplicate), Err(Error::DuplicatePseudo));
+
+        let mut status = GET.to_vec();
+        status.insert(0, (b":status", b"200"));
+        assert_eq!(validate(&status), Err(Error::UnknownPseudo));
+
+        assert_eq!(validate(&GET[1..]), Err(Error::MissingPseudo));
+        assert_eq!(validate(&GET[..3]), Err(Error::MissingPseudo));
+        assert_eq!(
+            validate(&[GET[0], GET[1], GET[3]]),
+            Err(Error::MissingPseudo)
+        );
+        assert_eq!(validate(&[]), Err(Error::MissingPseudo));
+
+        let empty_path = [GET[0], GET[1], GET[2], (b":path", b"")];
+        assert_eq!(validate(&empty_path), Err(Error::EmptyPath));
+    }
+
+    #[test]
+    fn connect_rules() {
+        let with_path = [
+            (&b":method"[..], &b"CONNECT"[..]),
+            (b":authority", b"example.com:443"),
+            (b":path", b"/"),
+        ];
+        assert_eq!(validate(&with_path), Err(Error::ConnectWithSchemeOrPath));
+
+        let no_authority = [(&b":method"[..], &b"CONNECT"[..])];
+        assert_eq!(validate(&no_authority), Err(Error::MissingPseudo));
+    }
+
+    #[test]
+    fn content_length_values() {
+        assert_eq!(parse_content_length(b"0"), Ok(0));
+        assert_eq!(parse_content_length(b"42"), Ok(42));
+        assert_eq!(parse_content_length(b"42, 42"), Ok(42));
+        assert_eq!(parse_content_length(b"18446744073709551615"), Ok(u64::MAX));
+        for bad in [
+            &b""[..],
+            b"-1",
+            b"+1",
+            b"0x10",
+            b"1 2",
+            b"42, 43",
+            b"42,",
+            b"18446744073709551616",
+        ] {
+            assert_eq!(
+                parse_content_length(bad),
+                Err(Error::InvalidContentLength),
+                "{bad:?}"
+            );
+        }
+
+        let mut fields = GET.to_vec();
+        fields.extend([
+            (&b"content-length"[..], &b"5"[..]),
+            (b"content-length", b"5"),
+        ]);
+        let mut v = RequestValidator::new();
+        for (name, value) in &fields {
+            v.field(name, value);
+        }
+        assert_eq!(v.finish(), Ok(()));
+        assert_eq!(v.content_length(), Some(5));
+
+        fields.push((b"content-length", b"6"));
+        assert_eq!(validate(&fields), Err(Error::InvalidContentLength));
+    }
+
+    #[test]
+    fn body_length_tracking() {
+        let mut body = BodyLength::new(Some(5));
+        assert_eq!(body.on_data(2), Ok(()));
+        assert_eq!(body.on_end(), Err(Error::BodyTooShort));
+        assert_eq!(body.on_data(3), Ok(()));
+        assert_eq!(body.on_end(), Ok(()));
+        assert_eq!(body.on_data(1), Err(Error::BodyTooLong));
+        assert_eq!(body.received(), 5, "rejected bytes are not counted");
+
+        let mut empty = BodyLength::new(Some(0));
+        assert_eq!(empty.on_data(0), Ok(()));
+        assert_eq!(empty.on_data(1), Err(Error::BodyTooLong));
+        assert_eq!(empty.on_end(), Ok(()));
+
+        let mut undeclared = BodyLength::new(None);
+        assert_eq!(undeclared.on_data(u64::MAX), Ok(()));
+        assert_eq!(undeclared.on_data(1), Err(Error::BodyTooLong));
+        assert_eq!(undeclared.on_end(), Ok(()));
+    }
+
+    #[test]
+    fn status_values() {
+        assert_eq!(parse_status(b"100"), Ok(100));
+        assert_eq!(parse_status(b"103"), Ok(103));
+        assert_eq!(parse_status(b"599"), Ok(599));
+        for bad in [&b""[..], b"99", b"099", b"600", b"2000", b"20a", b" 200"] {
+            assert_eq!(parse_status(bad), Err(Error::InvalidStatus), "{bad:?}");
+        }
+        assert!(is_interim(100) && is_interim(103) && is_interim(199));
+        assert!(!is_interim(200) && !is_interim(99));
+    }
+
+    #[test]
+    fn first_error_wins() {
+        let fields = [(&b"Upper"[..], &b"x"[..]), (b":method", b"GET")];
+        assert_eq!(validate(&fields), Err(Error::UppercaseName));
+    }
+}
@@ -1,7 +1,15 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
+#[cfg(any(feature = "alloc", test))]
 extern crate alloc;
 
+#[cfg(feature = "alloc")]
+pub mod bytes;
 pub mod codec;
 pub mod error;
 pub mod h3;
+#[cfg(feature = "alloc")]
+pub mod pool;
+pub mod qpack;
+#[cfg(feature = "alloc")]
+pub mod sf;
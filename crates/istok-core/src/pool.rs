@@ -0,0 +1,189 @@
+//! Buffer pools: reuse `Vec<u8>` allocations across streams and writes
+//! instead of going back to the allocator for each one.
+//!
+//! `SlabPool` keeps free buffers in power-of-two size classes. A request is
+//! served from the smallest class that fits it; a returned buffer goes to
+//! the largest class its capacity covers.
+//!
+//! Invariants:
+//! - `take` always returns an empty buffer with at least the requested
+//!   capacity, pooled or not.
+//! - Every buffer in class `n` has a capacity of at least `MIN_CLASS << n`.
+//! - The pool never holds more than `max_free` buffers per class; buffers
+//!   past that, smaller than `MIN_CLASS` or larger than `MAX_CLASS` are
+//!   dropped.
+
+use alloc::vec::Vec;
+
+/// Smallest pooled buffer capacity.
+pub const MIN_CLASS: usize = 64;
+/// Largest pooled buffer capacity.
+pub const MAX_CLASS: usize = 64 * 1024;
+
+const CLASSES: usize = (MAX_CLASS / MIN_CLASS).trailing_zeros() as usize + 1;
+
+/// Source of reusable byte buffers.
+pub trait BufferPool {
+    /// An empty buffer with room for at least `capacity` bytes.
+    fn take(&mut self, capacity: usize) -> Vec<u8>;
+
+    /// Hand `buf` back for reuse. The pool may drop it instead.
+    fn give(&mut self, buf: Vec<u8>);
+
+    fn stats(&self) -> PoolStats;
+}
+
+/// Counters kept by a `BufferPool`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// `take` calls served with a pooled buffer.
+    pub hits: u64,
+    /// `take` calls that had to allocate.
+    pub misses: u64,
+    /// Buffers `give` kept for reuse.
+    pub returned: u64,
+}
+
+/// Pool of buffers in power-of-two size classes from `MIN_CLASS` to
+/// `MAX_CLASS`.
+#[derive(Debug)]
+pub struct SlabPool {
+    classes: [Vec<Vec<u8>>; CLASSES],
+    max_free: usize,
+    stats: PoolStats,
+}
+
+impl SlabPool {
+    /// A pool keeping up to `max_free` idle buffers per size class.
+    pub fn new(max_free: usize) -> Self {
+        Self {
+            classes: Default::default(),
+            max_free,
+            stats: PoolStats::default(),
+        }
+    }
+
+    /// Idle buffers held.
+    pub fn pooled(&self) -> usize {
+        self.classes.iter().map(Vec::len).sum()
+    }
+
+    /// Class whose buffers all fit `capacity` bytes.
+    fn class_for_take(capacity: usize) -> Option<usize> {
+        if capacity > MAX_CLASS {
+            return None;
+        }
+        let size = capacity.max(MIN_CLASS).next_power_of_two();
+        Some((size / MIN_CLASS).trailing_zeros() as usize)
+    }
+
+    /// Largest class a buffer of `capacity` bytes covers.
+    fn class_for_give(capacity: usize) -> Option<usize> {
+        if !(MIN_CLASS..=MAX_CLASS).contains(&capacity) {
+            return None;
+        }
+        Some((capacity / MIN_CLASS).ilog2() as usize)
+    }
+}
+
+impl Default for SlabPool {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
+
+impl BufferPool for SlabPool {
+    fn take(&mut self, capacity: usize) -> Vec<u8> {
+        let Some(class) = Self::class_for_take(capacity) else {
+            self.stats.misses += 1;
+            return Vec::with_capacity(capacity);
+        };
+        match self.classes.get_mut(class).and_then(Vec::pop) {
+            Some(buf) => {
+                self.stats.hits += 1;
+                buf
+            }
+            None => {
+                self.stats.misses += 1;
+                Vec::with_capacity(MIN_CLASS << class)
+            }
+        }
+    }
+
+    fn give(&mut self, mut buf: Vec<u8>) {
+        let Some(free) =
+            Self::class_for_give(buf.capacity()).and_then(|class| self.classes.get_mut(class))
+        else {
+            return;
+        };
+        if free.len() < self.max_free {
+            buf.clear();
+            free.push(buf);
+            self.stats.returned += 1;
+        }
+    }
+
+    fn stats(&self) -> PoolStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn returned_buffers_are_reused() {
+        let mut pool = SlabPool::default();
+        let mut buf = pool.take(100);
+        assert!(buf.capacity() >= 100);
+        buf.extend_from_slice(b"stale");
+        let ptr = buf.as_ptr();
+        pool.give(buf);
+
+        let again = pool.take(128);
+        assert_eq!(again.as_ptr(), ptr);
+        assert!(again.is_empty());
+        assert_eq!(
+            pool.stats(),
+            PoolStats {
+                hits: 1,
+                misses: 1,
+                returned: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn buffers_serve_only_requests_they_fit() {
+        let mut pool = SlabPool::default();
+        pool.give(Vec::with_capacity(100));
+        // Filed under 64 bytes, so a 100-byte request misses.
+        assert!(pool.take(100).capacity() >= 100);
+        assert_eq!(pool.stats().misses, 1);
+        assert!(pool.take(64).capacity() >= 64);
+        assert_eq!(pool.stats().hits, 1);
+    }
+
+    #[test]
+    fn odd_sizes_bypass_the_pool() {
+        let mut pool = SlabPool::default();
+        let huge = pool.take(MAX_CLASS + 1);
+        assert!(huge.capacity() > MAX_CLASS);
+        pool.give(huge);
+        pool.give(Vec::with_capacity(MIN_CLASS - 1));
+        assert_eq!(pool.pooled(), 0);
+        assert_eq!(pool.stats().returned, 0);
+    }
+
+    #[test]
+    fn idle_buffers_are_capped_per_class() {
+        let mut pool = SlabPool::new(2);
+        for _ in 0..3 {
+            pool.give(Vec::with_capacity(MIN_CLASS));
+        }
+        pool.give(Vec::with_capacity(MAX_CLASS));
+        assert_eq!(pool.pooled(), 3);
+        assert_eq!(pool.stats().returned, 3);
+    }
+}
@@ -0,0 +1,19 @@
+//! QPACK field compression (RFC 9204), static table only.
+//!
+//! See `docs/rfcs/0002-qpack-minimal.md`. No dynamic table, no Huffman.
+
+#[cfg(feature = "alloc")]
+pub mod cookie;
+pub mod decoder;
+pub mod encoder;
+pub mod static_table;
+
+pub use decoder::{DecodeError, decode};
+pub use encoder::{EncodeError, encode};
+
+/// A header field borrowing its name and value from caller data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderField<'a> {
+    pub name: &'a [u8],
+    pub value: &'a [u8],
+}
@@ -0,0 +1,223 @@
+//! Cookie crumbling and reassembly (RFC 9114 §4.2.1).
+//!
+//! A `cookie` field may be split into one field line per cookie-pair
+//! ("crumb") so that each crumb compresses on its own; the receiver joins
+//! them back with "; " before passing the field on.
+//!
+//! Invariants:
+//! - `crumbs` splits on `;` and drops the spaces that follow it; empty
+//!   crumbs are skipped, so crumbling never emits an empty field line.
+//! - `encode_crumbled` leaves every field other than `cookie` untouched and
+//!   keeps crumbs in their original order.
+//! - `decode_joined` reports each non-cookie field as it is decoded and one
+//!   joined `cookie` field after the last field line, or none if the block
+//!   has no cookie. Crumbs are joined in the order they appear.
+//! - `decode_joined` returns the field lines on the wire, each crumb
+//!   counted, so limits on field lines cannot be dodged by crumbling.
+
+use alloc::vec::Vec;
+
+use crate::qpack::HeaderField;
+use crate::qpack::decoder::{self, DecodeError};
+use crate::qpack::encoder::{self, EncodeError};
+
+const COOKIE: &[u8] = b"cookie";
+const DELIMITER: &[u8] = b"; ";
+
+/// Iterator over the cookie-pairs in a `cookie` value.
+#[derive(Debug, Clone)]
+pub struct Crumbs<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Iterator for Crumbs<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        loop {
+            let trimmed = self.rest.trim_ascii_start();
+            if trimmed.is_empty() {
+                self.rest = trimmed;
+                return None;
+            }
+            let (crumb, rest) = match trimmed.iter().position(|&b| b == b';') {
+                Some(i) => (&trimmed[..i], &trimmed[i + 1..]),
+                None => (trimmed, &[][..]),
+            };
+            self.rest = rest;
+            let crumb = crumb.trim_ascii_end();
+            if !crumb.is_empty() {
+                return Some(crumb);
+            }
+        }
+    }
+}
+
+/// Split a `cookie` value into its crumbs.
+pub fn crumbs(value: &[u8]) -> Crumbs<'_> {
+    Crumbs { rest: value }
+}
+
+/// Like `encoder::encode`, but each `cookie` field is emitted as one field
+/// line per crumb.
+///
+/// Returns `bytes_written`.
+pub fn encode_crumbled(fields: &[HeaderField<'_>], out: &mut [u8]) -> Result<usize, EncodeError> {
+    let mut pos = encoder::encode(&[], out)?;
+    for field in fields {
+        if field.name != COOKIE {
+            pos += encoder::encode_field(field, &mut out[pos..])?;
+            continue;
+        }
+        for value in crumbs(field.value) {
+            let crumb = HeaderField {
+                name: COOKIE,
+                value,
+            };
+            pos += encoder::encode_field(&crumb, &mut out[pos..])?;
+        }
+    }
+    Ok(pos)
+}
+
+/// Like `decoder::decode`, but repeated `cookie` field lines are joined
+/// with "; " and reported once, after every other field.
+///
+/// Returns the number of field lines in `input`, before joining.
+pub fn decode_joined<F>(input: &[u8], mut visitor: F) -> Result<usize, DecodeError>
+where
+    F: FnMut(&[u8], &[u8]),
+{
+    let mut cookie: Option<Vec<u8>> = None;
+    let mut lines = 0usize;
+    decoder::decode(input, |name, value| {
+        lines += 1;
+        if name != COOKIE {
+            visitor(name, value);
+            return;
+        }
+        match &mut cookie {
+            Some(joined) => {
+                joined.extend_from_slice(DELIMITER);
+                joined.extend_from_slice(value);
+            }
+            None => cookie = Some(value.to_vec()),
+        }
+    })?;
+    if let Some(joined) = cookie {
+        visitor(COOKIE, &joined);
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Fields = Vec<(Vec<u8>, Vec<u8>)>;
+
+    fn field<'a>(name: &'a [u8], value: &'a [u8]) -> HeaderField<'a> {
+        HeaderField { name, value }
+    }
+
+    fn collect(input: &[u8], joined: bool) -> Fields {
+        let mut fields = Vec::new();
+        let push = |name: &[u8], value: &[u8]| fields.push((name.to_vec(), value.to_vec()));
+        if joined {
+            decode_joined(input, push).unwrap();
+        } else {
+            decoder::decode(input, push).unwrap();
+        }
+        fields
+    }
+
+    #[test]
+    fn crumbs_split_on_semicolons() {
+        let split: Vec<&[u8]> = crumbs(b"a=1; b=2;c=3 ;  ; d=4;").collect();
+        assert_eq!(split, [&b"a=1"[..], b"b=2", b"c=3", b"d=4"]);
+        assert_eq!(crumbs(b"").count(), 0);
+        assert_eq!(crumbs(b" ; ").count(), 0);
+    }
+
+    #[test]
+    fn crumbled_cookie_is_one_field_line_per_pair() {
+        let fields = [
+            field(b":path", b"/"),
+            field(b"cookie", b"a=1; b=2; c=3"),
+            field(b"accept", b"*/*"),
+        ];
+        let mut out = [0u8; 128];
+        let n = encode_crumbled(&fields, &mut out).unwrap();
+
+        let lines = collect(&out[..n], false);
+        let names: Vec<&[u8]> = lines.iter().map(|(n, _)| n.as_slice()).collect();
+        assert_eq!(
+            names,
+            [&b":path"[..], b"cookie", b"cookie", b"cookie", b"accept"]
+        );
+        assert_eq!(lines[3].1, b"c=3");
+    }
+
+    #[test]
+    fn crumbled_cookie_roundtrips_through_joined_decode() {
+        let fields = [
+            field(b"cookie", b"a=1; b=2"),
+            field(b":path", b"/"),
+            field(b"cookie", b"c=3"),
+        ];
+        let mut out = [0u8; 128];
+        let n = encode_crumbled(&fields, &mut out).unwrap();
+
+        assert_eq!(
+            collect(&out[..n], true),
+            [
+                (b":path".to_vec(), b"/".to_vec()),
+                (b"cookie".to_vec(), b"a=1; b=2; c=3".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn plain_encoding_roundtrips_unchanged() {
+        let fields = [field(b"cookie", b"a=1; b=2"), field(b"accept", b"*/*")];
+        let mut crumbled = [0u8; 64];
+        let mut plain = [0u8; 64];
+        let n = encode_crumbled(&[fields[1]], &mut crumbled).unwrap();
+        let m = encoder::encode(&[fields[1]], &mut plain).unwrap();
+        assert_eq!(crumbled[..n], plain[..m]);
+
+        let m = encoder::encode(&fields, &mut plain).unwrap();
+        assert_eq!(
+            collect(&plain[..m], true),
+            [
+                (b"accept".to_vec(), b"*/*".to_vec()),
+                (b"cookie".to_vec(), b"a=1; b=2".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn joined_decode_without_cookie_matches_decode() {
+        let mut out = [0u8; 64];
+        let n = encoder::encode(&[field(b":method", b"GET"), field(b"x", b"y")], &mut out).unwrap();
+        assert_eq!(collect(&out[..n], true), collect(&out[..n], false));
+    }
+
+    #[test]
+    fn joined_decode_counts_every_crumb() {
+        let fields = [field(b":path", b"/"), field(b"cookie", b"a=1; b=2; c=3")];
+        let mut out = [0u8; 64];
+        let n = encode_crumbled(&fields, &mut out).unwrap();
+        let mut reported = 0;
+        assert_eq!(decode_joined(&out[..n], |_, _| reported += 1), Ok(4));
+        assert_eq!(reported, 2);
+    }
+
+    #[test]
+    fn joined_decode_propagates_errors() {
+        assert_eq!(
+            decode_joined(&[0x01, 0x00], |_, _| {}),
+            Err(DecodeError::DynamicTableRequired)
+        );
+    }
+}
@@ -0,0 +1,277 @@
+//! QPACK header block decoder, static table only (RFC 9204 §4.5).
+//!
+//! Invariants:
+//! - Blocks with Required Insert Count > 0, or any field line referencing the
+//!   dynamic table, are rejected with `DynamicTableRequired`.
+//! - Huffman-encoded strings (H=1) are rejected with `HuffmanNotSupported`.
+//! - The visitor is called once per field line, in order; names and values
+//!   borrow from the input or from the static table. No allocation.
+
+use core::fmt;
+
+use crate::codec::prefix_int::{self, PrefixIntError};
+use crate::qpack::static_table;
+
+/// QPACK decoding errors. All of them map to `H3_QPACK_DECOMPRESSION_FAILED`
+/// at the engine level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Input ends in the middle of the prefix or a field line.
+    UnexpectedEnd,
+    /// A prefix integer has an over-long extension chain.
+    IntegerOverflow,
+    /// Static table index outside 0..=98.
+    InvalidIndex,
+    /// Block needs dynamic table state, which this decoder does not keep.
+    DynamicTableRequired,
+    /// String literal uses Huffman coding.
+    HuffmanNotSupported,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of header block"),
+            DecodeError::IntegerOverflow => write!(f, "prefix integer overflow"),
+            DecodeError::InvalidIndex => write!(f, "invalid static table index"),
+            DecodeError::DynamicTableRequired => write!(f, "dynamic table required"),
+            DecodeError::HuffmanNotSupported => write!(f, "huffman strings not supported"),
+        }
+    }
+}
+
+impl From<PrefixIntError> for DecodeError {
+    fn from(value: PrefixIntError) -> Self {
+        match value {
+            PrefixIntError::BufferTooSmall => Self::UnexpectedEnd,
+            PrefixIntError::Overflow | PrefixIntError::ValueTooLarge => Self::IntegerOverflow,
+        }
+    }
+}
+
+/// Decode a QPACK header block, calling `visitor(name, value)` for each field line.
+pub fn decode<'a, F>(input: &'a [u8], mut visitor: F) -> Result<(), DecodeError>
+where
+    F: FnMut(&'a [u8], &'a [u8]),
+{
+    let (required_insert_count, ric_len) = prefix_int::decode(input, 8)?;
+    if required_insert_count != 0 {
+        return Err(DecodeError::DynamicTableRequired);
+    }
+    let (_delta_base, base_len) = prefix_int::decode(&input[ric_len..], 7)?;
+    let mut pos = ric_len + base_len;
+
+    while pos < input.len() {
+        let rest = &input[pos..];
+        let first = rest[0];
+
+        let consumed = if first & 0b1000_0000 != 0 {
+            // Indexed Field Line: 1 T Index(6+).
+            if first & 0b0100_0000 == 0 {
+                return Err(DecodeError::DynamicTableRequired);
+            }
+            let (index, n) = prefix_int::decode(rest, 6)?;
+            let (name, value) = static_entry(index)?;
+            visitor(name, value);
+            n
+        } else if first & 0b0100_0000 != 0 {
+            // Literal Field Line with Name Reference: 0 1 N T Index(4+).
+            if first & 0b0001_0000 == 0 {
+                return Err(DecodeError::DynamicTableRequired);
+            }
+            let (index, n) = prefix_int::decode(rest, 4)?;
+            let (name, _) = static_entry(index)?;
+            let (value, m) = decode_string(&rest[n..], 7)?;
+            visitor(name, value);
+            n + m
+        } else if first & 0b0010_0000 != 0 {
+            // Literal Field Line with Literal Name: 0 0 1 N H NameLen(3+).
+            let (name, n) = decode_string(rest, 3)?;
+            let (value, m) = decode_string(&rest[n..], 7)?;
+            visitor(name, value);
+            n + m
+        } else {
+            // Post-Base Indexed (0001) and Post-Base Name Reference (0000)
+            // only exist relative to the dynamic table.
+            return Err(DecodeError::DynamicTableRequired);
+        };
+
+        pos += consumed;
+    }
+
+    Ok(())
+}
+
+fn static_entry(index: u64) -> Result<(&'static [u8], &'static [u8]), DecodeError> {
+    usize::try_from(index)
+        .ok()
+        .and_then(static_table::entry)
+        .ok_or(DecodeError::InvalidIndex)
+}
+
+/// Decode a string literal whose H flag sits just above the `prefix_bits` length prefix.
+fn decode_string(input: &[u8], prefix_bits: u8) -> Result<(&[u8], usize), DecodeError> {
+    let first = *input.first().ok_or(DecodeError::UnexpectedEnd)?;
+    if first & (1 << prefix_bits) != 0 {
+        return Err(DecodeError::HuffmanNotSupported);
+    }
+
+    let (len, len_len) = prefix_int::decode(input, prefix_bits)?;
+    let len = usize::try_from(len).map_err(|_| DecodeError::UnexpectedEnd)?;
+    let end = len_len.checked_add(len).ok_or(DecodeError::UnexpectedEnd)?;
+    if input.len() < end {
+        return Err(DecodeError::UnexpectedEnd);
+    }
+
+    Ok((&input[len_len..end], end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qpack::{HeaderField, encoder};
+    use alloc::vec::Vec;
+
+    type Fields = Vec<(Vec<u8>, Vec<u8>)>;
+
+    fn collect(input: &[u8]) -> Result<Fields, DecodeError> {
+        let mut fields = Vec::new();
+        decode(input, |name, value| {
+            fields.push((name.to_vec(), value.to_vec()))
+        })?;
+        Ok(fields)
+    }
+
+    #[test]
+    fn indexed_field_line_static() {
+        let fields = collect(&[0x00, 0x00, 0xc0 | 25]).unwrap();
+        assert_eq!(fields, [(b":status".to_vec(), b"200".to_vec())]);
+    }
+
+    #[test]
+    fn literal_with_static_name_ref() {
+        let fields = collect(&[0x00, 0x00, 0x50 | 1, 0x04, b'/', b'a', b'b', b'c']).unwrap();
+        assert_eq!(fields, [(b":path".to_vec(), b"/abc".to_vec())]);
+    }
+
+    #[test]
+    fn literal_with_literal_name() {
+        let mut input = alloc::vec![0x00, 0x00, 0x20 | 0x03];
+        input.extend_from_slice(b"foo");
+        input.push(0x02);
+        input.extend_from_slice(b"hi");
+        let fields = collect(&input).unwrap();
+        assert_eq!(fields, [(b"foo".to_vec(), b"hi".to_vec())]);
+    }
+
+    #[test]
+    fn multiple_fields_in_order() {
+        let fields = collect(&[0x00, 0x00, 0xc0 | 17, 0xc0 | 23, 0xc0 | 1]).unwrap();
+        let names: Vec<&[u8]> = fields.iter().map(|(n, _)| n.as_slice()).collect();
+        assert_eq!(names, [&b":method"[..], b":scheme", b":path"]);
+    }
+
+    #[test]
+    fn non_zero_required_insert_count() {
+        assert_eq!(
+            collect(&[0x01, 0x00]),
+            Err(DecodeError::DynamicTableRequired)
+        );
+    }
+
+    #[test]
+    fn indexed_dynamic_reference() {
+        assert_eq!(
+            collect(&[0x00, 0x00, 0x80 | 0x01]),
+            Err(DecodeError::DynamicTableRequired)
+        );
+    }
+
+    #[test]
+    fn literal_name_ref_dynamic_reference() {
+        assert_eq!(
+            collect(&[0x00, 0x00, 0x40, 0x00]),
+            Err(DecodeError::DynamicTableRequired)
+        );
+    }
+
+    #[test]
+    fn post_base_representations() {
+        assert_eq!(
+            collect(&[0x00, 0x00, 0x10]),
+            Err(DecodeError::DynamicTableRequired)
+        );
+        assert_eq!(
+            collect(&[0x00, 0x00, 0x00]),
+            Err(DecodeError::DynamicTableRequired)
+        );
+    }
+
+    #[test]
+    fn index_out_of_range() {
+        assert_eq!(
+            collect(&[0x00, 0x00, 0xff, 99 - 63]),
+            Err(DecodeError::InvalidIndex)
+        );
+    }
+
+    #[test]
+    fn huffman_string() {
+        assert_eq!(
+            collect(&[0x00, 0x00, 0x50 | 1, 0x80 | 0x01, 0x00]),
+            Err(DecodeError::HuffmanNotSupported)
+        );
+        assert_eq!(
+            collect(&[0x00, 0x00, 0x20 | 0x08 | 0x01, b'a', 0x00]),
+            Err(DecodeError::HuffmanNotSupported)
+        );
+    }
+
+    #[test]
+    fn truncated_field() {
+        assert_eq!(
+            collect(&[0x00, 0x00, 0x50 | 1, 0x04, b'/']),
+            Err(DecodeError::UnexpectedEnd)
+        );
+        assert_eq!(collect(&[0x00]), Err(DecodeError::UnexpectedEnd));
+        assert_eq!(collect(&[]), Err(DecodeError::UnexpectedEnd));
+    }
+
+    #[test]
+    fn empty_block_after_prefix() {
+        let mut called = false;
+        assert_eq!(decode(&[0x00, 0x00], |_, _| called = true), Ok(()));
+        assert!(!called);
+    }
+
+    #[test]
+    fn roundtrip_with_encoder() {
+        let input = [
+            HeaderField {
+                name: b":method",
+                value: b"CONNECT",
+            },
+            HeaderField {
+                name: b":protocol",
+                value: b"webtransport",
+            },
+            HeaderField {
+                name: b":authority",
+                value: b"example.com",
+            },
+            HeaderField {
+                name: b"x-custom",
+                value: b"val",
+            },
+        ];
+        let mut out = [0u8; 128];
+        let n = encoder::encode(&input, &mut out).unwrap();
+
+        let fields = collect(&out[..n]).unwrap();
+        assert_eq!(fields.len(), input.len());
+        for (decoded, original) in fields.iter().zip(input.iter()) {
+            assert_eq!(decoded.0, original.name);
+            assert_eq!(decoded.1, original.value);
+        }
+    }
+}
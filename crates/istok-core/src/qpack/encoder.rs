@@ -0,0 +1,178 @@
+//! QPACK header block encoder, static table only (RFC 9204 §4.5).
+//!
+//! Invariants:
+//! - Always emits Required Insert Count = 0 and Delta Base = 0 (two zero bytes).
+//! - Field lines are emitted in input order; no Huffman (H=0).
+//! - No allocation: output goes into a caller-supplied `&mut [u8]`.
+
+use core::fmt;
+
+use crate::codec::prefix_int::{self, PrefixIntError};
+use crate::qpack::{HeaderField, static_table};
+
+/// QPACK encoding errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeError {
+    BufferTooSmall,
+    /// A name or value is too long for a prefix integer length.
+    ValueTooLarge,
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::BufferTooSmall => write!(f, "buffer too small"),
+            EncodeError::ValueTooLarge => write!(f, "field too large"),
+        }
+    }
+}
+
+impl From<PrefixIntError> for EncodeError {
+    fn from(value: PrefixIntError) -> Self {
+        match value {
+            PrefixIntError::ValueTooLarge => Self::ValueTooLarge,
+            PrefixIntError::BufferTooSmall | PrefixIntError::Overflow => Self::BufferTooSmall,
+        }
+    }
+}
+
+// Field line instruction patterns (RFC 9204 §4.5.2, §4.5.4, §4.5.6).
+const INDEXED_STATIC: u8 = 0b1100_0000;
+const LITERAL_STATIC_NAME_REF: u8 = 0b0101_0000;
+const LITERAL_LITERAL_NAME: u8 = 0b0010_0000;
+
+/// Encode `fields` into a QPACK header block.
+///
+/// Returns `bytes_written`.
+pub fn encode(fields: &[HeaderField<'_>], out: &mut [u8]) -> Result<usize, EncodeError> {
+    if out.len() < 2 {
+        return Err(EncodeError::BufferTooSmall);
+    }
+    // Required Insert Count = 0, S = 0, Delta Base = 0.
+    out[0] = 0;
+    out[1] = 0;
+    let mut pos = 2;
+
+    for field in fields {
+        pos += encode_field(field, &mut out[pos..])?;
+    }
+
+    Ok(pos)
+}
+
+pub(super) fn encode_field(field: &HeaderField<'_>, out: &mut [u8]) -> Result<usize, EncodeError> {
+    if out.is_empty() {
+        return Err(EncodeError::BufferTooSmall);
+    }
+
+    match static_table::lookup(field.name, field.value) {
+        Some((index, true)) => {
+            out[0] = INDEXED_STATIC;
+            Ok(prefix_int::encode(index as u64, 6, out)?)
+        }
+        Some((index, false)) => {
+            out[0] = LITERAL_STATIC_NAME_REF;
+            let mut pos = prefix_int::encode(index as u64, 4, out)?;
+            pos += encode_string(field.value, 7, &mut out[pos..])?;
+            Ok(pos)
+        }
+        None => {
+            out[0] = LITERAL_LITERAL_NAME;
+            let mut pos = encode_string_with_first(field.name, 3, out)?;
+            pos += encode_string(field.value, 7, &mut out[pos..])?;
+            Ok(pos)
+        }
+    }
+}
+
+/// Encode a string literal whose length prefix starts a fresh byte (H=0).
+fn encode_string(s: &[u8], prefix_bits: u8, out: &mut [u8]) -> Result<usize, EncodeError> {
+    if out.is_empty() {
+        return Err(EncodeError::BufferTooSmall);
+    }
+    out[0] = 0;
+    encode_string_with_first(s, prefix_bits, out)
+}
+
+/// Encode a string literal whose length prefix shares `out[0]` with
+/// instruction bits the caller already wrote (H bit left at 0).
+fn encode_string_with_first(
+    s: &[u8],
+    prefix_bits: u8,
+    out: &mut [u8],
+) -> Result<usize, EncodeError> {
+    let len_len = prefix_int::encode(s.len() as u64, prefix_bits, out)?;
+    let end = len_len
+        .checked_add(s.len())
+        .ok_or(EncodeError::ValueTooLarge)?;
+    if out.len() < end {
+        return Err(EncodeError::BufferTooSmall);
+    }
+    out[len_len..end].copy_from_slice(s);
+    Ok(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field<'a>(name: &'a [u8], value: &'a [u8]) -> HeaderField<'a> {
+        HeaderField { name, value }
+    }
+
+    #[test]
+    fn exact_hit_is_indexed_field_line() {
+        let mut out = [0u8; 16];
+        let n = encode(&[field(b":status", b"200")], &mut out).unwrap();
+        assert_eq!(&out[..n], &[0x00, 0x00, 0xc0 | 25]);
+    }
+
+    #[test]
+    fn name_hit_is_literal_with_static_name_ref() {
+        let mut out = [0u8; 16];
+        let n = encode(&[field(b":status", b"999")], &mut out).unwrap();
+        assert_eq!(
+            &out[..n],
+            &[0x00, 0x00, 0x50 | 0x0f, 24 - 15, 0x03, b'9', b'9', b'9']
+        );
+    }
+
+    #[test]
+    fn miss_is_literal_with_literal_name() {
+        let mut out = [0u8; 32];
+        let n = encode(&[field(b"x-custom", b"val")], &mut out).unwrap();
+        let mut expected = alloc::vec![0x00, 0x00, 0x20 | 0x07, 0x01];
+        expected.extend_from_slice(b"x-custom");
+        expected.push(0x03);
+        expected.extend_from_slice(b"val");
+        assert_eq!(&out[..n], expected.as_slice());
+    }
+
+    #[test]
+    fn multiple_fields_preserve_order() {
+        let mut out = [0u8; 16];
+        let n = encode(
+            &[field(b":method", b"GET"), field(b":path", b"/")],
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(&out[..n], &[0x00, 0x00, 0xc0 | 17, 0xc0 | 1]);
+    }
+
+    #[test]
+    fn empty_field_list_is_prefix_only() {
+        let mut out = [0xffu8; 4];
+        let n = encode(&[], &mut out).unwrap();
+        assert_eq!(&out[..n], &[0x00, 0x00]);
+    }
+
+    #[test]
+    fn buffer_too_small() {
+        let mut out = [0u8; 6];
+        assert_eq!(
+            encode(&[field(b"x-custom", b"val")], &mut out),
+            Err(EncodeError::BufferTooSmall)
+        );
+        assert_eq!(encode(&[], &mut out[..1]), Err(EncodeError::BufferTooSmall));
+    }
+}
@@ -0,0 +1,192 @@
+//! Structured Field Values for HTTP (RFC 8941).
+//!
+//! Parses and serializes the three top-level types — Items, Lists and
+//! Dictionaries — used by modern header fields such as `priority`,
+//! `cache-status` and `proxy-status`.
+//!
+//! Invariants:
+//! - Parsing follows RFC 8941 §4.2 exactly; any deviation is `Syntax` with
+//!   the byte offset where parsing stopped. Multiple field lines must be
+//!   joined with `", "` by the caller before parsing.
+//! - Decimals are held as exact thousandths; no floating point is used.
+//! - Parameters and Dictionaries keep insertion order; a repeated key
+//!   overwrites the earlier value in place.
+//! - Serialization follows RFC 8941 §4.1 and fails on values the format
+//!   cannot represent (out-of-range numbers, bad strings, tokens or keys).
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+mod parse;
+mod serialize;
+
+pub use parse::{parse_dictionary, parse_item, parse_list};
+pub use serialize::{serialize_dictionary, serialize_item, serialize_list};
+
+/// Largest magnitude of an Integer (15 decimal digits).
+pub const MAX_INTEGER: i64 = 999_999_999_999_999;
+/// Largest magnitude of a Decimal in thousandths (12 integer + 3 fractional digits).
+pub const MAX_DECIMAL_THOUSANDTHS: i64 = 999_999_999_999_999;
+
+/// A Decimal with three fractional digits of precision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Decimal(i64);
+
+impl Decimal {
+    /// The Decimal `thousandths / 1000`.
+    pub const fn from_thousandths(thousandths: i64) -> Self {
+        Self(thousandths)
+    }
+
+    /// The value in thousandths.
+    pub const fn thousandths(self) -> i64 {
+        self.0
+    }
+}
+
+/// A bare item (RFC 8941 §3.3).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BareItem {
+    Integer(i64),
+    Decimal(Decimal),
+    /// Printable ASCII only.
+    String(String),
+    Token(String),
+    ByteSequence(Vec<u8>),
+    Boolean(bool),
+}
+
+/// Ordered key/value pairs attached to an item or inner list.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Parameters(pub Vec<(String, BareItem)>);
+
+impl Parameters {
+    /// The value of `key`, if present.
+    pub fn get(&self, key: &str) -> Option<&BareItem> {
+        self.0.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    /// Set `key`, overwriting an existing value in place.
+    pub fn insert(&mut self, key: String, value: BareItem) {
+        match self.0.iter_mut().find(|(k, _)| *k == key) {
+            Some(slot) => slot.1 = value,
+            None => self.0.push((key, value)),
+        }
+    }
+
+    /// Whether there are no parameters.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// An Item: a bare item with parameters (RFC 8941 §3.3).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+    /// The value.
+    pub bare: BareItem,
+    /// Parameters of the value.
+    pub params: Parameters,
+}
+
+impl Item {
+    /// An item without parameters.
+    pub fn new(bare: BareItem) -> Self {
+        Self {
+            bare,
+            params: Parameters::default(),
+        }
+    }
+}
+
+/// An Inner List: items with parameters of the whole list (RFC 8941 §3.1.1).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InnerList {
+    /// The members, in order.
+    pub items: Vec<Item>,
+    /// Parameters of the inner list.
+    pub params: Parameters,
+}
+
+/// A List member or Dictionary value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListEntry {
+    Item(Item),
+    InnerList(InnerList),
+}
+
+/// A List (RFC 8941 §3.1).
+pub type List = Vec<ListEntry>;
+
+/// Ordered Dictionary members.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dictionary(pub Vec<(String, ListEntry)>);
+
+impl Dictionary {
+    /// The member `key`, if present.
+    pub fn get(&self, key: &str) -> Option<&ListEntry> {
+        self.0.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    /// Set `key`, overwriting an existing member in place.
+    pub fn insert(&mut self, key: String, value: ListEntry) {
+        match self.0.iter_mut().find(|(k, _)| *k == key) {
+            Some(slot) => slot.1 = value,
+            None => self.0.push((key, value)),
+        }
+    }
+
+    /// Whether there are no members.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Why parsing or serialization failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Input is not a valid field value; parsing stopped at `offset`.
+    Syntax { offset: usize },
+    /// Integer outside `-MAX_INTEGER..=MAX_INTEGER`.
+    IntegerOutOfRange,
+    /// Decimal with more than 12 integer digits.
+    DecimalOutOfRange,
+    /// String with a character outside printable ASCII.
+    InvalidString,
+    /// Token that does not match the token grammar.
+    InvalidToken,
+    /// Key that does not match the key grammar.
+    InvalidKey,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Syntax { offset } => write!(f, "invalid structured field at byte {offset}"),
+            Error::IntegerOutOfRange => write!(f, "integer out of range"),
+            Error::DecimalOutOfRange => write!(f, "decimal out of range"),
+            Error::InvalidString => write!(f, "invalid string"),
+            Error::InvalidToken => write!(f, "invalid token"),
+            Error::InvalidKey => write!(f, "invalid key"),
+        }
+    }
+}
+
+/// RFC 9110 `tchar`.
+fn is_tchar(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+/// Characters allowed after the first one of a token.
+fn is_token_char(b: u8) -> bool {
+    is_tchar(b) || b == b':' || b == b'/'
+}
+
+fn is_key_start(b: u8) -> bool {
+    b.is_ascii_lowercase() || b == b'*'
+}
+
+fn is_key_char(b: u8) -> bool {
+    b.is_ascii_lowercase() || b.is_ascii_digit() || b"_-.*".contains(&b)
+}
@@ -0,0 +1,471 @@
+//! Structured Field parsing (RFC 8941 §4.2).
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::{
+    BareItem, Decimal, Dictionary, Error, InnerList, Item, List, ListEntry, Parameters,
+    is_key_char, is_key_start, is_token_char,
+};
+
+/// Parse a field value as an Item.
+pub fn parse_item(input: &[u8]) -> Result<Item, Error> {
+    let mut p = Parser::new(input);
+    let item = p.item()?;
+    p.finish()?;
+    Ok(item)
+}
+
+/// Parse a field value as a List.
+pub fn parse_list(input: &[u8]) -> Result<List, Error> {
+    let mut p = Parser::new(input);
+    let mut list = List::new();
+    while !p.at_end() {
+        list.push(p.list_entry()?);
+        if !p.next_member()? {
+            break;
+        }
+    }
+    p.finish()?;
+    Ok(list)
+}
+
+/// Parse a field value as a Dictionary.
+pub fn parse_dictionary(input: &[u8]) -> Result<Dictionary, Error> {
+    let mut p = Parser::new(input);
+    let mut dict = Dictionary::default();
+    while !p.at_end() {
+        let key = p.key()?;
+        let value = if p.eat(b'=') {
+            p.list_entry()?
+        } else {
+            ListEntry::Item(Item {
+                bare: BareItem::Boolean(true),
+                params: p.parameters()?,
+            })
+        };
+        dict.insert(key, value);
+        if !p.next_member()? {
+            break;
+        }
+    }
+    p.finish()?;
+    Ok(dict)
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Leading SP is discarded (RFC 8941 §4.2 step 2).
+    fn new(input: &'a [u8]) -> Self {
+        let mut p = Self { input, pos: 0 };
+        p.skip_sp();
+        p
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, b: u8) -> bool {
+        if self.peek() == Some(b) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.input.len()
+    }
+
+    fn fail<T>(&self) -> Result<T, Error> {
+        Err(Error::Syntax { offset: self.pos })
+    }
+
+    fn skip_sp(&mut self) {
+        while self.eat(b' ') {}
+    }
+
+    fn skip_ows(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.pos += 1;
+        }
+    }
+
+    /// Trailing SP is discarded; anything else left over is an error.
+    fn finish(&mut self) -> Result<(), Error> {
+        self.skip_sp();
+        if self.at_end() { Ok(()) } else { self.fail() }
+    }
+
+    /// After a List or Dictionary member: consume `OWS "," OWS`. Returns
+    /// `false` at the end of input; a trailing comma is an error.
+    fn next_member(&mut self) -> Result<bool, Error> {
+        self.skip_ows();
+        if self.at_end() {
+            return Ok(false);
+        }
+        if !self.eat(b',') {
+            return self.fail();
+        }
+        self.skip_ows();
+        if self.at_end() {
+            return self.fail();
+        }
+        Ok(true)
+    }
+
+    fn list_entry(&mut self) -> Result<ListEntry, Error> {
+        if self.peek() == Some(b'(') {
+            Ok(ListEntry::InnerList(self.inner_list()?))
+        } else {
+            Ok(ListEntry::Item(self.item()?))
+        }
+    }
+
+    fn inner_list(&mut self) -> Result<InnerList, Error> {
+        if !self.eat(b'(') {
+            return self.fail();
+        }
+        let mut items = Vec::new();
+        loop {
+            self.skip_sp();
+            if self.eat(b')') {
+                return Ok(InnerList {
+                    items,
+                    params: self.parameters()?,
+                });
+            }
+            items.push(self.item()?);
+            if !matches!(self.peek(), Some(b' ' | b')')) {
+                return self.fail();
+            }
+        }
+    }
+
+    fn item(&mut self) -> Result<Item, Error> {
+        Ok(Item {
+            bare: self.bare_item()?,
+            params: self.parameters()?,
+        })
+    }
+
+    fn parameters(&mut self) -> Result<Parameters, Error> {
+        let mut params = Parameters::default();
+        while self.eat(b';') {
+            self.skip_sp();
+            let key = self.key()?;
+            let value = if self.eat(b'=') {
+                self.bare_item()?
+            } else {
+                BareItem::Boolean(true)
+            };
+            params.insert(key, value);
+        }
+        Ok(params)
+    }
+
+    fn key(&mut self) -> Result<String, Error> {
+        let start = self.pos;
+        if !self.peek().is_some_and(is_key_start) {
+            return self.fail();
+        }
+        while self.peek().is_some_and(is_key_char) {
+            self.pos += 1;
+        }
+        Ok(ascii(&self.input[start..self.pos]))
+    }
+
+    fn bare_item(&mut self) -> Result<BareItem, Error> {
+        match self.peek() {
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(b'"') => self.string(),
+            Some(b'*' | b'a'..=b'z' | b'A'..=b'Z') => Ok(self.token()),
+            Some(b':') => self.byte_sequence(),
+            Some(b'?') => self.boolean(),
+            _ => self.fail(),
+        }
+    }
+
+    fn number(&mut self) -> Result<BareItem, Error> {
+        let negative = self.eat(b'-');
+        let mut integer: i64 = 0;
+        let mut int_digits = 0;
+        while let Some(d @ b'0'..=b'9') = self.peek() {
+            if int_digits == 15 {
+                return self.fail();
+            }
+            self.pos += 1;
+            int_digits += 1;
+            integer = integer * 10 + i64::from(d - b'0');
+        }
+        if int_digits == 0 {
+            return self.fail();
+        }
+        let sign = if negative { -1 } else { 1 };
+        if self.peek() != Some(b'.') {
+            return Ok(BareItem::Integer(sign * integer));
+        }
+
+        if int_digits > 12 {
+            return self.fail();
+        }
+        self.pos += 1;
+        let mut fraction: i64 = 0;
+        let mut frac_digits = 0;
+        while let Some(d @ b'0'..=b'9') = self.peek() {
+            if frac_digits == 3 {
+                return self.fail();
+            }
+            self.pos += 1;
+            frac_digits += 1;
+            fraction = fraction * 10 + i64::from(d - b'0');
+        }
+        if frac_digits == 0 {
+            return self.fail();
+        }
+        for _ in frac_digits..3 {
+            fraction *= 10;
+        }
+        Ok(BareItem::Decimal(Decimal::from_thousandths(
+            sign * (integer * 1000 + fraction),
+        )))
+    }
+
+    fn string(&mut self) -> Result<BareItem, Error> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(BareItem::String(out));
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(c @ (b'"' | b'\\')) => {
+                            self.pos += 1;
+                            out.push(char::from(c));
+                        }
+                        _ => return self.fail(),
+                    }
+                }
+                Some(c @ 0x20..=0x7e) => {
+                    self.pos += 1;
+                    out.push(char::from(c));
+                }
+                _ => return self.fail(),
+            }
+        }
+    }
+
+    fn token(&mut self) -> BareItem {
+        let start = self.pos;
+        self.pos += 1;
+        while self.peek().is_some_and(is_token_char) {
+            self.pos += 1;
+        }
+        BareItem::Token(ascii(&self.input[start..self.pos]))
+    }
+
+    fn byte_sequence(&mut self) -> Result<BareItem, Error> {
+        self.pos += 1;
+        let start = self.pos;
+        while let Some(b) = self.peek() {
+            if b == b':' {
+                let encoded = &self.input[start..self.pos];
+                self.pos += 1;
+                return match base64_decode(encoded) {
+                    Some(bytes) => Ok(BareItem::ByteSequence(bytes)),
+                    None => Err(Error::Syntax { offset: start }),
+                };
+            }
+            if !(b.is_ascii_alphanumeric() || b == b'+' || b == b'/' || b == b'=') {
+                return self.fail();
+            }
+            self.pos += 1;
+        }
+        self.fail()
+    }
+
+    fn boolean(&mut self) -> Result<BareItem, Error> {
+        self.pos += 1;
+        let value = match self.peek() {
+            Some(b'1') => true,
+            Some(b'0') => false,
+            _ => return self.fail(),
+        };
+        self.pos += 1;
+        Ok(BareItem::Boolean(value))
+    }
+}
+
+/// Callers only pass bytes already checked to be ASCII.
+fn ascii(bytes: &[u8]) -> String {
+    bytes.iter().copied().map(char::from).collect()
+}
+
+/// Standard-alphabet base64 (RFC 4648 §4). Padding may be omitted and
+/// non-zero pad bits are tolerated, as RFC 8941 §4.2.7 allows; `=` may only
+/// appear at the end.
+fn base64_decode(input: &[u8]) -> Option<Vec<u8>> {
+    let data_len = input.iter().position(|&b| b == b'=').unwrap_or(input.len());
+    let padding = &input[data_len..];
+    if padding.len() > 2 || padding.iter().any(|&b| b != b'=') {
+        return None;
+    }
+    if !padding.is_empty() && !input.len().is_multiple_of(4) {
+        return None;
+    }
+    if data_len % 4 == 1 {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(data_len * 3 / 4);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for &b in &input[..data_len] {
+        let v = match b {
+            b'A'..=b'Z' => b - b'A',
+            b'a'..=b'z' => b - b'a' + 26,
+            b'0'..=b'9' => b - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        acc = (acc << 6) | u32::from(v);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(s: &str) -> BareItem {
+        BareItem::Token(s.into())
+    }
+
+    #[test]
+    fn parses_item_with_parameters() {
+        let item = parse_item(b"  abc;a=1;b=?0;c  ").unwrap();
+        assert_eq!(item.bare, token("abc"));
+        assert_eq!(item.params.get("a"), Some(&BareItem::Integer(1)));
+        assert_eq!(item.params.get("b"), Some(&BareItem::Boolean(false)));
+        assert_eq!(item.params.get("c"), Some(&BareItem::Boolean(true)));
+    }
+
+    #[test]
+    fn parses_numbers_exactly() {
+        assert_eq!(parse_item(b"-0").unwrap().bare, BareItem::Integer(0));
+        assert_eq!(
+            parse_item(b"999999999999999").unwrap().bare,
+            BareItem::Integer(999_999_999_999_999)
+        );
+        assert_eq!(
+            parse_item(b"-1.5").unwrap().bare,
+            BareItem::Decimal(Decimal::from_thousandths(-1500))
+        );
+        assert_eq!(
+            parse_item(b"123456789012.123").unwrap().bare,
+            BareItem::Decimal(Decimal::from_thousandths(123_456_789_012_123))
+        );
+    }
+
+    #[test]
+    fn number_boundaries() {
+        for input in [
+            &b"1000000000000000"[..],
+            b"1234567890123.0",
+            b"1.1234",
+            b"1.",
+            b"-",
+            b"--1",
+        ] {
+            assert!(parse_item(input).is_err(), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn parses_inner_lists_in_list() {
+        let list = parse_list(b"(a b);q=1, c, ()").unwrap();
+        assert_eq!(list.len(), 3);
+        let ListEntry::InnerList(inner) = &list[0] else {
+            panic!("expected inner list");
+        };
+        assert_eq!(inner.items.len(), 2);
+        assert_eq!(inner.params.get("q"), Some(&BareItem::Integer(1)));
+        assert_eq!(
+            list[2],
+            ListEntry::InnerList(InnerList {
+                items: Vec::new(),
+                params: Parameters::default(),
+            })
+        );
+    }
+
+    #[test]
+    fn dictionary_duplicates_overwrite_in_place() {
+        let dict = parse_dictionary(b"a=1, b, a=3").unwrap();
+        assert_eq!(dict.0.len(), 2);
+        assert_eq!(dict.0[0].0, "a");
+        assert_eq!(
+            dict.get("a"),
+            Some(&ListEntry::Item(Item::new(BareItem::Integer(3))))
+        );
+        assert_eq!(
+            dict.get("b"),
+            Some(&ListEntry::Item(Item::new(BareItem::Boolean(true))))
+        );
+    }
+
+    #[test]
+    fn empty_list_and_dictionary() {
+        assert_eq!(parse_list(b""), Ok(List::new()));
+        assert_eq!(parse_dictionary(b"   "), Ok(Dictionary::default()));
+        assert!(parse_item(b"").is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert_eq!(parse_list(b"a,"), Err(Error::Syntax { offset: 2 }));
+        assert!(parse_list(b"a b").is_err());
+        assert!(parse_item(b"\"a\\b\"").is_err());
+        assert!(parse_item(b"\"\x7f\"").is_err());
+        assert!(parse_item(b"?2").is_err());
+        assert!(parse_item(b"\ta").is_err());
+        assert!(parse_dictionary(b"A=1").is_err());
+        assert!(parse_list(b"(a b").is_err());
+    }
+
+    #[test]
+    fn byte_sequences() {
+        assert_eq!(
+            parse_item(b":aGVsbG8=:").unwrap().bare,
+            BareItem::ByteSequence(b"hello".to_vec())
+        );
+        assert_eq!(
+            parse_item(b":aGVsbG8:").unwrap().bare,
+            BareItem::ByteSequence(b"hello".to_vec())
+        );
+        assert_eq!(
+            parse_item(b"::").unwrap().bare,
+            BareItem::ByteSequence(Vec::new())
+        );
+        for input in [&b":=aGVsbG8=:"[..], b":a=GVsbG8=:", b":aGVsbG8=", b":_-Ah:"] {
+            assert!(parse_item(input).is_err(), "{input:?}");
+        }
+    }
+}
@@ -0,0 +1,277 @@
+//! Structured Field serialization (RFC 8941 §4.1).
+
+use alloc::string::String;
+use core::fmt::Write;
+
+use super::{
+    BareItem, Decimal, Dictionary, Error, InnerList, Item, List, ListEntry,
+    MAX_DECIMAL_THOUSANDTHS, MAX_INTEGER, Parameters, is_key_char, is_key_start, is_token_char,
+};
+
+/// Serialize an Item.
+pub fn serialize_item(item: &Item) -> Result<String, Error> {
+    let mut out = String::new();
+    write_item(item, &mut out)?;
+    Ok(out)
+}
+
+/// Serialize a List. An empty List serializes to an empty string; the
+/// field should then be omitted.
+pub fn serialize_list(list: &List) -> Result<String, Error> {
+    let mut out = String::new();
+    for (i, entry) in list.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write_entry(entry, &mut out)?;
+    }
+    Ok(out)
+}
+
+/// Serialize a Dictionary. An empty Dictionary serializes to an empty
+/// string; the field should then be omitted.
+pub fn serialize_dictionary(dict: &Dictionary) -> Result<String, Error> {
+    let mut out = String::new();
+    for (i, (key, entry)) in dict.0.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write_key(key, &mut out)?;
+        match entry {
+            // `key` alone means `key=?1`.
+            ListEntry::Item(Item {
+                bare: BareItem::Boolean(true),
+                params,
+            }) => write_parameters(params, &mut out)?,
+            entry => {
+                out.push('=');
+                write_entry(entry, &mut out)?;
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn write_entry(entry: &ListEntry, out: &mut String) -> Result<(), Error> {
+    match entry {
+        ListEntry::Item(item) => write_item(item, out),
+        ListEntry::InnerList(inner) => write_inner_list(inner, out),
+    }
+}
+
+fn write_inner_list(inner: &InnerList, out: &mut String) -> Result<(), Error> {
+    out.push('(');
+    for (i, item) in inner.items.iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        write_item(item, out)?;
+    }
+    out.push(')');
+    write_parameters(&inner.params, out)
+}
+
+fn write_item(item: &Item, out: &mut String) -> Result<(), Error> {
+    write_bare_item(&item.bare, out)?;
+    write_parameters(&item.params, out)
+}
+
+fn write_parameters(params: &Parameters, out: &mut String) -> Result<(), Error> {
+    for (key, value) in &params.0 {
+        out.push(';');
+        write_key(key, out)?;
+        if *value != BareItem::Boolean(true) {
+            out.push('=');
+            write_bare_item(value, out)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_key(key: &str, out: &mut String) -> Result<(), Error> {
+    let bytes = key.as_bytes();
+    let valid = bytes.first().is_some_and(|&b| is_key_start(b))
+        && bytes[1..].iter().all(|&b| is_key_char(b));
+    if !valid {
+        return Err(Error::InvalidKey);
+    }
+    out.push_str(key);
+    Ok(())
+}
+
+fn write_bare_item(bare: &BareItem, out: &mut String) -> Result<(), Error> {
+    match bare {
+        BareItem::Integer(n) => {
+            if !(-MAX_INTEGER..=MAX_INTEGER).contains(n) {
+                return Err(Error::IntegerOutOfRange);
+            }
+            push_fmt(out, format_args!("{n}"));
+        }
+        BareItem::Decimal(d) => write_decimal(*d, out)?,
+        BareItem::String(s) => {
+            if !s.bytes().all(|b| (0x20..=0x7e).contains(&b)) {
+                return Err(Error::InvalidString);
+            }
+            out.push('"');
+            for c in s.chars() {
+                if c == '"' || c == '\\' {
+                    out.push('\\');
+                }
+                out.push(c);
+            }
+            out.push('"');
+        }
+        BareItem::Token(t) => {
+            let bytes = t.as_bytes();
+            let valid = bytes
+                .first()
+                .is_some_and(|&b| b.is_ascii_alphabetic() || b == b'*')
+                && bytes[1..].iter().all(|&b| is_token_char(b));
+            if !valid {
+                return Err(Error::InvalidToken);
+            }
+            out.push_str(t);
+        }
+        BareItem::ByteSequence(bytes) => {
+            out.push(':');
+            base64_encode(bytes, out);
+            out.push(':');
+        }
+        BareItem::Boolean(b) => out.push_str(if *b { "?1" } else { "?0" }),
+    }
+    Ok(())
+}
+
+/// Integer part, `.`, then the fractional thousandths without trailing
+/// zeros but with at least one digit.
+fn write_decimal(d: Decimal, out: &mut String) -> Result<(), Error> {
+    let thousandths = d.thousandths();
+    if !(-MAX_DECIMAL_THOUSANDTHS..=MAX_DECIMAL_THOUSANDTHS).contains(&thousandths) {
+        return Err(Error::DecimalOutOfRange);
+    }
+    if thousandths < 0 {
+        out.push('-');
+    }
+    let abs = thousandths.unsigned_abs();
+    let (int, mut frac) = (abs / 1000, abs % 1000);
+    let mut digits = 3;
+    while digits > 1 && frac % 10 == 0 {
+        frac /= 10;
+        digits -= 1;
+    }
+    push_fmt(out, format_args!("{int}.{frac:0digits$}"));
+    Ok(())
+}
+
+fn push_fmt(out: &mut String, args: core::fmt::Arguments<'_>) {
+    // Writing into a String cannot fail.
+    let _ = out.write_fmt(args);
+}
+
+fn base64_encode(input: &[u8], out: &mut String) {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    for chunk in input.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, &b)| acc | (u32::from(b) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (n >> (18 - 6 * i)) & 0x3f;
+                out.push(char::from(ALPHABET[index as usize]));
+            } else {
+                out.push('=');
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sf::{parse_dictionary, parse_list};
+    use alloc::vec::Vec;
+
+    #[test]
+    fn serializes_decimals_canonically() {
+        let d = |t| {
+            let mut out = String::new();
+            write_decimal(Decimal::from_thousandths(t), &mut out).map(|()| out)
+        };
+        assert_eq!(d(1500).as_deref(), Ok("1.5"));
+        assert_eq!(d(-20).as_deref(), Ok("-0.02"));
+        assert_eq!(d(7000).as_deref(), Ok("7.0"));
+        assert_eq!(d(1).as_deref(), Ok("0.001"));
+        assert_eq!(
+            d(MAX_DECIMAL_THOUSANDTHS).as_deref(),
+            Ok("999999999999.999")
+        );
+        assert_eq!(
+            d(MAX_DECIMAL_THOUSANDTHS + 1),
+            Err(Error::DecimalOutOfRange)
+        );
+    }
+
+    #[test]
+    fn rejects_unrepresentable_values() {
+        let item = |bare| serialize_item(&Item::new(bare));
+        assert_eq!(
+            item(BareItem::Integer(MAX_INTEGER + 1)),
+            Err(Error::IntegerOutOfRange)
+        );
+        assert_eq!(
+            item(BareItem::String("caf\u{e9}".into())),
+            Err(Error::InvalidString)
+        );
+        assert_eq!(item(BareItem::Token("1a".into())), Err(Error::InvalidToken));
+        assert_eq!(
+            item(BareItem::Token(String::new())),
+            Err(Error::InvalidToken)
+        );
+
+        let mut params = Parameters::default();
+        params.insert("Upper".into(), BareItem::Boolean(true));
+        let bad_key = Item {
+            bare: BareItem::Integer(1),
+            params,
+        };
+        assert_eq!(serialize_item(&bad_key), Err(Error::InvalidKey));
+    }
+
+    #[test]
+    fn base64_padding() {
+        for (input, expected) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"\xff\xe0!", "/+Ah"),
+        ] {
+            let mut out = String::new();
+            base64_encode(input, &mut out);
+            assert_eq!(out, expected);
+        }
+    }
+
+    #[test]
+    fn roundtrip_list_and_dictionary() {
+        let list = b"a;q=0.5, (\"x\\\"y\" :AQI=: ?0);r, -7";
+        let parsed = parse_list(list).unwrap();
+        let serialized = serialize_list(&parsed).unwrap();
+        assert_eq!(serialized.as_bytes(), list);
+
+        let dict = b"u=1, i, k=(1 2);p=tok, b=?0;x";
+        let parsed = parse_dictionary(dict).unwrap();
+        let serialized = serialize_dictionary(&parsed).unwrap();
+        assert_eq!(serialized.as_bytes(), dict);
+    }
+
+    #[test]
+    fn empty_containers_serialize_to_empty_string() {
+        assert_eq!(serialize_list(&Vec::new()).as_deref(), Ok(""));
+        assert_eq!(
+            serialize_dictionary(&Dictionary::default()).as_deref(),
+            Ok("")
+        );
+    }
+}
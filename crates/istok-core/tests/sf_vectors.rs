@@ -0,0 +1,635 @@
+//! Runs the structured-field-tests vectors and generated sweeps against
+//! `istok_core::sf`.
+
+#![cfg(feature = "alloc")]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use istok_core::sf::{
+    self, BareItem, Decimal, Dictionary, InnerList, Item, List, ListEntry, Parameters,
+};
+
+fn vectors_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/structured-field-tests")
+}
+
+#[test]
+fn parsing_vectors() {
+    let mut files: Vec<_> = fs::read_dir(vectors_dir())
+        .expect("vector directory exists")
+        .map(|entry| entry.expect("readable entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+    assert!(!files.is_empty(), "no vector files found");
+
+    let mut count = 0;
+    for path in files {
+        for test in load(&path) {
+            run_parse_test(&path, &test);
+            count += 1;
+        }
+    }
+    assert!(count > 100, "only {count} vectors ran");
+}
+
+#[test]
+fn serialisation_vectors() {
+    let path = vectors_dir().join("serialisation-tests/number.json");
+    for test in load(&path) {
+        let name = test.get("name").and_then(Json::as_str).unwrap_or("?");
+        let header_type = test.get("header_type").and_then(Json::as_str);
+        let expected = test
+            .get("expected")
+            .expect("serialisation test has expected");
+        let result = match to_value(header_type, expected) {
+            Some(value) => serialize(&value),
+            // Outside the range this crate can even hold: a serialization failure.
+            None => Err(sf::Error::DecimalOutOfRange),
+        };
+        check_serialized(&path, name, &test, result);
+    }
+}
+
+// The upstream `*-generated.json` files are sweeps of every character through
+// key, string and token positions. The cases below are generated the same
+// way, over every byte, with the expectation taken from the RFC 8941 grammar.
+
+fn is_key_char(c: u8) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit() || b"_-.*".contains(&c)
+}
+
+fn is_tchar(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c)
+}
+
+fn one(value: i64) -> Item {
+    Item::new(BareItem::Integer(value))
+}
+
+fn params(pairs: &[(&str, BareItem)]) -> Parameters {
+    Parameters(
+        pairs
+            .iter()
+            .map(|(k, v)| ((*k).to_owned(), v.clone()))
+            .collect(),
+    )
+}
+
+fn token(text: &str) -> BareItem {
+    BareItem::Token(text.to_owned())
+}
+
+/// `bytes` as text; only used where they are ASCII.
+fn string_of(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Check one generated case, then that the parsed value round-trips.
+fn run_sweep(name: &str, input: &[u8], parsed: Result<Value, sf::Error>, expected: Option<Value>) {
+    match (parsed, expected) {
+        (Err(_), None) => {}
+        (Ok(value), None) => panic!("{name}: parsed {input:?} as {value:?}, must fail"),
+        (Err(e), Some(_)) => panic!("{name}: {input:?} failed: {e}"),
+        (Ok(value), Some(expected)) => {
+            assert_eq!(value, expected, "{name}");
+            let text = serialize(&value).unwrap_or_else(|e| panic!("{name}: {e}"));
+            let reparsed = match &value {
+                Value::Item(_) => sf::parse_item(text.as_bytes()).map(Value::Item),
+                Value::List(_) => sf::parse_list(text.as_bytes()).map(Value::List),
+                Value::Dictionary(_) => {
+                    sf::parse_dictionary(text.as_bytes()).map(Value::Dictionary)
+                }
+            };
+            assert_eq!(reparsed, Ok(value), "{name}: {text:?} does not round-trip");
+        }
+    }
+}
+
+#[test]
+fn generated_key_sweep() {
+    for c in 0..=u8::MAX {
+        let key = string_of(&[b'a', c, b'a']);
+        let dict = |pairs: Vec<(String, ListEntry)>| Some(Value::Dictionary(Dictionary(pairs)));
+
+        let input = [b'a', c, b'a', b'=', b'1'];
+        let expected = match c {
+            _ if is_key_char(c) => dict(vec![(key.clone(), ListEntry::Item(one(1)))]),
+            // `a` and then `a=1`, which overwrites it.
+            b',' => dict(vec![("a".into(), ListEntry::Item(one(1)))]),
+            // `a` with the parameter `a=1`.
+            b';' => dict(vec![(
+                "a".into(),
+                ListEntry::Item(Item {
+                    bare: BareItem::Boolean(true),
+                    params: params(&[("a", BareItem::Integer(1))]),
+                }),
+            )]),
+            _ => None,
+        };
+        let name = format!("0x{c:02x} in dictionary key");
+        run_sweep(
+            &name,
+            &input,
+            sf::parse_dictionary(&input).map(Value::Dictionary),
+            expected,
+        );
+
+        let input = [c, b'a', b'=', b'1'];
+        let expected = match c {
+            b'a'..=b'z' | b'*' => dict(vec![(string_of(&[c, b'a']), ListEntry::Item(one(1)))]),
+            b' ' => dict(vec![("a".into(), ListEntry::Item(one(1)))]),
+            _ => None,
+        };
+        let name = format!("0x{c:02x} starting a dictionary key");
+        run_sweep(
+            &name,
+            &input,
+            sf::parse_dictionary(&input).map(Value::Dictionary),
+            expected,
+        );
+
+        let with_param = |key: &str| {
+            Some(Value::Item(Item {
+                bare: token("foo"),
+                params: params(&[(key, BareItem::Integer(1))]),
+            }))
+        };
+
+        let input = [b"foo; a".as_slice(), &[c], b"a=1"].concat();
+        let expected = match c {
+            _ if is_key_char(c) => with_param(&key),
+            // `a` and then `a=1`, which overwrites it.
+            b';' => with_param("a"),
+            _ => None,
+        };
+        let name = format!("0x{c:02x} in parameterised item key");
+        run_sweep(
+            &name,
+            &input,
+            sf::parse_item(&input).map(Value::Item),
+            expected,
+        );
+
+        let input = [b"foo; ".as_slice(), &[c], b"a=1"].concat();
+        let expected = match c {
+            b'a'..=b'z' | b'*' => with_param(&string_of(&[c, b'a'])),
+            b' ' => with_param("a"),
+            _ => None,
+        };
+        let name = format!("0x{c:02x} starting a parameterised item key");
+        run_sweep(
+            &name,
+            &input,
+            sf::parse_item(&input).map(Value::Item),
+            expected,
+        );
+    }
+}
+
+#[test]
+fn generated_string_sweep() {
+    for c in 0..=u8::MAX {
+        let printable = (0x20..=0x7e).contains(&c);
+        let string =
+            |bytes: &[u8]| Some(Value::Item(Item::new(BareItem::String(string_of(bytes)))));
+
+        let input = [b'"', c, b'"'];
+        let expected = match c {
+            b'"' | b'\\' => None,
+            _ if printable => string(&[c]),
+            _ => None,
+        };
+        let name = format!("0x{c:02x} in string");
+        run_sweep(
+            &name,
+            &input,
+            sf::parse_item(&input).map(Value::Item),
+            expected,
+        );
+
+        let input = [b'"', b'\\', c, b'"'];
+        let expected = match c {
+            b'"' | b'\\' => string(&[c]),
+            _ => None,
+        };
+        let name = format!("escaped 0x{c:02x} in string");
+        run_sweep(
+            &name,
+            &input,
+            sf::parse_item(&input).map(Value::Item),
+            expected,
+        );
+    }
+}
+
+#[test]
+fn generated_token_sweep() {
+    for c in 0..=u8::MAX {
+        let item = |bare: BareItem, params: Parameters| Some(Value::Item(Item { bare, params }));
+
+        let input = [b'a', c, b'a'];
+        let expected = match c {
+            _ if is_tchar(c) || c == b':' || c == b'/' => {
+                item(token(&string_of(&input)), Parameters::default())
+            }
+            b';' => item(token("a"), params(&[("a", BareItem::Boolean(true))])),
+            _ => None,
+        };
+        let name = format!("0x{c:02x} in token");
+        run_sweep(
+            &name,
+            &input,
+            sf::parse_item(&input).map(Value::Item),
+            expected,
+        );
+
+        let input = [c, b'a'];
+        let expected = match c {
+            _ if c.is_ascii_alphabetic() || c == b'*' => {
+                item(token(&string_of(&input)), Parameters::default())
+            }
+            b' ' => item(token("a"), Parameters::default()),
+            _ => None,
+        };
+        let name = format!("0x{c:02x} starting a token");
+        run_sweep(
+            &name,
+            &input,
+            sf::parse_item(&input).map(Value::Item),
+            expected,
+        );
+    }
+}
+
+#[test]
+fn generated_serialisation_sweep() {
+    for c in 0..=u8::MAX {
+        // Only ASCII fits in a `String` byte for byte; the rest is covered by
+        // the parsing sweeps.
+        if !c.is_ascii() {
+            continue;
+        }
+        let text = string_of(&[b'a', c, b'a']);
+
+        let item = Item {
+            bare: token("foo"),
+            params: params(&[(&text, BareItem::Integer(1))]),
+        };
+        let result = sf::serialize_item(&item);
+        if is_key_char(c) {
+            assert_eq!(result, Ok(format!("foo;{text}=1")), "0x{c:02x} in key");
+        } else {
+            assert_eq!(result, Err(sf::Error::InvalidKey), "0x{c:02x} in key");
+        }
+
+        let result = sf::serialize_item(&Item::new(token(&text)));
+        if is_tchar(c) || c == b':' || c == b'/' {
+            assert_eq!(result, Ok(text.clone()), "0x{c:02x} in token");
+        } else {
+            assert_eq!(result, Err(sf::Error::InvalidToken), "0x{c:02x} in token");
+        }
+
+        let result = sf::serialize_item(&Item::new(BareItem::String(string_of(&[c]))));
+        let expected = match c {
+            b'"' | b'\\' => Ok(format!("\"\\{}\"", char::from(c))),
+            0x20..=0x7e => Ok(format!("\"{}\"", char::from(c))),
+            _ => Err(sf::Error::InvalidString),
+        };
+        assert_eq!(result, expected, "0x{c:02x} in string");
+    }
+}
+
+fn load(path: &Path) -> Vec<Json> {
+    let text = fs::read_to_string(path).expect("vector file is readable");
+    match Json::parse(&text) {
+        Some(Json::Array(tests)) => tests,
+        _ => panic!("{}: not a JSON array", path.display()),
+    }
+}
+
+fn run_parse_test(path: &Path, test: &Json) {
+    let name = test.get("name").and_then(Json::as_str).unwrap_or("?");
+    let header_type = test.get("header_type").and_then(Json::as_str);
+    let raw: Vec<&str> = match test.get("raw") {
+        Some(Json::Array(lines)) => lines.iter().filter_map(Json::as_str).collect(),
+        _ => panic!("{}: {name}: missing raw", path.display()),
+    };
+    let must_fail = test.get("must_fail") == Some(&Json::Bool(true));
+    let can_fail = test.get("can_fail") == Some(&Json::Bool(true));
+
+    let input = raw.join(", ");
+    let parsed = match header_type {
+        Some("item") => sf::parse_item(input.as_bytes()).map(Value::Item),
+        Some("list") => sf::parse_list(input.as_bytes()).map(Value::List),
+        Some("dictionary") => sf::parse_dictionary(input.as_bytes()).map(Value::Dictionary),
+        other => panic!("{}: {name}: unknown header_type {other:?}", path.display()),
+    };
+
+    let parsed = match (parsed, must_fail, can_fail) {
+        (Err(_), true, _) | (Err(_), _, true) => return,
+        (Ok(value), true, _) => panic!(
+            "{}: {name}: parsed {input:?} as {value:?}, must fail",
+            path.display()
+        ),
+        (Err(e), false, false) => panic!("{}: {name}: {input:?} failed: {e}", path.display()),
+        (Ok(value), false, _) => value,
+    };
+
+    let expected = test
+        .get("expected")
+        .and_then(|json| to_value(header_type, json))
+        .unwrap_or_else(|| panic!("{}: {name}: unreadable expected", path.display()));
+    assert_eq!(parsed, expected, "{}: {name}", path.display());
+
+    check_serialized(path, name, test, serialize(&parsed));
+}
+
+/// Compare against `canonical`, which defaults to the joined `raw` lines.
+fn check_serialized(path: &Path, name: &str, test: &Json, result: Result<String, sf::Error>) {
+    let canonical = match test.get("canonical").or_else(|| test.get("raw")) {
+        Some(Json::Array(lines)) => lines.iter().filter_map(Json::as_str).collect::<Vec<_>>(),
+        _ => {
+            assert!(
+                result.is_err(),
+                "{}: {name}: serialized to {result:?}, must fail",
+                path.display()
+            );
+            return;
+        }
+    };
+    let serialized = result.unwrap_or_else(|e| panic!("{}: {name}: {e}", path.display()));
+    assert_eq!(
+        serialized,
+        canonical.join(", "),
+        "{}: {name}",
+        path.display()
+    );
+}
+
+#[derive(Debug, PartialEq)]
+enum Value {
+    Item(Item),
+    List(List),
+    Dictionary(Dictionary),
+}
+
+fn serialize(value: &Value) -> Result<String, sf::Error> {
+    match value {
+        Value::Item(item) => sf::serialize_item(item),
+        Value::List(list) => sf::serialize_list(list),
+        Value::Dictionary(dict) => sf::serialize_dictionary(dict),
+    }
+}
+
+fn to_value(header_type: Option<&str>, json: &Json) -> Option<Value> {
+    match header_type? {
+        "item" => to_item(json).map(Value::Item),
+        "list" => match json {
+            Json::Array(members) => members
+                .iter()
+                .map(to_entry)
+                .collect::<Option<_>>()
+                .map(Value::List),
+            _ => None,
+        },
+        "dictionary" => to_pairs(json, to_entry).map(|pairs| Value::Dictionary(Dictionary(pairs))),
+        _ => None,
+    }
+}
+
+/// `[[key, value], ...]`.
+fn to_pairs<T>(json: &Json, f: impl Fn(&Json) -> Option<T>) -> Option<Vec<(String, T)>> {
+    let Json::Array(pairs) = json else {
+        return None;
+    };
+    pairs
+        .iter()
+        .map(|pair| match pair {
+            Json::Array(kv) if kv.len() == 2 => Some((kv[0].as_str()?.to_owned(), f(&kv[1])?)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `[bare, params]` or `[[items...], params]`.
+fn to_entry(json: &Json) -> Option<ListEntry> {
+    match json {
+        Json::Array(parts) if parts.len() == 2 => match &parts[0] {
+            Json::Array(items) => Some(ListEntry::InnerList(InnerList {
+                items: items.iter().map(to_item).collect::<Option<_>>()?,
+                params: to_params(&parts[1])?,
+            })),
+            _ => to_item(json).map(ListEntry::Item),
+        },
+        _ => None,
+    }
+}
+
+fn to_item(json: &Json) -> Option<Item> {
+    match json {
+        Json::Array(parts) if parts.len() == 2 => Some(Item {
+            bare: to_bare(&parts[0])?,
+            params: to_params(&parts[1])?,
+        }),
+        _ => None,
+    }
+}
+
+fn to_params(json: &Json) -> Option<Parameters> {
+    to_pairs(json, to_bare).map(Parameters)
+}
+
+fn to_bare(json: &Json) -> Option<BareItem> {
+    match json {
+        Json::Bool(b) => Some(BareItem::Boolean(*b)),
+        Json::String(s) => Some(BareItem::String(s.clone())),
+        Json::Number(text) if text.contains(['.', 'e', 'E']) => {
+            decimal_thousandths(text).map(|t| BareItem::Decimal(Decimal::from_thousandths(t)))
+        }
+        Json::Number(text) => text.parse().ok().map(BareItem::Integer),
+        Json::Object(_) => {
+            let value = json.get("value")?.as_str()?;
+            match json.get("__type")?.as_str()? {
+                "token" => Some(BareItem::Token(value.to_owned())),
+                "binary" => base32_decode(value).map(BareItem::ByteSequence),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Exact decimal literal to thousandths, rounding half to even.
+fn decimal_thousandths(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+    let frac_digits: Vec<i64> = frac
+        .bytes()
+        .map(|b| b.is_ascii_digit().then_some(i64::from(b - b'0')))
+        .collect::<Option<_>>()?;
+    let mut value = int.parse::<i64>().ok()?.checked_mul(1000)?;
+    let mut scale = 100;
+    for d in frac_digits.iter().take(3) {
+        value += d * scale;
+        scale /= 10;
+    }
+    let rest = frac_digits.get(3..).unwrap_or(&[]);
+    let round_up = match rest.split_first() {
+        Some((&first, tail)) if first > 5 || (first == 5 && tail.iter().any(|&d| d != 0)) => true,
+        Some((&5, _)) => value % 2 == 1,
+        _ => false,
+    };
+    if round_up {
+        value += 1;
+    }
+    Some(if negative { -value } else { value })
+}
+
+fn base32_decode(input: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let (mut acc, mut bits) = (0u32, 0);
+    for c in input.bytes().take_while(|&c| c != b'=') {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'2'..=b'7' => c - b'2' + 26,
+            _ => return None,
+        };
+        acc = (acc << 5) | u32::from(v);
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+/// Just enough JSON for the vector files. Numbers keep their source text so
+/// decimals convert exactly.
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn parse(text: &str) -> Option<Json> {
+        let mut chars = text.chars().peekable();
+        let value = parse_value(&mut chars)?;
+        skip_ws(&mut chars);
+        chars.next().is_none().then_some(value)
+    }
+
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn skip_ws(chars: &mut Chars<'_>) {
+    while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+}
+
+fn parse_value(chars: &mut Chars<'_>) -> Option<Json> {
+    skip_ws(chars);
+    match *chars.peek()? {
+        '{' => {
+            chars.next();
+            let mut members = Vec::new();
+            loop {
+                skip_ws(chars);
+                if chars.next_if_eq(&'}').is_some() {
+                    return Some(Json::Object(members));
+                }
+                if !members.is_empty() {
+                    chars.next_if_eq(&',')?;
+                    skip_ws(chars);
+                }
+                chars.next_if_eq(&'"')?;
+                let key = parse_string(chars)?;
+                skip_ws(chars);
+                chars.next_if_eq(&':')?;
+                members.push((key, parse_value(chars)?));
+            }
+        }
+        '[' => {
+            chars.next();
+            let mut elements = Vec::new();
+            loop {
+                skip_ws(chars);
+                if chars.next_if_eq(&']').is_some() {
+                    return Some(Json::Array(elements));
+                }
+                if !elements.is_empty() {
+                    chars.next_if_eq(&',')?;
+                }
+                elements.push(parse_value(chars)?);
+            }
+        }
+        '"' => {
+            chars.next();
+            parse_string(chars).map(Json::String)
+        }
+        c if c == '-' || c.is_ascii_digit() => {
+            let mut text = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || "+-.eE".contains(*c)) {
+                text.push(c);
+            }
+            Some(Json::Number(text))
+        }
+        _ => {
+            let word: String =
+                std::iter::from_fn(|| chars.next_if(char::is_ascii_alphabetic)).collect();
+            match word.as_str() {
+                "true" => Some(Json::Bool(true)),
+                "false" => Some(Json::Bool(false)),
+                "null" => Some(Json::Null),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Parses the rest of a string whose opening quote was consumed.
+fn parse_string(chars: &mut Chars<'_>) -> Option<String> {
+    let mut out = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(out),
+            '\\' => out.push(match chars.next()? {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                'b' => '\u{8}',
+                'f' => '\u{c}',
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+                }
+                c => c,
+            }),
+            c => out.push(c),
+        }
+    }
+}
@@ -0,0 +1,426 @@
+//! Engine configuration.
+//!
+//! `H3Config` gathers every option `H3Engine::new` takes. It is built with
+//! `H3Config::builder()`, whose `build` checks the options against each
+//! other, so an engine never starts from a configuration it cannot honour.
+//!
+//! Invariants:
+//! - Every `H3Config` went through `H3ConfigBuilder::build`;
+//!   `H3Config::default()` is what an untouched builder produces.
+//! - `settings()` is exactly what the engine advertises. The dedicated
+//!   max-field-section-size and extended-CONNECT options are folded into it
+//!   at build time and win over the same fields set via `settings`.
+//! - Limits are never zero; timeouts are in milliseconds, non-zero, and
+//!   `None` disables them.
+//! - The memory budget holds at least one full request buffer.
+
+use core::fmt;
+
+use istok_core::h3::consts;
+use istok_core::h3::settings::{self, Settings};
+
+use crate::limits::DosLimits;
+
+/// Largest encoded frame header (two 8-byte varints).
+const MAX_FRAME_HEADER_LEN: usize = 16;
+
+/// Which end of the connection the engine plays. It decides who may open
+/// request streams (a client refuses server-initiated bidirectional
+/// streams unless WebTransport is enabled and sends its own with
+/// `AppAction::SendRequest`), whether request streams are answered or
+/// their responses reported, and which server-to-client frames are sent
+/// and which are acted on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Role {
+    #[default]
+    Server,
+    Client,
+}
+
+/// Why `H3ConfigBuilder::build` rejected a configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The advertised SETTINGS cannot be encoded or carry an out-of-range value.
+    InvalidSettings(settings::Error),
+    /// WebTransport sessions were advertised without extended CONNECT and
+    /// HTTP Datagrams, which they require.
+    WebTransportRequirements,
+    /// `max_concurrent_streams` is zero.
+    NoConcurrentStreams,
+    /// `max_header_frame_size` is zero.
+    NoHeaderFrameSize,
+    /// `max_request_buffer` cannot hold a HEADERS frame of
+    /// `max_header_frame_size` plus its frame header.
+    RequestBufferTooSmall,
+    /// A timeout of zero milliseconds.
+    ZeroTimeout,
+    /// `stream_high_water` is zero.
+    NoStreamHighWater,
+    /// `memory_budget` is smaller than `max_request_buffer`.
+    MemoryBudgetTooSmall,
+    /// `DosLimits` allowing no header field or no header expansion at all.
+    InvalidDosLimits,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::InvalidSettings(inner) => write!(f, "invalid settings: {inner}"),
+            ConfigError::WebTransportRequirements => write!(
+                f,
+                "webtransport requires extended connect and http datagrams"
+            ),
+            ConfigError::NoConcurrentStreams => write!(f, "max concurrent streams is zero"),
+            ConfigError::NoHeaderFrameSize => write!(f, "max header frame size is zero"),
+            ConfigError::RequestBufferTooSmall => {
+                write!(f, "request buffer smaller than a header frame")
+            }
+            ConfigError::ZeroTimeout => write!(f, "timeout of zero"),
+            ConfigError::NoStreamHighWater => write!(f, "stream high-water mark is zero"),
+            ConfigError::MemoryBudgetTooSmall => {
+                write!(f, "memory budget smaller than the request buffer")
+            }
+            ConfigError::InvalidDosLimits => write!(f, "dos limits reject every request"),
+        }
+    }
+}
+
+impl From<settings::Error> for ConfigError {
+    fn from(value: settings::Error) -> Self {
+        Self::InvalidSettings(value)
+    }
+}
+
+/// Validated engine configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct H3Config {
+    pub(crate) role: Role,
+    pub(crate) settings: Settings,
+    pub(crate) max_header_frame_size: usize,
+    pub(crate) max_request_buffer: usize,
+    pub(crate) max_concurrent_streams: usize,
+    pub(crate) idle_timeout_ms: Option<u64>,
+    pub(crate) header_read_timeout_ms: Option<u64>,
+    pub(crate) body_idle_timeout_ms: Option<u64>,
+    pub(crate) grease: Option<u64>,
+    pub(crate) enable_push: bool,
+    pub(crate) stream_high_water: usize,
+    pub(crate) memory_budget: usize,
+    pub(crate) dos_limits: DosLimits,
+    pub(crate) report_requests: bool,
+    pub(crate) crumble_cookies: bool,
+}
+
+impl H3Config {
+    /// Builder starting from the defaults.
+    pub fn builder() -> H3ConfigBuilder {
+        H3ConfigBuilder::new()
+    }
+
+    /// Which end of the connection the engine plays.
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// SETTINGS sent on our control stream.
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Largest request HEADERS frame payload accepted.
+    pub fn max_header_frame_size(&self) -> usize {
+        self.max_header_frame_size
+    }
+
+    /// Bytes buffered for a request stream before its HEADERS are parsed.
+    pub fn max_request_buffer(&self) -> usize {
+        self.max_request_buffer
+    }
+
+    /// Peer-initiated bidirectional streams open at once.
+    pub fn max_concurrent_streams(&self) -> usize {
+        self.max_concurrent_streams
+    }
+
+    /// Connection idle timeout: without activity for this long, the
+    /// connection is closed.
+    pub fn idle_timeout_ms(&self) -> Option<u64> {
+        self.idle_timeout_ms
+    }
+
+    /// Time a request stream gets from opening to complete HEADERS.
+    pub fn header_read_timeout_ms(&self) -> Option<u64> {
+        self.header_read_timeout_ms
+    }
+
+    /// Longest pause between reads of a request body.
+    pub fn body_idle_timeout_ms(&self) -> Option<u64> {
+        self.body_idle_timeout_ms
+    }
+
+    /// Seed of the GREASE value generator; `None` when GREASE is off.
+    pub fn grease(&self) -> Option<u64> {
+        self.grease
+    }
+
+    /// Bytes queued on one stream above which its producer is paused.
+    pub fn stream_high_water(&self) -> usize {
+        self.stream_high_water
+    }
+
+    /// Bytes the connection may buffer across all streams.
+    pub fn memory_budget(&self) -> usize {
+        self.memory_budget
+    }
+
+    /// Abuse thresholds answered with `H3_EXCESSIVE_LOAD`.
+    pub fn dos_limits(&self) -> &DosLimits {
+        &self.dos_limits
+    }
+
+    /// Whether server push is on. Only then does a server track the push
+    /// ids a client allows in MAX_PUSH_ID.
+    pub fn push_enabled(&self) -> bool {
+        self.enable_push
+    }
+
+    /// Whether each request served is reported as `AppEvent::Request`.
+    pub fn requests_reported(&self) -> bool {
+        self.report_requests
+    }
+
+    /// Whether `cookie` fields are sent as one field line per crumb.
+    pub fn cookies_crumbled(&self) -> bool {
+        self.crumble_cookies
+    }
+
+    /// Whether `SETTINGS_ENABLE_CONNECT_PROTOCOL` is advertised.
+    pub fn extended_connect_enabled(&self) -> bool {
+        self.settings.extended_connect_enabled()
+    }
+}
+
+impl Default for H3Config {
+    fn default() -> Self {
+        Self {
+            role: Role::Server,
+            settings: Settings::new(),
+            max_header_frame_size: 16 * 1024,
+            max_request_buffer: 16 * 1024 + MAX_FRAME_HEADER_LEN,
+            max_concurrent_streams: 100,
+            idle_timeout_ms: None,
+            header_read_timeout_ms: None,
+            body_idle_timeout_ms: None,
+            grease: None,
+            enable_push: false,
+            stream_high_water: 64 * 1024,
+            memory_budget: 1024 * 1024,
+            dos_limits: DosLimits::default(),
+            report_requests: false,
+            crumble_cookies: false,
+        }
+    }
+}
+
+/// Builder for `H3Config`; every option starts at its default.
+#[derive(Debug, Clone, Default)]
+pub struct H3ConfigBuilder {
+    config: H3Config,
+    max_field_section_size: Option<u64>,
+    extended_connect: Option<bool>,
+}
+
+impl H3ConfigBuilder {
+    /// Same as `H3Config::builder()`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Play `role`; the default is `Role::Server`.
+    pub fn role(mut self, role: Role) -> Self {
+        self.config.role = role;
+        self
+    }
+
+    /// SETTINGS to advertise. HTTP Datagrams are only exchanged when
+    /// `settings.h3_datagram == Some(1)` and the peer advertises the same.
+    pub fn settings(mut self, settings: Settings) -> Self {
+        self.config.settings = settings;
+        self
+    }
+
+    /// Advertise `SETTINGS_MAX_FIELD_SECTION_SIZE` and answer larger
+    /// request header sections with 431 (RFC 9114 §4.2.2).
+    pub fn max_field_section_size(mut self, size: u64) -> Self {
+        self.max_field_section_size = Some(size);
+        self
+    }
+
+    /// Largest request HEADERS frame payload accepted; larger ones close
+    /// the connection with `H3_FRAME_ERROR`.
+    pub fn max_header_frame_size(mut self, size: usize) -> Self {
+        self.config.max_header_frame_size = size;
+        self
+    }
+
+    /// Bytes buffered for a request stream before its HEADERS are parsed;
+    /// must hold a HEADERS frame of `max_header_frame_size`.
+    pub fn max_request_buffer(mut self, size: usize) -> Self {
+        self.config.max_request_buffer = size;
+        self
+    }
+
+    /// Peer bidirectional streams served at once; each is read on its own.
+    /// A stream opened beyond this is refused with `H3_REQUEST_REJECTED`
+    /// (RFC 9114 §4.1.1) until an open one finishes.
+    pub fn max_concurrent_streams(mut self, max: usize) -> Self {
+        self.config.max_concurrent_streams = max;
+        self
+    }
+
+    /// Close the connection with `H3_NO_ERROR` after this long without
+    /// activity.
+    pub fn idle_timeout_ms(mut self, timeout: Option<u64>) -> Self {
+        self.config.idle_timeout_ms = timeout;
+        self
+    }
+
+    /// Reset a request stream with `H3_REQUEST_INCOMPLETE` if its HEADERS
+    /// are not complete this long after it opened.
+    pub fn header_read_timeout_ms(mut self, timeout: Option<u64>) -> Self {
+        self.config.header_read_timeout_ms = timeout;
+        self
+    }
+
+    /// Reset a request stream with `H3_REQUEST_INCOMPLETE` if its body
+    /// makes no progress for this long.
+    pub fn body_idle_timeout_ms(mut self, timeout: Option<u64>) -> Self {
+        self.config.body_idle_timeout_ms = timeout;
+        self
+    }
+
+    /// Emit GREASE (RFC 9114 §9) with values drawn from an RNG seeded with
+    /// `seed`: a reserved setting in SETTINGS, a reserved frame on the
+    /// control stream and ahead of each response, and sometimes a reserved
+    /// uni stream that is finished at once. `None` turns it off.
+    pub fn grease(mut self, seed: Option<u64>) -> Self {
+        self.config.grease = seed;
+        self
+    }
+
+    /// Track the client's MAX_PUSH_ID (RFC 9114 §7.2.7). When off, the
+    /// frame is accepted and ignored.
+    pub fn enable_push(mut self, enable: bool) -> Self {
+        self.config.enable_push = enable;
+        self
+    }
+
+    /// Pause an application producer (`AppEvent::WritePaused`) once more
+    /// than `bytes` wait on its stream for QUIC flow control; it resumes
+    /// when half of that is left.
+    pub fn stream_high_water(mut self, bytes: usize) -> Self {
+        self.config.stream_high_water = bytes;
+        self
+    }
+
+    /// Cap on bytes buffered for the whole connection: request buffers,
+    /// queued writes and WebTransport capsules. New request streams are
+    /// refused with `H3_REQUEST_REJECTED` once a full request buffer would
+    /// no longer fit; past the budget, the streams holding the most are
+    /// reset with `H3_EXCESSIVE_LOAD`.
+    pub fn memory_budget(mut self, bytes: usize) -> Self {
+        self.config.memory_budget = bytes;
+        self
+    }
+
+    /// Thresholds for rapid reset, frame floods and header bombs; see
+    /// `limits`.
+    pub fn dos_limits(mut self, limits: DosLimits) -> Self {
+        self.config.dos_limits = limits;
+        self
+    }
+
+    /// Report the fields of every request served as `AppEvent::Request`,
+    /// with crumbled `cookie` lines joined into one (RFC 9114 §4.2.1).
+    pub fn report_requests(mut self, report: bool) -> Self {
+        self.config.report_requests = report;
+        self
+    }
+
+    /// Split every `cookie` field the engine encodes into one field line
+    /// per cookie-pair, so each may be compressed on its own (RFC 9114
+    /// §4.2.1).
+    pub fn crumble_cookies(mut self, crumble: bool) -> Self {
+        self.config.crumble_cookies = crumble;
+        self
+    }
+
+    /// Advertise `SETTINGS_ENABLE_CONNECT_PROTOCOL` (RFC 9220) and accept
+    /// requests carrying `:protocol`.
+    pub fn enable_extended_connect(mut self, enable: bool) -> Self {
+        self.extended_connect = Some(enable);
+        self
+    }
+
+    /// Check the options against each other and fold the dedicated
+    /// SETTINGS options into `settings`.
+    pub fn build(self) -> Result<H3Config, ConfigError> {
+        let mut config = self.config;
+        if let Some(size) = self.max_field_section_size {
+            config.settings.max_field_section_size = Some(size);
+        }
+        if let Some(enable) = self.extended_connect {
+            config.settings.enable_connect_protocol = enable.then_some(1);
+        }
+
+        let settings = &config.settings;
+        settings.encoded_len()?;
+        for (id, flag) in [
+            (
+                consts::SETTINGS_ENABLE_CONNECT_PROTOCOL,
+                settings.enable_connect_protocol,
+            ),
+            (consts::SETTINGS_H3_DATAGRAM, settings.h3_datagram),
+        ] {
+            if let Some(value) = flag.filter(|value| *value > 1) {
+                return Err(settings::Error::InvalidValue { id, value }.into());
+            }
+        }
+        if settings.webtransport_max_sessions.is_some_and(|n| n > 0)
+            && !settings.webtransport_enabled()
+        {
+            return Err(ConfigError::WebTransportRequirements);
+        }
+
+        if config.max_concurrent_streams == 0 {
+            return Err(ConfigError::NoConcurrentStreams);
+        }
+        if config.stream_high_water == 0 {
+            return Err(ConfigError::NoStreamHighWater);
+        }
+        if config.max_header_frame_size == 0 {
+            return Err(ConfigError::NoHeaderFrameSize);
+        }
+        let needed = config
+            .max_header_frame_size
+            .checked_add(MAX_FRAME_HEADER_LEN);
+        if needed.is_none_or(|needed| config.max_request_buffer < needed) {
+            return Err(ConfigError::RequestBufferTooSmall);
+        }
+        if config.memory_budget < config.max_request_buffer {
+            return Err(ConfigError::MemoryBudgetTooSmall);
+        }
+        let timeouts = [
+            config.idle_timeout_ms,
+            config.header_read_timeout_ms,
+            config.body_idle_timeout_ms,
+        ];
+        if timeouts.contains(&Some(0)) {
+            return Err(ConfigError::ZeroTimeout);
+        }
+        if !config.dos_limits.is_valid() {
+            return Err(ConfigError::InvalidDosLimits);
+        }
+        Ok(config)
+    }
+}
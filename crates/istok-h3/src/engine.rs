@@ -1,16 +1,22 @@
-use istok_transport::{QuicCommand, QuicEvent};
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use istok_core::bytes::Bytes;
+use istok_core::qpack::HeaderField;
+use istok_transport::{QuicCommand, QuicEvent, StreamId};
 
-/// Events that the engine consumes (from QUIC + timers + shutdown).
+/// Events that the engine consumes (from QUIC + timers + app + shutdown).
 pub enum EngineEvent<'a> {
     Boot,
     Quic(QuicEvent<'a>),
     TimerFired(TimerId),
+    App(AppAction<'a>),
     Shutdown,
 }
 
 /// Things engine wants the runtime to do.
 pub enum EngineCommand<'a> {
     Quic(QuicCommand<'a>),
+    App(AppEvent<'a>),
     ArmTimer {
         id: TimerId,
         deadline_ms_from_now: u64,
@@ -20,8 +26,140 @@ pub enum EngineCommand<'a> {
     },
 }
 
+/// Requests from the application toward the engine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AppAction<'a> {
+    /// Send an HTTP Datagram (RFC 9297) associated with request stream `id`.
+    /// Dropped unless both endpoints advertised `SETTINGS_H3_DATAGRAM=1`.
+    SendDatagram { id: StreamId, payload: &'a [u8] },
+    /// Abort request stream `id` in both directions, e.g. when the tunnel
+    /// target behind a CONNECT request failed.
+    ResetStream { id: StreamId, app_error: u64 },
+    /// The CONNECT or CONNECT-UDP target behind tunnel `id` is reachable:
+    /// send the 200 response.
+    AcceptTunnel { id: StreamId },
+    /// Refuse the CONNECT or CONNECT-UDP target behind tunnel `id` with
+    /// `status`, which should be 4xx or 5xx; other values are sent as 502.
+    /// Ignored after `AcceptTunnel`.
+    RejectTunnel { id: StreamId, status: u16 },
+    /// Relay bytes from the CONNECT target to the peer. `fin` half-closes the
+    /// tunnel toward the peer. Ignored before `AcceptTunnel`. `data` is
+    /// framed and handed to QUIC without being copied.
+    TunnelSend {
+        id: StreamId,
+        data: Bytes,
+        fin: bool,
+    },
+    /// Send a bodyless request carrying `fields` on bidirectional stream
+    /// `id`, which the application opened, and read its response (client
+    /// only). The response arrives as `InterimResponse`, `Response` and
+    /// `ResponseData` events. Ignored on a server; aborted with
+    /// `StreamAborted` when `id` already carries a request.
+    SendRequest {
+        id: StreamId,
+        fields: Vec<HeaderField<'a>>,
+    },
+}
+
+/// Notifications from the engine toward the application.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AppEvent<'a> {
+    /// HTTP Datagram received for request stream `id`, either as a QUIC
+    /// DATAGRAM (borrowed) or as a DATAGRAM capsule on the stream (owned).
+    Datagram {
+        id: StreamId,
+        payload: Cow<'a, [u8]>,
+    },
+    /// A CONNECT-UDP request (RFC 9298) for `host:port` arrived on stream
+    /// `id`. `host` is percent-decoded. Answer with `AppAction::AcceptTunnel`
+    /// or `AppAction::RejectTunnel`. UDP payloads arrive as `Datagram` events
+    /// prefixed with a Context ID (see `codec::connect_udp`).
+    ConnectUdp {
+        id: StreamId,
+        host: Vec<u8>,
+        port: u16,
+    },
+    /// The peer finished or aborted CONNECT-UDP stream `id`; the tunnel is
+    /// gone.
+    ConnectUdpClosed { id: StreamId },
+    /// A classic CONNECT request (RFC 9114 §4.4) for `authority`
+    /// (`host:port`) arrived on stream `id`. Answer with
+    /// `AppAction::AcceptTunnel`, `AppAction::RejectTunnel` or
+    /// `AppAction::ResetStream`.
+    ConnectTunnel { id: StreamId, authority: Vec<u8> },
+    /// Tunnel bytes received from the peer on CONNECT stream `id`. `fin`
+    /// means the peer half-closed the tunnel (`data` is then empty).
+    TunnelData {
+        id: StreamId,
+        data: Vec<u8>,
+        fin: bool,
+    },
+    /// The peer aborted CONNECT stream `id`; the tunnel is gone.
+    TunnelReset { id: StreamId, app_error: u64 },
+    /// A WebTransport session was established on CONNECT stream `id`.
+    /// Session datagrams arrive as `Datagram` events carrying the same `id`.
+    WebTransportSession { id: StreamId },
+    /// Bytes received on WebTransport stream `id` belonging to `session`.
+    WebTransportStreamData {
+        session: StreamId,
+        id: StreamId,
+        data: &'a [u8],
+        fin: bool,
+    },
+    /// The peer asked to wind session `id` down (DRAIN_WEBTRANSPORT_SESSION).
+    WebTransportSessionDraining { id: StreamId },
+    /// More than the configured high-water mark is queued on stream `id`
+    /// waiting for QUIC flow control. Stop producing body bytes for it
+    /// (`TunnelSend`, `WebTransportSession::send`) until `WriteResumed`.
+    WritePaused { id: StreamId },
+    /// Stream `id` drained below half the high-water mark after `WritePaused`.
+    WriteResumed { id: StreamId },
+    /// The engine aborted stream `id` with `app_error`, e.g.
+    /// `H3_EXCESSIVE_LOAD` when the connection ran over its memory budget.
+    /// Whatever the application ran on it (tunnel, WebTransport session,
+    /// stream or sent request) is gone. A request the server reset is
+    /// reported here too, with the server's code.
+    StreamAborted { id: StreamId, app_error: u64 },
+    /// Session `id` ended, either by CLOSE_WEBTRANSPORT_SESSION or by a clean
+    /// FIN on its CONNECT stream (reported as code 0 with an empty reason).
+    WebTransportSessionClosed {
+        id: StreamId,
+        code: u32,
+        reason: Vec<u8>,
+    },
+    /// A request arrived on stream `id` (server, with
+    /// `H3ConfigBuilder::report_requests`). `fields` are its field lines,
+    /// pseudo-headers first, with crumbled `cookie` lines joined into one.
+    /// The engine answers it as usual.
+    Request {
+        id: StreamId,
+        fields: Vec<(Vec<u8>, Vec<u8>)>,
+    },
+    /// A 1xx response to the request sent on stream `id` (client only).
+    /// `fields` follow `:status`; the final response is still to come.
+    InterimResponse {
+        id: StreamId,
+        status: u16,
+        fields: Vec<(Vec<u8>, Vec<u8>)>,
+    },
+    /// The final response to the request sent on stream `id` (client
+    /// only). `fields` follow `:status`; the body follows as `ResponseData`.
+    Response {
+        id: StreamId,
+        status: u16,
+        fields: Vec<(Vec<u8>, Vec<u8>)>,
+    },
+    /// Response body bytes on stream `id`. `fin` ends the response (`data`
+    /// is then empty); trailers are not reported.
+    ResponseData {
+        id: StreamId,
+        data: Vec<u8>,
+        fin: bool,
+    },
+}
+
 /// Stable ids for protocol timers (PTO, delayed ACK, etc). Expand later.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct TimerId(pub u32);
 
 /// Object-safe command sink.
@@ -30,6 +168,7 @@ pub trait CommandSink<'a> {
 }
 
 /// Pure engine step: consumes exactly one event and yields zero or more commands.
+/// `poll::PollEngine` drives an engine pull-style instead.
 pub trait Engine {
     fn on_event<'a>(&mut self, ev: EngineEvent<'a>, out: &mut dyn CommandSink<'a>);
 }
@@ -1,29 +1,164 @@
-use crate::engine::{CommandSink, Engine, EngineCommand, EngineEvent};
+use crate::config::{H3Config, Role};
+use crate::engine::{
+    AppAction, AppEvent, CommandSink, Engine, EngineCommand, EngineEvent, TimerId,
+};
+use crate::interim::{FinalResponse, InterimResponse, RequestInfo, ResponseHook};
+use crate::limits::{DosGuard, DosLimits};
+use crate::qlog::{Owner, Qlog};
+use crate::qpack_streams::{InstructionError, QpackStream, QpackStreams};
+use crate::scheduler::WriteScheduler;
+use crate::stats::EngineStats;
+use crate::timers::{IDLE_TIMER, stream_timer};
+use crate::trace::{self, Tracer};
+use crate::uni::{PendingUniTypes, TypeProgress};
+use crate::webtransport::{
+    CapsuleOutcome, MAX_CAPSULE_BUFFER, WebTransportSession, WebTransportState,
+};
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::vec::Vec;
-use istok_core::codec::{h3_frame, varint};
-use istok_core::h3::{consts, settings::Settings};
-use istok_transport::{QuicCommand, QuicEvent, StreamId, StreamKind};
+use istok_core::bytes::Bytes;
+use istok_core::codec::priority::{self, Priority};
+use istok_core::codec::{capsule, connect_udp, h3_datagram, h3_frame, origin, varint};
+use istok_core::h3::consts;
+use istok_core::h3::grease;
+use istok_core::h3::settings::{self, Settings};
+use istok_core::h3::validate::{self, BodyLength, RequestValidator};
+use istok_core::pool::{BufferPool, PoolStats, SlabPool};
+use istok_core::qpack::{self, HeaderField};
+use istok_transport::{
+    INLINE_HEADER_LEN, InlineHeader, QuicCommand, QuicEvent, StreamError, StreamId, StreamKind,
+};
 
-/// Minimal H3 engine skeleton.
-/// Codex will implement real behavior using istok-core codecs and settings encoder.
+/// HTTP/3 connection state machine over the QUIC events in `istok-transport`.
+///
+/// It owns the control and QPACK streams, parses request streams and answers
+/// them, and hands CONNECT tunnels, datagrams and WebTransport sessions to the
+/// application as `AppEvent`s. Every request stream is read on its own, up
+/// to `H3Config::max_concurrent_streams` peer streams at once; CONNECT
+/// tunnels and WebTransport sessions stay open next to ordinary requests.
+/// As a client it sends `AppAction::SendRequest` and reports the responses.
+/// It never does I/O: everything it wants done is pushed into the
+/// `CommandSink` passed to `Engine::on_event`.
 pub struct H3Engine {
     control_stream: Option<StreamId>,
     inbound_uni_pending_type: Option<StreamId>,
     inbound_uni_pending_buf: Vec<u8>,
     inbound_uni_state: InboundUniState,
+    /// Peer uni streams whose stream type is still arriving.
+    pending_uni_types: PendingUniTypes,
+    /// Payload bytes of an ignored control stream frame still to drop.
+    control_skip: u64,
+    /// Identifier in the last GOAWAY the peer sent.
+    peer_goaway: Option<u64>,
+    /// The peer's QPACK encoder and decoder streams.
+    qpack_streams: QpackStreams,
     inbound_control_stream: Option<StreamId>,
-    pending_request_stream: Option<StreamId>,
-    inbound_request_stream: Option<StreamId>,
-    request_stream_claimed: bool,
-    claimed_request_stream_id: Option<StreamId>,
-    pending_request_fin: bool,
-    inbound_request_buf: Vec<u8>,
-    inbound_request_state: InboundRequestState,
+    /// Request streams being read: peer requests on a server, the
+    /// application's requests on a client.
+    requests: BTreeMap<StreamId, RequestStream>,
+    config: H3Config,
+    peer_settings: Option<Settings>,
+    pub(crate) webtransport: WebTransportState,
+    /// Classic CONNECT tunnels, until both sides finished.
+    tunnels: BTreeMap<StreamId, ConnectTunnel>,
+    /// CONNECT-UDP tunnels, until the peer finished or either side aborted.
+    udp_tunnels: BTreeMap<StreamId, UdpTunnel>,
+    pub(crate) scheduler: WriteScheduler,
+    /// PRIORITY_UPDATE values for requests not read yet, applied by
+    /// `prioritize`; at most `MAX_PENDING_PRIORITIES`.
+    pending_priorities: BTreeMap<StreamId, Priority>,
+    response_hook: Option<Box<dyn ResponseHook>>,
+    /// Buffers for request reassembly and response framing.
+    pool: Box<dyn BufferPool>,
+    /// Origins advertised in an ORIGIN frame after SETTINGS (server only).
+    origin_set: Option<Vec<Vec<u8>>>,
+    /// Origins received in ORIGIN frames (client only), in arrival order.
+    peer_origins: Vec<Vec<u8>>,
+    /// Source of GREASE values; `None` when GREASE is off.
+    grease: Option<grease::Rng>,
+    /// Peer bidirectional streams not yet finished or aborted, counted
+    /// against `max_concurrent_streams`.
+    open_bidi_streams: BTreeSet<StreamId>,
+    /// Largest MAX_PUSH_ID received, when push is enabled.
+    peer_max_push_id: Option<u64>,
+    idle_timer_armed: bool,
+    /// Request streams whose timer is armed, and what it guards.
+    request_timers: BTreeMap<StreamId, RequestTimer>,
+    /// Set on shutdown or idle timeout; no timer is armed afterwards.
+    timers_stopped: bool,
+    /// Streams whose producer was sent `AppEvent::WritePaused`.
+    paused_streams: BTreeSet<StreamId>,
+    dos: DosGuard,
+    stats: EngineStats,
+    qlog: Option<Qlog>,
+    tracer: Tracer,
+}
+
+/// What the timer of a request stream guards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RequestTimer {
+    /// Opening to complete HEADERS; not extended by partial reads.
+    HeaderRead,
+    /// Gap between body reads; re-armed on every read.
+    BodyIdle,
+}
+
+/// A request stream being read.
+struct RequestStream {
+    /// Bytes received and not parsed yet.
+    buf: Vec<u8>,
+    state: InboundRequestState,
+    /// Opened before the peer's SETTINGS arrived: bytes are only buffered
+    /// until they do.
+    early: bool,
+    /// FIN arrived while the stream was `early`.
+    early_fin: bool,
+    /// DATA received: the request body, or the response body on a client.
+    body: BodyLength,
+    /// DATA sent in the response.
+    response_body: BodyLength,
+    /// The final response to a request sent with `expect: 100-continue`,
+    /// held until its body arrived (RFC 9110 §10.1.1).
+    deferred_response: Option<FinalResponse>,
+    /// Bytes arrived during this event.
+    activity: bool,
+}
+
+impl RequestStream {
+    fn new(early: bool) -> Self {
+        Self {
+            buf: Vec::new(),
+            state: InboundRequestState::NeedFrameHeader,
+            early,
+            early_fin: false,
+            body: BodyLength::default(),
+            response_body: BodyLength::default(),
+            deferred_response: None,
+            activity: false,
+        }
+    }
+}
+
+/// Classic CONNECT tunnel (RFC 9114 §4.4).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct ConnectTunnel {
+    accepted: bool,
+    local_fin: bool,
+    peer_fin: bool,
+}
+
+/// CONNECT-UDP tunnel (RFC 9298).
+#[derive(Default)]
+struct UdpTunnel {
+    accepted: bool,
+    /// Unparsed capsule bytes from the CONNECT-UDP stream.
+    capsules: Vec<u8>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum InboundUniState {
-    Type,
     FrameHeader,
     Payload { len: usize },
 }
@@ -31,211 +166,2993 @@ enum InboundUniState {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum InboundRequestState {
     NeedFrameHeader,
-    NeedPayload { len: usize },
+    NeedPayload {
+        len: usize,
+    },
+    /// Established CONNECT stream: only DATA frames may follow. DATA carries
+    /// capsules (WebTransport, CONNECT-UDP) or tunnel bytes (classic CONNECT).
+    ConnectFrameHeader,
+    ConnectFramePayload {
+        ty: u64,
+        remaining: usize,
+    },
+    /// Payload of a reserved or unknown frame before the request HEADERS.
+    SkipFramePayload {
+        remaining: usize,
+    },
+    /// Request body: DATA frames counted against `content-length`, then
+    /// optional trailers. Once `trailers` were seen only FIN may follow.
+    BodyFrameHeader {
+        trailers: bool,
+    },
+    /// `data` marks a DATA payload, as opposed to trailers or a skipped
+    /// frame.
+    BodyFramePayload {
+        remaining: usize,
+        trailers: bool,
+        data: bool,
+    },
     Complete,
 }
 
-const RESPONSE_HEADERS_PAYLOAD: [u8; 1] = [0x00];
+/// Request pseudo-headers the engine acts on.
+struct RequestHead {
+    connect: bool,
+    protocol: Option<Vec<u8>>,
+    path: Option<Vec<u8>>,
+    authority: Option<Vec<u8>>,
+    /// `priority` header; `None` when absent or unparsable (RFC 9218 §4).
+    priority: Option<Priority>,
+    content_length: Option<u64>,
+    expect_continue: bool,
+    /// Field section size in RFC 9114 §4.2.2 units.
+    size: u64,
+    /// Name and value bytes in the block.
+    decoded: u64,
+    /// Every field line, cookie crumbs joined, when requests are reported.
+    fields: Option<Vec<(Vec<u8>, Vec<u8>)>>,
+}
+
+/// Why a request HEADERS block was not turned into a `RequestHead`.
+enum HeadError {
+    /// Connection error `H3_QPACK_DECOMPRESSION_FAILED`.
+    Qpack,
+    /// Malformed request (RFC 9114 §4.1.2): stream error `H3_MESSAGE_ERROR`.
+    Malformed,
+    /// Too many field lines, or a block expanding past `DosLimits`: stream
+    /// error `H3_EXCESSIVE_LOAD`.
+    ExcessiveLoad,
+}
+
+impl RequestHead {
+    fn decode(block: &[u8], limits: &DosLimits, keep_fields: bool) -> Result<Self, HeadError> {
+        let mut validator = RequestValidator::new();
+        let mut fields = 0u32;
+        let mut decoded = 0u64;
+        let mut head = Self {
+            connect: false,
+            protocol: None,
+            path: None,
+            authority: None,
+            priority: None,
+            content_length: None,
+            expect_continue: false,
+            size: 0,
+            decoded: 0,
+            fields: keep_fields.then(Vec::new),
+        };
+        // The limit is on field lines as sent: every cookie crumb counts,
+        // though the crumbs reach the visitor as one joined field.
+        let lines = qpack::cookie::decode_joined(block, |name, value| {
+            fields = fields.saturating_add(1);
+            decoded += (name.len() + value.len()) as u64;
+            if fields > limits.max_header_fields {
+                return;
+            }
+            validator.field(name, value);
+            head.size += (name.len() + value.len()) as u64 + 32;
+            if let Some(fields) = head.fields.as_mut() {
+                fields.push((name.to_vec(), value.to_vec()));
+            }
+            match name {
+                b":method" => head.connect = value == b"CONNECT",
+                b":protocol" => head.protocol = Some(value.to_vec()),
+                b":path" => head.path = Some(value.to_vec()),
+                b":authority" => head.authority = Some(value.to_vec()),
+                b"priority" => head.priority = priority::parse_priority_field(value).ok(),
+                b"expect" => head.expect_continue = value.eq_ignore_ascii_case(b"100-continue"),
+                _ => {}
+            }
+        })
+        .map_err(|_| HeadError::Qpack)?;
+        let too_many = u32::try_from(lines).map_or(true, |lines| lines > limits.max_header_fields);
+        if too_many || limits.header_bomb(block.len(), decoded) {
+            return Err(HeadError::ExcessiveLoad);
+        }
+        validator.finish().map_err(|_| HeadError::Malformed)?;
+        head.content_length = validator.content_length();
+        head.decoded = decoded;
+        Ok(head)
+    }
+}
+
 const RESPONSE_DATA_PAYLOAD: [u8; 1] = [0x01];
-const MAX_REQUEST_HEADERS_PAYLOAD: usize = 16 * 1024;
-const MAX_EARLY_REQUEST_BUFFER: usize = MAX_REQUEST_HEADERS_PAYLOAD + 16;
+const MAX_SETTINGS_PAYLOAD: usize = 1024;
+/// Room for the configured settings plus one GREASE pair.
+const MAX_LOCAL_SETTINGS_PAYLOAD: usize = 80;
+const MAX_GREASE_FRAME_PAYLOAD: usize = 8;
+/// Interim responses carry application fields such as Early Hints links.
+const MAX_INTERIM_HEADERS_PAYLOAD: usize = 4 * 1024;
+const MAX_UDP_TARGET_HOST: usize = 255;
+const MAX_PRIORITY_UPDATE_PAYLOAD: usize = 1024;
+const MAX_ORIGIN_PAYLOAD: usize = 16 * 1024;
+const MAX_PUSH_ID_PAYLOAD: usize = 8;
+/// GOAWAY and CANCEL_PUSH carry one varint.
+const MAX_ID_PAYLOAD: usize = 8;
+/// PRIORITY_UPDATE values kept for streams whose request has not been
+/// read yet; beyond this the lowest stream id is evicted.
+const MAX_PENDING_PRIORITIES: usize = 64;
 
 impl H3Engine {
-    pub fn new() -> Self {
+    /// An engine that has not booted yet; `EngineEvent::Boot` opens its
+    /// control stream.
+    pub fn new(config: H3Config) -> Self {
+        let tracer = Tracer::new(config.role);
         Self {
             control_stream: None,
             inbound_uni_pending_type: None,
             inbound_uni_pending_buf: Vec::new(),
-            inbound_uni_state: InboundUniState::Type,
+            inbound_uni_state: InboundUniState::FrameHeader,
+            pending_uni_types: PendingUniTypes::default(),
+            control_skip: 0,
+            peer_goaway: None,
+            qpack_streams: QpackStreams::default(),
             inbound_control_stream: None,
-            pending_request_stream: None,
-            inbound_request_stream: None,
-            request_stream_claimed: false,
-            claimed_request_stream_id: None,
-            pending_request_fin: false,
-            inbound_request_buf: Vec::new(),
-            inbound_request_state: InboundRequestState::NeedFrameHeader,
+            requests: BTreeMap::new(),
+            grease: config.grease.map(grease::Rng::new),
+            config,
+            peer_settings: None,
+            webtransport: WebTransportState::new(),
+            tunnels: BTreeMap::new(),
+            udp_tunnels: BTreeMap::new(),
+            scheduler: WriteScheduler::new(),
+            pending_priorities: BTreeMap::new(),
+            response_hook: None,
+            pool: Box::new(SlabPool::default()),
+            origin_set: None,
+            peer_origins: Vec::new(),
+            open_bidi_streams: BTreeSet::new(),
+            peer_max_push_id: None,
+            idle_timer_armed: false,
+            request_timers: BTreeMap::new(),
+            timers_stopped: false,
+            paused_streams: BTreeSet::new(),
+            dos: DosGuard::default(),
+            stats: EngineStats::default(),
+            qlog: None,
+            tracer,
         }
     }
 
-    fn parse_request_stream<'a>(&mut self, id: StreamId, fin: bool, out: &mut dyn CommandSink<'a>) {
-        if self.inbound_request_stream != Some(id) {
+    /// Configuration the engine was built with.
+    pub fn config(&self) -> &H3Config {
+        &self.config
+    }
+
+    /// Send `origins` in an ORIGIN frame (RFC 9412) right after SETTINGS,
+    /// so clients may reuse the connection for them. Only a server sends
+    /// it; set before `EngineEvent::Boot`.
+    pub fn set_origin_set(&mut self, origins: Vec<Vec<u8>>) {
+        self.origin_set = Some(origins);
+    }
+
+    /// Origins the server advertised in ORIGIN frames, for connection-reuse
+    /// decisions. Always empty in the server role.
+    pub fn peer_origins(&self) -> &[Vec<u8>] {
+        &self.peer_origins
+    }
+
+    /// Bytes queued on stream `id` that QUIC flow control has not taken yet.
+    pub fn queued_bytes(&self, id: StreamId) -> usize {
+        self.scheduler.queued_bytes(id)
+    }
+
+    /// Bytes the connection currently buffers, counted against
+    /// `H3Config::memory_budget`.
+    pub fn memory_usage(&self) -> usize {
+        let queued: usize = self.scheduler.queued().map(|(_, len)| len).sum();
+        let requests: usize = self.requests.values().map(|stream| stream.buf.len()).sum();
+        let capsules: usize = self
+            .udp_tunnels
+            .values()
+            .map(|tunnel| tunnel.capsules.len())
+            .sum();
+        requests
+            + capsules
+            + self.inbound_uni_pending_buf.len()
+            + queued
+            + self.webtransport.buffered_bytes()
+    }
+
+    /// Largest push ID the client allows (RFC 9114 §7.2.7). Always `None`
+    /// unless push is enabled.
+    pub fn peer_max_push_id(&self) -> Option<u64> {
+        self.peer_max_push_id
+    }
+
+    /// Consult `hook` for interim responses and `expect: 100-continue`
+    /// decisions on every request that gets a regular response.
+    pub fn set_response_hook(&mut self, hook: impl ResponseHook + 'static) {
+        self.response_hook = Some(Box::new(hook));
+    }
+
+    /// Draw request reassembly and response framing buffers from `pool`
+    /// instead of the default `SlabPool`.
+    pub fn set_buffer_pool(&mut self, pool: impl BufferPool + 'static) {
+        self.pool = Box::new(pool);
+    }
+
+    /// Counters of what this connection did so far.
+    pub fn stats(&self) -> &EngineStats {
+        &self.stats
+    }
+
+    /// Trace HTTP/3 and QPACK events to `qlog`. Set before
+    /// `EngineEvent::Boot` for a complete trace.
+    pub fn set_qlog(&mut self, mut qlog: Qlog) {
+        qlog.start(self.config.role);
+        self.qlog = Some(qlog);
+    }
+
+    /// Hit and miss counters of the buffer pool.
+    pub fn pool_stats(&self) -> PoolStats {
+        self.pool.stats()
+    }
+
+    /// Hand back the buffer of a `StreamWriteOwned` (or the body of a
+    /// `StreamWriteVectored`, see `Bytes::try_into_vec`) once QUIC has
+    /// taken its bytes, so later writes can reuse it.
+    pub fn recycle(&mut self, buf: Vec<u8>) {
+        self.pool.give(buf);
+    }
+
+    /// Priority the writes of stream `id` are scheduled with, once one was
+    /// set from its request or a PRIORITY_UPDATE (RFC 9218).
+    pub fn stream_priority(&self, id: StreamId) -> Option<Priority> {
+        self.scheduler
+            .has_priority(id)
+            .then(|| self.scheduler.priority(id))
+    }
+
+    /// Handle to the established WebTransport session on CONNECT stream `id`.
+    pub fn webtransport_session(&mut self, id: StreamId) -> Option<WebTransportSession<'_>> {
+        if !self.webtransport.is_session(id) {
+            return None;
+        }
+        Some(WebTransportSession::new(self, id))
+    }
+
+    /// Identifier of the last GOAWAY the peer sent: the first request
+    /// stream a server will not process, or the first push id a client
+    /// refuses.
+    pub fn peer_goaway(&self) -> Option<u64> {
+        self.peer_goaway
+    }
+
+    /// SETTINGS received from the peer, once its control stream SETTINGS frame was accepted.
+    pub fn peer_settings(&self) -> Option<&Settings> {
+        self.peer_settings.as_ref()
+    }
+
+    fn datagrams_negotiated(&self) -> bool {
+        self.config.settings.datagrams_enabled()
+            && self
+                .peer_settings
+                .as_ref()
+                .is_some_and(Settings::datagrams_enabled)
+    }
+
+    fn on_datagram<'a>(&mut self, data: &'a [u8], out: &mut dyn CommandSink<'a>) {
+        // RFC 9297 §2.1.1: the peer may only send HTTP Datagrams if we advertised support.
+        if !self.config.settings.datagrams_enabled() {
+            self.close_with(
+                out,
+                consts::H3_DATAGRAM_ERROR,
+                "datagram without SETTINGS_H3_DATAGRAM",
+            );
+            return;
+        }
+
+        let (stream_id, payload) = match h3_datagram::decode(data) {
+            Ok(parsed) => parsed,
+            Err(_) => {
+                self.close_with(out, consts::H3_DATAGRAM_ERROR, "malformed HTTP Datagram");
+                return;
+            }
+        };
+
+        // Datagrams for streams we do not track (not yet opened, or already
+        // released) are dropped, which RFC 9297 §2.1 permits.
+        let id = StreamId(stream_id);
+        if !self.requests.contains_key(&id)
+            && !self.udp_tunnels.contains_key(&id)
+            && !self.webtransport.is_session(id)
+        {
+            return;
+        }
+
+        out.push(EngineCommand::App(AppEvent::Datagram {
+            id,
+            payload: Cow::Borrowed(payload),
+        }));
+    }
+
+    pub(crate) fn send_datagram<'a>(
+        &mut self,
+        id: StreamId,
+        payload: &[u8],
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        if !self.datagrams_negotiated() {
+            return;
+        }
+
+        let mut prefix = [0u8; 8];
+        let prefix_len = match h3_datagram::encode_header(id.0, &mut prefix) {
+            Ok(len) => len,
+            Err(_) => return,
+        };
+
+        let mut data = Vec::with_capacity(prefix_len + payload.len());
+        data.extend_from_slice(&prefix[..prefix_len]);
+        data.extend_from_slice(payload);
+        out.push(EngineCommand::Quic(QuicCommand::SendDatagram { data }));
+    }
+
+    /// Start reading peer request stream `id`. Until the peer's SETTINGS
+    /// arrived its bytes are only buffered.
+    fn open_request(&mut self, id: StreamId) {
+        let early = self.inbound_control_stream.is_none();
+        self.requests.insert(id, RequestStream::new(early));
+    }
+
+    /// Bytes read from request stream `id`.
+    fn on_request_data<'a>(
+        &mut self,
+        id: StreamId,
+        data: &[u8],
+        fin: bool,
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        let Some(stream) = self.requests.get_mut(&id) else {
+            return;
+        };
+        stream.activity |= !data.is_empty();
+        let fits = stream
+            .buf
+            .len()
+            .checked_add(data.len())
+            .is_some_and(|len| len <= self.config.max_request_buffer);
+
+        if stream.early {
+            if !fits {
+                self.close_request_with(
+                    id,
+                    out,
+                    consts::H3_FRAME_ERROR,
+                    "request exceeds max_request_buffer",
+                );
+                return;
+            }
+            stream.early_fin |= fin;
+            self.buffer_request_bytes(id, data);
+            return;
+        }
+
+        if stream.state == InboundRequestState::Complete {
+            if data.is_empty() && fin {
+                return;
+            }
+            self.close_request_with(
+                id,
+                out,
+                consts::H3_GENERAL_PROTOCOL_ERROR,
+                "data after the request stream ended",
+            );
             return;
         }
 
-        if self.inbound_request_state == InboundRequestState::Complete {
-            self.inbound_request_stream = None;
-            self.inbound_request_buf.clear();
-            self.pending_request_fin = false;
+        // Past HEADERS, body and tunnel payloads are drained as they are
+        // parsed, so large reads are fed in buffer-sized slices. Before
+        // that, the whole read must fit.
+        let streaming = !matches!(
+            stream.state,
+            InboundRequestState::NeedFrameHeader | InboundRequestState::NeedPayload { .. }
+        );
+        if !streaming && !fits {
+            self.close_request_with(
+                id,
+                out,
+                consts::H3_FRAME_ERROR,
+                "request exceeds max_request_buffer",
+            );
             return;
         }
+        let mut rest = data;
+        loop {
+            let room = self
+                .config
+                .max_request_buffer
+                .saturating_sub(self.request_buf(id).len());
+            if room == 0 {
+                self.close_request_with(
+                    id,
+                    out,
+                    consts::H3_FRAME_ERROR,
+                    "request exceeds max_request_buffer",
+                );
+                return;
+            }
+            let (chunk, tail) = rest.split_at(room.min(rest.len()));
+            rest = tail;
+            self.buffer_request_bytes(id, chunk);
+            self.parse_request_stream(id, fin && rest.is_empty(), out);
+            let reading = self
+                .requests
+                .get(&id)
+                .is_some_and(|stream| stream.state != InboundRequestState::Complete);
+            if rest.is_empty() || !reading {
+                return;
+            }
+        }
+    }
 
+    fn parse_request_stream<'a>(&mut self, id: StreamId, fin: bool, out: &mut dyn CommandSink<'a>) {
         loop {
-            match self.inbound_request_state {
+            let Some(stream) = self.requests.get(&id) else {
+                return;
+            };
+            match stream.state {
                 InboundRequestState::NeedFrameHeader => {
                     let (frame_header, consumed) =
-                        match h3_frame::decode_frame_header(&self.inbound_request_buf) {
+                        match h3_frame::decode_frame_header(self.request_buf(id)) {
+                            Ok(parsed) => parsed,
+                            Err(h3_frame::Error::VarInt(varint::VarIntError::BufferTooSmall)) => {
+                                if fin {
+                                    self.close_request_with(
+                                        id,
+                                        out,
+                                        consts::H3_FRAME_ERROR,
+                                        "truncated frame header",
+                                    );
+                                }
+                                return;
+                            }
+                            Err(_) => {
+                                self.close_request_with(
+                                    id,
+                                    out,
+                                    consts::H3_FRAME_ERROR,
+                                    "malformed frame header",
+                                );
+                                return;
+                            }
+                        };
+                    self.frame_in(id, frame_header.ty, frame_header.len);
+
+                    if frame_header.ty == consts::FRAME_TYPE_DATA
+                        || unexpected_on_request_stream(frame_header.ty)
+                    {
+                        self.close_request_with(
+                            id,
+                            out,
+                            consts::H3_FRAME_UNEXPECTED,
+                            "request stream does not start with HEADERS",
+                        );
+                        return;
+                    }
+                    if frame_header.ty != consts::FRAME_TYPE_HEADERS {
+                        // Reserved and unknown types are skipped (RFC 9114 §9).
+                        if !self.admit_frame(id, frame_header) {
+                            self.close_request_with(
+                                id,
+                                out,
+                                consts::H3_EXCESSIVE_LOAD,
+                                "too many non-HEADERS frames",
+                            );
+                            return;
+                        }
+                        let Ok(remaining) = usize::try_from(frame_header.len) else {
+                            self.close_request_with(
+                                id,
+                                out,
+                                consts::H3_FRAME_ERROR,
+                                "frame length exceeds usize",
+                            );
+                            return;
+                        };
+                        self.consume_request_bytes(id, consumed);
+                        self.set_request_state(
+                            id,
+                            InboundRequestState::SkipFramePayload { remaining },
+                        );
+                        continue;
+                    }
+
+                    let payload_len = match usize::try_from(frame_header.len) {
+                        Ok(len) => len,
+                        Err(_) => {
+                            self.close_request_with(
+                                id,
+                                out,
+                                consts::H3_FRAME_ERROR,
+                                "frame length exceeds usize",
+                            );
+                            return;
+                        }
+                    };
+
+                    if payload_len > self.config.max_header_frame_size {
+                        self.close_request_with(
+                            id,
+                            out,
+                            consts::H3_FRAME_ERROR,
+                            "HEADERS frame exceeds max_header_frame_size",
+                        );
+                        return;
+                    }
+
+                    self.consume_request_bytes(id, consumed);
+                    self.set_request_state(
+                        id,
+                        InboundRequestState::NeedPayload { len: payload_len },
+                    );
+                }
+                InboundRequestState::NeedPayload { len } => {
+                    if self.request_buf(id).len() < len {
+                        if fin {
+                            self.close_request_with(
+                                id,
+                                out,
+                                consts::H3_FRAME_ERROR,
+                                "truncated HEADERS frame",
+                            );
+                        }
+                        return;
+                    }
+
+                    if self.config.role == Role::Client {
+                        if self.on_response_headers(id, len, out) {
+                            continue;
+                        }
+                        return;
+                    }
+
+                    let block = &self.request_buf(id)[..len];
+                    let mut head = match RequestHead::decode(
+                        block,
+                        &self.config.dos_limits,
+                        self.config.report_requests,
+                    ) {
+                        Ok(head) => head,
+                        Err(HeadError::Qpack) => {
+                            self.close_request_with(
+                                id,
+                                out,
+                                consts::H3_QPACK_DECOMPRESSION_FAILED,
+                                "QPACK decoding failed",
+                            );
+                            return;
+                        }
+                        Err(HeadError::Malformed) => {
+                            self.reset_request_stream(
+                                id,
+                                consts::H3_MESSAGE_ERROR,
+                                "malformed request",
+                                out,
+                            );
+                            return;
+                        }
+                        Err(HeadError::ExcessiveLoad) => {
+                            self.reset_request_stream(
+                                id,
+                                consts::H3_EXCESSIVE_LOAD,
+                                "request exceeds header limits",
+                                out,
+                            );
+                            return;
+                        }
+                    };
+                    self.stats.qpack_in.record(len, head.decoded);
+                    if let Some(qlog) = self.qlog.as_mut()
+                        && let Some(stream) = self.requests.get(&id)
+                    {
+                        qlog.headers_decoded(id, &stream.buf[..len]);
+                    }
+                    self.consume_request_bytes(id, len);
+
+                    if self
+                        .config
+                        .settings
+                        .max_field_section_size
+                        .is_some_and(|max| head.size > max)
+                    {
+                        self.reject_request(id, b"431", out);
+                        return;
+                    }
+
+                    if let Some(protocol) = &head.protocol {
+                        if self.on_extended_connect(id, &head, protocol, out) {
+                            continue;
+                        }
+                        return;
+                    }
+
+                    if head.connect {
+                        if self.on_connect(id, head, out) {
+                            continue;
+                        }
+                        return;
+                    }
+
+                    let Some(interim) = self.interim_responses(id, &head, out) else {
+                        return;
+                    };
+                    if let Some(fields) = head.fields.take() {
+                        out.push(EngineCommand::App(AppEvent::Request { id, fields }));
+                    }
+                    let response = self
+                        .response_hook
+                        .as_mut()
+                        .and_then(|hook| hook.final_response(&request_info(id, &head)))
+                        .unwrap_or_else(|| {
+                            FinalResponse::new(200).with_body(&RESPONSE_DATA_PAYLOAD)
+                        });
+                    if let Some(stream) = self.requests.get_mut(&id) {
+                        stream.body = BodyLength::new(head.content_length);
+                        stream.state = InboundRequestState::BodyFrameHeader { trailers: false };
+                    }
+
+                    self.prioritize(id, head.priority);
+                    if let Some(frame) = self.grease_frame(id) {
+                        self.scheduler.push(id, frame, false);
+                    }
+                    for headers in interim {
+                        self.scheduler.push(id, headers, false);
+                    }
+                    // After 100 (Continue) the final response waits for the
+                    // body, so a body that breaks the request still gets an
+                    // error instead of a 200 already on the wire.
+                    if head.expect_continue
+                        && self.response_hook.is_some()
+                        && let Some(stream) = self.requests.get_mut(&id)
+                    {
+                        stream.deferred_response = Some(response);
+                        self.flush_writes(out);
+                        continue;
+                    }
+                    if !self.queue_final_response(id, &response, out) {
+                        return;
+                    }
+                }
+                InboundRequestState::BodyFrameHeader { trailers } => {
+                    if self.request_buf(id).is_empty() {
+                        if fin {
+                            self.on_request_body_fin(id, out);
+                        }
+                        return;
+                    }
+
+                    let (frame_header, consumed) =
+                        match h3_frame::decode_frame_header(self.request_buf(id)) {
+                            Ok(parsed) => parsed,
+                            Err(h3_frame::Error::VarInt(varint::VarIntError::BufferTooSmall)) => {
+                                if fin {
+                                    self.close_request_with(
+                                        id,
+                                        out,
+                                        consts::H3_FRAME_ERROR,
+                                        "truncated frame header",
+                                    );
+                                }
+                                return;
+                            }
+                            Err(_) => {
+                                self.close_request_with(
+                                    id,
+                                    out,
+                                    consts::H3_FRAME_ERROR,
+                                    "malformed frame header",
+                                );
+                                return;
+                            }
+                        };
+                    self.frame_in(id, frame_header.ty, frame_header.len);
+
+                    // DATA* then at most one trailing HEADERS (RFC 9114 §4.1);
+                    // unknown frame types are skipped.
+                    let unexpected = match frame_header.ty {
+                        consts::FRAME_TYPE_DATA | consts::FRAME_TYPE_HEADERS => trailers,
+                        ty => unexpected_on_request_stream(ty),
+                    };
+                    if unexpected {
+                        self.close_request_with(
+                            id,
+                            out,
+                            consts::H3_FRAME_UNEXPECTED,
+                            "frame not allowed on a request stream",
+                        );
+                        return;
+                    }
+                    if !self.admit_frame(id, frame_header) {
+                        self.close_request_with(
+                            id,
+                            out,
+                            consts::H3_EXCESSIVE_LOAD,
+                            "too many non-HEADERS frames",
+                        );
+                        return;
+                    }
+
+                    let remaining = match usize::try_from(frame_header.len) {
+                        Ok(len) => len,
+                        Err(_) => {
+                            self.close_request_with(
+                                id,
+                                out,
+                                consts::H3_FRAME_ERROR,
+                                "frame length exceeds usize",
+                            );
+                            return;
+                        }
+                    };
+
+                    // Judged on the frame header so excess bytes are never
+                    // accepted as body.
+                    if frame_header.ty == consts::FRAME_TYPE_DATA
+                        && self
+                            .requests
+                            .get_mut(&id)
+                            .is_some_and(|stream| stream.body.on_data(frame_header.len).is_err())
+                    {
+                        self.reset_request_stream(
+                            id,
+                            consts::H3_MESSAGE_ERROR,
+                            "message body exceeds content-length",
+                            out,
+                        );
+                        return;
+                    }
+
+                    self.consume_request_bytes(id, consumed);
+                    self.set_request_state(
+                        id,
+                        InboundRequestState::BodyFramePayload {
+                            remaining,
+                            trailers: trailers || frame_header.ty == consts::FRAME_TYPE_HEADERS,
+                            data: frame_header.ty == consts::FRAME_TYPE_DATA,
+                        },
+                    );
+                }
+                InboundRequestState::BodyFramePayload {
+                    remaining,
+                    trailers,
+                    data,
+                } => {
+                    // The auto-response does not consume the request body or
+                    // trailers; a client hands the response body on.
+                    let take = remaining.min(self.request_buf(id).len());
+                    if data && take > 0 && self.config.role == Role::Client {
+                        out.push(EngineCommand::App(AppEvent::ResponseData {
+                            id,
+                            data: self.request_buf(id)[..take].to_vec(),
+                            fin: false,
+                        }));
+                    }
+                    self.consume_request_bytes(id, take);
+
+                    if take < remaining {
+                        self.set_request_state(
+                            id,
+                            InboundRequestState::BodyFramePayload {
+                                remaining: remaining - take,
+                                trailers,
+                                data,
+                            },
+                        );
+                        if fin {
+                            self.close_request_with(
+                                id,
+                                out,
+                                consts::H3_FRAME_ERROR,
+                                "truncated DATA frame",
+                            );
+                        }
+                        return;
+                    }
+                    self.set_request_state(id, InboundRequestState::BodyFrameHeader { trailers });
+                }
+                InboundRequestState::SkipFramePayload { remaining } => {
+                    let take = remaining.min(self.request_buf(id).len());
+                    self.consume_request_bytes(id, take);
+                    if take < remaining {
+                        self.set_request_state(
+                            id,
+                            InboundRequestState::SkipFramePayload {
+                                remaining: remaining - take,
+                            },
+                        );
+                        if fin {
+                            self.close_request_with(
+                                id,
+                                out,
+                                consts::H3_FRAME_ERROR,
+                                "truncated frame",
+                            );
+                        }
+                        return;
+                    }
+                    self.set_request_state(id, InboundRequestState::NeedFrameHeader);
+                }
+                InboundRequestState::ConnectFrameHeader => {
+                    if self.request_buf(id).is_empty() {
+                        if fin {
+                            self.on_connect_stream_fin(id, out);
+                        }
+                        return;
+                    }
+
+                    let (frame_header, consumed) =
+                        match h3_frame::decode_frame_header(self.request_buf(id)) {
                             Ok(parsed) => parsed,
                             Err(h3_frame::Error::VarInt(varint::VarIntError::BufferTooSmall)) => {
                                 if fin {
-                                    self.close_request_with(out, consts::H3_FRAME_ERROR);
+                                    self.close_request_with(
+                                        id,
+                                        out,
+                                        consts::H3_FRAME_ERROR,
+                                        "truncated frame header",
+                                    );
                                 }
                                 return;
                             }
                             Err(_) => {
-                                self.close_request_with(out, consts::H3_FRAME_ERROR);
+                                self.close_request_with(
+                                    id,
+                                    out,
+                                    consts::H3_FRAME_ERROR,
+                                    "malformed frame header",
+                                );
+                                return;
+                            }
+                        };
+                    self.frame_in(id, frame_header.ty, frame_header.len);
+
+                    // A CONNECT stream is a tunnel: only DATA frames (and
+                    // unknown, ignorable types) may follow the response.
+                    if frame_header.ty == consts::FRAME_TYPE_HEADERS
+                        || unexpected_on_request_stream(frame_header.ty)
+                    {
+                        self.close_request_with(
+                            id,
+                            out,
+                            consts::H3_FRAME_UNEXPECTED,
+                            "frame not allowed on a request stream",
+                        );
+                        return;
+                    }
+                    if !self.admit_frame(id, frame_header) {
+                        self.close_request_with(
+                            id,
+                            out,
+                            consts::H3_EXCESSIVE_LOAD,
+                            "too many non-HEADERS frames",
+                        );
+                        return;
+                    }
+
+                    let remaining = match usize::try_from(frame_header.len) {
+                        Ok(len) => len,
+                        Err(_) => {
+                            self.close_request_with(
+                                id,
+                                out,
+                                consts::H3_FRAME_ERROR,
+                                "frame length exceeds usize",
+                            );
+                            return;
+                        }
+                    };
+
+                    self.consume_request_bytes(id, consumed);
+                    self.set_request_state(
+                        id,
+                        InboundRequestState::ConnectFramePayload {
+                            ty: frame_header.ty,
+                            remaining,
+                        },
+                    );
+                }
+                InboundRequestState::ConnectFramePayload { ty, remaining } => {
+                    let take = remaining.min(self.request_buf(id).len());
+                    if ty == consts::FRAME_TYPE_DATA && take > 0 && self.is_tunnel(id) {
+                        out.push(EngineCommand::App(AppEvent::TunnelData {
+                            id,
+                            data: self.request_buf(id)[..take].to_vec(),
+                            fin: false,
+                        }));
+                    }
+                    if ty == consts::FRAME_TYPE_DATA
+                        && take > 0
+                        && let Some(tunnel) = self.udp_tunnels.get_mut(&id)
+                        && let Some(stream) = self.requests.get(&id)
+                        && let Err(app_error) =
+                            read_udp_capsules(&mut tunnel.capsules, id, &stream.buf[..take], out)
+                    {
+                        self.close_request_with(id, out, app_error, "malformed capsule");
+                        return;
+                    }
+                    if ty == consts::FRAME_TYPE_DATA
+                        && take > 0
+                        && self.webtransport.is_session(id)
+                        && let Some(stream) = self.requests.get(&id)
+                    {
+                        match self.webtransport.on_capsule_bytes(
+                            id,
+                            &stream.buf[..take],
+                            &mut self.scheduler,
+                            out,
+                        ) {
+                            Ok(CapsuleOutcome::Open) => {}
+                            Ok(CapsuleOutcome::Closed) => {
+                                self.flush_writes(out);
+                                self.finish_request_stream(id);
+                                return;
+                            }
+                            Err(app_error) => {
+                                self.close_request_with(id, out, app_error, "malformed capsule");
+                                return;
+                            }
+                        }
+                    }
+                    self.consume_request_bytes(id, take);
+
+                    if take < remaining {
+                        self.set_request_state(
+                            id,
+                            InboundRequestState::ConnectFramePayload {
+                                ty,
+                                remaining: remaining - take,
+                            },
+                        );
+                        if fin {
+                            self.close_request_with(
+                                id,
+                                out,
+                                consts::H3_FRAME_ERROR,
+                                "truncated DATA frame",
+                            );
+                        }
+                        return;
+                    }
+                    self.set_request_state(id, InboundRequestState::ConnectFrameHeader);
+                }
+                InboundRequestState::Complete => return,
+            }
+        }
+    }
+
+    /// Count a DATA or skipped frame after the request HEADERS against the
+    /// `DosLimits` flood checks. Returns `false` once they are exceeded.
+    fn admit_frame(&mut self, id: StreamId, frame_header: h3_frame::FrameHeader) -> bool {
+        let limits = &self.config.dos_limits;
+        match frame_header.ty {
+            consts::FRAME_TYPE_DATA => self.dos.on_data_frame(id, frame_header.len, limits),
+            consts::FRAME_TYPE_HEADERS => true,
+            _ => self.dos.on_unknown_frame(id, limits),
+        }
+    }
+
+    /// Handle a classic CONNECT request (RFC 9114 §4.4).
+    ///
+    /// Returns `true` when the request is well-formed and the rest of the
+    /// stream should be relayed as tunnel bytes. The response is deferred
+    /// until the application accepts the tunnel.
+    fn on_connect<'a>(
+        &mut self,
+        id: StreamId,
+        head: RequestHead,
+        out: &mut dyn CommandSink<'a>,
+    ) -> bool {
+        // Validation already required `:authority` and no `:scheme`/`:path`.
+        let Some(authority) = head.authority else {
+            self.reset_request_stream(
+                id,
+                consts::H3_MESSAGE_ERROR,
+                "CONNECT without :authority",
+                out,
+            );
+            return false;
+        };
+
+        self.prioritize(id, head.priority);
+        self.set_request_state(id, InboundRequestState::ConnectFrameHeader);
+        self.tunnels.insert(id, ConnectTunnel::default());
+        out.push(EngineCommand::App(AppEvent::ConnectTunnel {
+            id,
+            authority,
+        }));
+        true
+    }
+
+    fn is_tunnel(&self, id: StreamId) -> bool {
+        self.tunnels.contains_key(&id)
+    }
+
+    /// The application reached the CONNECT or CONNECT-UDP target: send the
+    /// 2xx response.
+    fn on_app_accept_tunnel<'a>(&mut self, id: StreamId, out: &mut dyn CommandSink<'a>) {
+        if let Some(tunnel) = self.tunnels.get_mut(&id) {
+            if tunnel.accepted {
+                return;
+            }
+            tunnel.accepted = true;
+            self.send_response_headers(id, b"200", &[], false, out);
+        } else if let Some(tunnel) = self.udp_tunnels.get_mut(&id) {
+            if tunnel.accepted {
+                return;
+            }
+            tunnel.accepted = true;
+            // RFC 9298 §3.3: the capsule protocol carries the datagrams.
+            let fields = [HeaderField {
+                name: b"capsule-protocol",
+                value: b"?1",
+            }];
+            self.send_response_headers(id, b"200", &fields, false, out);
+        }
+    }
+
+    /// The application refused the CONNECT or CONNECT-UDP target: answer
+    /// with `status` and end the stream.
+    fn on_app_reject_tunnel<'a>(
+        &mut self,
+        id: StreamId,
+        status: u16,
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        let accepted = match (self.tunnels.get(&id), self.udp_tunnels.get(&id)) {
+            (Some(tunnel), _) => tunnel.accepted,
+            (None, Some(tunnel)) => tunnel.accepted,
+            (None, None) => return,
+        };
+        if accepted {
+            return;
+        }
+        self.tunnels.remove(&id);
+        self.udp_tunnels.remove(&id);
+        let status = if (400..600).contains(&status) {
+            status
+        } else {
+            502
+        };
+        self.reject_request(id, &status_digits(status), out);
+    }
+
+    /// Relay bytes from the CONNECT target to the peer as a DATA frame.
+    /// Ignored until the tunnel was accepted and after the local FIN.
+    fn on_app_tunnel_send<'a>(
+        &mut self,
+        id: StreamId,
+        data: Bytes,
+        fin: bool,
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        let Some(&tunnel) = self.tunnels.get(&id) else {
+            return;
+        };
+        if !tunnel.accepted || tunnel.local_fin || (data.is_empty() && !fin) {
+            return;
+        }
+
+        let header = if data.is_empty() {
+            InlineHeader::default()
+        } else {
+            match frame_header(consts::FRAME_TYPE_DATA, data.len()) {
+                Ok(header) => {
+                    self.frame_out(id, consts::FRAME_TYPE_DATA, data.len());
+                    header
+                }
+                Err(_) => {
+                    self.tunnels.remove(&id);
+                    self.reset_request_stream(
+                        id,
+                        consts::H3_INTERNAL_ERROR,
+                        "DATA frame header does not encode",
+                        out,
+                    );
+                    return;
+                }
+            }
+        };
+
+        if fin && tunnel.peer_fin {
+            self.tunnels.remove(&id);
+        } else if let Some(tunnel) = self.tunnels.get_mut(&id) {
+            tunnel.local_fin = fin;
+        }
+        self.queue_app_write(id, header, data, fin, out);
+    }
+
+    /// Send a bodyless request on `id` and read its response (client only).
+    /// A request on a stream that already carries one is cancelled.
+    fn send_request<'a>(
+        &mut self,
+        id: StreamId,
+        fields: &[HeaderField<'_>],
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        if self.config.role != Role::Client {
+            return;
+        }
+        if self.requests.contains_key(&id) {
+            self.abort_request(id, consts::H3_REQUEST_CANCELLED, out);
+            return;
+        }
+
+        let mut block = alloc::vec![0u8; block_capacity(fields)];
+        let Ok(len) = self.encode_section(fields, &mut block) else {
+            self.abort_request(id, consts::H3_INTERNAL_ERROR, out);
+            return;
+        };
+        let Ok(frame) = self.pooled_frame(id, consts::FRAME_TYPE_HEADERS, &block[..len]) else {
+            self.abort_request(id, consts::H3_INTERNAL_ERROR, out);
+            return;
+        };
+        self.stats.qpack_out.record(len, field_bytes(fields));
+        if let Some(qlog) = self.qlog.as_mut() {
+            qlog.stream_type_set(Owner::Local, id, "request");
+            qlog.headers(Owner::Local, id, fields.iter().copied(), len);
+        }
+        self.scheduler.push(id, frame, true);
+        self.flush_writes(out);
+
+        self.requests.insert(id, RequestStream::new(false));
+    }
+
+    /// Reset request stream `id`, which the application opened, before
+    /// anything was sent on it, and tell the application.
+    fn abort_request<'a>(&mut self, id: StreamId, app_error: u64, out: &mut dyn CommandSink<'a>) {
+        out.push(EngineCommand::Quic(QuicCommand::ResetStream {
+            id,
+            app_error,
+        }));
+        out.push(EngineCommand::App(AppEvent::StreamAborted {
+            id,
+            app_error,
+        }));
+    }
+
+    /// Response HEADERS in the first `len` bytes of the request buffer
+    /// (client only). An interim response is reported and the next HEADERS
+    /// awaited; a final response is reported and its body read. Returns
+    /// `false` once the stream or connection was torn down.
+    fn on_response_headers<'a>(
+        &mut self,
+        id: StreamId,
+        len: usize,
+        out: &mut dyn CommandSink<'a>,
+    ) -> bool {
+        let limits = &self.config.dos_limits;
+        let block = &self.request_buf(id)[..len];
+        let mut count = 0u32;
+        let mut decoded = 0u64;
+        let mut status = None;
+        let mut malformed = false;
+        let mut fields = Vec::new();
+        let result = qpack::decode(block, |name, value| {
+            count = count.saturating_add(1);
+            decoded += (name.len() + value.len()) as u64;
+            if count > limits.max_header_fields {
+                return;
+            }
+            if name == b":status" {
+                // Exactly one `:status`, ahead of the regular fields.
+                malformed |= status.is_some() || !fields.is_empty();
+                status = validate::parse_status(value).ok();
+                malformed |= status.is_none();
+            } else if name.first() == Some(&b':') {
+                malformed = true;
+            } else {
+                fields.push((name.to_vec(), value.to_vec()));
+            }
+        });
+        if result.is_err() {
+            self.close_request_with(
+                id,
+                out,
+                consts::H3_QPACK_DECOMPRESSION_FAILED,
+                "QPACK decoding failed",
+            );
+            return false;
+        }
+        if count > limits.max_header_fields || limits.header_bomb(len, decoded) {
+            self.reset_request_stream(
+                id,
+                consts::H3_EXCESSIVE_LOAD,
+                "response exceeds header limits",
+                out,
+            );
+            return false;
+        }
+        let content_length = fields
+            .iter()
+            .filter(|(name, _)| name.as_slice() == b"content-length")
+            .try_fold(None, |seen, (_, value)| {
+                let length = validate::parse_content_length(value).ok()?;
+                match seen {
+                    Some(seen) if seen != length => None,
+                    _ => Some(Some(length)),
+                }
+            });
+        let (Some(status), Some(content_length), false) = (status, content_length, malformed)
+        else {
+            self.reset_request_stream(id, consts::H3_MESSAGE_ERROR, "malformed response", out);
+            return false;
+        };
+        // HTTP/3 has no protocol upgrade (RFC 9114 §4.5).
+        if status == 101 {
+            self.reset_request_stream(id, consts::H3_MESSAGE_ERROR, "101 response", out);
+            return false;
+        }
+        self.stats.qpack_in.record(len, decoded);
+        if let Some(qlog) = self.qlog.as_mut()
+            && let Some(stream) = self.requests.get(&id)
+        {
+            qlog.headers_decoded(id, &stream.buf[..len]);
+        }
+        self.consume_request_bytes(id, len);
+
+        if validate::is_interim(status) {
+            out.push(EngineCommand::App(AppEvent::InterimResponse {
+                id,
+                status,
+                fields,
+            }));
+            self.set_request_state(id, InboundRequestState::NeedFrameHeader);
+            return true;
+        }
+        out.push(EngineCommand::App(AppEvent::Response {
+            id,
+            status,
+            fields,
+        }));
+        if let Some(stream) = self.requests.get_mut(&id) {
+            stream.body = BodyLength::new(content_length);
+            stream.state = InboundRequestState::BodyFrameHeader { trailers: false };
+        }
+        true
+    }
+
+    /// Queue a DATA frame of the response on `id`, counted against the
+    /// `content-length` the response declared. A body that disagrees with
+    /// it is never sent: the stream is reset with `H3_MESSAGE_ERROR` instead.
+    /// Returns `false` once the stream or connection was torn down.
+    fn queue_response_data<'a>(
+        &mut self,
+        id: StreamId,
+        payload: &[u8],
+        fin: bool,
+        out: &mut dyn CommandSink<'a>,
+    ) -> bool {
+        let counted = self.requests.get_mut(&id).map_or(Ok(()), |stream| {
+            stream
+                .response_body
+                .on_data(payload.len() as u64)
+                .and_then(|()| match fin {
+                    true => stream.response_body.on_end(),
+                    false => Ok(()),
+                })
+        });
+        if counted.is_err() {
+            self.reset_request_stream(
+                id,
+                consts::H3_MESSAGE_ERROR,
+                "response body disagrees with content-length",
+                out,
+            );
+            return false;
+        }
+
+        match frame_header(consts::FRAME_TYPE_DATA, payload.len()) {
+            Ok(header) => {
+                self.frame_out(id, consts::FRAME_TYPE_DATA, payload.len());
+                let mut body = self.pool.take(payload.len());
+                body.extend_from_slice(payload);
+                self.scheduler
+                    .push_framed(id, header, Bytes::from(body), fin);
+                true
+            }
+            Err(_) => {
+                self.close_request_with(
+                    id,
+                    out,
+                    consts::H3_FRAME_ERROR,
+                    "DATA frame header does not encode",
+                );
+                false
+            }
+        }
+    }
+
+    /// FIN after the message body: it must match the declared
+    /// `content-length`. A deferred final response is sent now; a client
+    /// reports the end of the response.
+    fn on_request_body_fin<'a>(&mut self, id: StreamId, out: &mut dyn CommandSink<'a>) {
+        let Some(stream) = self.requests.get_mut(&id) else {
+            return;
+        };
+        if stream.body.on_end().is_err() {
+            self.reset_request_stream(
+                id,
+                consts::H3_MESSAGE_ERROR,
+                "message body disagrees with content-length",
+                out,
+            );
+            return;
+        }
+        if let Some(response) = stream.deferred_response.take()
+            && !self.queue_final_response(id, &response, out)
+        {
+            return;
+        }
+        if self.config.role == Role::Client {
+            out.push(EngineCommand::App(AppEvent::ResponseData {
+                id,
+                data: Vec::new(),
+                fin: true,
+            }));
+        }
+        self.finish_request_stream(id);
+    }
+
+    /// Queue `response` behind whatever is already queued on `id`, and
+    /// flush. A body that disagrees with the response's `content-length`
+    /// resets the stream instead. Returns `false` once the stream or
+    /// connection was torn down.
+    fn queue_final_response<'a>(
+        &mut self,
+        id: StreamId,
+        response: &FinalResponse,
+        out: &mut dyn CommandSink<'a>,
+    ) -> bool {
+        if !(200..600).contains(&response.status) {
+            self.close_request_with(
+                id,
+                out,
+                consts::H3_INTERNAL_ERROR,
+                "final response status is not final",
+            );
+            return false;
+        }
+        let status = status_digits(response.status);
+        let fields: Vec<HeaderField<'_>> = response
+            .fields
+            .iter()
+            .map(|(name, value)| HeaderField { name, value })
+            .collect();
+        if response.body.is_empty() {
+            return self.send_response_headers(id, &status, &fields, true, out);
+        }
+        let Some(response_headers) = self.encode_response_headers(id, &status, &fields, out) else {
+            return false;
+        };
+        self.scheduler.push(id, response_headers, false);
+        if !self.queue_response_data(id, &response.body, true, out) {
+            return false;
+        }
+        self.flush_writes(out);
+        true
+    }
+
+    /// Apply the request's `priority` header to stream `id`, unless a
+    /// PRIORITY_UPDATE already arrived for it (RFC 9218 §7.1).
+    fn prioritize(&mut self, id: StreamId, priority: Option<Priority>) {
+        let priority = self.pending_priorities.remove(&id).or(priority);
+        self.scheduler
+            .set_priority(id, priority.unwrap_or_default());
+    }
+
+    /// Hand queued writes to QUIC in priority order, as far as stream
+    /// credit allows, and resume producers that drained to half the
+    /// high-water mark.
+    pub(crate) fn flush_writes<'a>(&mut self, out: &mut dyn CommandSink<'a>) {
+        pop_writes(&mut self.scheduler, out);
+
+        let low_water = self.config.stream_high_water / 2;
+        let scheduler = &self.scheduler;
+        self.paused_streams.retain(|&id| {
+            if !scheduler.contains(id) {
+                // Finished or reset: nothing left to resume.
+                return false;
+            }
+            if scheduler.queued_bytes(id) > low_water {
+                return true;
+            }
+            out.push(EngineCommand::App(AppEvent::WriteResumed { id }));
+            false
+        });
+    }
+
+    /// Queue bytes produced by the application on stream `id`, behind
+    /// `header`, and flush. Crossing the high-water mark pauses the producer
+    /// with `AppEvent::WritePaused`; what it sends meanwhile is still queued.
+    pub(crate) fn queue_app_write<'a>(
+        &mut self,
+        id: StreamId,
+        header: InlineHeader,
+        data: Bytes,
+        fin: bool,
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        self.scheduler.push_framed(id, header, data, fin);
+        self.flush_writes(out);
+        if self.scheduler.queued_bytes(id) > self.config.stream_high_water
+            && self.paused_streams.insert(id)
+        {
+            out.push(EngineCommand::App(AppEvent::WritePaused { id }));
+        }
+        self.enforce_memory_budget(out);
+    }
+
+    /// Whether a new request stream still fits in the memory budget with a
+    /// full request buffer.
+    fn has_room_for_stream(&self) -> bool {
+        self.memory_usage()
+            .checked_add(self.config.max_request_buffer)
+            .is_some_and(|needed| needed <= self.config.memory_budget)
+    }
+
+    /// Bytes buffered on behalf of stream `id`.
+    fn stream_memory(&self, id: StreamId) -> usize {
+        let request = self.request_buf(id).len();
+        let capsules = self
+            .webtransport
+            .capsule_buffers()
+            .find(|(session, _)| *session == id)
+            .map_or(0, |(_, len)| len);
+        let udp_capsules = self
+            .udp_tunnels
+            .get(&id)
+            .map_or(0, |tunnel| tunnel.capsules.len());
+        self.scheduler.queued_bytes(id) + request + capsules + udp_capsules
+    }
+
+    /// Reset the streams holding the most until usage is back within
+    /// `H3Config::memory_budget`. Critical streams are never shed.
+    fn enforce_memory_budget<'a>(&mut self, out: &mut dyn CommandSink<'a>) {
+        while self.memory_usage() > self.config.memory_budget {
+            let largest = self
+                .scheduler
+                .queued()
+                .map(|(id, _)| id)
+                .chain(self.requests.keys().copied())
+                .chain(self.udp_tunnels.keys().copied())
+                .chain(self.webtransport.capsule_buffers().map(|(id, _)| id))
+                .map(|id| (self.stream_memory(id), id))
+                .filter(|(usage, _)| *usage > 0)
+                .max();
+            let Some((_, id)) = largest else {
+                return;
+            };
+            self.shed_stream(id, out);
+        }
+    }
+
+    /// Abort stream `id` with `H3_EXCESSIVE_LOAD`, dropping everything
+    /// buffered for it, and tell the application.
+    fn shed_stream<'a>(&mut self, id: StreamId, out: &mut dyn CommandSink<'a>) {
+        let app_error = consts::H3_EXCESSIVE_LOAD;
+        self.paused_streams.remove(&id);
+        if self.webtransport.is_stream(id) {
+            self.webtransport.abort_stream(
+                id,
+                app_error,
+                "memory budget exceeded",
+                &mut self.scheduler,
+                out,
+            );
+        } else {
+            self.webtransport.drop_session(id, &mut self.scheduler, out);
+            self.tunnels.remove(&id);
+            self.udp_tunnels.remove(&id);
+            if self.requests.contains_key(&id) {
+                self.reset_request_stream(id, app_error, "memory budget exceeded", out);
+            } else {
+                // Only our response is left on it.
+                trace::reset(id, app_error, "memory budget exceeded", None);
+                self.tracer.aborted(id);
+                self.scheduler.remove(id);
+                out.push(EngineCommand::Quic(QuicCommand::ResetStream {
+                    id,
+                    app_error,
+                }));
+            }
+        }
+        out.push(EngineCommand::App(AppEvent::StreamAborted {
+            id,
+            app_error,
+        }));
+    }
+
+    /// QUIC flow control on stream `id` opened up to `capacity` bytes.
+    /// Only streams the engine writes to are tracked.
+    fn on_stream_writable<'a>(
+        &mut self,
+        id: StreamId,
+        capacity: u64,
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        let tracked = self.scheduler.contains(id)
+            || self.requests.contains_key(&id)
+            || self.tunnels.contains_key(&id)
+            || self.udp_tunnels.contains_key(&id)
+            || self.webtransport.is_session(id)
+            || self.webtransport.is_stream(id);
+        if !tracked {
+            return;
+        }
+        self.scheduler.set_capacity(id, capacity);
+        self.flush_writes(out);
+    }
+
+    /// Reprioritize a request after a PRIORITY_UPDATE frame on the peer's
+    /// control stream. Returns `false` once the connection was closed.
+    fn on_priority_update<'a>(
+        &mut self,
+        ty: u64,
+        element_id: u64,
+        priority: Result<Priority, priority::Error>,
+        out: &mut dyn CommandSink<'a>,
+    ) -> bool {
+        // We never promise pushes, so no Push ID can be prioritized; request
+        // updates must name a client-initiated bidirectional stream.
+        if ty == consts::FRAME_TYPE_PRIORITY_UPDATE_PUSH || !element_id.is_multiple_of(4) {
+            self.close_with(
+                out,
+                consts::H3_ID_ERROR,
+                "PRIORITY_UPDATE for a push or non-request stream",
+            );
+            return false;
+        }
+        let Ok(priority) = priority else {
+            self.close_with(
+                out,
+                consts::H3_GENERAL_PROTOCOL_ERROR,
+                "malformed PRIORITY_UPDATE field value",
+            );
+            return false;
+        };
+
+        let id = StreamId(element_id);
+        if self.scheduler.has_priority(id) {
+            self.scheduler.set_priority(id, priority);
+            return true;
+        }
+        // Kept for when the request is read; a stream that never opens
+        // only holds its slot until evicted.
+        if !self.pending_priorities.contains_key(&id)
+            && self.pending_priorities.len() >= MAX_PENDING_PRIORITIES
+        {
+            self.pending_priorities.pop_first();
+        }
+        self.pending_priorities.insert(id, priority);
+        true
+    }
+
+    /// The peer aborted stream `id`.
+    fn on_stream_error<'a>(
+        &mut self,
+        id: StreamId,
+        err: StreamError,
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        if self.is_critical_stream(id) {
+            self.close_with(
+                out,
+                consts::H3_CLOSED_CRITICAL_STREAM,
+                "critical stream reset",
+            );
+            return;
+        }
+        self.pending_priorities.remove(&id);
+        if self.open_bidi_streams.remove(&id) {
+            self.stats.streams_reset += 1;
+            if !self.dos.on_stream_reset(&self.config.dos_limits) {
+                self.close_with(out, consts::H3_EXCESSIVE_LOAD, "too many stream resets");
+                return;
+            }
+        }
+        let app_error = match err {
+            StreamError::Reset(code) | StreamError::StopSending(code) => code,
+        };
+        if self.tunnels.remove(&id).is_some() {
+            out.push(EngineCommand::App(AppEvent::TunnelReset { id, app_error }));
+            if self.requests.contains_key(&id) {
+                self.reset_request_stream(id, app_error, "tunnel reset by the peer", out);
+            }
+            return;
+        }
+        if self.udp_tunnels.remove(&id).is_some() {
+            out.push(EngineCommand::App(AppEvent::ConnectUdpClosed { id }));
+        }
+        // The request was abandoned.
+        if self.requests.contains_key(&id) {
+            if self.config.role == Role::Client {
+                out.push(EngineCommand::App(AppEvent::StreamAborted {
+                    id,
+                    app_error,
+                }));
+            }
+            self.scheduler.remove(id);
+            self.finish_request_stream(id);
+        }
+    }
+
+    /// Either side's control stream or one of the peer's QPACK streams,
+    /// which must stay open for the whole connection.
+    fn is_critical_stream(&self, id: StreamId) -> bool {
+        self.control_stream == Some(id)
+            || self.inbound_control_stream == Some(id)
+            || self.inbound_uni_pending_type == Some(id)
+            || self.qpack_streams.kind(id).is_some()
+    }
+
+    /// Handle a request carrying `:protocol` (RFC 9220 extended CONNECT).
+    ///
+    /// Returns `true` when a WebTransport session or CONNECT-UDP tunnel was
+    /// established and the rest of the stream should be parsed as capsules.
+    fn on_extended_connect<'a>(
+        &mut self,
+        id: StreamId,
+        head: &RequestHead,
+        protocol: &[u8],
+        out: &mut dyn CommandSink<'a>,
+    ) -> bool {
+        // RFC 9220 §3: `:protocol` is only valid on CONNECT, and only once
+        // we advertised SETTINGS_ENABLE_CONNECT_PROTOCOL.
+        if !head.connect || !self.config.settings.extended_connect_enabled() {
+            self.reset_request_stream(
+                id,
+                consts::H3_MESSAGE_ERROR,
+                "extended CONNECT not enabled",
+                out,
+            );
+            return false;
+        }
+
+        match protocol {
+            b"webtransport" if self.config.settings.webtransport_enabled() => {
+                let max_sessions = self.config.settings.webtransport_max_sessions.unwrap_or(0);
+                if self.webtransport.session_count() as u64 >= max_sessions {
+                    self.reset_request_stream(
+                        id,
+                        consts::H3_REQUEST_REJECTED,
+                        "too many WebTransport sessions",
+                        out,
+                    );
+                    return false;
+                }
+                if !self.send_response_headers(id, b"200", &[], false, out) {
+                    return false;
+                }
+                self.set_request_state(id, InboundRequestState::ConnectFrameHeader);
+                self.webtransport.open_session(id, out);
+                true
+            }
+            b"connect-udp" if self.config.settings.datagrams_enabled() => {
+                self.on_connect_udp(id, head.path.as_deref(), out)
+            }
+            _ => {
+                self.reject_request(id, b"501", out);
+                false
+            }
+        }
+    }
+
+    /// Read a CONNECT-UDP request (RFC 9298) whose `:path` follows the
+    /// default URI template. The response waits for the application to
+    /// accept or reject the target; UDP payloads are relayed by it.
+    fn on_connect_udp<'a>(
+        &mut self,
+        id: StreamId,
+        path: Option<&[u8]>,
+        out: &mut dyn CommandSink<'a>,
+    ) -> bool {
+        let mut host = [0u8; MAX_UDP_TARGET_HOST];
+        let target = path
+            .ok_or(connect_udp::Error::InvalidPath)
+            .and_then(connect_udp::parse_udp_path)
+            .and_then(|target| {
+                let len = connect_udp::percent_decode(target.host, &mut host)?;
+                Ok((len, target.port))
+            });
+        let Ok((host_len, port)) = target else {
+            self.reject_request(id, b"400", out);
+            return false;
+        };
+
+        self.set_request_state(id, InboundRequestState::ConnectFrameHeader);
+        self.udp_tunnels.insert(id, UdpTunnel::default());
+        out.push(EngineCommand::App(AppEvent::ConnectUdp {
+            id,
+            host: host[..host_len].to_vec(),
+            port,
+        }));
+        true
+    }
+
+    /// The peer finished an extended CONNECT stream.
+    fn on_connect_stream_fin<'a>(&mut self, id: StreamId, out: &mut dyn CommandSink<'a>) {
+        if let Some(tunnel) = self.tunnels.get_mut(&id) {
+            // Half-close: the application keeps sending until it finishes too.
+            tunnel.peer_fin = true;
+            if tunnel.local_fin {
+                self.tunnels.remove(&id);
+            }
+            out.push(EngineCommand::App(AppEvent::TunnelData {
+                id,
+                data: Vec::new(),
+                fin: true,
+            }));
+        } else if let Some(tunnel) = self.udp_tunnels.remove(&id) {
+            out.push(EngineCommand::App(AppEvent::ConnectUdpClosed { id }));
+            if tunnel.accepted {
+                self.scheduler.push(id, Vec::new(), true);
+            } else {
+                // No response was sent: there is nothing to finish.
+                self.scheduler.remove(id);
+                out.push(EngineCommand::Quic(QuicCommand::ResetStream {
+                    id,
+                    app_error: consts::H3_REQUEST_CANCELLED,
+                }));
+            }
+        } else {
+            self.webtransport
+                .on_session_fin(id, &mut self.scheduler, out);
+        }
+        self.flush_writes(out);
+        self.finish_request_stream(id);
+    }
+
+    /// Answer with a bodyless `status` and stop reading the request.
+    fn reject_request<'a>(&mut self, id: StreamId, status: &[u8], out: &mut dyn CommandSink<'a>) {
+        self.open_bidi_streams.remove(&id);
+        if self.send_response_headers(id, status, &[], true, out) {
+            out.push(EngineCommand::Quic(QuicCommand::StopSending {
+                id,
+                app_error: consts::H3_NO_ERROR,
+            }));
+            self.finish_request_stream(id);
+        }
+    }
+
+    /// Application-requested abort of stream `id`.
+    fn on_app_reset_stream<'a>(
+        &mut self,
+        id: StreamId,
+        app_error: u64,
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        self.udp_tunnels.remove(&id);
+        // The request side of a tunnel may already be finished after a
+        // peer FIN.
+        if self.tunnels.remove(&id).is_some() && !self.requests.contains_key(&id) {
+            trace::reset(id, app_error, "reset by the application", None);
+            self.tracer.aborted(id);
+            self.scheduler.remove(id);
+            out.push(EngineCommand::Quic(QuicCommand::ResetStream {
+                id,
+                app_error,
+            }));
+            return;
+        }
+        if self.requests.contains_key(&id) {
+            self.reset_request_stream(id, app_error, "reset by the application", out);
+        }
+    }
+
+    /// The local side ended WebTransport session `id`; stop reading its
+    /// CONNECT stream.
+    pub(crate) fn end_webtransport_session(&mut self, id: StreamId) {
+        self.finish_request_stream(id);
+    }
+
+    /// Write a HEADERS frame carrying `:status` followed by `extra` fields.
+    /// Closes the connection and returns `false` if it cannot be encoded.
+    fn send_response_headers<'a>(
+        &mut self,
+        id: StreamId,
+        status: &[u8],
+        extra: &[HeaderField<'_>],
+        fin: bool,
+        out: &mut dyn CommandSink<'a>,
+    ) -> bool {
+        let Some(data) = self.encode_response_headers(id, status, extra, out) else {
+            return false;
+        };
+        if fin
+            && self
+                .requests
+                .get_mut(&id)
+                .is_some_and(|stream| stream.response_body.on_end().is_err())
+        {
+            self.reset_request_stream(
+                id,
+                consts::H3_MESSAGE_ERROR,
+                "response body disagrees with content-length",
+                out,
+            );
+            return false;
+        }
+        self.scheduler.push(id, data, fin);
+        self.flush_writes(out);
+        true
+    }
+
+    /// Build a HEADERS frame carrying `:status` followed by `extra` fields,
+    /// and start counting the response body against its `content-length`.
+    /// Closes the connection and returns `None` if it cannot be encoded.
+    fn encode_response_headers<'a>(
+        &mut self,
+        id: StreamId,
+        status: &[u8],
+        extra: &[HeaderField<'_>],
+        out: &mut dyn CommandSink<'a>,
+    ) -> Option<Vec<u8>> {
+        let content_length = extra
+            .iter()
+            .filter(|field| field.name == b"content-length")
+            .try_fold(None, |seen, field| {
+                let length = validate::parse_content_length(field.value).ok()?;
+                match seen {
+                    Some(seen) if seen != length => None,
+                    _ => Some(Some(length)),
+                }
+            });
+        let Some(content_length) = content_length else {
+            self.close_request_with(
+                id,
+                out,
+                consts::H3_INTERNAL_ERROR,
+                "conflicting response content-length",
+            );
+            return None;
+        };
+        if let Some(stream) = self.requests.get_mut(&id) {
+            stream.response_body = BodyLength::new(content_length);
+        }
+
+        let mut fields = Vec::with_capacity(1 + extra.len());
+        fields.push(HeaderField {
+            name: b":status",
+            value: status,
+        });
+        fields.extend_from_slice(extra);
+        let mut block = alloc::vec![0u8; block_capacity(&fields)];
+        let frame = self
+            .encode_section(&fields, &mut block)
+            .ok()
+            .and_then(|len| {
+                self.stats.qpack_out.record(len, field_bytes(&fields));
+                if let Some(qlog) = self.qlog.as_mut() {
+                    qlog.headers(Owner::Local, id, fields.iter().copied(), len);
+                }
+                self.pooled_frame(id, consts::FRAME_TYPE_HEADERS, &block[..len])
+                    .ok()
+            });
+        if frame.is_none() {
+            self.close_request_with(
+                id,
+                out,
+                consts::H3_INTERNAL_ERROR,
+                "response HEADERS do not encode",
+            );
+        }
+        frame
+    }
+
+    /// QPACK-encode `fields` into `block`, one line per cookie crumb when
+    /// `H3ConfigBuilder::crumble_cookies` is on.
+    fn encode_section(
+        &self,
+        fields: &[HeaderField<'_>],
+        block: &mut [u8],
+    ) -> Result<usize, qpack::EncodeError> {
+        if self.config.crumble_cookies {
+            qpack::cookie::encode_crumbled(fields, block)
+        } else {
+            qpack::encode(fields, block)
+        }
+    }
+
+    /// Ask the response hook which 1xx HEADERS frames precede the final
+    /// response to `head`. Returns `None` once the request was answered
+    /// with 417 or the connection was closed.
+    fn interim_responses<'a>(
+        &mut self,
+        id: StreamId,
+        head: &RequestHead,
+        out: &mut dyn CommandSink<'a>,
+    ) -> Option<Vec<Vec<u8>>> {
+        let Some(hook) = self.response_hook.as_mut() else {
+            return Some(Vec::new());
+        };
+        let request = request_info(id, head);
+        let mut responses = Vec::new();
+        if head.expect_continue {
+            if !hook.expect_continue(&request) {
+                self.reject_request(id, b"417", out);
+                return None;
+            }
+            responses.push(InterimResponse::new(100));
+        }
+        responses.extend(hook.interim_responses(&request));
+
+        let mut frames = Vec::with_capacity(responses.len());
+        for response in &responses {
+            let Some(frame) = self.encode_interim_headers(id, response) else {
+                self.close_request_with(
+                    id,
+                    out,
+                    consts::H3_INTERNAL_ERROR,
+                    "interim response does not encode",
+                );
+                return None;
+            };
+            frames.push(frame);
+        }
+        Some(frames)
+    }
+
+    /// Abort request stream `id` in both directions with `app_error`.
+    fn reset_request_stream<'a>(
+        &mut self,
+        id: StreamId,
+        app_error: u64,
+        reason: &'static str,
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        trace::reset(id, app_error, reason, self.unread_offset(id));
+        self.tracer.aborted(id);
+        out.push(EngineCommand::Quic(QuicCommand::StopSending {
+            id,
+            app_error,
+        }));
+        out.push(EngineCommand::Quic(QuicCommand::ResetStream {
+            id,
+            app_error,
+        }));
+        self.scheduler.remove(id);
+        self.open_bidi_streams.remove(&id);
+        if self.config.role == Role::Client && self.requests.contains_key(&id) {
+            out.push(EngineCommand::App(AppEvent::StreamAborted {
+                id,
+                app_error,
+            }));
+        }
+        self.finish_request_stream(id);
+    }
+
+    /// Refuse a peer bidirectional stream beyond `max_concurrent_streams`
+    /// before reading any of it (RFC 9114 §4.1.1).
+    fn refuse_stream<'a>(&mut self, id: StreamId, out: &mut dyn CommandSink<'a>) {
+        self.refuse_stream_with(
+            id,
+            consts::H3_REQUEST_REJECTED,
+            "max_concurrent_streams reached",
+            out,
+        );
+    }
+
+    fn refuse_stream_with<'a>(
+        &mut self,
+        id: StreamId,
+        app_error: u64,
+        reason: &'static str,
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        trace::reset(id, app_error, reason, None);
+        self.tracer.aborted(id);
+        for cmd in [
+            QuicCommand::StopSending { id, app_error },
+            QuicCommand::ResetStream { id, app_error },
+        ] {
+            out.push(EngineCommand::Quic(cmd));
+        }
+    }
+
+    // Stop tracking request stream `id`; later data on it is ignored.
+    fn finish_request_stream(&mut self, id: StreamId) {
+        self.pending_priorities.remove(&id);
+        if let Some(stream) = self.requests.remove(&id) {
+            self.recycle_request_buf(stream.buf);
+        }
+    }
+
+    /// Append `data` to the reassembly buffer of request stream `id`,
+    /// drawing one from the pool when the stream has none yet.
+    fn buffer_request_bytes(&mut self, id: StreamId, data: &[u8]) {
+        let Some(stream) = self.requests.get_mut(&id) else {
+            return;
+        };
+        if stream.buf.capacity() == 0 {
+            stream.buf = self.pool.take(data.len());
+        }
+        stream.buf.extend_from_slice(data);
+    }
+
+    /// Bytes buffered unparsed on request stream `id`.
+    fn request_buf(&self, id: StreamId) -> &[u8] {
+        self.requests.get(&id).map_or(&[], |stream| &stream.buf)
+    }
+
+    /// Drop the first `n` buffered bytes of request stream `id`.
+    fn consume_request_bytes(&mut self, id: StreamId, n: usize) {
+        if let Some(stream) = self.requests.get_mut(&id) {
+            stream.buf.drain(..n);
+        }
+    }
+
+    fn set_request_state(&mut self, id: StreamId, state: InboundRequestState) {
+        if let Some(stream) = self.requests.get_mut(&id) {
+            stream.state = state;
+        }
+    }
+
+    /// Return a request reassembly buffer to the pool.
+    fn recycle_request_buf(&mut self, buf: Vec<u8>) {
+        if buf.capacity() > 0 {
+            self.pool.give(buf);
+        }
+    }
+
+    /// A frame of type `ty` around `payload`, in a pooled buffer.
+    fn pooled_frame(
+        &mut self,
+        id: StreamId,
+        ty: u64,
+        payload: &[u8],
+    ) -> Result<Vec<u8>, h3_frame::Error> {
+        let header = frame_header(ty, payload.len())?;
+        let mut frame = self.pool.take(header.len() + payload.len());
+        frame.extend_from_slice(header.as_slice());
+        frame.extend_from_slice(payload);
+        self.frame_out(id, ty, payload.len());
+        Ok(frame)
+    }
+
+    /// Bytes on a peer uni stream whose type is not known yet. Once it is,
+    /// the stream becomes the control stream or a WebTransport stream;
+    /// reserved and unknown types are refused with STOP_SENDING
+    /// (RFC 9114 §6.2).
+    fn on_uni_stream_start<'a>(
+        &mut self,
+        id: StreamId,
+        data: &'a [u8],
+        fin: bool,
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        let TypeProgress::Complete { ty, consumed } = self.pending_uni_types.feed(id, data) else {
+            if fin {
+                self.pending_uni_types.remove(id);
+                self.close_with(
+                    out,
+                    consts::H3_GENERAL_PROTOCOL_ERROR,
+                    "truncated stream type",
+                );
+            }
+            return;
+        };
+        let rest = &data[consumed..];
+
+        match ty {
+            consts::STREAM_TYPE_CONTROL => {
+                if self.inbound_control_stream.is_some() || self.inbound_uni_pending_type.is_some()
+                {
+                    self.close_with(
+                        out,
+                        consts::H3_STREAM_CREATION_ERROR,
+                        "second control stream",
+                    );
+                    return;
+                }
+                if let Some(qlog) = self.qlog.as_mut() {
+                    qlog.stream_type_set(Owner::Remote, id, "control");
+                }
+                self.inbound_uni_pending_type = Some(id);
+                self.inbound_uni_pending_buf.clear();
+                self.inbound_uni_state = InboundUniState::FrameHeader;
+                self.on_control_stream_data(id, rest, fin, out);
+            }
+            consts::STREAM_TYPE_QPACK_ENCODER | consts::STREAM_TYPE_QPACK_DECODER => {
+                let kind = match ty {
+                    consts::STREAM_TYPE_QPACK_ENCODER => QpackStream::Encoder,
+                    _ => QpackStream::Decoder,
+                };
+                if !self.qpack_streams.open(kind, id) {
+                    self.close_with(
+                        out,
+                        consts::H3_STREAM_CREATION_ERROR,
+                        "second QPACK stream of one type",
+                    );
+                    return;
+                }
+                if let Some(qlog) = self.qlog.as_mut() {
+                    let name = match kind {
+                        QpackStream::Encoder => "qpack_encode",
+                        QpackStream::Decoder => "qpack_decode",
+                    };
+                    qlog.stream_type_set(Owner::Remote, id, name);
+                }
+                self.on_qpack_stream_data(kind, rest, fin, out);
+            }
+            consts::STREAM_TYPE_WEBTRANSPORT if self.config.settings.webtransport_enabled() => {
+                self.webtransport.track_incoming(id, StreamKind::Uni);
+                self.webtransport.on_pending_data(id, rest, fin, out);
+            }
+            consts::STREAM_TYPE_PUSH => {
+                self.close_with(
+                    out,
+                    consts::H3_GENERAL_PROTOCOL_ERROR,
+                    "unsupported unidirectional stream type",
+                );
+            }
+            _ => {
+                // Reserved (GREASE) and unknown types are not processed.
+                out.push(EngineCommand::Quic(QuicCommand::StopSending {
+                    id,
+                    app_error: consts::H3_STREAM_CREATION_ERROR,
+                }));
+            }
+        }
+    }
+
+    /// Instructions on the peer's QPACK encoder or decoder stream.
+    fn on_qpack_stream_data<'a>(
+        &mut self,
+        kind: QpackStream,
+        data: &[u8],
+        fin: bool,
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        match self.qpack_streams.on_data(kind, data) {
+            Err(InstructionError::Encoder) => self.close_with(
+                out,
+                consts::H3_QPACK_ENCODER_STREAM_ERROR,
+                "dynamic table instruction on the encoder stream",
+            ),
+            Err(InstructionError::Decoder) => self.close_with(
+                out,
+                consts::H3_QPACK_DECODER_STREAM_ERROR,
+                "dynamic table instruction on the decoder stream",
+            ),
+            Ok(()) if fin => {
+                self.close_with(
+                    out,
+                    consts::H3_CLOSED_CRITICAL_STREAM,
+                    "QPACK stream closed",
+                );
+            }
+            Ok(()) => {}
+        }
+    }
+
+    /// Bytes on the peer control stream: SETTINGS first, then the frames
+    /// `parse_control_stream_after_settings` handles.
+    fn on_control_stream_data<'a>(
+        &mut self,
+        id: StreamId,
+        data: &[u8],
+        fin: bool,
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        self.inbound_uni_pending_buf.extend_from_slice(data);
+        if self.inbound_uni_pending_type != Some(id) {
+            self.parse_control_stream_after_settings(id, fin, out);
+            return;
+        }
+
+        loop {
+            match self.inbound_uni_state {
+                InboundUniState::FrameHeader => {
+                    if fin && self.inbound_uni_pending_buf.is_empty() {
+                        self.close_with(
+                            out,
+                            consts::H3_CLOSED_CRITICAL_STREAM,
+                            "control stream closed",
+                        );
+                        return;
+                    }
+                    let (frame_header, consumed) =
+                        match h3_frame::decode_frame_header(&self.inbound_uni_pending_buf) {
+                            Ok(parsed) => parsed,
+                            Err(h3_frame::Error::VarInt(varint::VarIntError::BufferTooSmall)) => {
+                                if fin {
+                                    self.close_with(
+                                        out,
+                                        consts::H3_FRAME_ERROR,
+                                        "truncated frame header",
+                                    );
+                                }
+                                return;
+                            }
+                            Err(_) => {
+                                self.close_with(
+                                    out,
+                                    consts::H3_FRAME_ERROR,
+                                    "malformed frame header",
+                                );
+                                return;
+                            }
+                        };
+
+                    if frame_header.ty != consts::FRAME_TYPE_SETTINGS {
+                        self.close_with(
+                            out,
+                            consts::H3_FRAME_UNEXPECTED,
+                            "control stream does not start with SETTINGS",
+                        );
+                        return;
+                    }
+
+                    let payload_len = match usize::try_from(frame_header.len) {
+                        Ok(len) => len,
+                        Err(_) => {
+                            self.close_with(
+                                out,
+                                consts::H3_FRAME_ERROR,
+                                "frame length exceeds usize",
+                            );
+                            return;
+                        }
+                    };
+
+                    if payload_len > MAX_SETTINGS_PAYLOAD {
+                        self.close_with(out, consts::H3_FRAME_ERROR, "SETTINGS frame too large");
+                        return;
+                    }
+
+                    self.inbound_uni_pending_buf.drain(..consumed);
+                    self.inbound_uni_state = InboundUniState::Payload { len: payload_len };
+                }
+                InboundUniState::Payload { len } => {
+                    if self.inbound_uni_pending_buf.len() < len {
+                        if fin {
+                            self.close_with(
+                                out,
+                                consts::H3_FRAME_ERROR,
+                                "truncated SETTINGS frame",
+                            );
+                        }
+                        return;
+                    }
+
+                    let peer_settings =
+                        match Settings::decode_payload(&self.inbound_uni_pending_buf[..len]) {
+                            Ok(settings) => settings,
+                            Err(settings::Error::Truncated | settings::Error::VarInt(_)) => {
+                                self.close_with(
+                                    out,
+                                    consts::H3_FRAME_ERROR,
+                                    "malformed SETTINGS frame",
+                                );
+                                return;
+                            }
+                            Err(_) => {
+                                self.close_with(out, consts::H3_SETTINGS_ERROR, "invalid SETTINGS");
                                 return;
                             }
                         };
 
-                    if frame_header.ty != consts::FRAME_TYPE_HEADERS {
-                        self.close_request_with(out, consts::H3_FRAME_UNEXPECTED);
-                        return;
+                    self.stats.frame_in(consts::FRAME_TYPE_SETTINGS, len as u64);
+                    if let Some(qlog) = self.qlog.as_mut() {
+                        qlog.frame(
+                            Owner::Remote,
+                            id,
+                            consts::FRAME_TYPE_SETTINGS,
+                            len as u64,
+                            Some(&peer_settings),
+                        );
+                        qlog.parameters_set(Owner::Remote, &peer_settings);
                     }
+                    self.inbound_uni_pending_buf.drain(..len);
+                    self.peer_settings = Some(peer_settings);
+                    self.inbound_control_stream = Some(id);
+                    self.inbound_uni_pending_type = None;
 
-                    let payload_len = match usize::try_from(frame_header.len) {
-                        Ok(len) => len,
-                        Err(_) => {
-                            self.close_request_with(out, consts::H3_FRAME_ERROR);
-                            return;
-                        }
-                    };
-
-                    if payload_len > MAX_REQUEST_HEADERS_PAYLOAD {
-                        self.close_request_with(out, consts::H3_FRAME_ERROR);
-                        return;
+                    let early: Vec<StreamId> = self
+                        .requests
+                        .iter()
+                        .filter(|(_, stream)| stream.early)
+                        .map(|(id, _)| *id)
+                        .collect();
+                    for req_id in early {
+                        let Some(stream) = self.requests.get_mut(&req_id) else {
+                            continue;
+                        };
+                        stream.early = false;
+                        let fin = stream.early_fin;
+                        self.parse_request_stream(req_id, fin, out);
                     }
 
-                    self.inbound_request_buf.drain(0..consumed);
-                    self.inbound_request_state =
-                        InboundRequestState::NeedPayload { len: payload_len };
+                    self.parse_control_stream_after_settings(id, fin, out);
+                    return;
                 }
-                InboundRequestState::NeedPayload { len } => {
-                    if self.inbound_request_buf.len() < len {
+            }
+        }
+    }
+
+    /// Frames after SETTINGS on the peer control stream. PRIORITY_UPDATE,
+    /// ORIGIN, MAX_PUSH_ID, GOAWAY and CANCEL_PUSH are understood; frames
+    /// that belong elsewhere are unexpected, and reserved or unknown types
+    /// are skipped (RFC 9114 §9).
+    fn parse_control_stream_after_settings<'a>(
+        &mut self,
+        id: StreamId,
+        fin: bool,
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        while !self.inbound_uni_pending_buf.is_empty() {
+            if self.control_skip > 0 {
+                let buffered = self.inbound_uni_pending_buf.len();
+                let take = usize::try_from(self.control_skip).map_or(buffered, |n| n.min(buffered));
+                self.inbound_uni_pending_buf.drain(..take);
+                self.control_skip -= take as u64;
+                continue;
+            }
+            let (frame_header, consumed) =
+                match h3_frame::decode_frame_header(&self.inbound_uni_pending_buf) {
+                    Ok(parsed) => parsed,
+                    Err(h3_frame::Error::VarInt(varint::VarIntError::BufferTooSmall)) => {
                         if fin {
-                            self.close_request_with(out, consts::H3_FRAME_ERROR);
+                            self.close_with(out, consts::H3_FRAME_ERROR, "truncated frame header");
                         }
                         return;
                     }
-
-                    self.inbound_request_buf.drain(0..len);
-                    if !self.inbound_request_buf.is_empty() {
-                        self.close_request_with(out, consts::H3_FRAME_ERROR);
+                    Err(_) => {
+                        self.close_with(out, consts::H3_FRAME_ERROR, "malformed frame header");
                         return;
                     }
+                };
 
-                    self.inbound_request_state = InboundRequestState::Complete;
-                    self.inbound_request_stream = None;
-                    self.inbound_request_buf.clear();
-                    self.pending_request_fin = false;
-
-                    let mut frame_header = [0u8; 16];
-                    let header_len = match h3_frame::encode_frame_header(
-                        h3_frame::FrameHeader {
-                            ty: consts::FRAME_TYPE_HEADERS,
-                            len: RESPONSE_HEADERS_PAYLOAD.len() as u64,
-                        },
-                        &mut frame_header,
-                    ) {
-                        Ok(len) => len,
-                        Err(_) => {
-                            self.close_request_with(out, consts::H3_FRAME_ERROR);
-                            return;
-                        }
-                    };
-
-                    let mut response_headers =
-                        Vec::with_capacity(header_len + RESPONSE_HEADERS_PAYLOAD.len());
-                    response_headers.extend_from_slice(&frame_header[..header_len]);
-                    response_headers.extend_from_slice(&RESPONSE_HEADERS_PAYLOAD);
+            let max_payload = match frame_header.ty {
+                consts::FRAME_TYPE_PRIORITY_UPDATE_REQUEST
+                | consts::FRAME_TYPE_PRIORITY_UPDATE_PUSH => MAX_PRIORITY_UPDATE_PAYLOAD,
+                consts::FRAME_TYPE_ORIGIN => MAX_ORIGIN_PAYLOAD,
+                consts::FRAME_TYPE_MAX_PUSH_ID => MAX_PUSH_ID_PAYLOAD,
+                consts::FRAME_TYPE_GOAWAY | consts::FRAME_TYPE_CANCEL_PUSH => MAX_ID_PAYLOAD,
+                consts::FRAME_TYPE_DATA
+                | consts::FRAME_TYPE_HEADERS
+                | consts::FRAME_TYPE_SETTINGS
+                | consts::FRAME_TYPE_PUSH_PROMISE => {
+                    self.close_with(
+                        out,
+                        consts::H3_FRAME_UNEXPECTED,
+                        "frame not allowed on the control stream",
+                    );
+                    return;
+                }
+                ty if consts::FRAME_TYPES_HTTP2_RESERVED.contains(&ty) => {
+                    self.close_with(out, consts::H3_FRAME_UNEXPECTED, "HTTP/2 frame type");
+                    return;
+                }
+                ty => {
+                    self.frame_in(id, ty, frame_header.len);
+                    self.inbound_uni_pending_buf.drain(..consumed);
+                    self.control_skip = frame_header.len;
+                    continue;
+                }
+            };
 
-                    out.push(EngineCommand::Quic(QuicCommand::StreamWriteOwned {
-                        id,
-                        data: response_headers,
-                        fin: false,
-                    }));
+            let payload_len = match usize::try_from(frame_header.len) {
+                Ok(len) if len <= max_payload => len,
+                _ => {
+                    self.close_with(out, consts::H3_FRAME_ERROR, "control frame too large");
+                    return;
+                }
+            };
+            let end = consumed + payload_len;
+            if self.inbound_uni_pending_buf.len() < end {
+                if fin {
+                    self.close_with(out, consts::H3_FRAME_ERROR, "truncated control frame");
+                }
+                return;
+            }
+            self.frame_in(id, frame_header.ty, frame_header.len);
 
-                    let data_header_len = match h3_frame::encode_frame_header(
-                        h3_frame::FrameHeader {
-                            ty: consts::FRAME_TYPE_DATA,
-                            len: RESPONSE_DATA_PAYLOAD.len() as u64,
-                        },
-                        &mut frame_header,
-                    ) {
-                        Ok(len) => len,
-                        Err(_) => {
-                            self.close_request_with(out, consts::H3_FRAME_ERROR);
-                            return;
-                        }
-                    };
+            if frame_header.ty == consts::FRAME_TYPE_ORIGIN {
+                if !self.on_origin(consumed, end, out) {
+                    return;
+                }
+                self.inbound_uni_pending_buf.drain(..end);
+                continue;
+            }
 
-                    let mut response_data =
-                        Vec::with_capacity(data_header_len + RESPONSE_DATA_PAYLOAD.len());
-                    response_data.extend_from_slice(&frame_header[..data_header_len]);
-                    response_data.extend_from_slice(&RESPONSE_DATA_PAYLOAD);
+            if frame_header.ty == consts::FRAME_TYPE_MAX_PUSH_ID {
+                if !self.on_max_push_id(consumed, end, out) {
+                    return;
+                }
+                self.inbound_uni_pending_buf.drain(..end);
+                continue;
+            }
 
-                    out.push(EngineCommand::Quic(QuicCommand::StreamWriteOwned {
-                        id,
-                        data: response_data,
-                        fin: true,
-                    }));
+            if frame_header.ty == consts::FRAME_TYPE_GOAWAY
+                || frame_header.ty == consts::FRAME_TYPE_CANCEL_PUSH
+            {
+                let id_value = match varint::decode(&self.inbound_uni_pending_buf[consumed..end]) {
+                    Ok((value, len)) if consumed + len == end => value,
+                    _ => {
+                        self.close_with(out, consts::H3_FRAME_ERROR, "malformed control frame");
+                        return;
+                    }
+                };
+                let accepted = match frame_header.ty {
+                    consts::FRAME_TYPE_GOAWAY => self.on_goaway(id_value, out),
+                    _ => {
+                        // No push is ever promised, so no push id can be cancelled.
+                        self.close_with(out, consts::H3_ID_ERROR, "CANCEL_PUSH for unknown push");
+                        false
+                    }
+                };
+                if !accepted {
                     return;
                 }
-                InboundRequestState::Complete => return,
+                self.inbound_uni_pending_buf.drain(..end);
+                continue;
+            }
+
+            let update =
+                priority::decode_priority_update(&self.inbound_uni_pending_buf[consumed..end]);
+            let Ok(update) = update else {
+                self.close_with(out, consts::H3_FRAME_ERROR, "malformed PRIORITY_UPDATE");
+                return;
+            };
+            let element_id = update.element_id;
+            let parsed = priority::parse_priority_field(update.field_value);
+            self.inbound_uni_pending_buf.drain(..end);
+            if !self.on_priority_update(frame_header.ty, element_id, parsed, out) {
+                return;
             }
         }
+        if fin {
+            self.close_with(
+                out,
+                consts::H3_CLOSED_CRITICAL_STREAM,
+                "control stream closed",
+            );
+        }
     }
 
-    fn parse_control_stream_after_settings<'a>(
+    /// ORIGIN frame whose payload is `inbound_uni_pending_buf[start..end]`.
+    /// A server ignores it (RFC 9412 §2); a client adds its entries to the
+    /// peer's origin set. Returns `false` once the connection was closed.
+    fn on_origin<'a>(&mut self, start: usize, end: usize, out: &mut dyn CommandSink<'a>) -> bool {
+        if self.config.role == Role::Server {
+            return true;
+        }
+        let payload = &self.inbound_uni_pending_buf[start..end];
+        let Ok(origins) = origin::entries(payload).collect::<Result<Vec<_>, _>>() else {
+            self.close_with(out, consts::H3_FRAME_ERROR, "malformed ORIGIN frame");
+            return false;
+        };
+        for entry in origins {
+            if !self.peer_origins.iter().any(|known| known == entry) {
+                self.peer_origins.push(entry.to_vec());
+            }
+        }
+        true
+    }
+
+    /// GOAWAY carrying `goaway_id`. From a server it names a client request
+    /// stream; either way it may never increase (RFC 9114 §5.2). Returns
+    /// `false` once the connection was closed.
+    fn on_goaway<'a>(&mut self, goaway_id: u64, out: &mut dyn CommandSink<'a>) -> bool {
+        let bad_stream = self.config.role == Role::Client && !goaway_id.is_multiple_of(4);
+        if bad_stream || self.peer_goaway.is_some_and(|last| goaway_id > last) {
+            self.close_with(out, consts::H3_ID_ERROR, "invalid GOAWAY identifier");
+            return false;
+        }
+        self.peer_goaway = Some(goaway_id);
+        true
+    }
+
+    /// MAX_PUSH_ID frame whose payload is `inbound_uni_pending_buf[start..end]`.
+    /// Only clients send it; the value may never decrease (RFC 9114 §7.2.7).
+    /// Returns `false` once the connection was closed.
+    fn on_max_push_id<'a>(
         &mut self,
-        fin: bool,
+        start: usize,
+        end: usize,
         out: &mut dyn CommandSink<'a>,
-    ) {
-        if self.inbound_uni_pending_buf.is_empty() {
+    ) -> bool {
+        if self.config.role == Role::Client {
+            self.close_with(
+                out,
+                consts::H3_FRAME_UNEXPECTED,
+                "MAX_PUSH_ID sent to a client",
+            );
+            return false;
+        }
+        let push_id = match varint::decode(&self.inbound_uni_pending_buf[start..end]) {
+            Ok((push_id, len)) if start + len == end => push_id,
+            _ => {
+                self.close_with(out, consts::H3_FRAME_ERROR, "malformed MAX_PUSH_ID");
+                return false;
+            }
+        };
+        if !self.config.enable_push {
+            return true;
+        }
+        if self.peer_max_push_id.is_some_and(|max| push_id < max) {
+            self.close_with(out, consts::H3_ID_ERROR, "MAX_PUSH_ID reduced");
+            return false;
+        }
+        self.peer_max_push_id = Some(push_id);
+        true
+    }
+
+    /// Append a reserved (identifier, value) pair to a SETTINGS payload.
+    /// Returns `bytes_written`, `0` when GREASE is off.
+    fn encode_grease_setting(&mut self, out: &mut [u8]) -> Result<usize, settings::Error> {
+        let Some(rng) = self.grease.as_mut() else {
+            return Ok(0);
+        };
+        let id_len = varint::encode(rng.reserved(), out)?;
+        let value_len = varint::encode(rng.below(1 << 32), &mut out[id_len..])?;
+        Ok(id_len + value_len)
+    }
+
+    /// A reserved frame type with a few random payload bytes for stream
+    /// `id`, if GREASE is on.
+    fn grease_frame(&mut self, id: StreamId) -> Option<Vec<u8>> {
+        let rng = self.grease.as_mut()?;
+        let mut payload = [0u8; MAX_GREASE_FRAME_PAYLOAD];
+        let len = rng.below(MAX_GREASE_FRAME_PAYLOAD as u64 + 1) as usize;
+        rng.fill(&mut payload[..len]);
+        let ty = rng.reserved();
+        let frame = encode_frame(ty, &payload[..len]).ok()?;
+        self.frame_out(id, ty, len);
+        Some(frame)
+    }
+
+    /// Follow SETTINGS on control stream `control` with a reserved frame,
+    /// and on a coin flip open a uni stream of a reserved type and finish it.
+    fn send_grease_on_boot<'a>(&mut self, control: StreamId, out: &mut dyn CommandSink<'a>) {
+        if let Some(data) = self.grease_frame(control) {
+            out.push(EngineCommand::Quic(QuicCommand::StreamWriteOwned {
+                id: control,
+                data,
+                fin: false,
+            }));
+        }
+        let Some(rng) = self.grease.as_mut() else {
             return;
+        };
+        if rng.below(2) == 0 {
+            return;
+        }
+        let mut stream_type = [0u8; 8];
+        let Ok(len) = varint::encode(rng.reserved(), &mut stream_type) else {
+            return;
+        };
+        let id = self.webtransport.alloc_local_uni();
+        out.push(EngineCommand::Quic(QuicCommand::OpenUni {
+            id_hint: Some(id),
+        }));
+        if let Some(qlog) = self.qlog.as_mut() {
+            qlog.stream_type_set(Owner::Local, id, "reserved");
         }
+        out.push(EngineCommand::Quic(QuicCommand::StreamWriteOwned {
+            id,
+            data: stream_type[..len].to_vec(),
+            fin: true,
+        }));
+    }
 
-        match h3_frame::decode_frame_header(&self.inbound_uni_pending_buf) {
-            Ok(_) => {
-                self.close_with(out, consts::H3_FRAME_UNEXPECTED);
+    /// The deadline request stream `stream` should be under right now.
+    /// Only a server guards the requests it reads.
+    fn request_deadline(&self, stream: &RequestStream) -> Option<(RequestTimer, u64)> {
+        if self.config.role == Role::Client {
+            return None;
+        }
+        match stream.state {
+            InboundRequestState::NeedFrameHeader | InboundRequestState::NeedPayload { .. } => {
+                Some((
+                    RequestTimer::HeaderRead,
+                    self.config.header_read_timeout_ms?,
+                ))
             }
-            Err(h3_frame::Error::VarInt(varint::VarIntError::BufferTooSmall)) => {
-                if fin {
-                    self.close_with(out, consts::H3_FRAME_ERROR);
-                }
+            InboundRequestState::BodyFrameHeader { .. }
+            | InboundRequestState::BodyFramePayload { .. } => {
+                Some((RequestTimer::BodyIdle, self.config.body_idle_timeout_ms?))
             }
-            Err(_) => {
-                self.close_with(out, consts::H3_FRAME_ERROR);
+            _ => None,
+        }
+    }
+
+    /// Bring the armed timers in line with the engine state after an
+    /// event. `active` means the event counts as connection activity.
+    fn sync_timers<'a>(&mut self, active: bool, out: &mut dyn CommandSink<'a>) {
+        let mut wanted = Vec::new();
+        for (&id, stream) in &self.requests {
+            if let Some((kind, timeout)) = self.request_deadline(stream) {
+                wanted.push((id, kind, timeout, stream.activity));
+            }
+        }
+        for stream in self.requests.values_mut() {
+            stream.activity = false;
+        }
+        if self.timers_stopped {
+            if core::mem::take(&mut self.idle_timer_armed) {
+                out.push(EngineCommand::CancelTimer { id: IDLE_TIMER });
             }
+            wanted.clear();
+        }
+
+        if !self.timers_stopped
+            && let Some(timeout) = self.config.idle_timeout_ms
+            && (active || !self.idle_timer_armed)
+        {
+            self.idle_timer_armed = true;
+            out.push(EngineCommand::ArmTimer {
+                id: IDLE_TIMER,
+                deadline_ms_from_now: timeout,
+            });
+        }
+
+        let stale: Vec<StreamId> = self
+            .request_timers
+            .keys()
+            .filter(|id| !wanted.iter().any(|(wanted, ..)| wanted == *id))
+            .copied()
+            .collect();
+        for id in stale {
+            self.request_timers.remove(&id);
+            if let Some(timer) = stream_timer(id) {
+                out.push(EngineCommand::CancelTimer { id: timer });
+            }
+        }
+        for (id, kind, timeout, activity) in wanted {
+            let armed = self.request_timers.get(&id).copied();
+            if armed == Some(kind) && !(kind == RequestTimer::BodyIdle && activity) {
+                continue;
+            }
+            let Some(timer) = stream_timer(id) else {
+                continue;
+            };
+            self.request_timers.insert(id, kind);
+            out.push(EngineCommand::ArmTimer {
+                id: timer,
+                deadline_ms_from_now: timeout,
+            });
+        }
+    }
+
+    /// A timer armed by `sync_timers` expired. An idle connection is closed
+    /// with `H3_NO_ERROR`; a stalled request is reset with
+    /// `H3_REQUEST_INCOMPLETE`.
+    fn on_timer<'a>(&mut self, timer: TimerId, out: &mut dyn CommandSink<'a>) {
+        if timer == IDLE_TIMER && self.idle_timer_armed {
+            self.idle_timer_armed = false;
+            self.timers_stopped = true;
+            self.close_with(out, consts::H3_NO_ERROR, "idle timeout");
+            return;
+        }
+        let Some(id) = self
+            .request_timers
+            .keys()
+            .copied()
+            .find(|id| stream_timer(*id) == Some(timer))
+        else {
+            return;
+        };
+        self.request_timers.remove(&id);
+        self.reset_request_stream(id, consts::H3_REQUEST_INCOMPLETE, "request timed out", out);
+    }
+
+    /// Count a frame of type `ty` with a `len`-byte payload parsed on `id`,
+    /// and trace it.
+    fn frame_in(&mut self, id: StreamId, ty: u64, len: u64) {
+        self.stats.frame_in(ty, len);
+        if let Some(qlog) = self.qlog.as_mut() {
+            qlog.frame(Owner::Remote, id, ty, len, None);
+        }
+    }
+
+    /// Count a frame of type `ty` with a `len`-byte payload built for `id`,
+    /// and trace it.
+    pub(crate) fn frame_out(&mut self, id: StreamId, ty: u64, len: usize) {
+        self.stats.frame_out(ty, len);
+        if let Some(qlog) = self.qlog.as_mut() {
+            qlog.frame(Owner::Local, id, ty, len as u64, None);
+        }
+    }
+
+    /// Build an ORIGIN frame carrying `origins`, or `None` if one is too long.
+    fn encode_origin_frame(&mut self, id: StreamId, origins: &[Vec<u8>]) -> Option<Vec<u8>> {
+        let origins: Vec<&[u8]> = origins.iter().map(Vec::as_slice).collect();
+        let mut payload = alloc::vec![0u8; origin::encoded_len(&origins)];
+        let len = origin::encode_origin(&origins, &mut payload).ok()?;
+        let frame = encode_frame(consts::FRAME_TYPE_ORIGIN, &payload[..len]).ok()?;
+        self.frame_out(id, consts::FRAME_TYPE_ORIGIN, len);
+        Some(frame)
+    }
+
+    /// Build the HEADERS frame of an interim response, or `None` if its status
+    /// is not a sendable 1xx or its fields do not fit.
+    fn encode_interim_headers(
+        &mut self,
+        id: StreamId,
+        response: &InterimResponse,
+    ) -> Option<Vec<u8>> {
+        if !validate::is_interim(response.status) || response.status == 101 {
+            return None;
         }
+        let status = status_digits(response.status);
+        let mut fields = Vec::with_capacity(1 + response.fields.len());
+        fields.push(HeaderField {
+            name: b":status",
+            value: &status,
+        });
+        fields.extend(
+            response
+                .fields
+                .iter()
+                .map(|(name, value)| HeaderField { name, value }),
+        );
+
+        let mut block = alloc::vec![0u8; MAX_INTERIM_HEADERS_PAYLOAD];
+        let len = self.encode_section(&fields, &mut block).ok()?;
+        let frame = encode_frame(consts::FRAME_TYPE_HEADERS, &block[..len]).ok()?;
+        self.stats.qpack_out.record(len, field_bytes(&fields));
+        if let Some(qlog) = self.qlog.as_mut() {
+            qlog.headers(Owner::Local, id, fields.iter().copied(), len);
+        }
+        self.frame_out(id, consts::FRAME_TYPE_HEADERS, len);
+        Some(frame)
     }
 
     // M1.3 terminal teardown for request-path closes: once we decide to close,
     // prevent any further request buffering/parsing on subsequent events.
-    fn close_request_with<'a>(&mut self, out: &mut dyn CommandSink<'a>, app_error: u64) {
-        self.close_with(out, app_error);
-        self.inbound_request_stream = None;
-        self.pending_request_stream = None;
-        self.inbound_request_buf.clear();
-        self.pending_request_fin = false;
-        self.inbound_request_state = InboundRequestState::Complete;
+    fn close_request_with<'a>(
+        &mut self,
+        id: StreamId,
+        out: &mut dyn CommandSink<'a>,
+        app_error: u64,
+        reason: &'static str,
+    ) {
+        self.close_with(out, app_error, reason);
+        let Some(stream) = self.requests.get_mut(&id) else {
+            return;
+        };
+        let buf = core::mem::take(&mut stream.buf);
+        stream.state = InboundRequestState::Complete;
+        stream.early = false;
+        stream.deferred_response = None;
+        self.recycle_request_buf(buf);
     }
 
-    fn close_with<'a>(&self, out: &mut dyn CommandSink<'a>, app_error: u64) {
+    fn close_with<'a>(&self, out: &mut dyn CommandSink<'a>, app_error: u64, reason: &'static str) {
+        let at = self
+            .tracer
+            .current()
+            .and_then(|id| Some((id, self.unread_offset(id)?)));
+        trace::close(app_error, reason, at);
         out.push(EngineCommand::Quic(QuicCommand::CloseConnection {
             app_error,
         }));
     }
+
+    /// Offset of the first byte buffered unparsed on `id`, for traces.
+    fn unread_offset(&self, id: StreamId) -> Option<u64> {
+        let unread = if self.inbound_control_stream == Some(id)
+            || self.inbound_uni_pending_type == Some(id)
+        {
+            self.inbound_uni_pending_buf.len()
+        } else if let Some(stream) = self.requests.get(&id) {
+            stream.buf.len()
+        } else {
+            0
+        };
+        self.tracer.offset(id, unread)
+    }
+}
+
+/// Whether a frame of type `ty` has no place on a request stream: control
+/// stream frames, PUSH_PROMISE from a client and HTTP/2 types (RFC 9114
+/// §7.2, RFC 9218 §7.1).
+fn unexpected_on_request_stream(ty: u64) -> bool {
+    matches!(
+        ty,
+        consts::FRAME_TYPE_CANCEL_PUSH
+            | consts::FRAME_TYPE_SETTINGS
+            | consts::FRAME_TYPE_PUSH_PROMISE
+            | consts::FRAME_TYPE_GOAWAY
+            | consts::FRAME_TYPE_ORIGIN
+            | consts::FRAME_TYPE_MAX_PUSH_ID
+            | consts::FRAME_TYPE_PRIORITY_UPDATE_REQUEST
+            | consts::FRAME_TYPE_PRIORITY_UPDATE_PUSH
+    ) || consts::FRAME_TYPES_HTTP2_RESERVED.contains(&ty)
+}
+
+/// Hand every write `scheduler` can release to QUIC in priority order.
+pub(crate) fn pop_writes<'a>(scheduler: &mut WriteScheduler, out: &mut dyn CommandSink<'a>) {
+    while let Some(write) = scheduler.pop() {
+        let cmd = if write.header.is_empty() {
+            QuicCommand::StreamWriteOwned {
+                id: write.id,
+                data: write.data.into_vec(),
+                fin: write.fin,
+            }
+        } else {
+            QuicCommand::StreamWriteVectored {
+                id: write.id,
+                header: write.header,
+                body: write.data,
+                fin: write.fin,
+            }
+        };
+        out.push(EngineCommand::Quic(cmd));
+    }
+}
+
+/// What a `ResponseHook` sees of `head`.
+fn request_info(id: StreamId, head: &RequestHead) -> RequestInfo<'_> {
+    RequestInfo {
+        id,
+        authority: head.authority.as_deref(),
+        path: head.path.as_deref(),
+        content_length: head.content_length,
+        expect_continue: head.expect_continue,
+    }
+}
+
+/// Feed DATA frame payload from CONNECT-UDP stream `id` into the capsule
+/// parser buffering in `buf`. DATAGRAM capsules are delivered like QUIC
+/// DATAGRAMs (RFC 9297 §3.5); other capsule types are skipped.
+///
+/// Returns the H3 error code to close the connection with on failure.
+fn read_udp_capsules<'a>(
+    buf: &mut Vec<u8>,
+    id: StreamId,
+    bytes: &[u8],
+    out: &mut dyn CommandSink<'a>,
+) -> Result<(), u64> {
+    if buf.len() + bytes.len() > MAX_CAPSULE_BUFFER {
+        return Err(consts::H3_EXCESSIVE_LOAD);
+    }
+    buf.extend_from_slice(bytes);
+
+    loop {
+        let (capsule, consumed) = match capsule::decode_capsule(buf) {
+            Ok(parsed) => parsed,
+            Err(capsule::Error::BufferTooSmall) => return Ok(()),
+            Err(_) => return Err(consts::H3_MESSAGE_ERROR),
+        };
+        if let capsule::Capsule::Datagram(payload) = capsule {
+            out.push(EngineCommand::App(AppEvent::Datagram {
+                id,
+                payload: Cow::Owned(payload.to_vec()),
+            }));
+        }
+        buf.drain(..consumed);
+    }
+}
+
+/// Three ASCII digits of a status from 100 to 999.
+fn status_digits(status: u16) -> [u8; 3] {
+    [
+        b'0' + (status / 100 % 10) as u8,
+        b'0' + (status / 10 % 10) as u8,
+        b'0' + (status % 10) as u8,
+    ]
+}
+
+/// Room to encode `fields` as a field section: the two-byte prefix and
+/// every field as a literal with both lengths prefixed, a crumbled
+/// `cookie` taking one such line per `;`-separated crumb.
+fn block_capacity(fields: &[HeaderField<'_>]) -> usize {
+    2 + fields
+        .iter()
+        .map(|field| {
+            let crumbs = match field.name {
+                b"cookie" => 1 + field.value.iter().filter(|&&b| b == b';').count(),
+                _ => 1,
+            };
+            crumbs * (field.name.len() + 20) + field.value.len()
+        })
+        .sum::<usize>()
+}
+
+/// Name and value bytes of `fields`.
+fn field_bytes(fields: &[HeaderField<'_>]) -> u64 {
+    fields
+        .iter()
+        .map(|field| (field.name.len() + field.value.len()) as u64)
+        .sum()
+}
+
+/// Build the header of a frame of type `ty` with a `len`-byte payload.
+pub(crate) fn frame_header(ty: u64, len: usize) -> Result<InlineHeader, h3_frame::Error> {
+    let mut header = [0u8; INLINE_HEADER_LEN];
+    let header_len = h3_frame::encode_frame_header(
+        h3_frame::FrameHeader {
+            ty,
+            len: len as u64,
+        },
+        &mut header,
+    )?;
+    InlineHeader::new(&header[..header_len]).ok_or(h3_frame::Error::BufferTooSmall)
+}
+
+/// Build a complete frame of type `ty` around `payload`.
+pub(crate) fn encode_frame(ty: u64, payload: &[u8]) -> Result<Vec<u8>, h3_frame::Error> {
+    let header = frame_header(ty, payload.len())?;
+    let mut frame = Vec::with_capacity(header.len() + payload.len());
+    frame.extend_from_slice(header.as_slice());
+    frame.extend_from_slice(payload);
+    Ok(frame)
 }
 
 impl Default for H3Engine {
     fn default() -> Self {
-        Self::new()
+        Self::new(H3Config::default())
     }
 }
 
 impl Engine for H3Engine {
     fn on_event<'a>(&mut self, ev: EngineEvent<'a>, out: &mut dyn CommandSink<'a>) {
+        let active = matches!(
+            ev,
+            EngineEvent::Boot | EngineEvent::Quic(_) | EngineEvent::App(_)
+        );
+        let _entered = self.tracer.begin(&ev);
+        let mut tally = Tally {
+            out,
+            stats: EngineStats::default(),
+        };
+        self.dispatch(ev, &mut tally);
+        self.enforce_memory_budget(&mut tally);
+        self.sync_timers(active, &mut tally);
+        self.tracer.end();
+        self.stats.merge(&tally.stats);
+        self.stats.peak_buffered_bytes = self.stats.peak_buffered_bytes.max(self.memory_usage());
+    }
+}
+
+/// Passes commands on to `out`, noting the codes of closes and resets.
+struct Tally<'s, 'a> {
+    out: &'s mut dyn CommandSink<'a>,
+    stats: EngineStats,
+}
+
+impl<'a> CommandSink<'a> for Tally<'_, 'a> {
+    fn push(&mut self, cmd: EngineCommand<'a>) {
+        match &cmd {
+            EngineCommand::Quic(QuicCommand::CloseConnection { app_error }) => {
+                self.stats.closed(*app_error)
+            }
+            EngineCommand::Quic(QuicCommand::ResetStream { app_error, .. }) => {
+                self.stats.reset(*app_error)
+            }
+            _ => {}
+        }
+        self.out.push(cmd);
+    }
+}
+
+impl H3Engine {
+    fn dispatch<'a>(&mut self, ev: EngineEvent<'a>, out: &mut dyn CommandSink<'a>) {
         match ev {
             EngineEvent::Boot => {
                 let id = StreamId(2);
@@ -245,29 +3162,35 @@ impl Engine for H3Engine {
                     id_hint: Some(id),
                 }));
 
-                let settings = Settings::new();
-
-                // M0: settings payload is empty; later this will be a real buffer.
-                let mut payload_buf = [0u8; 0];
-                let payload_len = match settings.encode_payload(&mut payload_buf) {
+                let mut payload_buf = [0u8; MAX_LOCAL_SETTINGS_PAYLOAD];
+                let payload_len = match self
+                    .config
+                    .settings
+                    .encode_payload(&mut payload_buf)
+                    .and_then(|len| Ok(len + self.encode_grease_setting(&mut payload_buf[len..])?))
+                {
                     Ok(len) => len,
                     Err(_) => {
-                        out.push(EngineCommand::Quic(QuicCommand::CloseConnection {
-                            app_error: consts::H3_FRAME_ERROR,
-                        }));
+                        self.close_with(
+                            out,
+                            consts::H3_FRAME_ERROR,
+                            "local SETTINGS do not encode",
+                        );
                         return;
                     }
                 };
 
-                let mut bytes = [0u8; 32];
+                let mut bytes = [0u8; 32 + MAX_LOCAL_SETTINGS_PAYLOAD];
 
                 let stream_type_len = match varint::encode(consts::STREAM_TYPE_CONTROL, &mut bytes)
                 {
                     Ok(len) => len,
                     Err(_) => {
-                        out.push(EngineCommand::Quic(QuicCommand::CloseConnection {
-                            app_error: consts::H3_FRAME_ERROR,
-                        }));
+                        self.close_with(
+                            out,
+                            consts::H3_FRAME_ERROR,
+                            "control stream type does not encode",
+                        );
                         return;
                     }
                 };
@@ -280,219 +3203,181 @@ impl Engine for H3Engine {
                     match h3_frame::encode_frame_header(header, &mut bytes[stream_type_len..]) {
                         Ok(len) => len,
                         Err(_) => {
-                            out.push(EngineCommand::Quic(QuicCommand::CloseConnection {
-                                app_error: consts::H3_FRAME_ERROR,
-                            }));
+                            self.close_with(
+                                out,
+                                consts::H3_FRAME_ERROR,
+                                "SETTINGS frame header does not encode",
+                            );
                             return;
                         }
                     };
 
-                let total = stream_type_len + header_len;
+                let payload_start = stream_type_len + header_len;
+                let total = payload_start + payload_len;
+                bytes[payload_start..total].copy_from_slice(&payload_buf[..payload_len]);
+                self.stats
+                    .frame_out(consts::FRAME_TYPE_SETTINGS, payload_len);
+                if let Some(qlog) = self.qlog.as_mut() {
+                    let settings = &self.config.settings;
+                    qlog.stream_type_set(Owner::Local, id, "control");
+                    qlog.frame(
+                        Owner::Local,
+                        id,
+                        consts::FRAME_TYPE_SETTINGS,
+                        payload_len as u64,
+                        Some(settings),
+                    );
+                    qlog.parameters_set(Owner::Local, settings);
+                }
                 out.push(EngineCommand::Quic(QuicCommand::StreamWriteOwned {
                     id,
                     data: bytes[..total].to_vec(),
                     fin: false,
                 }));
+
+                let origin_set = self.origin_set.take();
+                if let Some(origins) = origin_set
+                    .as_ref()
+                    .filter(|_| self.config.role == Role::Server)
+                {
+                    let Some(data) = self.encode_origin_frame(id, origins) else {
+                        self.close_with(
+                            out,
+                            consts::H3_INTERNAL_ERROR,
+                            "ORIGIN frame does not encode",
+                        );
+                        return;
+                    };
+                    out.push(EngineCommand::Quic(QuicCommand::StreamWriteOwned {
+                        id,
+                        data,
+                        fin: false,
+                    }));
+                }
+
+                self.origin_set = origin_set;
+                self.send_grease_on_boot(id, out);
             }
             EngineEvent::Quic(QuicEvent::StreamOpened {
                 id,
                 kind: StreamKind::Uni,
-            }) => {
-                if self.inbound_uni_pending_type.is_none() {
-                    self.inbound_uni_pending_type = Some(id);
-                    self.inbound_uni_pending_buf.clear();
-                    self.inbound_uni_state = InboundUniState::Type;
-                }
+            }) if !self.pending_uni_types.open(id) => {
+                self.close_with(
+                    out,
+                    consts::H3_EXCESSIVE_LOAD,
+                    "too many streams without a type",
+                );
             }
+            EngineEvent::Quic(QuicEvent::StreamOpened {
+                kind: StreamKind::Uni,
+                ..
+            }) => {}
             EngineEvent::Quic(QuicEvent::StreamOpened {
                 id,
                 kind: StreamKind::Bidi,
             }) => {
-                if self.request_stream_claimed {
+                let webtransport = self.config.settings.webtransport_enabled();
+                if self.config.role == Role::Client && !webtransport {
+                    // RFC 9114 §6.1: servers do not open bidirectional streams.
+                    self.close_with(
+                        out,
+                        consts::H3_STREAM_CREATION_ERROR,
+                        "server-initiated bidirectional stream",
+                    );
                     return;
                 }
+                if self.open_bidi_streams.len() >= self.config.max_concurrent_streams
+                    || !self.has_room_for_stream()
+                {
+                    self.refuse_stream(id, out);
+                    return;
+                }
+                self.open_bidi_streams.insert(id);
+                self.stats.streams_opened += 1;
+                if let Some(qlog) = self.qlog.as_mut() {
+                    qlog.stream_type_set(Owner::Remote, id, "request");
+                }
 
-                if self.inbound_request_stream.is_none() && self.pending_request_stream.is_none() {
-                    self.pending_request_stream = Some(id);
-                    self.request_stream_claimed = true;
-                    self.claimed_request_stream_id = Some(id);
+                // The first bytes tell a WebTransport stream from a request.
+                if webtransport {
+                    self.webtransport.track_incoming(id, StreamKind::Bidi);
+                    return;
                 }
+                self.open_request(id);
             }
             EngineEvent::Quic(QuicEvent::StreamReadable { id, data, fin }) => {
-                if self.inbound_control_stream == Some(id)
-                    && self.inbound_uni_pending_type != Some(id)
-                {
-                    self.inbound_uni_pending_buf.extend_from_slice(data);
-                    self.parse_control_stream_after_settings(fin, out);
-                    return;
+                if fin && self.open_bidi_streams.remove(&id) {
+                    self.dos.on_stream_finished();
+                    self.stats.streams_completed += 1;
                 }
-
-                if self.inbound_uni_pending_type == Some(id) {
-                    self.inbound_uni_pending_buf.extend_from_slice(data);
-
-                    loop {
-                        match self.inbound_uni_state {
-                            InboundUniState::Type => {
-                                let (stream_ty, consumed) =
-                                    match varint::decode(&self.inbound_uni_pending_buf) {
-                                        Ok(parsed) => parsed,
-                                        Err(varint::VarIntError::BufferTooSmall) => {
-                                            if fin {
-                                                self.close_with(
-                                                    out,
-                                                    consts::H3_GENERAL_PROTOCOL_ERROR,
-                                                );
-                                            }
-                                            return;
-                                        }
-                                        Err(_) => {
-                                            self.close_with(out, consts::H3_GENERAL_PROTOCOL_ERROR);
-                                            return;
-                                        }
-                                    };
-
-                                if stream_ty != consts::STREAM_TYPE_CONTROL {
-                                    self.close_with(out, consts::H3_GENERAL_PROTOCOL_ERROR);
-                                    return;
-                                }
-
-                                // M1/M1.2 simplicity: front-drain from Vec. This is O(n);
-                                // a cursor/ring-buffer is a likely M2+ follow-up.
-                                self.inbound_uni_pending_buf.drain(0..consumed);
-                                self.inbound_uni_state = InboundUniState::FrameHeader;
-                            }
-                            InboundUniState::FrameHeader => {
-                                let (frame_header, consumed) = match h3_frame::decode_frame_header(
-                                    &self.inbound_uni_pending_buf,
-                                ) {
-                                    Ok(parsed) => parsed,
-                                    Err(h3_frame::Error::VarInt(
-                                        varint::VarIntError::BufferTooSmall,
-                                    )) => {
-                                        if fin {
-                                            self.close_with(out, consts::H3_FRAME_ERROR);
-                                        }
-                                        return;
-                                    }
-                                    Err(_) => {
-                                        self.close_with(out, consts::H3_FRAME_ERROR);
-                                        return;
-                                    }
-                                };
-
-                                if frame_header.ty != consts::FRAME_TYPE_SETTINGS {
-                                    self.close_with(out, consts::H3_FRAME_UNEXPECTED);
-                                    return;
-                                }
-
-                                let payload_len = match usize::try_from(frame_header.len) {
-                                    Ok(len) => len,
-                                    Err(_) => {
-                                        self.close_with(out, consts::H3_FRAME_ERROR);
-                                        return;
-                                    }
-                                };
-
-                                if payload_len != 0 {
-                                    self.close_with(out, consts::H3_FRAME_ERROR);
-                                    return;
-                                }
-
-                                self.inbound_uni_pending_buf.drain(0..consumed);
-                                self.inbound_uni_state =
-                                    InboundUniState::Payload { len: payload_len };
-                            }
-                            InboundUniState::Payload { len } => {
-                                if self.inbound_uni_pending_buf.len() < len {
-                                    if fin {
-                                        self.close_with(out, consts::H3_FRAME_ERROR);
-                                    }
-                                    return;
-                                }
-
-                                self.inbound_uni_pending_buf.drain(0..len);
-                                self.inbound_control_stream = Some(id);
-                                self.inbound_uni_pending_type = None;
-                                self.inbound_uni_state = InboundUniState::Type;
-
-                                if let Some(req_id) = self.pending_request_stream {
-                                    self.pending_request_stream = None;
-                                    self.inbound_request_stream = Some(req_id);
-                                    self.inbound_request_state =
-                                        InboundRequestState::NeedFrameHeader;
-                                    self.parse_request_stream(
-                                        req_id,
-                                        self.pending_request_fin,
-                                        out,
-                                    );
-                                }
-
-                                self.parse_control_stream_after_settings(fin, out);
-                                return;
-                            }
-                        }
-                    }
+                if self.webtransport.is_stream(id) {
+                    self.webtransport.on_stream_data(id, data, fin, out);
+                    return;
                 }
-
-                if self.pending_request_stream == Some(id) && self.inbound_control_stream.is_none()
-                {
-                    let new_len = match self.inbound_request_buf.len().checked_add(data.len()) {
-                        Some(len) => len,
-                        None => {
-                            self.close_request_with(out, consts::H3_FRAME_ERROR);
-                            return;
-                        }
+                if self.webtransport.is_pending(id) {
+                    let Some(read) = self.webtransport.on_pending_data(id, data, fin, out) else {
+                        return;
                     };
-                    if new_len > MAX_EARLY_REQUEST_BUFFER {
-                        self.close_request_with(out, consts::H3_FRAME_ERROR);
+                    if self.config.role == Role::Client {
+                        // RFC 9114 §6.1: servers do not open request streams.
+                        self.close_with(
+                            out,
+                            consts::H3_STREAM_CREATION_ERROR,
+                            "server-initiated bidirectional stream",
+                        );
                         return;
                     }
-
-                    self.inbound_request_buf.extend_from_slice(data);
-                    self.pending_request_fin |= fin;
+                    self.open_request(id);
+                    self.on_request_data(id, &read, fin, out);
                     return;
                 }
 
-                if self.inbound_request_stream.is_none()
-                    && self.pending_request_stream.is_none()
-                    && self.claimed_request_stream_id == Some(id)
-                {
-                    if data.is_empty() && fin {
-                        return;
-                    }
-
-                    self.close_request_with(out, consts::H3_GENERAL_PROTOCOL_ERROR);
+                if self.pending_uni_types.contains(id) {
+                    self.on_uni_stream_start(id, data, fin, out);
                     return;
                 }
 
-                if self.inbound_request_stream.is_none()
-                    && self.inbound_control_stream.is_some()
-                    && self.pending_request_stream == Some(id)
+                if self.inbound_control_stream == Some(id)
+                    || self.inbound_uni_pending_type == Some(id)
                 {
-                    self.pending_request_stream = None;
-                    self.inbound_request_stream = Some(id);
-                    self.inbound_request_state = InboundRequestState::NeedFrameHeader;
-                }
-
-                if self.inbound_request_stream != Some(id) {
+                    self.on_control_stream_data(id, data, fin, out);
                     return;
                 }
 
-                let new_len = match self.inbound_request_buf.len().checked_add(data.len()) {
-                    Some(len) => len,
-                    None => {
-                        self.close_request_with(out, consts::H3_FRAME_ERROR);
-                        return;
-                    }
-                };
-                if new_len > MAX_EARLY_REQUEST_BUFFER {
-                    self.close_request_with(out, consts::H3_FRAME_ERROR);
+                if let Some(kind) = self.qpack_streams.kind(id) {
+                    self.on_qpack_stream_data(kind, data, fin, out);
                     return;
                 }
 
-                self.inbound_request_buf.extend_from_slice(data);
-                self.parse_request_stream(id, fin, out);
+                self.on_request_data(id, data, fin, out);
+            }
+            EngineEvent::Quic(QuicEvent::Datagram { data }) => self.on_datagram(data, out),
+            EngineEvent::App(AppAction::SendDatagram { id, payload }) => {
+                self.send_datagram(id, payload, out);
+            }
+            EngineEvent::App(AppAction::ResetStream { id, app_error }) => {
+                self.on_app_reset_stream(id, app_error, out);
+            }
+            EngineEvent::App(AppAction::AcceptTunnel { id }) => {
+                self.on_app_accept_tunnel(id, out);
+            }
+            EngineEvent::App(AppAction::RejectTunnel { id, status }) => {
+                self.on_app_reject_tunnel(id, status, out);
+            }
+            EngineEvent::App(AppAction::TunnelSend { id, data, fin }) => {
+                self.on_app_tunnel_send(id, data, fin, out);
+            }
+            EngineEvent::App(AppAction::SendRequest { id, fields }) => {
+                self.send_request(id, &fields, out);
+            }
+            EngineEvent::Quic(QuicEvent::StreamError { id, err }) => {
+                self.on_stream_error(id, err, out);
+            }
+            EngineEvent::Quic(QuicEvent::StreamWritable { id, capacity }) => {
+                self.on_stream_writable(id, capacity, out);
             }
+            EngineEvent::TimerFired(timer) => self.on_timer(timer, out),
+            EngineEvent::Shutdown => self.timers_stopped = true,
             _ => {}
         }
     }
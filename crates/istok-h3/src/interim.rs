@@ -0,0 +1,127 @@
+//! Interim (1xx) responses ahead of the final response (RFC 9114 §4.1,
+//! RFC 9110 §15.2), and the final response itself.
+//!
+//! A server installs a `ResponseHook` with `H3Engine::set_response_hook`.
+//! The engine consults it once per request, after the request HEADERS were
+//! validated and before the final response is queued.
+//!
+//! Invariants:
+//! - Interim responses are written before the final response on the same
+//!   stream, in the order the hook returned them; none follow it.
+//! - Only 1xx statuses other than 101 are sent (RFC 9114 §4.5); anything
+//!   else closes the connection with `H3_INTERNAL_ERROR`.
+//! - For `expect: 100-continue`, `100 (Continue)` precedes the hook's own
+//!   interim responses, and the final response waits until the whole body
+//!   arrived, so a body that breaks the request is still refused. Without
+//!   a hook the expectation is not acted on: the final response already
+//!   answers it (RFC 9110 §10.1.1).
+//! - A final response whose body disagrees with its own `content-length` is
+//!   never sent: the stream is reset with `H3_MESSAGE_ERROR` instead.
+//! - A client reports the interim responses it receives as
+//!   `AppEvent::InterimResponse`, apart from the final `AppEvent::Response`.
+
+use alloc::vec::Vec;
+use istok_transport::StreamId;
+
+/// The parts of a request a `ResponseHook` decides on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestInfo<'a> {
+    /// The request stream.
+    pub id: StreamId,
+    /// `:authority`, if the request carried one.
+    pub authority: Option<&'a [u8]>,
+    /// `:path`, if the request carried one.
+    pub path: Option<&'a [u8]>,
+    /// The declared `content-length`.
+    pub content_length: Option<u64>,
+    /// The request carried `expect: 100-continue`.
+    pub expect_continue: bool,
+}
+
+/// A 1xx response: its status and the fields that follow `:status`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterimResponse {
+    /// A 1xx status other than 101.
+    pub status: u16,
+    /// Name and value of each field, in order.
+    pub fields: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl InterimResponse {
+    /// A response with `status` and no fields.
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Append a field, e.g. a `link` for 103 (Early Hints).
+    pub fn with_field(mut self, name: &[u8], value: &[u8]) -> Self {
+        self.fields.push((name.to_vec(), value.to_vec()));
+        self
+    }
+}
+
+/// The final response to a request: its status, the fields that follow
+/// `:status`, and the whole body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FinalResponse {
+    /// A status from 200 to 599.
+    pub status: u16,
+    /// Name and value of each field, in order.
+    pub fields: Vec<(Vec<u8>, Vec<u8>)>,
+    /// Sent in one DATA frame; an empty body sends none.
+    pub body: Vec<u8>,
+}
+
+impl FinalResponse {
+    /// A response with `status`, no fields and an empty body.
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            fields: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Append a field, e.g. `content-type`.
+    pub fn with_field(mut self, name: &[u8], value: &[u8]) -> Self {
+        self.fields.push((name.to_vec(), value.to_vec()));
+        self
+    }
+
+    /// Replace the body.
+    pub fn with_body(mut self, body: &[u8]) -> Self {
+        self.body = body.to_vec();
+        self
+    }
+}
+
+/// Server-side decisions taken between a request's HEADERS and its final
+/// response.
+pub trait ResponseHook {
+    /// Interim responses to send before the final response to `request`,
+    /// such as 103 (Early Hints) with preload links.
+    fn interim_responses(&mut self, request: &RequestInfo<'_>) -> Vec<InterimResponse> {
+        let _ = request;
+        Vec::new()
+    }
+
+    /// Whether to read the body of a request sent with
+    /// `expect: 100-continue`. `true` sends 100 (Continue) first; `false`
+    /// answers 417 (Expectation Failed) and stops reading the request.
+    fn expect_continue(&mut self, request: &RequestInfo<'_>) -> bool {
+        let _ = request;
+        true
+    }
+
+    /// The final response to `request`. `None` keeps the engine's own
+    /// placeholder, a 200 with a one-byte body. A `content-length` among
+    /// the fields is held against the body; a status outside 200 to 599
+    /// closes the connection with `H3_INTERNAL_ERROR`.
+    fn final_response(&mut self, request: &RequestInfo<'_>) -> Option<FinalResponse> {
+        let _ = request;
+        None
+    }
+}
@@ -2,10 +2,32 @@
 
 extern crate alloc;
 
+pub mod config;
 pub mod engine;
 pub mod mock;
 
 pub mod h3_engine;
+pub mod interim;
+pub mod limits;
+pub mod poll;
+pub mod qlog;
+mod qpack_streams;
+pub mod record;
+pub mod scheduler;
+pub mod stats;
+pub mod timers;
+mod trace;
+mod uni;
+pub mod webtransport;
 
-pub use engine::{Engine, EngineCommand, EngineEvent, TimerId};
+pub use config::{ConfigError, H3Config, H3ConfigBuilder, Role};
+pub use engine::{AppAction, AppEvent, Engine, EngineCommand, EngineEvent, TimerId};
 pub use h3_engine::H3Engine;
+pub use interim::{FinalResponse, InterimResponse, RequestInfo, ResponseHook};
+pub use limits::DosLimits;
+pub use poll::{Deadline, PollEngine};
+pub use qlog::{Qlog, QlogClock, QlogWriter};
+pub use record::Recorder;
+pub use scheduler::WriteScheduler;
+pub use stats::EngineStats;
+pub use webtransport::WebTransportSession;
@@ -0,0 +1,122 @@
+//! Abuse limits: peer behaviour that costs us far more than it costs the
+//! peer is answered with `H3_EXCESSIVE_LOAD`.
+//!
+//! Covered patterns, HTTP/2 attacks carried over to HTTP/3:
+//! - rapid reset: request streams opened and reset before they finish;
+//! - floods of unknown or reserved frame types, which are otherwise skipped;
+//! - request bodies or tunnels chopped into tiny DATA frames;
+//! - header blocks that expand far beyond their wire size ("QPACK bombs");
+//! - header sections with an excessive number of field lines.
+//!
+//! Invariants:
+//! - Limits are counts and ratios, never rates: the engine has no clock, so
+//!   the same input always trips the same limit at the same byte.
+//! - Per-stream counters start over for every request stream.
+//! - Header limits reset the offending stream; the other limits close the
+//!   connection, since they describe the peer rather than one request.
+
+use istok_transport::StreamId;
+
+/// Header blocks decoding to at most this many name and value bytes are
+/// never judged by their expansion ratio.
+pub const HEADER_EXPANSION_FLOOR: u64 = 4 * 1024;
+
+/// Thresholds for `H3Config::dos_limits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DosLimits {
+    /// Request streams the peer may reset before finishing them, beyond the
+    /// number of streams it did finish.
+    pub rapid_resets: u32,
+    /// Unknown or reserved frames tolerated on one request stream.
+    pub unknown_frames_per_stream: u32,
+    /// DATA frames on one stream before `min_data_frame_avg` applies.
+    pub data_frame_burst: u32,
+    /// Smallest average DATA payload, in bytes, past the burst; `0` turns
+    /// the check off.
+    pub min_data_frame_avg: u32,
+    /// Field lines in one header section.
+    pub max_header_fields: u32,
+    /// Decoded name and value bytes per header block byte, once past
+    /// `HEADER_EXPANSION_FLOOR`.
+    pub max_header_expansion: u32,
+}
+
+impl DosLimits {
+    /// Whether any request at all could pass these limits.
+    pub(crate) fn is_valid(&self) -> bool {
+        self.max_header_fields > 0 && self.max_header_expansion > 0
+    }
+
+    /// Whether `decoded` bytes of names and values from a `wire`-byte block
+    /// exceed the expansion ratio.
+    pub(crate) fn header_bomb(&self, wire: usize, decoded: u64) -> bool {
+        let allowed = (wire as u64).saturating_mul(u64::from(self.max_header_expansion));
+        decoded > allowed.max(HEADER_EXPANSION_FLOOR)
+    }
+}
+
+impl Default for DosLimits {
+    fn default() -> Self {
+        Self {
+            rapid_resets: 100,
+            unknown_frames_per_stream: 32,
+            data_frame_burst: 1024,
+            min_data_frame_avg: 16,
+            max_header_fields: 256,
+            max_header_expansion: 16,
+        }
+    }
+}
+
+/// Counters checked against `DosLimits`.
+#[derive(Debug, Default)]
+pub(crate) struct DosGuard {
+    peer_resets: u64,
+    finished_streams: u64,
+    /// Request stream the counters below belong to.
+    stream: Option<StreamId>,
+    unknown_frames: u32,
+    data_frames: u64,
+    data_bytes: u64,
+}
+
+impl DosGuard {
+    /// The peer finished a request stream, earning back one reset.
+    pub(crate) fn on_stream_finished(&mut self) {
+        self.finished_streams += 1;
+    }
+
+    /// The peer reset a request stream it had not finished. Returns `false`
+    /// once it reset too many.
+    pub(crate) fn on_stream_reset(&mut self, limits: &DosLimits) -> bool {
+        self.peer_resets += 1;
+        self.peer_resets <= self.finished_streams + u64::from(limits.rapid_resets)
+    }
+
+    fn counters(&mut self, id: StreamId) {
+        if self.stream != Some(id) {
+            self.stream = Some(id);
+            self.unknown_frames = 0;
+            self.data_frames = 0;
+            self.data_bytes = 0;
+        }
+    }
+
+    /// An unknown frame type on request stream `id` is about to be skipped.
+    /// Returns `false` once the stream carried too many.
+    pub(crate) fn on_unknown_frame(&mut self, id: StreamId, limits: &DosLimits) -> bool {
+        self.counters(id);
+        self.unknown_frames += 1;
+        self.unknown_frames <= limits.unknown_frames_per_stream
+    }
+
+    /// A DATA frame of `len` bytes starts on stream `id`. Returns `false`
+    /// once the stream's frames are too small on average.
+    pub(crate) fn on_data_frame(&mut self, id: StreamId, len: u64, limits: &DosLimits) -> bool {
+        self.counters(id);
+        self.data_frames += 1;
+        self.data_bytes = self.data_bytes.saturating_add(len);
+        self.data_frames <= u64::from(limits.data_frame_burst)
+            || self.data_bytes >= self.data_frames * u64::from(limits.min_data_frame_avg)
+    }
+}
@@ -1,7 +1,15 @@
 use alloc::vec::Vec;
 
-use crate::engine::{CommandSink, Engine, EngineCommand, EngineEvent, TimerId};
-use istok_transport::{QuicCommand, QuicEvent, StreamId, StreamKind};
+use crate::engine::{
+    AppAction, AppEvent, CommandSink, Engine, EngineCommand, EngineEvent, TimerId,
+};
+use crate::h3_engine::encode_frame;
+use istok_core::bytes::Bytes;
+use istok_core::codec::varint;
+use istok_core::h3::consts;
+use istok_core::h3::settings::Settings;
+use istok_core::qpack::{self, HeaderField};
+use istok_transport::{QuicCommand, QuicEvent, StreamError, StreamId, StreamKind};
 
 #[derive(Clone, Debug)]
 pub enum ScriptStep {
@@ -15,7 +23,45 @@ pub enum ScriptStep {
         data: Vec<u8>,
         fin: bool,
     },
+    InQuicDatagram {
+        data: Vec<u8>,
+    },
+    InQuicStreamError {
+        id: StreamId,
+        err: StreamError,
+    },
+    InQuicWritable {
+        id: StreamId,
+        capacity: u64,
+    },
+    InQuicConnectionClosed {
+        app_error: Option<u64>,
+    },
     InTimer(TimerId),
+    InAppSendDatagram {
+        id: StreamId,
+        payload: Vec<u8>,
+    },
+    InAppResetStream {
+        id: StreamId,
+        app_error: u64,
+    },
+    InAppAcceptTunnel {
+        id: StreamId,
+    },
+    InAppRejectTunnel {
+        id: StreamId,
+        status: u16,
+    },
+    InAppTunnelSend {
+        id: StreamId,
+        data: Vec<u8>,
+        fin: bool,
+    },
+    InAppSendRequest {
+        id: StreamId,
+        fields: Vec<(Vec<u8>, Vec<u8>)>,
+    },
     InShutdown,
 
     // Expectations about commands produced immediately after the last input step.
@@ -26,6 +72,7 @@ pub enum ScriptStep {
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum ExpectCommand {
     QuicOpenUni,
+    QuicOpenBidi,
     QuicStreamWrite {
         id: StreamId,
         /// Expected prefix of written bytes (useful when payload has varints etc).
@@ -35,14 +82,175 @@ pub enum ExpectCommand {
     QuicCloseConnection {
         app_error: u64,
     },
+    QuicSendDatagram {
+        data: Vec<u8>,
+    },
+    AppDatagram {
+        id: StreamId,
+        payload: Vec<u8>,
+    },
+    QuicResetStream {
+        id: StreamId,
+        app_error: u64,
+    },
+    QuicStopSending {
+        id: StreamId,
+        app_error: u64,
+    },
+    AppConnectUdp {
+        id: StreamId,
+        host: Vec<u8>,
+        port: u16,
+    },
+    AppConnectUdpClosed {
+        id: StreamId,
+    },
+    AppConnectTunnel {
+        id: StreamId,
+        authority: Vec<u8>,
+    },
+    AppTunnelData {
+        id: StreamId,
+        data: Vec<u8>,
+        fin: bool,
+    },
+    AppTunnelReset {
+        id: StreamId,
+        app_error: u64,
+    },
+    AppWebTransportSession {
+        id: StreamId,
+    },
+    AppWebTransportStreamData {
+        session: StreamId,
+        id: StreamId,
+        data: Vec<u8>,
+        fin: bool,
+    },
+    AppWebTransportSessionDraining {
+        id: StreamId,
+    },
+    AppWebTransportSessionClosed {
+        id: StreamId,
+        code: u32,
+        reason: Vec<u8>,
+    },
+    AppWritePaused {
+        id: StreamId,
+    },
+    AppWriteResumed {
+        id: StreamId,
+    },
+    AppStreamAborted {
+        id: StreamId,
+        app_error: u64,
+    },
+    AppRequest {
+        id: StreamId,
+        fields: Vec<(Vec<u8>, Vec<u8>)>,
+    },
+    AppInterimResponse {
+        id: StreamId,
+        status: u16,
+        fields: Vec<(Vec<u8>, Vec<u8>)>,
+    },
+    AppResponse {
+        id: StreamId,
+        status: u16,
+        fields: Vec<(Vec<u8>, Vec<u8>)>,
+    },
+    AppResponseData {
+        id: StreamId,
+        data: Vec<u8>,
+        fin: bool,
+    },
     ArmTimer {
         id: TimerId,
+        deadline_ms_from_now: u64,
     },
     CancelTimer {
         id: TimerId,
     },
 }
 
+impl ScriptStep {
+    /// The engine event of an input step; `None` for expectations.
+    pub fn event(&self) -> Option<EngineEvent<'_>> {
+        let ev = match self {
+            ScriptStep::InBoot => EngineEvent::Boot,
+            ScriptStep::InQuicOpen { id, kind } => EngineEvent::Quic(QuicEvent::StreamOpened {
+                id: *id,
+                kind: *kind,
+            }),
+            ScriptStep::InQuicData { id, data, fin } => {
+                EngineEvent::Quic(QuicEvent::StreamReadable {
+                    id: *id,
+                    data: data.as_slice(),
+                    fin: *fin,
+                })
+            }
+            ScriptStep::InQuicDatagram { data } => EngineEvent::Quic(QuicEvent::Datagram {
+                data: data.as_slice(),
+            }),
+            ScriptStep::InQuicStreamError { id, err } => {
+                EngineEvent::Quic(QuicEvent::StreamError { id: *id, err: *err })
+            }
+            ScriptStep::InQuicWritable { id, capacity } => {
+                EngineEvent::Quic(QuicEvent::StreamWritable {
+                    id: *id,
+                    capacity: *capacity,
+                })
+            }
+            ScriptStep::InQuicConnectionClosed { app_error } => {
+                EngineEvent::Quic(QuicEvent::ConnectionClosed {
+                    app_error: *app_error,
+                })
+            }
+            ScriptStep::InTimer(id) => EngineEvent::TimerFired(*id),
+            ScriptStep::InAppSendDatagram { id, payload } => {
+                EngineEvent::App(AppAction::SendDatagram {
+                    id: *id,
+                    payload: payload.as_slice(),
+                })
+            }
+            ScriptStep::InAppResetStream { id, app_error } => {
+                EngineEvent::App(AppAction::ResetStream {
+                    id: *id,
+                    app_error: *app_error,
+                })
+            }
+            ScriptStep::InAppAcceptTunnel { id } => {
+                EngineEvent::App(AppAction::AcceptTunnel { id: *id })
+            }
+            ScriptStep::InAppRejectTunnel { id, status } => {
+                EngineEvent::App(AppAction::RejectTunnel {
+                    id: *id,
+                    status: *status,
+                })
+            }
+            ScriptStep::InAppTunnelSend { id, data, fin } => {
+                EngineEvent::App(AppAction::TunnelSend {
+                    id: *id,
+                    data: Bytes::copy_from_slice(data),
+                    fin: *fin,
+                })
+            }
+            ScriptStep::InAppSendRequest { id, fields } => {
+                EngineEvent::App(AppAction::SendRequest {
+                    id: *id,
+                    fields: fields
+                        .iter()
+                        .map(|(name, value)| HeaderField { name, value })
+                        .collect(),
+                })
+            }
+            ScriptStep::InShutdown => EngineEvent::Shutdown,
+            ScriptStep::Expect(_) | ScriptStep::ExpectNone => return None,
+        };
+        Some(ev)
+    }
+}
+
 pub struct MockHarness<E: Engine> {
     engine: E,
     pending: Vec<EngineCommandOwned>,
@@ -51,6 +259,7 @@ pub struct MockHarness<E: Engine> {
 #[derive(Clone, Debug, PartialEq, Eq)]
 enum EngineCommandOwned {
     QuicOpenUni,
+    QuicOpenBidi,
     QuicStreamWrite {
         id: StreamId,
         data: Vec<u8>,
@@ -59,6 +268,88 @@ enum EngineCommandOwned {
     QuicCloseConnection {
         app_error: u64,
     },
+    QuicSendDatagram {
+        data: Vec<u8>,
+    },
+    AppDatagram {
+        id: StreamId,
+        payload: Vec<u8>,
+    },
+    QuicResetStream {
+        id: StreamId,
+        app_error: u64,
+    },
+    QuicStopSending {
+        id: StreamId,
+        app_error: u64,
+    },
+    AppConnectUdp {
+        id: StreamId,
+        host: Vec<u8>,
+        port: u16,
+    },
+    AppConnectUdpClosed {
+        id: StreamId,
+    },
+    AppConnectTunnel {
+        id: StreamId,
+        authority: Vec<u8>,
+    },
+    AppTunnelData {
+        id: StreamId,
+        data: Vec<u8>,
+        fin: bool,
+    },
+    AppTunnelReset {
+        id: StreamId,
+        app_error: u64,
+    },
+    AppWebTransportSession {
+        id: StreamId,
+    },
+    AppWebTransportStreamData {
+        session: StreamId,
+        id: StreamId,
+        data: Vec<u8>,
+        fin: bool,
+    },
+    AppWebTransportSessionDraining {
+        id: StreamId,
+    },
+    AppWebTransportSessionClosed {
+        id: StreamId,
+        code: u32,
+        reason: Vec<u8>,
+    },
+    AppWritePaused {
+        id: StreamId,
+    },
+    AppWriteResumed {
+        id: StreamId,
+    },
+    AppStreamAborted {
+        id: StreamId,
+        app_error: u64,
+    },
+    AppRequest {
+        id: StreamId,
+        fields: Vec<(Vec<u8>, Vec<u8>)>,
+    },
+    AppInterimResponse {
+        id: StreamId,
+        status: u16,
+        fields: Vec<(Vec<u8>, Vec<u8>)>,
+    },
+    AppResponse {
+        id: StreamId,
+        status: u16,
+        fields: Vec<(Vec<u8>, Vec<u8>)>,
+    },
+    AppResponseData {
+        id: StreamId,
+        data: Vec<u8>,
+        fin: bool,
+    },
     ArmTimer {
         id: TimerId,
         deadline_ms_from_now: u64,
@@ -97,36 +388,32 @@ impl<E: Engine> MockHarness<E> {
         // Expectation steps consume from `pending`.
         for step in script {
             match step {
-                ScriptStep::InBoot => self.step(EngineEvent::Boot),
-                ScriptStep::InQuicOpen { id, kind } => {
-                    let ev = QuicEvent::StreamOpened {
-                        id: *id,
-                        kind: *kind,
-                    };
-                    self.step(EngineEvent::Quic(ev));
-                }
-                ScriptStep::InQuicData { id, data, fin } => {
-                    let ev = QuicEvent::StreamReadable {
-                        id: *id,
-                        data: data.as_slice(),
-                        fin: *fin,
-                    };
-                    self.step(EngineEvent::Quic(ev));
-                }
-                ScriptStep::InTimer(id) => self.step(EngineEvent::TimerFired(*id)),
-                ScriptStep::InShutdown => self.step(EngineEvent::Shutdown),
-
                 ScriptStep::Expect(exp) => self.expect_one(exp),
                 ScriptStep::ExpectNone => self.expect_none(),
+                input => {
+                    if let Some(ev) = input.event() {
+                        self.step(ev);
+                    }
+                }
             }
         }
-
         // If script ends without consuming all pending expectations, fail loudly.
         if !self.pending.is_empty() {
             panic!("script ended with unconsumed commands: {:?}", self.pending);
         }
     }
 
+    /// Call into the engine outside of `on_event` (e.g. through an
+    /// application handle); produced commands become pending expectations.
+    pub fn apply<R>(&mut self, f: impl FnOnce(&mut E, &mut dyn CommandSink<'static>) -> R) -> R {
+        let mut sink = VecSink::new();
+        let result = f(&mut self.engine, &mut sink);
+        for cmd in sink.out {
+            self.pending.push(to_owned(cmd));
+        }
+        result
+    }
+
     fn step<'a>(&mut self, ev: EngineEvent<'a>) {
         let mut sink = VecSink::new();
         self.engine.on_event(ev, &mut sink);
@@ -144,14 +431,204 @@ impl<E: Engine> MockHarness<E> {
         let got = self.pending.remove(0);
         match (exp, got) {
             (ExpectCommand::QuicOpenUni, EngineCommandOwned::QuicOpenUni) => {}
+            (ExpectCommand::QuicOpenBidi, EngineCommandOwned::QuicOpenBidi) => {}
+            (
+                ExpectCommand::QuicResetStream { id, app_error },
+                EngineCommandOwned::QuicResetStream {
+                    id: got_id,
+                    app_error: got,
+                },
+            )
+            | (
+                ExpectCommand::QuicStopSending { id, app_error },
+                EngineCommandOwned::QuicStopSending {
+                    id: got_id,
+                    app_error: got,
+                },
+            ) => {
+                assert_eq!(*id, got_id);
+                assert_eq!(*app_error, got);
+            }
+            (
+                ExpectCommand::AppConnectTunnel { id, authority },
+                EngineCommandOwned::AppConnectTunnel {
+                    id: got_id,
+                    authority: got,
+                },
+            ) => {
+                assert_eq!(*id, got_id);
+                assert_eq!(*authority, got, "connect authority mismatch");
+            }
+            (
+                ExpectCommand::AppTunnelData { id, data, fin },
+                EngineCommandOwned::AppTunnelData {
+                    id: got_id,
+                    data: got,
+                    fin: got_fin,
+                },
+            ) => {
+                assert_eq!(*id, got_id);
+                assert_eq!(*data, got, "tunnel data mismatch");
+                assert_eq!(*fin, got_fin);
+            }
+            (
+                ExpectCommand::AppRequest { id, fields },
+                EngineCommandOwned::AppRequest {
+                    id: got_id,
+                    fields: got,
+                },
+            ) => {
+                assert_eq!(*id, got_id);
+                assert_eq!(*fields, got, "request fields mismatch");
+            }
+            (
+                ExpectCommand::AppInterimResponse { id, status, fields },
+                EngineCommandOwned::AppInterimResponse {
+                    id: got_id,
+                    status: got_status,
+                    fields: got,
+                },
+            )
+            | (
+                ExpectCommand::AppResponse { id, status, fields },
+                EngineCommandOwned::AppResponse {
+                    id: got_id,
+                    status: got_status,
+                    fields: got,
+                },
+            ) => {
+                assert_eq!(*id, got_id);
+                assert_eq!(*status, got_status);
+                assert_eq!(*fields, got, "response fields mismatch");
+            }
+            (
+                ExpectCommand::AppResponseData { id, data, fin },
+                EngineCommandOwned::AppResponseData {
+                    id: got_id,
+                    data: got,
+                    fin: got_fin,
+                },
+            ) => {
+                assert_eq!(*id, got_id);
+                assert_eq!(*data, got, "response data mismatch");
+                assert_eq!(*fin, got_fin);
+            }
+            (
+                ExpectCommand::AppTunnelReset { id, app_error },
+                EngineCommandOwned::AppTunnelReset {
+                    id: got_id,
+                    app_error: got,
+                },
+            )
+            | (
+                ExpectCommand::AppStreamAborted { id, app_error },
+                EngineCommandOwned::AppStreamAborted {
+                    id: got_id,
+                    app_error: got,
+                },
+            ) => {
+                assert_eq!(*id, got_id);
+                assert_eq!(*app_error, got);
+            }
+            (
+                ExpectCommand::AppConnectUdp { id, host, port },
+                EngineCommandOwned::AppConnectUdp {
+                    id: got_id,
+                    host: got_host,
+                    port: got_port,
+                },
+            ) => {
+                assert_eq!(*id, got_id);
+                assert_eq!(*host, got_host, "connect-udp host mismatch");
+                assert_eq!(*port, got_port);
+            }
+            (
+                ExpectCommand::AppConnectUdpClosed { id },
+                EngineCommandOwned::AppConnectUdpClosed { id: got },
+            )
+            | (
+                ExpectCommand::AppWebTransportSession { id },
+                EngineCommandOwned::AppWebTransportSession { id: got },
+            )
+            | (
+                ExpectCommand::AppWebTransportSessionDraining { id },
+                EngineCommandOwned::AppWebTransportSessionDraining { id: got },
+            )
+            | (
+                ExpectCommand::AppWritePaused { id },
+                EngineCommandOwned::AppWritePaused { id: got },
+            )
+            | (
+                ExpectCommand::AppWriteResumed { id },
+                EngineCommandOwned::AppWriteResumed { id: got },
+            ) => {
+                assert_eq!(*id, got);
+            }
+            (
+                ExpectCommand::AppWebTransportStreamData {
+                    session,
+                    id,
+                    data,
+                    fin,
+                },
+                EngineCommandOwned::AppWebTransportStreamData {
+                    session: got_session,
+                    id: got_id,
+                    data: got,
+                    fin: got_fin,
+                },
+            ) => {
+                assert_eq!(*session, got_session);
+                assert_eq!(*id, got_id);
+                assert_eq!(*data, got, "webtransport stream data mismatch");
+                assert_eq!(*fin, got_fin);
+            }
+            (
+                ExpectCommand::AppWebTransportSessionClosed { id, code, reason },
+                EngineCommandOwned::AppWebTransportSessionClosed {
+                    id: got_id,
+                    code: got_code,
+                    reason: got_reason,
+                },
+            ) => {
+                assert_eq!(*id, got_id);
+                assert_eq!(*code, got_code);
+                assert_eq!(*reason, got_reason);
+            }
             (
                 ExpectCommand::QuicCloseConnection { app_error },
                 EngineCommandOwned::QuicCloseConnection { app_error: a },
             ) => {
                 assert_eq!(*app_error, a);
             }
-            (ExpectCommand::ArmTimer { id }, EngineCommandOwned::ArmTimer { id: got, .. }) => {
-                assert_eq!(*id, got);
+            (
+                ExpectCommand::QuicSendDatagram { data },
+                EngineCommandOwned::QuicSendDatagram { data: got },
+            ) => {
+                assert_eq!(*data, got, "datagram mismatch");
+            }
+            (
+                ExpectCommand::AppDatagram { id, payload },
+                EngineCommandOwned::AppDatagram {
+                    id: got_id,
+                    payload: got,
+                },
+            ) => {
+                assert_eq!(*id, got_id);
+                assert_eq!(*payload, got, "app datagram mismatch");
+            }
+            (
+                ExpectCommand::ArmTimer {
+                    id,
+                    deadline_ms_from_now,
+                },
+                EngineCommandOwned::ArmTimer {
+                    id: got_id,
+                    deadline_ms_from_now: got,
+                },
+            ) => {
+                assert_eq!(*id, got_id);
+                assert_eq!(*deadline_ms_from_now, got, "timer deadline mismatch");
             }
             (ExpectCommand::CancelTimer { id }, EngineCommandOwned::CancelTimer { id: got }) => {
                 assert_eq!(*id, got);
@@ -192,6 +669,7 @@ fn to_owned<'a>(cmd: EngineCommand<'a>) -> EngineCommandOwned {
     match cmd {
         EngineCommand::Quic(q) => match q {
             QuicCommand::OpenUni { .. } => EngineCommandOwned::QuicOpenUni,
+            QuicCommand::OpenBidi { .. } => EngineCommandOwned::QuicOpenBidi,
             QuicCommand::StreamWrite { id, data, fin } => EngineCommandOwned::QuicStreamWrite {
                 id,
                 data: data.to_vec(),
@@ -200,21 +678,80 @@ fn to_owned<'a>(cmd: EngineCommand<'a>) -> EngineCommandOwned {
             QuicCommand::StreamWriteOwned { id, data, fin } => {
                 EngineCommandOwned::QuicStreamWrite { id, data, fin }
             }
+            // Scripts see the bytes on the wire, however they were gathered.
+            QuicCommand::StreamWriteVectored {
+                id,
+                header,
+                body,
+                fin,
+            } => {
+                let mut data = header.as_slice().to_vec();
+                data.extend_from_slice(&body);
+                EngineCommandOwned::QuicStreamWrite { id, data, fin }
+            }
             QuicCommand::CloseConnection { app_error } => {
                 EngineCommandOwned::QuicCloseConnection { app_error }
             }
-            // Add more as engine grows:
-            QuicCommand::ResetStream {
-                id: _,
-                app_error: _,
-            } => {
-                panic!("ResetStream not yet supported by MockHarness expectations")
+            QuicCommand::SendDatagram { data } => EngineCommandOwned::QuicSendDatagram { data },
+            QuicCommand::ResetStream { id, app_error } => {
+                EngineCommandOwned::QuicResetStream { id, app_error }
             }
-            QuicCommand::StopSending {
-                id: _,
-                app_error: _,
-            } => {
-                panic!("StopSending not yet supported by MockHarness expectations")
+            QuicCommand::StopSending { id, app_error } => {
+                EngineCommandOwned::QuicStopSending { id, app_error }
+            }
+        },
+        EngineCommand::App(app) => match app {
+            AppEvent::Datagram { id, payload } => EngineCommandOwned::AppDatagram {
+                id,
+                payload: payload.into_owned(),
+            },
+            AppEvent::ConnectUdp { id, host, port } => {
+                EngineCommandOwned::AppConnectUdp { id, host, port }
+            }
+            AppEvent::ConnectUdpClosed { id } => EngineCommandOwned::AppConnectUdpClosed { id },
+            AppEvent::ConnectTunnel { id, authority } => {
+                EngineCommandOwned::AppConnectTunnel { id, authority }
+            }
+            AppEvent::TunnelData { id, data, fin } => {
+                EngineCommandOwned::AppTunnelData { id, data, fin }
+            }
+            AppEvent::TunnelReset { id, app_error } => {
+                EngineCommandOwned::AppTunnelReset { id, app_error }
+            }
+            AppEvent::WebTransportSession { id } => {
+                EngineCommandOwned::AppWebTransportSession { id }
+            }
+            AppEvent::WebTransportStreamData {
+                session,
+                id,
+                data,
+                fin,
+            } => EngineCommandOwned::AppWebTransportStreamData {
+                session,
+                id,
+                data: data.to_vec(),
+                fin,
+            },
+            AppEvent::WebTransportSessionDraining { id } => {
+                EngineCommandOwned::AppWebTransportSessionDraining { id }
+            }
+            AppEvent::WebTransportSessionClosed { id, code, reason } => {
+                EngineCommandOwned::AppWebTransportSessionClosed { id, code, reason }
+            }
+            AppEvent::WritePaused { id } => EngineCommandOwned::AppWritePaused { id },
+            AppEvent::WriteResumed { id } => EngineCommandOwned::AppWriteResumed { id },
+            AppEvent::StreamAborted { id, app_error } => {
+                EngineCommandOwned::AppStreamAborted { id, app_error }
+            }
+            AppEvent::Request { id, fields } => EngineCommandOwned::AppRequest { id, fields },
+            AppEvent::InterimResponse { id, status, fields } => {
+                EngineCommandOwned::AppInterimResponse { id, status, fields }
+            }
+            AppEvent::Response { id, status, fields } => {
+                EngineCommandOwned::AppResponse { id, status, fields }
+            }
+            AppEvent::ResponseData { id, data, fin } => {
+                EngineCommandOwned::AppResponseData { id, data, fin }
             }
         },
         EngineCommand::ArmTimer {
@@ -227,3 +764,167 @@ fn to_owned<'a>(cmd: EngineCommand<'a>) -> EngineCommandOwned {
         EngineCommand::CancelTimer { id } => EngineCommandOwned::CancelTimer { id },
     }
 }
+
+/// What a script expects to see for `cmd`: the command itself, with the
+/// whole write as its prefix.
+pub(crate) fn expectation(cmd: EngineCommand<'_>) -> ExpectCommand {
+    match to_owned(cmd) {
+        EngineCommandOwned::QuicOpenUni => ExpectCommand::QuicOpenUni,
+        EngineCommandOwned::QuicOpenBidi => ExpectCommand::QuicOpenBidi,
+        EngineCommandOwned::QuicStreamWrite { id, data, fin } => ExpectCommand::QuicStreamWrite {
+            id,
+            data_prefix: data,
+            fin,
+        },
+        EngineCommandOwned::QuicCloseConnection { app_error } => {
+            ExpectCommand::QuicCloseConnection { app_error }
+        }
+        EngineCommandOwned::QuicSendDatagram { data } => ExpectCommand::QuicSendDatagram { data },
+        EngineCommandOwned::AppDatagram { id, payload } => {
+            ExpectCommand::AppDatagram { id, payload }
+        }
+        EngineCommandOwned::QuicResetStream { id, app_error } => {
+            ExpectCommand::QuicResetStream { id, app_error }
+        }
+        EngineCommandOwned::QuicStopSending { id, app_error } => {
+            ExpectCommand::QuicStopSending { id, app_error }
+        }
+        EngineCommandOwned::AppConnectUdp { id, host, port } => {
+            ExpectCommand::AppConnectUdp { id, host, port }
+        }
+        EngineCommandOwned::AppConnectUdpClosed { id } => ExpectCommand::AppConnectUdpClosed { id },
+        EngineCommandOwned::AppConnectTunnel { id, authority } => {
+            ExpectCommand::AppConnectTunnel { id, authority }
+        }
+        EngineCommandOwned::AppTunnelData { id, data, fin } => {
+            ExpectCommand::AppTunnelData { id, data, fin }
+        }
+        EngineCommandOwned::AppTunnelReset { id, app_error } => {
+            ExpectCommand::AppTunnelReset { id, app_error }
+        }
+        EngineCommandOwned::AppWebTransportSession { id } => {
+            ExpectCommand::AppWebTransportSession { id }
+        }
+        EngineCommandOwned::AppWebTransportStreamData {
+            session,
+            id,
+            data,
+            fin,
+        } => ExpectCommand::AppWebTransportStreamData {
+            session,
+            id,
+            data,
+            fin,
+        },
+        EngineCommandOwned::AppWebTransportSessionDraining { id } => {
+            ExpectCommand::AppWebTransportSessionDraining { id }
+        }
+        EngineCommandOwned::AppWebTransportSessionClosed { id, code, reason } => {
+            ExpectCommand::AppWebTransportSessionClosed { id, code, reason }
+        }
+        EngineCommandOwned::AppWritePaused { id } => ExpectCommand::AppWritePaused { id },
+        EngineCommandOwned::AppWriteResumed { id } => ExpectCommand::AppWriteResumed { id },
+        EngineCommandOwned::AppStreamAborted { id, app_error } => {
+            ExpectCommand::AppStreamAborted { id, app_error }
+        }
+        EngineCommandOwned::AppRequest { id, fields } => ExpectCommand::AppRequest { id, fields },
+        EngineCommandOwned::AppInterimResponse { id, status, fields } => {
+            ExpectCommand::AppInterimResponse { id, status, fields }
+        }
+        EngineCommandOwned::AppResponse { id, status, fields } => {
+            ExpectCommand::AppResponse { id, status, fields }
+        }
+        EngineCommandOwned::AppResponseData { id, data, fin } => {
+            ExpectCommand::AppResponseData { id, data, fin }
+        }
+        EngineCommandOwned::ArmTimer {
+            id,
+            deadline_ms_from_now,
+        } => ExpectCommand::ArmTimer {
+            id,
+            deadline_ms_from_now,
+        },
+        EngineCommandOwned::CancelTimer { id } => ExpectCommand::CancelTimer { id },
+    }
+}
+
+/// A frame of type `ty` around `payload`, as a peer would send it.
+pub fn frame(ty: u64, payload: &[u8]) -> Vec<u8> {
+    encode_frame(ty, payload).expect("frame header encodes")
+}
+
+/// The opening bytes of a peer control stream: its stream type and a
+/// SETTINGS frame around `settings_payload`.
+pub fn control_stream(settings_payload: &[u8]) -> Vec<u8> {
+    let mut control = varint_bytes(consts::STREAM_TYPE_CONTROL);
+    control.extend_from_slice(&frame(consts::FRAME_TYPE_SETTINGS, settings_payload));
+    control
+}
+
+/// The SETTINGS frame payload advertising `settings`.
+pub fn settings_payload(settings: &Settings) -> Vec<u8> {
+    let mut payload = alloc::vec![0u8; settings.encoded_len().expect("settings encode")];
+    let len = settings
+        .encode_payload(&mut payload)
+        .expect("settings encode");
+    payload.truncate(len);
+    payload
+}
+
+/// A HEADERS frame carrying `fields`, QPACK-encoded.
+pub fn headers(fields: &[HeaderField<'_>]) -> Vec<u8> {
+    let mut block = alloc::vec![0u8; 4096];
+    let len = qpack::encode(fields, &mut block).expect("qpack encodes");
+    frame(consts::FRAME_TYPE_HEADERS, &block[..len])
+}
+
+/// A response HEADERS frame carrying only `:status`.
+pub fn status(code: &[u8]) -> Vec<u8> {
+    headers(&[HeaderField {
+        name: b":status",
+        value: code,
+    }])
+}
+
+/// `value` as a QUIC varint.
+pub fn varint_bytes(value: u64) -> Vec<u8> {
+    let mut buf = [0u8; 8];
+    let len = varint::encode(value, &mut buf).expect("varint encodes");
+    buf[..len].to_vec()
+}
+
+/// Stream, bytes and FIN of a stream write, however its bytes were gathered.
+pub fn written(cmd: &EngineCommand<'_>) -> Option<(StreamId, Vec<u8>, bool)> {
+    match cmd {
+        EngineCommand::Quic(QuicCommand::StreamWrite { id, data, fin }) => {
+            Some((*id, data.to_vec(), *fin))
+        }
+        EngineCommand::Quic(QuicCommand::StreamWriteOwned { id, data, fin }) => {
+            Some((*id, data.clone(), *fin))
+        }
+        EngineCommand::Quic(QuicCommand::StreamWriteVectored {
+            id,
+            header,
+            body,
+            fin,
+        }) => Some((*id, [header.as_slice(), body].concat(), *fin)),
+        _ => None,
+    }
+}
+
+/// Command sink dropping everything, for engines driven outside a script.
+pub struct Discard;
+
+impl<'a> CommandSink<'a> for Discard {
+    fn push(&mut self, _cmd: EngineCommand<'a>) {}
+}
+
+/// Command sink keeping everything in order.
+#[derive(Default)]
+pub struct Collect<'a>(pub Vec<EngineCommand<'a>>);
+
+impl<'a> CommandSink<'a> for Collect<'a> {
+    fn push(&mut self, cmd: EngineCommand<'a>) {
+        self.0.push(cmd);
+    }
+}
@@ -0,0 +1,264 @@
+//! Pull-style driver around an `Engine`, in the manner of quinn-proto and
+//! quiche.
+//!
+//! `Engine::on_event` pushes commands into a sink whose lifetime is tied to
+//! the input event, so the runtime has to act on them before the next read.
+//! `PollEngine` queues them instead:
+//! - `handle_event(now_ms, ev)` runs the engine;
+//! - `poll_command` hands out the QUIC commands it produced, in order;
+//! - `poll_app_event` hands out the notifications meant for the application;
+//! - `poll_timeout` reports the earliest armed timer and `handle_timeout`
+//!   fires every timer that is due.
+//!
+//! Invariants:
+//! - Nothing is lost or reordered: each queue preserves the order in which
+//!   the engine emitted its entries.
+//! - Data borrowed from an input event is copied once when queued; a
+//!   borrowed `StreamWrite` becomes `StreamWriteOwned`.
+//! - Time is the caller's monotonic clock in milliseconds and never moves
+//!   backwards: an earlier `now_ms` than already seen is ignored.
+
+use alloc::borrow::Cow;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec::Vec;
+
+use istok_transport::{QuicCommand, StreamId};
+
+use crate::engine::{AppEvent, CommandSink, Engine, EngineCommand, EngineEvent, TimerId};
+
+/// When an armed timer expires, on the clock passed to `PollEngine`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Deadline {
+    /// Expiry time in milliseconds.
+    pub at_ms: u64,
+    /// The engine timer that expires then.
+    pub id: TimerId,
+}
+
+/// `AppEvent` with borrowed payloads copied out of the input event.
+enum QueuedAppEvent {
+    Event(AppEvent<'static>),
+    Datagram {
+        id: StreamId,
+        payload: Vec<u8>,
+    },
+    WebTransportStreamData {
+        session: StreamId,
+        id: StreamId,
+        data: Vec<u8>,
+        fin: bool,
+    },
+}
+
+impl QueuedAppEvent {
+    fn new(event: AppEvent<'_>) -> Self {
+        let event = match event {
+            AppEvent::Datagram { id, payload } => {
+                return Self::Datagram {
+                    id,
+                    payload: payload.into_owned(),
+                };
+            }
+            AppEvent::WebTransportStreamData {
+                session,
+                id,
+                data,
+                fin,
+            } => {
+                return Self::WebTransportStreamData {
+                    session,
+                    id,
+                    data: data.to_vec(),
+                    fin,
+                };
+            }
+            AppEvent::ConnectUdp { id, host, port } => AppEvent::ConnectUdp { id, host, port },
+            AppEvent::ConnectUdpClosed { id } => AppEvent::ConnectUdpClosed { id },
+            AppEvent::ConnectTunnel { id, authority } => AppEvent::ConnectTunnel { id, authority },
+            AppEvent::TunnelData { id, data, fin } => AppEvent::TunnelData { id, data, fin },
+            AppEvent::TunnelReset { id, app_error } => AppEvent::TunnelReset { id, app_error },
+            AppEvent::WebTransportSession { id } => AppEvent::WebTransportSession { id },
+            AppEvent::WebTransportSessionDraining { id } => {
+                AppEvent::WebTransportSessionDraining { id }
+            }
+            AppEvent::WritePaused { id } => AppEvent::WritePaused { id },
+            AppEvent::WriteResumed { id } => AppEvent::WriteResumed { id },
+            AppEvent::StreamAborted { id, app_error } => AppEvent::StreamAborted { id, app_error },
+            AppEvent::WebTransportSessionClosed { id, code, reason } => {
+                AppEvent::WebTransportSessionClosed { id, code, reason }
+            }
+            AppEvent::Request { id, fields } => AppEvent::Request { id, fields },
+            AppEvent::InterimResponse { id, status, fields } => {
+                AppEvent::InterimResponse { id, status, fields }
+            }
+            AppEvent::Response { id, status, fields } => AppEvent::Response { id, status, fields },
+            AppEvent::ResponseData { id, data, fin } => AppEvent::ResponseData { id, data, fin },
+        };
+        Self::Event(event)
+    }
+
+    fn as_event(&self) -> AppEvent<'_> {
+        match self {
+            Self::Event(event) => event.clone(),
+            Self::Datagram { id, payload } => AppEvent::Datagram {
+                id: *id,
+                payload: Cow::Borrowed(payload),
+            },
+            Self::WebTransportStreamData {
+                session,
+                id,
+                data,
+                fin,
+            } => AppEvent::WebTransportStreamData {
+                session: *session,
+                id: *id,
+                data,
+                fin: *fin,
+            },
+        }
+    }
+}
+
+/// Queues of everything an engine emitted, not yet polled.
+#[derive(Default)]
+struct Queues {
+    now_ms: u64,
+    commands: VecDeque<QuicCommand<'static>>,
+    app_events: VecDeque<QueuedAppEvent>,
+    timers: BTreeMap<TimerId, u64>,
+}
+
+impl<'a> CommandSink<'a> for Queues {
+    fn push(&mut self, cmd: EngineCommand<'a>) {
+        match cmd {
+            EngineCommand::Quic(cmd) => self.commands.push_back(own(cmd)),
+            EngineCommand::App(event) => self.app_events.push_back(QueuedAppEvent::new(event)),
+            EngineCommand::ArmTimer {
+                id,
+                deadline_ms_from_now,
+            } => {
+                let at_ms = self.now_ms.saturating_add(deadline_ms_from_now);
+                self.timers.insert(id, at_ms);
+            }
+            EngineCommand::CancelTimer { id } => {
+                self.timers.remove(&id);
+            }
+        }
+    }
+}
+
+/// Copy whatever `cmd` borrows.
+fn own(cmd: QuicCommand<'_>) -> QuicCommand<'static> {
+    match cmd {
+        QuicCommand::OpenUni { id_hint } => QuicCommand::OpenUni { id_hint },
+        QuicCommand::OpenBidi { id_hint } => QuicCommand::OpenBidi { id_hint },
+        QuicCommand::StreamWrite { id, data, fin } => QuicCommand::StreamWriteOwned {
+            id,
+            data: data.to_vec(),
+            fin,
+        },
+        QuicCommand::StreamWriteOwned { id, data, fin } => {
+            QuicCommand::StreamWriteOwned { id, data, fin }
+        }
+        QuicCommand::StreamWriteVectored {
+            id,
+            header,
+            body,
+            fin,
+        } => QuicCommand::StreamWriteVectored {
+            id,
+            header,
+            body,
+            fin,
+        },
+        QuicCommand::SendDatagram { data } => QuicCommand::SendDatagram { data },
+        QuicCommand::ResetStream { id, app_error } => QuicCommand::ResetStream { id, app_error },
+        QuicCommand::StopSending { id, app_error } => QuicCommand::StopSending { id, app_error },
+        QuicCommand::CloseConnection { app_error } => QuicCommand::CloseConnection { app_error },
+    }
+}
+
+/// Pull-style wrapper around engine `E`; see the module docs.
+pub struct PollEngine<E: Engine> {
+    engine: E,
+    queues: Queues,
+    /// The event handed out by the last `poll_app_event`.
+    app_event: Option<QueuedAppEvent>,
+}
+
+impl<E: Engine> PollEngine<E> {
+    /// Wrap `engine`, with empty queues and the clock at zero.
+    pub fn new(engine: E) -> Self {
+        Self {
+            engine,
+            queues: Queues::default(),
+            app_event: None,
+        }
+    }
+
+    /// The wrapped engine, e.g. for its stats.
+    pub fn engine(&self) -> &E {
+        &self.engine
+    }
+
+    /// Call into the engine directly, e.g. for a WebTransport session
+    /// handle; what it emits into the sink is queued like any output.
+    pub fn with_engine<R>(&mut self, f: impl FnOnce(&mut E, &mut dyn CommandSink<'_>) -> R) -> R {
+        f(&mut self.engine, &mut self.queues)
+    }
+
+    /// Unwrap the engine, dropping whatever is still queued.
+    pub fn into_inner(self) -> E {
+        self.engine
+    }
+
+    /// Latest time seen by `handle_event` or `handle_timeout`.
+    pub fn now_ms(&self) -> u64 {
+        self.queues.now_ms
+    }
+
+    /// Run the engine on `ev`, observed at `now_ms`, and queue its output.
+    pub fn handle_event(&mut self, now_ms: u64, ev: EngineEvent<'_>) {
+        self.advance(now_ms);
+        self.engine.on_event(ev, &mut self.queues);
+    }
+
+    /// Fire every timer due at `now_ms`, earliest first. Timers the engine
+    /// arms for `now_ms` or earlier while doing so fire too.
+    pub fn handle_timeout(&mut self, now_ms: u64) {
+        self.advance(now_ms);
+        while let Some(deadline) = self
+            .poll_timeout()
+            .filter(|d| d.at_ms <= self.queues.now_ms)
+        {
+            self.queues.timers.remove(&deadline.id);
+            self.engine
+                .on_event(EngineEvent::TimerFired(deadline.id), &mut self.queues);
+        }
+    }
+
+    /// Next QUIC command to carry out, oldest first.
+    pub fn poll_command(&mut self) -> Option<QuicCommand<'static>> {
+        self.queues.commands.pop_front()
+    }
+
+    /// Earliest armed timer, if any.
+    pub fn poll_timeout(&self) -> Option<Deadline> {
+        self.queues
+            .timers
+            .iter()
+            .map(|(&id, &at_ms)| Deadline { at_ms, id })
+            .min()
+    }
+
+    /// Next notification for the application, oldest first. It borrows the
+    /// engine until the next call.
+    pub fn poll_app_event(&mut self) -> Option<AppEvent<'_>> {
+        self.app_event = self.queues.app_events.pop_front();
+        self.app_event.as_ref().map(QueuedAppEvent::as_event)
+    }
+
+    fn advance(&mut self, now_ms: u64) {
+        self.queues.now_ms = self.queues.now_ms.max(now_ms);
+    }
+}
@@ -0,0 +1,291 @@
+//! qlog tracing of HTTP/3 and QPACK events, for loading into qvis.
+//!
+//! `Qlog` writes a JSON-SEQ trace (RFC 7464: every record is `0x1e`, one
+//! JSON text, `\n`) following the qlog 0.3 HTTP/3 and QPACK event
+//! definitions: `http:parameters_set`, `http:stream_type_set`,
+//! `http:frame_created`, `http:frame_parsed`, `qpack:headers_encoded` and
+//! `qpack:headers_decoded`. Install one with `H3Engine::set_qlog`.
+//!
+//! Invariants:
+//! - Every timestamp comes from the injected `QlogClock`, so the same
+//!   events with the same clock produce byte-identical traces.
+//! - Frames are logged where `EngineStats` counts them. Field lines travel
+//!   in the QPACK events, not in the HEADERS frame events.
+//! - Names and values are written as JSON strings; bytes outside printable
+//!   ASCII are escaped one by one as `\u00XX`.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use istok_core::h3::consts;
+use istok_core::h3::settings::Settings;
+use istok_core::qpack::{self, HeaderField};
+use istok_transport::StreamId;
+
+use crate::config::Role;
+
+const RECORD_SEPARATOR: char = '\u{1e}';
+
+/// Source of event timestamps, in milliseconds since the trace started.
+pub trait QlogClock {
+    fn now_ms(&mut self) -> f64;
+}
+
+impl<F: FnMut() -> f64> QlogClock for F {
+    fn now_ms(&mut self) -> f64 {
+        self()
+    }
+}
+
+/// Destination of complete JSON-SEQ records.
+pub trait QlogWriter {
+    fn write_record(&mut self, record: &[u8]);
+}
+
+impl QlogWriter for Vec<u8> {
+    fn write_record(&mut self, record: &[u8]) {
+        self.extend_from_slice(record);
+    }
+}
+
+/// Adapts a `std::io::Write`, such as a file, as a `QlogWriter`. Write
+/// errors drop the record: tracing never fails the connection.
+#[cfg(feature = "std")]
+pub struct IoWriter<W: std::io::Write>(pub W);
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> QlogWriter for IoWriter<W> {
+    fn write_record(&mut self, record: &[u8]) {
+        let _ = self.0.write_all(record);
+    }
+}
+
+/// Which endpoint an event is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Owner {
+    Local,
+    Remote,
+}
+
+impl Owner {
+    fn as_str(self) -> &'static str {
+        match self {
+            Owner::Local => "local",
+            Owner::Remote => "remote",
+        }
+    }
+}
+
+/// A qlog trace being written.
+pub struct Qlog {
+    writer: Box<dyn QlogWriter>,
+    clock: Box<dyn QlogClock>,
+    title: String,
+    /// Record being built; reused between events.
+    record: String,
+}
+
+impl Qlog {
+    /// A trace titled `title`, written to `writer` once the engine starts
+    /// it.
+    pub fn new(
+        title: &str,
+        writer: impl QlogWriter + 'static,
+        clock: impl QlogClock + 'static,
+    ) -> Self {
+        Self {
+            writer: Box::new(writer),
+            clock: Box::new(clock),
+            title: String::from(title),
+            record: String::new(),
+        }
+    }
+
+    /// Write the header record, from the point of view of `role`.
+    pub(crate) fn start(&mut self, role: Role) {
+        let vantage = match role {
+            Role::Server => "server",
+            Role::Client => "client",
+        };
+        self.record.clear();
+        self.record.push(RECORD_SEPARATOR);
+        self.record
+            .push_str("{\"qlog_version\":\"0.3\",\"qlog_format\":\"JSON-SEQ\",\"title\":");
+        push_str(&mut self.record, self.title.as_bytes());
+        let _ = writeln!(
+            self.record,
+            ",\"trace\":{{\"vantage_point\":{{\"type\":\"{vantage}\"}},\
+             \"common_fields\":{{\"time_format\":\"relative\",\"reference_time\":0}}}}}}"
+        );
+        self.writer.write_record(self.record.as_bytes());
+    }
+
+    pub(crate) fn parameters_set(&mut self, owner: Owner, settings: &Settings) {
+        self.begin("http:parameters_set");
+        let _ = write!(self.record, "\"owner\":\"{}\"", owner.as_str());
+        if owner == Owner::Local {
+            // Static table only: no dynamic table, no blocked streams.
+            self.record
+                .push_str(",\"max_table_capacity\":0,\"blocked_streams_count\":0");
+        }
+        for (name, value) in settings_fields(settings) {
+            let _ = write!(self.record, ",\"{name}\":{value}");
+        }
+        self.finish();
+    }
+
+    pub(crate) fn stream_type_set(&mut self, owner: Owner, id: StreamId, new: &str) {
+        self.begin("http:stream_type_set");
+        let _ = write!(
+            self.record,
+            "\"owner\":\"{}\",\"stream_id\":{},\"new\":\"{new}\"",
+            owner.as_str(),
+            id.0
+        );
+        self.finish();
+    }
+
+    /// A frame of type `ty` with a `len`-byte payload; `settings` is the
+    /// content of a SETTINGS frame.
+    pub(crate) fn frame(
+        &mut self,
+        owner: Owner,
+        id: StreamId,
+        ty: u64,
+        len: u64,
+        settings: Option<&Settings>,
+    ) {
+        self.begin(match owner {
+            Owner::Local => "http:frame_created",
+            Owner::Remote => "http:frame_parsed",
+        });
+        let _ = write!(
+            self.record,
+            "\"stream_id\":{},\"length\":{len},\"frame\":{{\"frame_type\":",
+            id.0
+        );
+        match ty {
+            consts::FRAME_TYPE_DATA => self.record.push_str("\"data\""),
+            consts::FRAME_TYPE_HEADERS => self.record.push_str("\"headers\""),
+            consts::FRAME_TYPE_GOAWAY => self.record.push_str("\"goaway\""),
+            consts::FRAME_TYPE_MAX_PUSH_ID => self.record.push_str("\"max_push_id\""),
+            consts::FRAME_TYPE_PRIORITY_UPDATE_REQUEST
+            | consts::FRAME_TYPE_PRIORITY_UPDATE_PUSH => {
+                self.record.push_str("\"priority_update\"")
+            }
+            consts::FRAME_TYPE_SETTINGS => {
+                self.record.push_str("\"settings\",\"settings\":[");
+                for (i, (name, value)) in settings
+                    .map(settings_fields)
+                    .into_iter()
+                    .flatten()
+                    .enumerate()
+                {
+                    let comma = if i == 0 { "" } else { "," };
+                    let _ = write!(
+                        self.record,
+                        "{comma}{{\"name\":\"settings_{name}\",\"value\":{value}}}"
+                    );
+                }
+                self.record.push(']');
+            }
+            ty if is_reserved(ty) => self.record.push_str("\"reserved\""),
+            ty => {
+                let _ = write!(self.record, "\"unknown\",\"raw_frame_type\":{ty}");
+            }
+        }
+        self.record.push('}');
+        self.finish();
+    }
+
+    /// A field section of `len` bytes carrying `fields`.
+    pub(crate) fn headers<'f>(
+        &mut self,
+        owner: Owner,
+        id: StreamId,
+        fields: impl IntoIterator<Item = HeaderField<'f>>,
+        len: usize,
+    ) {
+        self.begin(match owner {
+            Owner::Local => "qpack:headers_encoded",
+            Owner::Remote => "qpack:headers_decoded",
+        });
+        let _ = write!(self.record, "\"stream_id\":{},\"headers\":[", id.0);
+        for (i, field) in fields.into_iter().enumerate() {
+            if i > 0 {
+                self.record.push(',');
+            }
+            self.record.push_str("{\"name\":");
+            push_str(&mut self.record, field.name);
+            self.record.push_str(",\"value\":");
+            push_str(&mut self.record, field.value);
+            self.record.push('}');
+        }
+        let _ = write!(self.record, "],\"length\":{len}");
+        self.finish();
+    }
+
+    /// A received field section: `block`, as parsed on `id`.
+    pub(crate) fn headers_decoded(&mut self, id: StreamId, block: &[u8]) {
+        let mut fields = Vec::new();
+        let decoded = qpack::decode(block, |name, value| {
+            fields.push(HeaderField { name, value })
+        });
+        if decoded.is_ok() {
+            self.headers(Owner::Remote, id, fields, block.len());
+        }
+    }
+
+    fn begin(&mut self, name: &str) {
+        let time = self.clock.now_ms();
+        self.record.clear();
+        self.record.push(RECORD_SEPARATOR);
+        let _ = write!(
+            self.record,
+            "{{\"time\":{time},\"name\":\"{name}\",\"data\":{{"
+        );
+    }
+
+    fn finish(&mut self) {
+        self.record.push_str("}}\n");
+        self.writer.write_record(self.record.as_bytes());
+    }
+}
+
+/// Reserved frame types `0x1f * N + 0x21` (RFC 9114 §7.2.8).
+fn is_reserved(ty: u64) -> bool {
+    ty >= 0x21 && (ty - 0x21).is_multiple_of(0x1f)
+}
+
+/// qlog names and values of the settings present in `settings`.
+fn settings_fields(settings: &Settings) -> impl Iterator<Item = (&'static str, u64)> {
+    [
+        ("max_field_section_size", settings.max_field_section_size),
+        ("enable_connect_protocol", settings.enable_connect_protocol),
+        ("h3_datagram", settings.h3_datagram),
+        (
+            "webtransport_max_sessions",
+            settings.webtransport_max_sessions,
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| Some((name, value?)))
+}
+
+/// Append `bytes` as a JSON string.
+fn push_str(record: &mut String, bytes: &[u8]) {
+    record.push('"');
+    for &byte in bytes {
+        match byte {
+            b'"' => record.push_str("\\\""),
+            b'\\' => record.push_str("\\\\"),
+            0x20..=0x7e => record.push(char::from(byte)),
+            _ => {
+                let _ = write!(record, "\\u{byte:04x}");
+            }
+        }
+    }
+    record.push('"');
+}
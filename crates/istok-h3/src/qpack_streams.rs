@@ -0,0 +1,115 @@
+//! The peer's QPACK encoder and decoder streams (RFC 9204 §4.2).
+//!
+//! The engine never uses the dynamic table: it advertises no
+//! `SETTINGS_QPACK_MAX_TABLE_CAPACITY`, so the peer's encoder may only set
+//! the capacity to zero, and our encoder never inserts, so the peer's
+//! decoder has nothing to acknowledge. Both streams are still accepted and
+//! their instructions checked, one byte at a time, so split reads need no
+//! buffering.
+//!
+//! Invariants:
+//! - At most one stream of each type; a second is reported, not replaced.
+//! - Nothing is buffered; only the position inside a Stream Cancellation
+//!   integer survives between reads.
+
+use istok_transport::StreamId;
+
+/// Continuation bytes allowed in a Stream Cancellation stream id; enough
+/// for any 62-bit value.
+const MAX_CONTINUATION: u8 = 9;
+
+/// Which QPACK stream a peer uni stream is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum QpackStream {
+    Encoder,
+    Decoder,
+}
+
+/// A peer QPACK stream carried an instruction we cannot accept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum InstructionError {
+    /// Encoder stream: an insert, a duplicate, or a non-zero capacity.
+    Encoder,
+    /// Decoder stream: a Section Acknowledgment, an Insert Count Increment,
+    /// or an overlong integer.
+    Decoder,
+}
+
+/// The peer's QPACK streams; see the module docs.
+#[derive(Debug, Default)]
+pub(crate) struct QpackStreams {
+    encoder: Option<StreamId>,
+    decoder: Option<StreamId>,
+    /// Continuation bytes read so far of a Stream Cancellation stream id,
+    /// `None` between instructions.
+    cancellation: Option<u8>,
+}
+
+impl QpackStreams {
+    /// Record `id` as the peer's `kind` stream; `false` if it already has one.
+    pub(crate) fn open(&mut self, kind: QpackStream, id: StreamId) -> bool {
+        let slot = match kind {
+            QpackStream::Encoder => &mut self.encoder,
+            QpackStream::Decoder => &mut self.decoder,
+        };
+        if slot.is_some() {
+            return false;
+        }
+        *slot = Some(id);
+        true
+    }
+
+    /// Which QPACK stream `id` is, if any.
+    pub(crate) fn kind(&self, id: StreamId) -> Option<QpackStream> {
+        if self.encoder == Some(id) {
+            Some(QpackStream::Encoder)
+        } else if self.decoder == Some(id) {
+            Some(QpackStream::Decoder)
+        } else {
+            None
+        }
+    }
+
+    /// Check the next bytes of the peer's `kind` stream.
+    pub(crate) fn on_data(
+        &mut self,
+        kind: QpackStream,
+        data: &[u8],
+    ) -> Result<(), InstructionError> {
+        match kind {
+            QpackStream::Encoder => on_encoder_data(data),
+            QpackStream::Decoder => data.iter().try_for_each(|&byte| self.on_decoder_byte(byte)),
+        }
+    }
+
+    fn on_decoder_byte(&mut self, byte: u8) -> Result<(), InstructionError> {
+        if let Some(read) = self.cancellation {
+            if read == MAX_CONTINUATION {
+                return Err(InstructionError::Decoder);
+            }
+            self.cancellation = (byte & 0x80 != 0).then_some(read + 1);
+            return Ok(());
+        }
+        match byte {
+            // Section Acknowledgment: no section ever referenced the table.
+            0x80..=0xff => Err(InstructionError::Decoder),
+            // Stream Cancellation, 6-bit prefix stream id.
+            0x40..=0x7f => {
+                self.cancellation = (byte & 0x3f == 0x3f).then_some(0);
+                Ok(())
+            }
+            // Insert Count Increment: nothing was ever inserted.
+            _ => Err(InstructionError::Decoder),
+        }
+    }
+}
+
+/// With a zero maximum capacity the only valid encoder instruction is Set
+/// Dynamic Table Capacity to 0, the single byte `0x20`.
+fn on_encoder_data(data: &[u8]) -> Result<(), InstructionError> {
+    if data.iter().all(|&byte| byte == 0x20) {
+        Ok(())
+    } else {
+        Err(InstructionError::Encoder)
+    }
+}
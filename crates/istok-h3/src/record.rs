@@ -0,0 +1,891 @@
+//! Recording and deterministic replay of engine sessions.
+//!
+//! `Recorder` wraps any `Engine` and appends every event it handles, and
+//! then the commands the engine produced for it, to a compact binary
+//! recording. `decode` turns a recording back into a `MockHarness` script:
+//! each event becomes its input step and each command an `Expect` step, so
+//! a connection captured in the field runs as a regression test. `replay`
+//! feeds a script to a fresh engine and reports the events whose commands
+//! differ instead of panicking.
+//!
+//! Format: the magic `istokrec` and a version byte, then the configuration
+//! entry, then one entry per event or command: a tag byte and its fields.
+//! Integers are LEB128, as events carry full `u64`s that QUIC varints
+//! cannot; byte strings are a length and the bytes. Commands belong to the
+//! event before them.
+//!
+//! Invariants:
+//! - A recording is the concatenation of the chunks `take_recording`
+//!   returns, so a runtime may append them to a file as it goes.
+//! - Writes are recorded as the bytes put on the wire, however the engine
+//!   gathered them, and `OpenUni`/`OpenBidi` without their id hint: what
+//!   `MockHarness` compares.
+//! - Only `Engine::on_event` is recorded. Commands produced through
+//!   handles such as `WebTransportSession` are not, and show up as
+//!   differences when the session is replayed.
+//! - The configuration entry holds the recorded engine's whole `H3Config`,
+//!   so `Recording::engine` replays with the SETTINGS, limits and timeouts
+//!   the recording was made with. State set through engine methods, such as
+//!   the ORIGIN set or a `ResponseHook`, is not recorded.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use istok_core::h3::settings::Settings;
+use istok_transport::{QuicCommand, QuicEvent, StreamError, StreamId, StreamKind};
+
+use crate::config::{ConfigError, H3Config, Role};
+use crate::engine::{
+    AppAction, AppEvent, CommandSink, Engine, EngineCommand, EngineEvent, TimerId,
+};
+use crate::h3_engine::H3Engine;
+use crate::limits::DosLimits;
+use crate::mock::{ExpectCommand, ScriptStep, expectation};
+
+/// First bytes of every recording.
+pub const MAGIC: &[u8; 8] = b"istokrec";
+
+/// Version of the format written by `Recorder`.
+pub const VERSION: u8 = 2;
+
+/// Owned field lines, as carried by response events.
+type Fields = Vec<(Vec<u8>, Vec<u8>)>;
+
+mod tag {
+    /// The configuration entry after the version byte.
+    pub const CONFIG: u8 = 0x00;
+    pub const BOOT: u8 = 0x01;
+    pub const STREAM_OPENED: u8 = 0x02;
+    pub const STREAM_READABLE: u8 = 0x03;
+    pub const STREAM_ERROR: u8 = 0x04;
+    pub const STREAM_WRITABLE: u8 = 0x05;
+    pub const DATAGRAM: u8 = 0x06;
+    pub const CONNECTION_CLOSED: u8 = 0x07;
+    pub const TIMER_FIRED: u8 = 0x08;
+    pub const APP_SEND_DATAGRAM: u8 = 0x09;
+    pub const APP_RESET_STREAM: u8 = 0x0a;
+    pub const APP_ACCEPT_TUNNEL: u8 = 0x0b;
+    pub const APP_TUNNEL_SEND: u8 = 0x0c;
+    pub const SHUTDOWN: u8 = 0x0d;
+    pub const APP_SEND_REQUEST: u8 = 0x0e;
+    pub const APP_REJECT_TUNNEL: u8 = 0x0f;
+
+    /// Tags from here on are commands.
+    pub const FIRST_COMMAND: u8 = 0x40;
+    pub const OPEN_UNI: u8 = 0x40;
+    pub const OPEN_BIDI: u8 = 0x41;
+    pub const STREAM_WRITE: u8 = 0x42;
+    pub const CLOSE_CONNECTION: u8 = 0x43;
+    pub const SEND_DATAGRAM: u8 = 0x44;
+    pub const RESET_STREAM: u8 = 0x45;
+    pub const STOP_SENDING: u8 = 0x46;
+    pub const ARM_TIMER: u8 = 0x47;
+    pub const CANCEL_TIMER: u8 = 0x48;
+    pub const APP_DATAGRAM: u8 = 0x50;
+    pub const APP_CONNECT_UDP: u8 = 0x51;
+    pub const APP_CONNECT_UDP_CLOSED: u8 = 0x52;
+    pub const APP_CONNECT_TUNNEL: u8 = 0x53;
+    pub const APP_TUNNEL_DATA: u8 = 0x54;
+    pub const APP_TUNNEL_RESET: u8 = 0x55;
+    pub const APP_WEBTRANSPORT_SESSION: u8 = 0x56;
+    pub const APP_WEBTRANSPORT_STREAM_DATA: u8 = 0x57;
+    pub const APP_WEBTRANSPORT_SESSION_DRAINING: u8 = 0x58;
+    pub const APP_WEBTRANSPORT_SESSION_CLOSED: u8 = 0x59;
+    pub const APP_WRITE_PAUSED: u8 = 0x5a;
+    pub const APP_WRITE_RESUMED: u8 = 0x5b;
+    pub const APP_STREAM_ABORTED: u8 = 0x5c;
+    pub const APP_INTERIM_RESPONSE: u8 = 0x5d;
+    pub const APP_RESPONSE: u8 = 0x5e;
+    pub const APP_RESPONSE_DATA: u8 = 0x5f;
+    pub const APP_REQUEST: u8 = 0x60;
+}
+
+/// Why a recording could not be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordError {
+    /// The input does not start with `MAGIC`.
+    BadMagic,
+    /// Written by a newer version of the format.
+    UnsupportedVersion(u8),
+    /// The input ends inside an entry.
+    Truncated,
+    /// An entry tag this version of the format does not define.
+    UnknownTag(u8),
+    /// A field of the entry with this tag is out of range.
+    InvalidField(u8),
+    /// A command precedes the first event.
+    CommandBeforeEvent,
+    /// The recorded configuration does not build.
+    InvalidConfig(ConfigError),
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordError::BadMagic => write!(f, "not a recording"),
+            RecordError::UnsupportedVersion(version) => {
+                write!(f, "unsupported recording version {version}")
+            }
+            RecordError::Truncated => write!(f, "recording is truncated"),
+            RecordError::UnknownTag(tag) => write!(f, "unknown entry tag {tag:#04x}"),
+            RecordError::InvalidField(tag) => {
+                write!(f, "invalid field in entry with tag {tag:#04x}")
+            }
+            RecordError::CommandBeforeEvent => write!(f, "command before the first event"),
+            RecordError::InvalidConfig(inner) => write!(f, "invalid recorded config: {inner}"),
+        }
+    }
+}
+
+/// Engines a `Recorder` can wrap: their configuration goes into the
+/// recording so that a replay starts from the same one.
+pub trait Recordable: Engine {
+    /// The configuration the engine was built with.
+    fn recorded_config(&self) -> &H3Config;
+}
+
+impl Recordable for H3Engine {
+    fn recorded_config(&self) -> &H3Config {
+        self.config()
+    }
+}
+
+/// Records the events an engine handles and the commands it produces.
+pub struct Recorder<E: Recordable> {
+    engine: E,
+    recording: Vec<u8>,
+}
+
+impl<E: Recordable> Recorder<E> {
+    /// Start a recording of `engine`, beginning with its configuration.
+    pub fn new(engine: E) -> Self {
+        let mut recording = Vec::new();
+        recording.extend_from_slice(MAGIC);
+        recording.push(VERSION);
+        put_config(&mut recording, engine.recorded_config());
+        Self { engine, recording }
+    }
+
+    /// The recorded engine.
+    pub fn engine(&self) -> &E {
+        &self.engine
+    }
+
+    /// The recorded engine, e.g. to use its handles. Commands produced
+    /// through it are not recorded.
+    pub fn engine_mut(&mut self) -> &mut E {
+        &mut self.engine
+    }
+
+    /// Bytes recorded since the last call.
+    pub fn take_recording(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.recording)
+    }
+
+    /// The engine and whatever was not taken yet.
+    pub fn into_parts(self) -> (E, Vec<u8>) {
+        (self.engine, self.recording)
+    }
+}
+
+impl<E: Recordable> Engine for Recorder<E> {
+    fn on_event<'a>(&mut self, ev: EngineEvent<'a>, out: &mut dyn CommandSink<'a>) {
+        put_event(&mut self.recording, &ev);
+        let mut sink = RecordingSink {
+            out,
+            recording: &mut self.recording,
+        };
+        self.engine.on_event(ev, &mut sink);
+    }
+}
+
+/// Passes commands on to `out` after recording them.
+struct RecordingSink<'s, 'a> {
+    out: &'s mut dyn CommandSink<'a>,
+    recording: &'s mut Vec<u8>,
+}
+
+impl<'a> CommandSink<'a> for RecordingSink<'_, 'a> {
+    fn push(&mut self, cmd: EngineCommand<'a>) {
+        put_command(self.recording, &cmd);
+        self.out.push(cmd);
+    }
+}
+
+/// A decoded recording.
+#[derive(Debug, Clone)]
+pub struct Recording {
+    /// Configuration of the recorded engine.
+    pub config: H3Config,
+    /// Each event as an input step, followed by its commands as `Expect`
+    /// steps.
+    pub script: Vec<ScriptStep>,
+}
+
+impl Recording {
+    /// A fresh engine configured as the recorded one, ready for `replay`.
+    pub fn engine(&self) -> H3Engine {
+        H3Engine::new(self.config.clone())
+    }
+}
+
+/// The configuration and `MockHarness` script of `recording`.
+pub fn decode(recording: &[u8]) -> Result<Recording, RecordError> {
+    let body = recording
+        .strip_prefix(MAGIC.as_slice())
+        .ok_or(RecordError::BadMagic)?;
+    let mut input = Reader {
+        buf: body,
+        tag: tag::CONFIG,
+    };
+    let version = input.byte()?;
+    if version != VERSION {
+        return Err(RecordError::UnsupportedVersion(version));
+    }
+    let config = input.config()?;
+
+    let mut script = Vec::new();
+    while !input.buf.is_empty() {
+        let tag = input.byte()?;
+        input.tag = tag;
+        let step = if tag < tag::FIRST_COMMAND {
+            input.event()?
+        } else if script.is_empty() {
+            return Err(RecordError::CommandBeforeEvent);
+        } else {
+            ScriptStep::Expect(input.command()?)
+        };
+        script.push(step);
+    }
+    Ok(Recording { config, script })
+}
+
+/// An event whose replayed commands differ from the recorded ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayDiff {
+    /// Position of the event among the script's input steps.
+    pub event: usize,
+    /// Commands recorded for the event.
+    pub expected: Vec<ExpectCommand>,
+    /// Commands the replayed engine produced instead.
+    pub actual: Vec<ExpectCommand>,
+}
+
+/// Run the input steps of `script` through `engine`, comparing the
+/// commands of each with the `Expect` steps after it. Empty when every
+/// event replays as recorded.
+pub fn replay<E: Engine>(mut engine: E, script: &[ScriptStep]) -> Vec<ReplayDiff> {
+    let mut diffs = Vec::new();
+    let mut steps = script.iter().peekable();
+    let mut event = 0;
+    while let Some(step) = steps.next() {
+        let Some(ev) = step.event() else {
+            continue;
+        };
+        let mut actual = Expectations(Vec::new());
+        engine.on_event(ev, &mut actual);
+
+        let mut expected = Vec::new();
+        while let Some(step) = steps.next_if(|step| step.event().is_none()) {
+            if let ScriptStep::Expect(exp) = step {
+                expected.push(exp.clone());
+            }
+        }
+        if expected != actual.0 {
+            diffs.push(ReplayDiff {
+                event,
+                expected,
+                actual: actual.0,
+            });
+        }
+        event += 1;
+    }
+    diffs
+}
+
+struct Expectations(Vec<ExpectCommand>);
+
+impl<'a> CommandSink<'a> for Expectations {
+    fn push(&mut self, cmd: EngineCommand<'a>) {
+        self.0.push(expectation(cmd));
+    }
+}
+
+fn put_uint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    put_uint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+fn put_option(out: &mut Vec<u8>, value: Option<u64>) {
+    match value {
+        Some(value) => {
+            out.push(1);
+            put_uint(out, value);
+        }
+        None => out.push(0),
+    }
+}
+
+/// The configuration entry: every `H3Config` option in builder order.
+fn put_config(out: &mut Vec<u8>, config: &H3Config) {
+    out.push(tag::CONFIG);
+    out.push(u8::from(config.role == Role::Client));
+    let settings = &config.settings;
+    for value in [
+        settings.max_field_section_size,
+        settings.enable_connect_protocol,
+        settings.h3_datagram,
+        settings.webtransport_max_sessions,
+    ] {
+        put_option(out, value);
+    }
+    for value in [
+        config.max_header_frame_size,
+        config.max_request_buffer,
+        config.max_concurrent_streams,
+    ] {
+        put_uint(out, value as u64);
+    }
+    for value in [
+        config.idle_timeout_ms,
+        config.header_read_timeout_ms,
+        config.body_idle_timeout_ms,
+        config.grease,
+    ] {
+        put_option(out, value);
+    }
+    out.push(u8::from(config.enable_push));
+    put_uint(out, config.stream_high_water as u64);
+    put_uint(out, config.memory_budget as u64);
+    let limits = &config.dos_limits;
+    for value in [
+        limits.rapid_resets,
+        limits.unknown_frames_per_stream,
+        limits.data_frame_burst,
+        limits.min_data_frame_avg,
+        limits.max_header_fields,
+        limits.max_header_expansion,
+    ] {
+        put_uint(out, u64::from(value));
+    }
+    out.push(u8::from(config.report_requests));
+    out.push(u8::from(config.crumble_cookies));
+}
+
+fn put_entry(out: &mut Vec<u8>, tag: u8, id: StreamId) {
+    out.push(tag);
+    put_uint(out, id.0);
+}
+
+fn put_event(out: &mut Vec<u8>, ev: &EngineEvent<'_>) {
+    match ev {
+        EngineEvent::Boot => out.push(tag::BOOT),
+        EngineEvent::Quic(QuicEvent::StreamOpened { id, kind }) => {
+            put_entry(out, tag::STREAM_OPENED, *id);
+            out.push(u8::from(*kind == StreamKind::Uni));
+        }
+        EngineEvent::Quic(QuicEvent::StreamReadable { id, data, fin }) => {
+            put_entry(out, tag::STREAM_READABLE, *id);
+            out.push(u8::from(*fin));
+            put_bytes(out, data);
+        }
+        EngineEvent::Quic(QuicEvent::StreamError { id, err }) => {
+            put_entry(out, tag::STREAM_ERROR, *id);
+            let (kind, code) = match err {
+                StreamError::Reset(code) => (0, code),
+                StreamError::StopSending(code) => (1, code),
+            };
+            out.push(kind);
+            put_uint(out, *code);
+        }
+        EngineEvent::Quic(QuicEvent::StreamWritable { id, capacity }) => {
+            put_entry(out, tag::STREAM_WRITABLE, *id);
+            put_uint(out, *capacity);
+        }
+        EngineEvent::Quic(QuicEvent::Datagram { data }) => {
+            out.push(tag::DATAGRAM);
+            put_bytes(out, data);
+        }
+        EngineEvent::Quic(QuicEvent::ConnectionClosed { app_error }) => {
+            out.push(tag::CONNECTION_CLOSED);
+            out.push(u8::from(app_error.is_some()));
+            if let Some(code) = app_error {
+                put_uint(out, *code);
+            }
+        }
+        EngineEvent::TimerFired(id) => {
+            out.push(tag::TIMER_FIRED);
+            put_uint(out, u64::from(id.0));
+        }
+        EngineEvent::App(AppAction::SendDatagram { id, payload }) => {
+            put_entry(out, tag::APP_SEND_DATAGRAM, *id);
+            put_bytes(out, payload);
+        }
+        EngineEvent::App(AppAction::ResetStream { id, app_error }) => {
+            put_entry(out, tag::APP_RESET_STREAM, *id);
+            put_uint(out, *app_error);
+        }
+        EngineEvent::App(AppAction::AcceptTunnel { id }) => {
+            put_entry(out, tag::APP_ACCEPT_TUNNEL, *id);
+        }
+        EngineEvent::App(AppAction::RejectTunnel { id, status }) => {
+            put_entry(out, tag::APP_REJECT_TUNNEL, *id);
+            put_uint(out, u64::from(*status));
+        }
+        EngineEvent::App(AppAction::TunnelSend { id, data, fin }) => {
+            put_entry(out, tag::APP_TUNNEL_SEND, *id);
+            out.push(u8::from(*fin));
+            put_bytes(out, data);
+        }
+        EngineEvent::App(AppAction::SendRequest { id, fields }) => {
+            put_entry(out, tag::APP_SEND_REQUEST, *id);
+            put_uint(out, fields.len() as u64);
+            for field in fields {
+                put_bytes(out, field.name);
+                put_bytes(out, field.value);
+            }
+        }
+        EngineEvent::Shutdown => out.push(tag::SHUTDOWN),
+    }
+}
+
+fn put_command(out: &mut Vec<u8>, cmd: &EngineCommand<'_>) {
+    match cmd {
+        EngineCommand::Quic(QuicCommand::OpenUni { .. }) => out.push(tag::OPEN_UNI),
+        EngineCommand::Quic(QuicCommand::OpenBidi { .. }) => out.push(tag::OPEN_BIDI),
+        EngineCommand::Quic(QuicCommand::StreamWrite { id, data, fin }) => {
+            put_write(out, *id, &[data], *fin)
+        }
+        EngineCommand::Quic(QuicCommand::StreamWriteOwned { id, data, fin }) => {
+            put_write(out, *id, &[data], *fin)
+        }
+        EngineCommand::Quic(QuicCommand::StreamWriteVectored {
+            id,
+            header,
+            body,
+            fin,
+        }) => put_write(out, *id, &[header.as_slice(), body], *fin),
+        EngineCommand::Quic(QuicCommand::CloseConnection { app_error }) => {
+            out.push(tag::CLOSE_CONNECTION);
+            put_uint(out, *app_error);
+        }
+        EngineCommand::Quic(QuicCommand::SendDatagram { data }) => {
+            out.push(tag::SEND_DATAGRAM);
+            put_bytes(out, data);
+        }
+        EngineCommand::Quic(QuicCommand::ResetStream { id, app_error }) => {
+            put_entry(out, tag::RESET_STREAM, *id);
+            put_uint(out, *app_error);
+        }
+        EngineCommand::Quic(QuicCommand::StopSending { id, app_error }) => {
+            put_entry(out, tag::STOP_SENDING, *id);
+            put_uint(out, *app_error);
+        }
+        EngineCommand::App(app) => put_app_event(out, app),
+        EngineCommand::ArmTimer {
+            id,
+            deadline_ms_from_now,
+        } => {
+            out.push(tag::ARM_TIMER);
+            put_uint(out, u64::from(id.0));
+            put_uint(out, *deadline_ms_from_now);
+        }
+        EngineCommand::CancelTimer { id } => {
+            out.push(tag::CANCEL_TIMER);
+            put_uint(out, u64::from(id.0));
+        }
+    }
+}
+
+/// A write of `parts` joined, as it goes on the wire.
+fn put_write(out: &mut Vec<u8>, id: StreamId, parts: &[&[u8]], fin: bool) {
+    put_entry(out, tag::STREAM_WRITE, id);
+    out.push(u8::from(fin));
+    put_uint(out, parts.iter().map(|part| part.len() as u64).sum());
+    for part in parts {
+        out.extend_from_slice(part);
+    }
+}
+
+fn put_app_event(out: &mut Vec<u8>, ev: &AppEvent<'_>) {
+    match ev {
+        AppEvent::Datagram { id, payload } => {
+            put_entry(out, tag::APP_DATAGRAM, *id);
+            put_bytes(out, payload);
+        }
+        AppEvent::ConnectUdp { id, host, port } => {
+            put_entry(out, tag::APP_CONNECT_UDP, *id);
+            put_bytes(out, host);
+            put_uint(out, u64::from(*port));
+        }
+        AppEvent::ConnectUdpClosed { id } => put_entry(out, tag::APP_CONNECT_UDP_CLOSED, *id),
+        AppEvent::ConnectTunnel { id, authority } => {
+            put_entry(out, tag::APP_CONNECT_TUNNEL, *id);
+            put_bytes(out, authority);
+        }
+        AppEvent::TunnelData { id, data, fin } => {
+            put_entry(out, tag::APP_TUNNEL_DATA, *id);
+            out.push(u8::from(*fin));
+            put_bytes(out, data);
+        }
+        AppEvent::TunnelReset { id, app_error } => {
+            put_entry(out, tag::APP_TUNNEL_RESET, *id);
+            put_uint(out, *app_error);
+        }
+        AppEvent::WebTransportSession { id } => put_entry(out, tag::APP_WEBTRANSPORT_SESSION, *id),
+        AppEvent::WebTransportStreamData {
+            session,
+            id,
+            data,
+            fin,
+        } => {
+            put_entry(out, tag::APP_WEBTRANSPORT_STREAM_DATA, *id);
+            put_uint(out, session.0);
+            out.push(u8::from(*fin));
+            put_bytes(out, data);
+        }
+        AppEvent::WebTransportSessionDraining { id } => {
+            put_entry(out, tag::APP_WEBTRANSPORT_SESSION_DRAINING, *id)
+        }
+        AppEvent::WebTransportSessionClosed { id, code, reason } => {
+            put_entry(out, tag::APP_WEBTRANSPORT_SESSION_CLOSED, *id);
+            put_uint(out, u64::from(*code));
+            put_bytes(out, reason);
+        }
+        AppEvent::WritePaused { id } => put_entry(out, tag::APP_WRITE_PAUSED, *id),
+        AppEvent::WriteResumed { id } => put_entry(out, tag::APP_WRITE_RESUMED, *id),
+        AppEvent::StreamAborted { id, app_error } => {
+            put_entry(out, tag::APP_STREAM_ABORTED, *id);
+            put_uint(out, *app_error);
+        }
+        AppEvent::Request { id, fields } => {
+            put_entry(out, tag::APP_REQUEST, *id);
+            put_fields(out, fields);
+        }
+        AppEvent::InterimResponse { id, status, fields } => {
+            put_entry(out, tag::APP_INTERIM_RESPONSE, *id);
+            put_response(out, *status, fields);
+        }
+        AppEvent::Response { id, status, fields } => {
+            put_entry(out, tag::APP_RESPONSE, *id);
+            put_response(out, *status, fields);
+        }
+        AppEvent::ResponseData { id, data, fin } => {
+            put_entry(out, tag::APP_RESPONSE_DATA, *id);
+            out.push(u8::from(*fin));
+            put_bytes(out, data);
+        }
+    }
+}
+
+/// A status, then the fields.
+fn put_response(out: &mut Vec<u8>, status: u16, fields: &[(Vec<u8>, Vec<u8>)]) {
+    put_uint(out, u64::from(status));
+    put_fields(out, fields);
+}
+
+/// A count, then that many name and value pairs.
+fn put_fields(out: &mut Vec<u8>, fields: &[(Vec<u8>, Vec<u8>)]) {
+    put_uint(out, fields.len() as u64);
+    for (name, value) in fields {
+        put_bytes(out, name);
+        put_bytes(out, value);
+    }
+}
+
+/// Decodes the fields of one entry at a time.
+struct Reader<'r> {
+    buf: &'r [u8],
+    /// Tag of the entry being decoded, for errors.
+    tag: u8,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, RecordError> {
+        let (&byte, rest) = self.buf.split_first().ok_or(RecordError::Truncated)?;
+        self.buf = rest;
+        Ok(byte)
+    }
+
+    fn flag(&mut self) -> Result<bool, RecordError> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(RecordError::InvalidField(self.tag)),
+        }
+    }
+
+    fn uint(&mut self) -> Result<u64, RecordError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            let bits = u64::from(byte & 0x7f);
+            if shift == 63 && bits > 1 {
+                return Err(RecordError::InvalidField(self.tag));
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(RecordError::InvalidField(self.tag))
+    }
+
+    fn narrow<T: TryFrom<u64>>(&mut self) -> Result<T, RecordError> {
+        let value = self.uint()?;
+        T::try_from(value).map_err(|_| RecordError::InvalidField(self.tag))
+    }
+
+    fn id(&mut self) -> Result<StreamId, RecordError> {
+        self.uint().map(StreamId)
+    }
+
+    fn timer(&mut self) -> Result<TimerId, RecordError> {
+        self.narrow().map(TimerId)
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>, RecordError> {
+        let len: usize = self.narrow()?;
+        if len > self.buf.len() {
+            return Err(RecordError::Truncated);
+        }
+        let (bytes, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(bytes.to_vec())
+    }
+
+    fn option(&mut self) -> Result<Option<u64>, RecordError> {
+        if self.flag()? {
+            self.uint().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// The configuration entry, built like any other configuration.
+    fn config(&mut self) -> Result<H3Config, RecordError> {
+        if self.byte()? != tag::CONFIG {
+            return Err(RecordError::InvalidField(tag::CONFIG));
+        }
+        let role = if self.flag()? {
+            Role::Client
+        } else {
+            Role::Server
+        };
+        let settings = Settings {
+            max_field_section_size: self.option()?,
+            enable_connect_protocol: self.option()?,
+            h3_datagram: self.option()?,
+            webtransport_max_sessions: self.option()?,
+        };
+        let builder = H3Config::builder()
+            .role(role)
+            .settings(settings)
+            .max_header_frame_size(self.narrow()?)
+            .max_request_buffer(self.narrow()?)
+            .max_concurrent_streams(self.narrow()?)
+            .idle_timeout_ms(self.option()?)
+            .header_read_timeout_ms(self.option()?)
+            .body_idle_timeout_ms(self.option()?)
+            .grease(self.option()?)
+            .enable_push(self.flag()?)
+            .stream_high_water(self.narrow()?)
+            .memory_budget(self.narrow()?)
+            .dos_limits(DosLimits {
+                rapid_resets: self.narrow()?,
+                unknown_frames_per_stream: self.narrow()?,
+                data_frame_burst: self.narrow()?,
+                min_data_frame_avg: self.narrow()?,
+                max_header_fields: self.narrow()?,
+                max_header_expansion: self.narrow()?,
+            })
+            .report_requests(self.flag()?)
+            .crumble_cookies(self.flag()?);
+        builder.build().map_err(RecordError::InvalidConfig)
+    }
+
+    /// A count, then that many name and value pairs.
+    fn fields(&mut self) -> Result<Fields, RecordError> {
+        let count: usize = self.narrow()?;
+        // Every pair takes at least two bytes.
+        if count > self.buf.len() / 2 {
+            return Err(RecordError::Truncated);
+        }
+        (0..count)
+            .map(|_| Ok((self.bytes()?, self.bytes()?)))
+            .collect()
+    }
+
+    fn event(&mut self) -> Result<ScriptStep, RecordError> {
+        let step = match self.tag {
+            tag::BOOT => ScriptStep::InBoot,
+            tag::STREAM_OPENED => ScriptStep::InQuicOpen {
+                id: self.id()?,
+                kind: match self.flag()? {
+                    true => StreamKind::Uni,
+                    false => StreamKind::Bidi,
+                },
+            },
+            tag::STREAM_READABLE => ScriptStep::InQuicData {
+                id: self.id()?,
+                fin: self.flag()?,
+                data: self.bytes()?,
+            },
+            tag::STREAM_ERROR => {
+                let id = self.id()?;
+                let stop = self.flag()?;
+                let code = self.uint()?;
+                let err = match stop {
+                    true => StreamError::StopSending(code),
+                    false => StreamError::Reset(code),
+                };
+                ScriptStep::InQuicStreamError { id, err }
+            }
+            tag::STREAM_WRITABLE => ScriptStep::InQuicWritable {
+                id: self.id()?,
+                capacity: self.uint()?,
+            },
+            tag::DATAGRAM => ScriptStep::InQuicDatagram {
+                data: self.bytes()?,
+            },
+            tag::CONNECTION_CLOSED => ScriptStep::InQuicConnectionClosed {
+                app_error: match self.flag()? {
+                    true => Some(self.uint()?),
+                    false => None,
+                },
+            },
+            tag::TIMER_FIRED => ScriptStep::InTimer(self.timer()?),
+            tag::APP_SEND_DATAGRAM => ScriptStep::InAppSendDatagram {
+                id: self.id()?,
+                payload: self.bytes()?,
+            },
+            tag::APP_RESET_STREAM => ScriptStep::InAppResetStream {
+                id: self.id()?,
+                app_error: self.uint()?,
+            },
+            tag::APP_ACCEPT_TUNNEL => ScriptStep::InAppAcceptTunnel { id: self.id()? },
+            tag::APP_REJECT_TUNNEL => ScriptStep::InAppRejectTunnel {
+                id: self.id()?,
+                status: self.narrow()?,
+            },
+            tag::APP_TUNNEL_SEND => ScriptStep::InAppTunnelSend {
+                id: self.id()?,
+                fin: self.flag()?,
+                data: self.bytes()?,
+            },
+            tag::APP_SEND_REQUEST => ScriptStep::InAppSendRequest {
+                id: self.id()?,
+                fields: self.fields()?,
+            },
+            tag::SHUTDOWN => ScriptStep::InShutdown,
+            tag => return Err(RecordError::UnknownTag(tag)),
+        };
+        Ok(step)
+    }
+
+    fn command(&mut self) -> Result<ExpectCommand, RecordError> {
+        let cmd = match self.tag {
+            tag::OPEN_UNI => ExpectCommand::QuicOpenUni,
+            tag::OPEN_BIDI => ExpectCommand::QuicOpenBidi,
+            tag::STREAM_WRITE => ExpectCommand::QuicStreamWrite {
+                id: self.id()?,
+                fin: self.flag()?,
+                data_prefix: self.bytes()?,
+            },
+            tag::CLOSE_CONNECTION => ExpectCommand::QuicCloseConnection {
+                app_error: self.uint()?,
+            },
+            tag::SEND_DATAGRAM => ExpectCommand::QuicSendDatagram {
+                data: self.bytes()?,
+            },
+            tag::RESET_STREAM => ExpectCommand::QuicResetStream {
+                id: self.id()?,
+                app_error: self.uint()?,
+            },
+            tag::STOP_SENDING => ExpectCommand::QuicStopSending {
+                id: self.id()?,
+                app_error: self.uint()?,
+            },
+            tag::ARM_TIMER => ExpectCommand::ArmTimer {
+                id: self.timer()?,
+                deadline_ms_from_now: self.uint()?,
+            },
+            tag::CANCEL_TIMER => ExpectCommand::CancelTimer { id: self.timer()? },
+            tag::APP_DATAGRAM => ExpectCommand::AppDatagram {
+                id: self.id()?,
+                payload: self.bytes()?,
+            },
+            tag::APP_CONNECT_UDP => ExpectCommand::AppConnectUdp {
+                id: self.id()?,
+                host: self.bytes()?,
+                port: self.narrow()?,
+            },
+            tag::APP_CONNECT_UDP_CLOSED => ExpectCommand::AppConnectUdpClosed { id: self.id()? },
+            tag::APP_CONNECT_TUNNEL => ExpectCommand::AppConnectTunnel {
+                id: self.id()?,
+                authority: self.bytes()?,
+            },
+            tag::APP_TUNNEL_DATA => ExpectCommand::AppTunnelData {
+                id: self.id()?,
+                fin: self.flag()?,
+                data: self.bytes()?,
+            },
+            tag::APP_TUNNEL_RESET => ExpectCommand::AppTunnelReset {
+                id: self.id()?,
+                app_error: self.uint()?,
+            },
+            tag::APP_WEBTRANSPORT_SESSION => {
+                ExpectCommand::AppWebTransportSession { id: self.id()? }
+            }
+            tag::APP_WEBTRANSPORT_STREAM_DATA => ExpectCommand::AppWebTransportStreamData {
+                id: self.id()?,
+                session: self.id()?,
+                fin: self.flag()?,
+                data: self.bytes()?,
+            },
+            tag::APP_WEBTRANSPORT_SESSION_DRAINING => {
+                ExpectCommand::AppWebTransportSessionDraining { id: self.id()? }
+            }
+            tag::APP_WEBTRANSPORT_SESSION_CLOSED => ExpectCommand::AppWebTransportSessionClosed {
+                id: self.id()?,
+                code: self.narrow()?,
+                reason: self.bytes()?,
+            },
+            tag::APP_WRITE_PAUSED => ExpectCommand::AppWritePaused { id: self.id()? },
+            tag::APP_WRITE_RESUMED => ExpectCommand::AppWriteResumed { id: self.id()? },
+            tag::APP_STREAM_ABORTED => ExpectCommand::AppStreamAborted {
+                id: self.id()?,
+                app_error: self.uint()?,
+            },
+            tag::APP_REQUEST => ExpectCommand::AppRequest {
+                id: self.id()?,
+                fields: self.fields()?,
+            },
+            tag::APP_INTERIM_RESPONSE => ExpectCommand::AppInterimResponse {
+                id: self.id()?,
+                status: self.narrow()?,
+                fields: self.fields()?,
+            },
+            tag::APP_RESPONSE => ExpectCommand::AppResponse {
+                id: self.id()?,
+                status: self.narrow()?,
+                fields: self.fields()?,
+            },
+            tag::APP_RESPONSE_DATA => ExpectCommand::AppResponseData {
+                id: self.id()?,
+                fin: self.flag()?,
+                data: self.bytes()?,
+            },
+            tag => return Err(RecordError::UnknownTag(tag)),
+        };
+        Ok(cmd)
+    }
+}
@@ -0,0 +1,244 @@
+//! Outbound write scheduler driven by Extensible Priorities (RFC 9218 §10).
+//!
+//! Writes are queued per stream and handed out one at a time:
+//! - the lowest urgency with queued data is served first;
+//! - within an urgency, non-incremental streams go first, one at a time in
+//!   stream id order, so each response completes before the next starts;
+//! - incremental streams of that urgency then share bandwidth round-robin,
+//!   one queued write per turn.
+//!
+//! Each stream may also carry a send credit, the bytes the transport will
+//! currently take (`QuicEvent::StreamWritable`). A stream without credit
+//! is skipped, and a write larger than the credit is split: the front
+//! part is handed out and the rest stays queued. Streams whose credit was
+//! never set are unbounded.
+//!
+//! A write is an inline header (typically a frame header) and a shared
+//! body; splitting one only moves offsets, never copies the body.
+
+use alloc::collections::{BTreeMap, VecDeque};
+
+use istok_core::bytes::Bytes;
+use istok_core::codec::priority::{MAX_URGENCY, Priority};
+use istok_transport::{InlineHeader, StreamId};
+
+/// One queued stream write: `header`, then `data`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingWrite {
+    /// Stream written to.
+    pub id: StreamId,
+    /// Frame header, or the unwritten rest of it.
+    pub header: InlineHeader,
+    /// Shared payload following `header`.
+    pub data: Bytes,
+    /// Whether the stream ends after `data`.
+    pub fin: bool,
+}
+
+impl PendingWrite {
+    /// Bytes written to the stream.
+    pub fn len(&self) -> usize {
+        self.header.len() + self.data.len()
+    }
+
+    /// Whether nothing is left to write; a FIN may still be.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A queued write, not yet assigned to a stream.
+struct Chunk {
+    header: InlineHeader,
+    data: Bytes,
+    fin: bool,
+}
+
+impl Chunk {
+    fn len(&self) -> usize {
+        self.header.len() + self.data.len()
+    }
+}
+
+#[derive(Default)]
+struct StreamQueue {
+    priority: Priority,
+    /// Whether `priority` was set rather than defaulted.
+    prioritized: bool,
+    writes: VecDeque<Chunk>,
+    /// Bytes the transport takes before the next `set_capacity`; `None`
+    /// when unbounded.
+    credit: Option<u64>,
+}
+
+impl StreamQueue {
+    /// Whether the front write can be handed out. A bare FIN needs no credit.
+    fn ready(&self) -> bool {
+        self.writes
+            .front()
+            .is_some_and(|chunk| chunk.len() == 0 || self.credit != Some(0))
+    }
+
+    fn queued_bytes(&self) -> usize {
+        self.writes.iter().map(Chunk::len).sum()
+    }
+}
+
+/// Per-stream write queues, served in priority order; see the module docs.
+#[derive(Default)]
+pub struct WriteScheduler {
+    streams: BTreeMap<StreamId, StreamQueue>,
+    /// Incremental stream served last, per urgency.
+    last_incremental: [Option<StreamId>; MAX_URGENCY as usize + 1],
+}
+
+impl WriteScheduler {
+    /// A scheduler with nothing queued.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the priority of stream `id`, which may not have queued data yet.
+    /// Urgencies above `MAX_URGENCY` are clamped.
+    pub fn set_priority(&mut self, id: StreamId, priority: Priority) {
+        let priority = Priority {
+            urgency: priority.urgency.min(MAX_URGENCY),
+            ..priority
+        };
+        let queue = self.streams.entry(id).or_default();
+        queue.priority = priority;
+        queue.prioritized = true;
+    }
+
+    /// Whether stream `id` has a priority from `set_priority`, rather than
+    /// only queued writes or credit.
+    pub fn has_priority(&self, id: StreamId) -> bool {
+        self.streams.get(&id).is_some_and(|queue| queue.prioritized)
+    }
+
+    /// Current priority of stream `id` (the default if unknown).
+    pub fn priority(&self, id: StreamId) -> Priority {
+        self.streams
+            .get(&id)
+            .map(|queue| queue.priority)
+            .unwrap_or_default()
+    }
+
+    /// Whether stream `id` has a priority or queued writes.
+    pub fn contains(&self, id: StreamId) -> bool {
+        self.streams.contains_key(&id)
+    }
+
+    /// Number of streams tracked.
+    pub fn len(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// Whether no writes are queued on any stream.
+    pub fn is_empty(&self) -> bool {
+        self.streams.values().all(|queue| queue.writes.is_empty())
+    }
+
+    /// Let stream `id`, which may not have queued data yet, send `capacity`
+    /// more bytes from now on.
+    pub fn set_capacity(&mut self, id: StreamId, capacity: u64) {
+        self.streams.entry(id).or_default().credit = Some(capacity);
+    }
+
+    /// Bytes queued on stream `id` and not yet handed out.
+    pub fn queued_bytes(&self, id: StreamId) -> usize {
+        self.streams.get(&id).map_or(0, StreamQueue::queued_bytes)
+    }
+
+    /// Bytes queued on each tracked stream, in stream id order.
+    pub fn queued(&self) -> impl Iterator<Item = (StreamId, usize)> + '_ {
+        self.streams
+            .iter()
+            .map(|(id, queue)| (*id, queue.queued_bytes()))
+    }
+
+    /// Queue `data` for stream `id`. After a `fin` write is handed out the
+    /// stream is forgotten.
+    pub fn push(&mut self, id: StreamId, data: impl Into<Bytes>, fin: bool) {
+        self.push_framed(id, InlineHeader::default(), data.into(), fin);
+    }
+
+    /// Queue `header` followed by `data` for stream `id`, as `push` does.
+    pub fn push_framed(&mut self, id: StreamId, header: InlineHeader, data: Bytes, fin: bool) {
+        self.streams
+            .entry(id)
+            .or_default()
+            .writes
+            .push_back(Chunk { header, data, fin });
+    }
+
+    /// Drop stream `id` and everything queued on it, e.g. after a reset.
+    pub fn remove(&mut self, id: StreamId) {
+        self.streams.remove(&id);
+    }
+
+    /// Take the next write in priority order, cut to its stream's credit.
+    pub fn pop(&mut self) -> Option<PendingWrite> {
+        let urgency = self
+            .streams
+            .values()
+            .filter(|queue| queue.ready())
+            .map(|queue| queue.priority.urgency)
+            .min()?;
+        let id = self.next_stream(urgency)?;
+
+        let queue = self.streams.get_mut(&id)?;
+        let mut chunk = queue.writes.pop_front()?;
+        if let Some(credit) = queue.credit.as_mut() {
+            let len = chunk.len();
+            let take = usize::try_from(*credit).map_or(len, |credit| credit.min(len));
+            if take < len {
+                let header = chunk.header.split_to(take);
+                let data = chunk.data.split_to(take - header.len());
+                queue.writes.push_front(chunk);
+                chunk = Chunk {
+                    header,
+                    data,
+                    fin: false,
+                };
+            }
+            *credit -= take as u64;
+        }
+        if queue.priority.incremental {
+            self.last_incremental[usize::from(urgency)] = Some(id);
+        }
+        if chunk.fin {
+            self.streams.remove(&id);
+        }
+        Some(PendingWrite {
+            id,
+            header: chunk.header,
+            data: chunk.data,
+            fin: chunk.fin,
+        })
+    }
+
+    fn next_stream(&self, urgency: u8) -> Option<StreamId> {
+        let mut ready = self
+            .streams
+            .iter()
+            .filter(|(_, queue)| queue.ready() && queue.priority.urgency == urgency);
+        if let Some((id, _)) = ready.clone().find(|(_, queue)| !queue.priority.incremental) {
+            return Some(*id);
+        }
+
+        // Round-robin: the first incremental stream after the one served
+        // last, wrapping around to the lowest id.
+        let last = self.last_incremental[usize::from(urgency)];
+        let mut first = None;
+        for (id, _) in ready.by_ref() {
+            if first.is_none() {
+                first = Some(*id);
+            }
+            if last.is_none_or(|last| *id > last) {
+                return Some(*id);
+            }
+        }
+        first
+    }
+}
@@ -0,0 +1,190 @@
+//! Per-connection counters, read with `H3Engine::stats`.
+//!
+//! The engine bumps these as it parses and encodes; nothing is sampled or
+//! timed. `EngineStats::merge` adds up the counters of many connections.
+//!
+//! Invariants:
+//! - Counters only grow; `peak_buffered_bytes` is the largest
+//!   `H3Engine::memory_usage` seen after any event.
+//! - A frame is counted once: inbound when its header is consumed (or, on
+//!   the control stream, once its payload is complete), outbound when the
+//!   engine encodes it. Header and body bytes are frame payload bytes.
+//! - Close and reset codes cover the commands `Engine::on_event` emits;
+//!   writes made through a `WebTransportSession` handle bypass them.
+
+use alloc::collections::BTreeMap;
+use istok_core::h3::consts;
+
+/// Frames seen in one direction, by type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameCounts {
+    /// DATA frames.
+    pub data: u64,
+    /// HEADERS frames, interim responses and trailers included.
+    pub headers: u64,
+    /// SETTINGS frames.
+    pub settings: u64,
+    /// GOAWAY frames.
+    pub goaway: u64,
+    /// ORIGIN frames.
+    pub origin: u64,
+    /// MAX_PUSH_ID frames.
+    pub max_push_id: u64,
+    /// PRIORITY_UPDATE for request and push streams.
+    pub priority_update: u64,
+    /// Any other type, reserved (GREASE) and unknown ones included.
+    pub other: u64,
+}
+
+impl FrameCounts {
+    /// Frames of every type.
+    pub fn total(&self) -> u64 {
+        self.data
+            + self.headers
+            + self.settings
+            + self.goaway
+            + self.origin
+            + self.max_push_id
+            + self.priority_update
+            + self.other
+    }
+
+    fn record(&mut self, ty: u64) {
+        let count = match ty {
+            consts::FRAME_TYPE_DATA => &mut self.data,
+            consts::FRAME_TYPE_HEADERS => &mut self.headers,
+            consts::FRAME_TYPE_SETTINGS => &mut self.settings,
+            consts::FRAME_TYPE_GOAWAY => &mut self.goaway,
+            consts::FRAME_TYPE_ORIGIN => &mut self.origin,
+            consts::FRAME_TYPE_MAX_PUSH_ID => &mut self.max_push_id,
+            consts::FRAME_TYPE_PRIORITY_UPDATE_REQUEST
+            | consts::FRAME_TYPE_PRIORITY_UPDATE_PUSH => &mut self.priority_update,
+            _ => &mut self.other,
+        };
+        *count += 1;
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.data += other.data;
+        self.headers += other.headers;
+        self.settings += other.settings;
+        self.goaway += other.goaway;
+        self.origin += other.origin;
+        self.max_push_id += other.max_push_id;
+        self.priority_update += other.priority_update;
+        self.other += other.other;
+    }
+}
+
+/// QPACK field sections coded in one direction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QpackCounts {
+    /// Field sections.
+    pub blocks: u64,
+    /// Encoded field section bytes.
+    pub encoded_bytes: u64,
+    /// Name and value bytes those sections carry.
+    pub decoded_bytes: u64,
+}
+
+impl QpackCounts {
+    /// Decoded bytes per encoded byte; `None` before the first section.
+    pub fn compression_ratio(&self) -> Option<f64> {
+        (self.encoded_bytes > 0).then(|| self.decoded_bytes as f64 / self.encoded_bytes as f64)
+    }
+
+    pub(crate) fn record(&mut self, encoded: usize, decoded: u64) {
+        self.blocks += 1;
+        self.encoded_bytes += encoded as u64;
+        self.decoded_bytes += decoded;
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.blocks += other.blocks;
+        self.encoded_bytes += other.encoded_bytes;
+        self.decoded_bytes += other.decoded_bytes;
+    }
+}
+
+/// Snapshot of what a connection did so far.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EngineStats {
+    /// Peer request streams accepted.
+    pub streams_opened: u64,
+    /// Request streams the peer finished sending.
+    pub streams_completed: u64,
+    /// Request streams the peer aborted before finishing them.
+    pub streams_reset: u64,
+    /// Frames received.
+    pub frames_in: FrameCounts,
+    /// Frames sent.
+    pub frames_out: FrameCounts,
+    /// HEADERS payload bytes received.
+    pub header_bytes_in: u64,
+    /// HEADERS payload bytes sent.
+    pub header_bytes_out: u64,
+    /// DATA payload bytes received.
+    pub body_bytes_in: u64,
+    /// DATA payload bytes sent.
+    pub body_bytes_out: u64,
+    /// Request header sections decoded.
+    pub qpack_in: QpackCounts,
+    /// Response header sections encoded, interim ones included.
+    pub qpack_out: QpackCounts,
+    /// Connection closes sent, by application error code.
+    pub close_codes: BTreeMap<u64, u64>,
+    /// Streams this side reset, by application error code.
+    pub reset_codes: BTreeMap<u64, u64>,
+    /// Largest `H3Engine::memory_usage` seen after any event.
+    pub peak_buffered_bytes: usize,
+}
+
+impl EngineStats {
+    /// Add the counters of `other`; peaks keep the larger value.
+    pub fn merge(&mut self, other: &Self) {
+        self.streams_opened += other.streams_opened;
+        self.streams_completed += other.streams_completed;
+        self.streams_reset += other.streams_reset;
+        self.frames_in.merge(&other.frames_in);
+        self.frames_out.merge(&other.frames_out);
+        self.header_bytes_in += other.header_bytes_in;
+        self.header_bytes_out += other.header_bytes_out;
+        self.body_bytes_in += other.body_bytes_in;
+        self.body_bytes_out += other.body_bytes_out;
+        self.qpack_in.merge(&other.qpack_in);
+        self.qpack_out.merge(&other.qpack_out);
+        for (code, count) in &other.close_codes {
+            *self.close_codes.entry(*code).or_default() += count;
+        }
+        for (code, count) in &other.reset_codes {
+            *self.reset_codes.entry(*code).or_default() += count;
+        }
+        self.peak_buffered_bytes = self.peak_buffered_bytes.max(other.peak_buffered_bytes);
+    }
+
+    pub(crate) fn frame_in(&mut self, ty: u64, len: u64) {
+        self.frames_in.record(ty);
+        match ty {
+            consts::FRAME_TYPE_HEADERS => self.header_bytes_in += len,
+            consts::FRAME_TYPE_DATA => self.body_bytes_in += len,
+            _ => {}
+        }
+    }
+
+    pub(crate) fn frame_out(&mut self, ty: u64, len: usize) {
+        self.frames_out.record(ty);
+        match ty {
+            consts::FRAME_TYPE_HEADERS => self.header_bytes_out += len as u64,
+            consts::FRAME_TYPE_DATA => self.body_bytes_out += len as u64,
+            _ => {}
+        }
+    }
+
+    pub(crate) fn closed(&mut self, app_error: u64) {
+        *self.close_codes.entry(app_error).or_default() += 1;
+    }
+
+    pub(crate) fn reset(&mut self, app_error: u64) {
+        *self.reset_codes.entry(app_error).or_default() += 1;
+    }
+}
@@ -0,0 +1,28 @@
+//! Timer ids `H3Engine` arms through `EngineCommand::ArmTimer`.
+//!
+//! The connection idle timeout uses `IDLE_TIMER`. Each request stream has
+//! one timer, `stream_timer(id)`, which first guards the header read and
+//! then, once HEADERS arrived, the pauses between body reads.
+//!
+//! Invariants:
+//! - Ids are derived from what they guard, never allocated, so a runtime
+//!   and a test script can name them up front.
+//! - Arming an id that is already armed replaces its deadline. The engine
+//!   cancels every armed id it stops caring about.
+//! - A `TimerFired` for an id the engine does not have armed is ignored.
+
+use crate::engine::TimerId;
+use istok_transport::StreamId;
+
+/// Connection idle timeout.
+pub const IDLE_TIMER: TimerId = TimerId(0);
+
+/// Timer of client-initiated bidirectional stream `id`; `None` for other
+/// stream kinds and for ids past the `TimerId` range.
+pub fn stream_timer(id: StreamId) -> Option<TimerId> {
+    if !id.0.is_multiple_of(4) {
+        return None;
+    }
+    let index = u32::try_from(id.0 / 4).ok()?;
+    index.checked_add(1).map(TimerId)
+}
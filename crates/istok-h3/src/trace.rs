@@ -0,0 +1,198 @@
+//! `tracing` spans and events for engine decisions.
+//!
+//! With the `tracing` feature every event runs inside an `h3_connection`
+//! span, or inside the `h3_stream` span of the stream it concerns, and each
+//! decision to close the connection or reset a stream emits a `debug`
+//! event with its code, a short reason and, when bytes on a stream were at
+//! fault, their offset. Without the feature `Tracer` is zero-sized and
+//! every call compiles to nothing, so builds that do not ask for it never
+//! depend on `tracing`.
+//!
+//! Invariants:
+//! - A stream span opens with the stream and ends once the peer's side of
+//!   it is finished or reset, or we abort it. Later events on the stream
+//!   run in the connection span.
+//! - An offset counts the bytes the peer sent on the stream minus those
+//!   still buffered unparsed: the start of the frame, or frame payload,
+//!   being parsed, or the end of a read refused before it was buffered.
+//! - Tracing never changes what the engine does.
+
+use istok_transport::StreamId;
+
+#[cfg(feature = "tracing")]
+pub(crate) use on::Tracer;
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) use off::Tracer;
+
+/// The connection is closed with `app_error`; `at` is the stream and
+/// offset of the offending bytes.
+#[inline]
+pub(crate) fn close(app_error: u64, reason: &'static str, at: Option<(StreamId, u64)>) {
+    #[cfg(feature = "tracing")]
+    match at {
+        Some((id, offset)) => tracing::debug!(
+            app_error,
+            reason,
+            stream = id.0,
+            offset,
+            "closing connection"
+        ),
+        None => tracing::debug!(app_error, reason, "closing connection"),
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = (app_error, reason, at);
+}
+
+/// Stream `id` is reset with `app_error`; `offset` locates the offending
+/// bytes, if any.
+#[inline]
+pub(crate) fn reset(id: StreamId, app_error: u64, reason: &'static str, offset: Option<u64>) {
+    #[cfg(feature = "tracing")]
+    match offset {
+        Some(offset) => {
+            tracing::debug!(app_error, reason, stream = id.0, offset, "resetting stream")
+        }
+        None => tracing::debug!(app_error, reason, stream = id.0, "resetting stream"),
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = (id, app_error, reason, offset);
+}
+
+#[cfg(feature = "tracing")]
+mod on {
+    use alloc::collections::BTreeMap;
+
+    use istok_transport::{QuicEvent, StreamId};
+    use tracing::Span;
+
+    use crate::config::Role;
+    use crate::engine::EngineEvent;
+
+    pub(crate) type Entered = tracing::span::EnteredSpan;
+
+    struct StreamTrace {
+        span: Span,
+        /// Bytes the peer sent on the stream so far.
+        received: u64,
+    }
+
+    pub(crate) struct Tracer {
+        connection: Span,
+        streams: BTreeMap<StreamId, StreamTrace>,
+        /// Stream the current event delivered bytes on.
+        current: Option<StreamId>,
+        /// Stream whose peer side the current event ends.
+        ending: Option<StreamId>,
+    }
+
+    impl Tracer {
+        pub(crate) fn new(role: Role) -> Self {
+            Self {
+                connection: tracing::debug_span!("h3_connection", ?role),
+                streams: BTreeMap::new(),
+                current: None,
+                ending: None,
+            }
+        }
+
+        /// Note what `ev` delivers and enter the span it runs in.
+        pub(crate) fn begin(&mut self, ev: &EngineEvent<'_>) -> Entered {
+            let id = match ev {
+                EngineEvent::Quic(QuicEvent::StreamOpened { id, .. }) => {
+                    self.stream(*id);
+                    Some(*id)
+                }
+                EngineEvent::Quic(QuicEvent::StreamReadable { id, data, fin }) => {
+                    self.stream(*id).received += data.len() as u64;
+                    self.current = Some(*id);
+                    if *fin {
+                        self.ending = Some(*id);
+                    }
+                    Some(*id)
+                }
+                EngineEvent::Quic(QuicEvent::StreamError { id, .. }) => {
+                    self.ending = Some(*id);
+                    Some(*id)
+                }
+                EngineEvent::Quic(QuicEvent::StreamWritable { id, .. }) => Some(*id),
+                _ => None,
+            };
+            id.and_then(|id| self.streams.get(&id))
+                .map_or(&self.connection, |stream| &stream.span)
+                .clone()
+                .entered()
+        }
+
+        /// The current event was handled.
+        pub(crate) fn end(&mut self) {
+            self.current = None;
+            if let Some(id) = self.ending.take() {
+                self.streams.remove(&id);
+            }
+        }
+
+        /// We aborted stream `id`.
+        pub(crate) fn aborted(&mut self, id: StreamId) {
+            self.streams.remove(&id);
+        }
+
+        pub(crate) fn current(&self) -> Option<StreamId> {
+            self.current
+        }
+
+        /// Offset on `id` of the first of its `unread` buffered bytes.
+        pub(crate) fn offset(&self, id: StreamId, unread: usize) -> Option<u64> {
+            let stream = self.streams.get(&id)?;
+            Some(stream.received.saturating_sub(unread as u64))
+        }
+
+        fn stream(&mut self, id: StreamId) -> &mut StreamTrace {
+            let connection = &self.connection;
+            self.streams.entry(id).or_insert_with(|| StreamTrace {
+                span: tracing::debug_span!(parent: connection, "h3_stream", id = id.0),
+                received: 0,
+            })
+        }
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+mod off {
+    use istok_transport::StreamId;
+
+    use crate::config::Role;
+    use crate::engine::EngineEvent;
+
+    pub(crate) struct Entered;
+
+    pub(crate) struct Tracer;
+
+    impl Tracer {
+        #[inline]
+        pub(crate) fn new(_role: Role) -> Self {
+            Self
+        }
+
+        #[inline]
+        pub(crate) fn begin(&mut self, _ev: &EngineEvent<'_>) -> Entered {
+            Entered
+        }
+
+        #[inline]
+        pub(crate) fn end(&mut self) {}
+
+        #[inline]
+        pub(crate) fn aborted(&mut self, _id: StreamId) {}
+
+        #[inline]
+        pub(crate) fn current(&self) -> Option<StreamId> {
+            None
+        }
+
+        #[inline]
+        pub(crate) fn offset(&self, _id: StreamId, _unread: usize) -> Option<u64> {
+            None
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Peer unidirectional streams whose stream type has not fully arrived.
+//!
+//! A uni stream opens with a varint stream type (RFC 9114 §6.2). Until it
+//! is complete the engine cannot tell a control stream from a QPACK,
+//! WebTransport, reserved or unknown one, so the first bytes of every new
+//! peer uni stream are collected here, one small slot per stream.
+//!
+//! Invariants:
+//! - At most `MAX_PENDING_UNI` streams wait for their type at once; the
+//!   table never allocates.
+//! - A slot holds at most the 8 bytes of one varint and is released as
+//!   soon as the type decodes.
+
+use istok_core::codec::varint;
+use istok_transport::StreamId;
+
+/// Peer uni streams waiting for their stream type at once.
+pub(crate) const MAX_PENDING_UNI: usize = 16;
+
+/// Outcome of feeding bytes of a stream still waiting for its type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TypeProgress {
+    /// The stream is not in the table.
+    Unknown,
+    /// The varint is still incomplete; everything given was kept.
+    Partial,
+    /// The stream type, and how many of the given bytes it used.
+    Complete { ty: u64, consumed: usize },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    id: StreamId,
+    bytes: [u8; 8],
+    len: usize,
+}
+
+/// Streams waiting for their type; see the module docs.
+#[derive(Debug, Default)]
+pub(crate) struct PendingUniTypes {
+    slots: [Option<Slot>; MAX_PENDING_UNI],
+}
+
+impl PendingUniTypes {
+    /// Start waiting for the type of `id`; `false` if the table is full.
+    pub(crate) fn open(&mut self, id: StreamId) -> bool {
+        if self.contains(id) {
+            return true;
+        }
+        let Some(free) = self.slots.iter_mut().find(|slot| slot.is_none()) else {
+            return false;
+        };
+        *free = Some(Slot {
+            id,
+            bytes: [0; 8],
+            len: 0,
+        });
+        true
+    }
+
+    pub(crate) fn contains(&self, id: StreamId) -> bool {
+        self.slots.iter().flatten().any(|slot| slot.id == id)
+    }
+
+    /// Stop waiting for `id`, whatever arrived so far.
+    pub(crate) fn remove(&mut self, id: StreamId) {
+        for slot in &mut self.slots {
+            if slot.is_some_and(|slot| slot.id == id) {
+                *slot = None;
+            }
+        }
+    }
+
+    /// Append the start of `data` to what `id` sent so far. Once the type
+    /// is complete the slot is released.
+    pub(crate) fn feed(&mut self, id: StreamId, data: &[u8]) -> TypeProgress {
+        let Some(entry) = self
+            .slots
+            .iter_mut()
+            .find(|slot| slot.is_some_and(|slot| slot.id == id))
+        else {
+            return TypeProgress::Unknown;
+        };
+        let Some(slot) = entry.as_mut() else {
+            return TypeProgress::Unknown;
+        };
+        let mut consumed = 0;
+        for &byte in data {
+            let needed = match slot.len {
+                0 => varint_len(byte),
+                _ => varint_len(slot.bytes[0]),
+            };
+            if slot.len == needed {
+                break;
+            }
+            slot.bytes[slot.len] = byte;
+            slot.len += 1;
+            consumed += 1;
+        }
+        match varint::decode(&slot.bytes[..slot.len]) {
+            Ok((ty, _)) => {
+                *entry = None;
+                TypeProgress::Complete { ty, consumed }
+            }
+            Err(_) => TypeProgress::Partial,
+        }
+    }
+}
+
+/// Length of the varint starting with `first` (RFC 9000 §16).
+fn varint_len(first: u8) -> usize {
+    1 << (first >> 6)
+}
@@ -0,0 +1,630 @@
+//! WebTransport over HTTP/3 (draft-ietf-webtrans-http3).
+//!
+//! A session is an extended CONNECT stream carrying `:protocol=webtransport`.
+//! Up to our `SETTINGS_WEBTRANSPORT_MAX_SESSIONS` sessions run at once; a
+//! CONNECT beyond that is reset with `H3_REQUEST_REJECTED`. Streams join a session by starting with a header naming the session's
+//! CONNECT stream id:
+//! - uni streams: stream type `0x54` followed by the session id;
+//! - bidi streams: signal value `0x41` followed by the session id.
+//!
+//! Session datagrams are plain HTTP Datagrams whose Quarter Stream ID points
+//! at the CONNECT stream, or DATAGRAM capsules on the CONNECT stream itself.
+//! Other capsules on the CONNECT stream close or drain the session.
+//!
+//! Streams naming a session that is not (yet) established are rejected with
+//! `H3_WEBTRANSPORT_BUFFERED_STREAM_REJECTED` instead of being buffered.
+
+use alloc::borrow::Cow;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec::Vec;
+use core::fmt;
+
+use istok_core::bytes::Bytes;
+use istok_core::codec::{capsule, varint};
+use istok_core::h3::consts;
+use istok_transport::{InlineHeader, QuicCommand, StreamId, StreamKind};
+
+use crate::engine::{AppEvent, CommandSink, EngineCommand};
+use crate::h3_engine::{H3Engine, encode_frame, pop_writes};
+use crate::scheduler::WriteScheduler;
+use crate::trace;
+
+/// Upper bound on unparsed capsule bytes buffered per CONNECT stream.
+pub(crate) const MAX_CAPSULE_BUFFER: usize = 16 * 1024;
+
+/// Errors returned by [`WebTransportSession`] operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The stream is not a WebTransport stream of this session.
+    UnknownStream,
+    /// The stream is receive-only for us.
+    NotWritable,
+    /// Close reason longer than `capsule::MAX_CLOSE_REASON_LEN`.
+    ReasonTooLong,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnknownStream => write!(f, "unknown webtransport stream"),
+            Error::NotWritable => write!(f, "webtransport stream is not writable"),
+            Error::ReasonTooLong => write!(f, "close reason too long"),
+        }
+    }
+}
+
+/// Per-session bookkeeping.
+struct Session {
+    incoming_uni: VecDeque<StreamId>,
+    incoming_bi: VecDeque<StreamId>,
+    capsule_buf: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct WtStream {
+    session: StreamId,
+    kind: StreamKind,
+    local: bool,
+}
+
+impl WtStream {
+    fn writable(&self) -> bool {
+        self.kind == StreamKind::Bidi || self.local
+    }
+
+    fn readable(&self) -> bool {
+        self.kind == StreamKind::Bidi || !self.local
+    }
+}
+
+/// Peer stream whose WebTransport header has not been fully read yet.
+struct PendingStream {
+    kind: StreamKind,
+    buf: Vec<u8>,
+}
+
+/// Outcome of feeding CONNECT stream DATA into the capsule parser.
+pub(crate) enum CapsuleOutcome {
+    Open,
+    /// A CLOSE_WEBTRANSPORT_SESSION capsule ended the session.
+    Closed,
+}
+
+/// WebTransport state owned by the engine.
+pub(crate) struct WebTransportState {
+    sessions: BTreeMap<StreamId, Session>,
+    streams: BTreeMap<StreamId, WtStream>,
+    pending: BTreeMap<StreamId, PendingStream>,
+    // Local stream ids follow the numbering of the local control stream (2).
+    next_local_uni: u64,
+    next_local_bidi: u64,
+}
+
+impl WebTransportState {
+    pub(crate) fn new() -> Self {
+        Self {
+            sessions: BTreeMap::new(),
+            streams: BTreeMap::new(),
+            pending: BTreeMap::new(),
+            next_local_uni: 6,
+            next_local_bidi: 1,
+        }
+    }
+
+    /// Id for the next uni stream we open; the control stream took the first.
+    pub(crate) fn alloc_local_uni(&mut self) -> StreamId {
+        let id = StreamId(self.next_local_uni);
+        self.next_local_uni += 4;
+        id
+    }
+
+    pub(crate) fn is_session(&self, id: StreamId) -> bool {
+        self.sessions.contains_key(&id)
+    }
+
+    pub(crate) fn is_stream(&self, id: StreamId) -> bool {
+        self.streams.contains_key(&id)
+    }
+
+    pub(crate) fn is_pending(&self, id: StreamId) -> bool {
+        self.pending.contains_key(&id)
+    }
+
+    /// Number of established sessions.
+    pub(crate) fn session_count(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Unparsed capsule bytes buffered per session.
+    pub(crate) fn capsule_buffers(&self) -> impl Iterator<Item = (StreamId, usize)> + '_ {
+        self.sessions
+            .iter()
+            .map(|(id, session)| (*id, session.capsule_buf.len()))
+    }
+
+    /// Bytes buffered by all sessions and pending stream headers.
+    pub(crate) fn buffered_bytes(&self) -> usize {
+        let capsules: usize = self.capsule_buffers().map(|(_, len)| len).sum();
+        let headers: usize = self.pending.values().map(|pending| pending.buf.len()).sum();
+        capsules + headers
+    }
+
+    pub(crate) fn open_session<'a>(&mut self, id: StreamId, out: &mut dyn CommandSink<'a>) {
+        self.sessions.insert(
+            id,
+            Session {
+                incoming_uni: VecDeque::new(),
+                incoming_bi: VecDeque::new(),
+                capsule_buf: Vec::new(),
+            },
+        );
+        out.push(EngineCommand::App(AppEvent::WebTransportSession { id }));
+    }
+
+    /// Start reading the WebTransport header of peer stream `id`. For uni
+    /// streams the `0x54` stream type has already been consumed.
+    pub(crate) fn track_incoming(&mut self, id: StreamId, kind: StreamKind) {
+        self.pending.insert(
+            id,
+            PendingStream {
+                kind,
+                buf: Vec::new(),
+            },
+        );
+    }
+
+    /// Feed bytes of a pending peer stream. Once its header is complete the
+    /// stream joins its session and any remaining bytes are surfaced. A bidi
+    /// stream that does not start with the `0x41` signal value is no
+    /// WebTransport stream: it is forgotten and everything read from it
+    /// returned.
+    pub(crate) fn on_pending_data<'a>(
+        &mut self,
+        id: StreamId,
+        data: &'a [u8],
+        fin: bool,
+        out: &mut dyn CommandSink<'a>,
+    ) -> Option<Vec<u8>> {
+        let pending = self.pending.get_mut(&id)?;
+        let kind = pending.kind;
+        // Buffered bytes are always a strict prefix of the header, so the
+        // header ends inside `data`.
+        let buffered = pending.buf.len();
+        pending.buf.extend_from_slice(data);
+
+        let mut pos = 0;
+        if kind == StreamKind::Bidi {
+            match varint::decode(&pending.buf) {
+                Ok((consts::FRAME_TYPE_WEBTRANSPORT_STREAM, n)) => pos = n,
+                Ok(_) => return self.pending.remove(&id).map(|pending| pending.buf),
+                Err(_) => {
+                    if fin {
+                        return self.pending.remove(&id).map(|pending| pending.buf);
+                    }
+                    return None;
+                }
+            }
+        }
+
+        let (session, n) = match varint::decode(&pending.buf[pos..]) {
+            Ok(parsed) => parsed,
+            Err(_) => {
+                if fin {
+                    self.pending.remove(&id);
+                }
+                return None;
+            }
+        };
+        pos += n;
+        self.pending.remove(&id);
+
+        let session = StreamId(session);
+        let Some(state) = self.sessions.get_mut(&session) else {
+            reject(
+                id,
+                kind,
+                consts::H3_WEBTRANSPORT_BUFFERED_STREAM_REJECTED,
+                "no such session",
+                out,
+            );
+            return None;
+        };
+
+        match kind {
+            StreamKind::Uni => state.incoming_uni.push_back(id),
+            StreamKind::Bidi => state.incoming_bi.push_back(id),
+        }
+        self.streams.insert(
+            id,
+            WtStream {
+                session,
+                kind,
+                local: false,
+            },
+        );
+
+        let rest = &data[pos - buffered..];
+        if !rest.is_empty() || fin {
+            self.on_stream_data(id, rest, fin, out);
+        }
+        None
+    }
+
+    pub(crate) fn on_stream_data<'a>(
+        &mut self,
+        id: StreamId,
+        data: &'a [u8],
+        fin: bool,
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        let Some(stream) = self.streams.get(&id).copied() else {
+            return;
+        };
+        if !stream.readable() {
+            return;
+        }
+
+        out.push(EngineCommand::App(AppEvent::WebTransportStreamData {
+            session: stream.session,
+            id,
+            data,
+            fin,
+        }));
+
+        if fin && stream.kind == StreamKind::Uni {
+            self.streams.remove(&id);
+        }
+    }
+
+    /// Feed DATA frame payload from CONNECT stream `id` into its capsule parser.
+    ///
+    /// Returns the H3 error code to close the connection with on failure.
+    pub(crate) fn on_capsule_bytes<'a>(
+        &mut self,
+        id: StreamId,
+        bytes: &[u8],
+        scheduler: &mut WriteScheduler,
+        out: &mut dyn CommandSink<'a>,
+    ) -> Result<CapsuleOutcome, u64> {
+        let Some(session) = self.sessions.get_mut(&id) else {
+            return Ok(CapsuleOutcome::Open);
+        };
+        if session.capsule_buf.len() + bytes.len() > MAX_CAPSULE_BUFFER {
+            return Err(consts::H3_EXCESSIVE_LOAD);
+        }
+        session.capsule_buf.extend_from_slice(bytes);
+
+        loop {
+            let (capsule, consumed) = match capsule::decode_capsule(&session.capsule_buf) {
+                Ok(parsed) => parsed,
+                Err(capsule::Error::BufferTooSmall) => return Ok(CapsuleOutcome::Open),
+                Err(_) => return Err(consts::H3_MESSAGE_ERROR),
+            };
+
+            match capsule {
+                capsule::Capsule::CloseWebTransportSession { code, reason } => {
+                    let reason = reason.to_vec();
+                    out.push(EngineCommand::App(AppEvent::WebTransportSessionClosed {
+                        id,
+                        code,
+                        reason,
+                    }));
+                    self.terminate(id, scheduler, out);
+                    return Ok(CapsuleOutcome::Closed);
+                }
+                capsule::Capsule::DrainWebTransportSession => {
+                    out.push(EngineCommand::App(AppEvent::WebTransportSessionDraining {
+                        id,
+                    }));
+                }
+                capsule::Capsule::Datagram(payload) => {
+                    out.push(EngineCommand::App(AppEvent::Datagram {
+                        id,
+                        payload: Cow::Owned(payload.to_vec()),
+                    }));
+                }
+                capsule::Capsule::Unknown { .. } => {}
+            }
+            session.capsule_buf.drain(..consumed);
+        }
+    }
+
+    /// The peer finished the CONNECT stream without a CLOSE capsule.
+    pub(crate) fn on_session_fin<'a>(
+        &mut self,
+        id: StreamId,
+        scheduler: &mut WriteScheduler,
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        if !self.is_session(id) {
+            return;
+        }
+        out.push(EngineCommand::App(AppEvent::WebTransportSessionClosed {
+            id,
+            code: 0,
+            reason: Vec::new(),
+        }));
+        self.terminate(id, scheduler, out);
+    }
+
+    /// Drop session `id` and abort all of its streams with
+    /// `H3_WEBTRANSPORT_SESSION_GONE`, leaving its CONNECT stream to the caller.
+    pub(crate) fn drop_session<'a>(
+        &mut self,
+        id: StreamId,
+        scheduler: &mut WriteScheduler,
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        if self.sessions.remove(&id).is_some() {
+            self.abort_streams(id, scheduler, out);
+        }
+    }
+
+    /// Abort WebTransport stream `id` in the directions we use with
+    /// `app_error` and drop whatever is queued on it.
+    pub(crate) fn abort_stream<'a>(
+        &mut self,
+        id: StreamId,
+        app_error: u64,
+        reason: &'static str,
+        scheduler: &mut WriteScheduler,
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        let Some(stream) = self.streams.remove(&id) else {
+            return;
+        };
+        if let Some(session) = self.sessions.get_mut(&stream.session) {
+            session.incoming_uni.retain(|incoming| *incoming != id);
+            session.incoming_bi.retain(|incoming| *incoming != id);
+        }
+        abort(id, stream, app_error, reason, scheduler, out);
+    }
+
+    /// Drop session `id`, FIN our side of its CONNECT stream and abort all of
+    /// its streams with `H3_WEBTRANSPORT_SESSION_GONE`.
+    fn terminate<'a>(
+        &mut self,
+        id: StreamId,
+        scheduler: &mut WriteScheduler,
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        self.sessions.remove(&id);
+        scheduler.push(id, Vec::new(), true);
+        pop_writes(scheduler, out);
+        self.abort_streams(id, scheduler, out);
+    }
+
+    fn abort_streams<'a>(
+        &mut self,
+        session: StreamId,
+        scheduler: &mut WriteScheduler,
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        let app_error = consts::H3_WEBTRANSPORT_SESSION_GONE;
+        self.streams.retain(|&id, stream| {
+            if stream.session != session {
+                return true;
+            }
+            abort(id, *stream, app_error, "session gone", scheduler, out);
+            false
+        });
+    }
+}
+
+fn abort<'a>(
+    id: StreamId,
+    stream: WtStream,
+    app_error: u64,
+    reason: &'static str,
+    scheduler: &mut WriteScheduler,
+    out: &mut dyn CommandSink<'a>,
+) {
+    trace::reset(id, app_error, reason, None);
+    if stream.readable() {
+        out.push(EngineCommand::Quic(QuicCommand::StopSending {
+            id,
+            app_error,
+        }));
+    }
+    if stream.writable() {
+        scheduler.remove(id);
+        out.push(EngineCommand::Quic(QuicCommand::ResetStream {
+            id,
+            app_error,
+        }));
+    }
+}
+
+fn reject<'a>(
+    id: StreamId,
+    kind: StreamKind,
+    app_error: u64,
+    reason: &'static str,
+    out: &mut dyn CommandSink<'a>,
+) {
+    trace::reset(id, app_error, reason, None);
+    out.push(EngineCommand::Quic(QuicCommand::StopSending {
+        id,
+        app_error,
+    }));
+    if kind == StreamKind::Bidi {
+        out.push(EngineCommand::Quic(QuicCommand::ResetStream {
+            id,
+            app_error,
+        }));
+    }
+}
+
+/// Application handle to an established WebTransport session.
+///
+/// Obtained from [`H3Engine::webtransport_session`]. Commands produced by the
+/// handle go to the `out` sink passed to each call, like `Engine::on_event`.
+pub struct WebTransportSession<'e> {
+    engine: &'e mut H3Engine,
+    id: StreamId,
+}
+
+impl<'e> WebTransportSession<'e> {
+    pub(crate) fn new(engine: &'e mut H3Engine, id: StreamId) -> Self {
+        Self { engine, id }
+    }
+
+    /// The session id: the stream id of its CONNECT request.
+    pub fn id(&self) -> StreamId {
+        self.id
+    }
+
+    fn state(&mut self) -> Option<&mut Session> {
+        self.engine.webtransport.sessions.get_mut(&self.id)
+    }
+
+    /// Next peer-initiated uni stream of this session, if any.
+    pub fn accept_uni(&mut self) -> Option<StreamId> {
+        self.state()?.incoming_uni.pop_front()
+    }
+
+    /// Next peer-initiated bidi stream of this session, if any.
+    pub fn accept_bi(&mut self) -> Option<StreamId> {
+        self.state()?.incoming_bi.pop_front()
+    }
+
+    /// Open a uni stream and write its WebTransport stream header.
+    pub fn open_uni<'a>(&mut self, out: &mut dyn CommandSink<'a>) -> StreamId {
+        let id = self.engine.webtransport.alloc_local_uni();
+        out.push(EngineCommand::Quic(QuicCommand::OpenUni {
+            id_hint: Some(id),
+        }));
+        self.open_local(id, StreamKind::Uni, consts::STREAM_TYPE_WEBTRANSPORT, out);
+        id
+    }
+
+    /// Open a bidi stream and write its WebTransport signal header.
+    pub fn open_bi<'a>(&mut self, out: &mut dyn CommandSink<'a>) -> StreamId {
+        let wt = &mut self.engine.webtransport;
+        let id = StreamId(wt.next_local_bidi);
+        wt.next_local_bidi += 4;
+        out.push(EngineCommand::Quic(QuicCommand::OpenBidi {
+            id_hint: Some(id),
+        }));
+        self.open_local(
+            id,
+            StreamKind::Bidi,
+            consts::FRAME_TYPE_WEBTRANSPORT_STREAM,
+            out,
+        );
+        id
+    }
+
+    fn open_local<'a>(
+        &mut self,
+        id: StreamId,
+        kind: StreamKind,
+        header_ty: u64,
+        out: &mut dyn CommandSink<'a>,
+    ) {
+        let mut header = [0u8; 16];
+        let mut len = 0;
+        // Both values fit in a varint: stream ids come from QUIC and the
+        // header types are small constants.
+        if let Ok(n) = varint::encode(header_ty, &mut header) {
+            len += n;
+        }
+        if let Ok(n) = varint::encode(self.id.0, &mut header[len..]) {
+            len += n;
+        }
+
+        self.engine.webtransport.streams.insert(
+            id,
+            WtStream {
+                session: self.id,
+                kind,
+                local: true,
+            },
+        );
+        self.engine
+            .scheduler
+            .push(id, header[..len].to_vec(), false);
+        self.engine.flush_writes(out);
+    }
+
+    /// Write `data` to WebTransport stream `stream` of this session. Bytes
+    /// QUIC flow control cannot take yet are queued; see
+    /// `AppEvent::WritePaused`.
+    pub fn send<'a>(
+        &mut self,
+        stream: StreamId,
+        data: &[u8],
+        fin: bool,
+        out: &mut dyn CommandSink<'a>,
+    ) -> Result<(), Error> {
+        let wt = &mut self.engine.webtransport;
+        let state = match wt.streams.get(&stream) {
+            Some(state) if state.session == self.id => *state,
+            _ => return Err(Error::UnknownStream),
+        };
+        if !state.writable() {
+            return Err(Error::NotWritable);
+        }
+
+        if fin && state.kind == StreamKind::Uni {
+            wt.streams.remove(&stream);
+        }
+        self.engine.queue_app_write(
+            stream,
+            InlineHeader::default(),
+            Bytes::copy_from_slice(data),
+            fin,
+            out,
+        );
+        Ok(())
+    }
+
+    /// Send a session datagram. Dropped unless HTTP Datagrams were negotiated.
+    pub fn send_datagram<'a>(&mut self, payload: &[u8], out: &mut dyn CommandSink<'a>) {
+        self.engine.send_datagram(self.id, payload, out);
+    }
+
+    /// Close the session with CLOSE_WEBTRANSPORT_SESSION and FIN the CONNECT stream.
+    pub fn close<'a>(
+        self,
+        code: u32,
+        reason: &[u8],
+        out: &mut dyn CommandSink<'a>,
+    ) -> Result<(), Error> {
+        let mut buf = [0u8; 16 + capsule::MAX_CLOSE_REASON_LEN];
+        let capsule_len = capsule::encode_close_webtransport_session(code, reason, &mut buf)
+            .map_err(|_| Error::ReasonTooLong)?;
+        // A DATA frame header around at most 1 KiB always encodes.
+        let data = encode_frame(consts::FRAME_TYPE_DATA, &buf[..capsule_len]).unwrap_or_default();
+        self.engine
+            .frame_out(self.id, consts::FRAME_TYPE_DATA, capsule_len);
+
+        self.engine.end_webtransport_session(self.id);
+        self.engine.webtransport.sessions.remove(&self.id);
+        self.engine.scheduler.push(self.id, data, true);
+        self.engine.flush_writes(out);
+        let engine = &mut *self.engine;
+        engine
+            .webtransport
+            .abort_streams(self.id, &mut engine.scheduler, out);
+        Ok(())
+    }
+
+    /// Ask the peer to wind the session down with DRAIN_WEBTRANSPORT_SESSION.
+    pub fn drain<'a>(&mut self, out: &mut dyn CommandSink<'a>) {
+        let mut buf = [0u8; 16];
+        let header = capsule::CapsuleHeader {
+            ty: consts::CAPSULE_TYPE_DRAIN_WEBTRANSPORT_SESSION,
+            len: 0,
+        };
+        let Ok(len) = capsule::encode_capsule_header(header, &mut buf) else {
+            return;
+        };
+        let Ok(data) = encode_frame(consts::FRAME_TYPE_DATA, &buf[..len]) else {
+            return;
+        };
+        self.engine.frame_out(self.id, consts::FRAME_TYPE_DATA, len);
+        self.engine.scheduler.push(self.id, data, false);
+        self.engine.flush_writes(out);
+    }
+}
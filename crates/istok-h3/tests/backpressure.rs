@@ -0,0 +1,215 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::h3::consts;
+use istok_core::qpack::{self, HeaderField};
+use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
+use istok_h3::mock::{control_stream, frame};
+use istok_h3::{H3Config, H3Engine};
+use istok_transport::{StreamId, StreamKind};
+
+const PEER_CONTROL: StreamId = StreamId(3);
+const REQUEST: StreamId = StreamId(0);
+
+fn connect_request() -> Vec<u8> {
+    let fields = [
+        HeaderField {
+            name: b":method",
+            value: b"CONNECT",
+        },
+        HeaderField {
+            name: b":authority",
+            value: b"example.com:443",
+        },
+    ];
+    let mut block = [0u8; 64];
+    let len = qpack::encode(&fields, &mut block).expect("qpack encodes");
+    frame(consts::FRAME_TYPE_HEADERS, &block[..len])
+}
+
+fn write(data: &[u8], fin: bool) -> ScriptStep {
+    ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+        id: REQUEST,
+        data_prefix: data.to_vec(),
+        fin,
+    })
+}
+
+fn writable(capacity: u64) -> ScriptStep {
+    ScriptStep::InQuicWritable {
+        id: REQUEST,
+        capacity,
+    }
+}
+
+fn queued(h: &mut MockHarness<H3Engine>) -> usize {
+    h.apply(|engine, _| engine.queued_bytes(REQUEST))
+}
+
+/// Peer control stream, then the request stream opened.
+fn open_request(h: &mut MockHarness<H3Engine>) {
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: PEER_CONTROL,
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::InQuicData {
+            id: PEER_CONTROL,
+            data: control_stream(&[]),
+            fin: false,
+        },
+        ScriptStep::InQuicOpen {
+            id: REQUEST,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+/// CONNECT tunnel on `REQUEST`, accepted while QUIC takes nothing.
+fn blocked_tunnel(high_water: usize) -> MockHarness<H3Engine> {
+    let config = H3Config::builder()
+        .stream_high_water(high_water)
+        .build()
+        .expect("valid config");
+    let mut h = MockHarness::new(H3Engine::new(config));
+    open_request(&mut h);
+    h.run_script(&[
+        writable(0),
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: connect_request(),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::AppConnectTunnel {
+            id: REQUEST,
+            authority: b"example.com:443".to_vec(),
+        }),
+        ScriptStep::InAppAcceptTunnel { id: REQUEST },
+        ScriptStep::ExpectNone,
+    ]);
+    assert_eq!(queued(&mut h), 5, "response HEADERS wait for credit");
+    h
+}
+
+#[test]
+fn response_is_released_as_credit_arrives() {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_request(&mut h);
+    h.run_script(&[
+        writable(2),
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: frame(
+                consts::FRAME_TYPE_HEADERS,
+                &[0x00, 0x00, 0xd1, 0xd7, 0xc1, 0x50, 0x01, b'a'],
+            ),
+            fin: true,
+        },
+        write(&[0x01, 0x03], false),
+        ScriptStep::ExpectNone,
+    ]);
+    assert_eq!(queued(&mut h), 6);
+
+    h.run_script(&[
+        writable(4),
+        write(&[0x00, 0x00, 0xd9], false),
+        write(&[0x00], false),
+        ScriptStep::ExpectNone,
+    ]);
+    assert_eq!(queued(&mut h), 2);
+
+    h.run_script(&[
+        writable(10),
+        write(&[0x01, 0x01], true),
+        ScriptStep::ExpectNone,
+    ]);
+    assert_eq!(queued(&mut h), 0);
+}
+
+#[test]
+fn capacity_for_unknown_streams_is_ignored() {
+    let mut h = MockHarness::new(H3Engine::default());
+    h.run_script(&[
+        ScriptStep::InQuicWritable {
+            id: StreamId(40),
+            capacity: 100,
+        },
+        ScriptStep::ExpectNone,
+    ]);
+    assert_eq!(h.apply(|engine, _| engine.queued_bytes(StreamId(40))), 0);
+}
+
+#[test]
+fn producer_pauses_above_high_water_and_resumes_at_half() {
+    let mut h = blocked_tunnel(16);
+    let first = frame(consts::FRAME_TYPE_DATA, b"0123456789abcdef");
+    h.run_script(&[
+        ScriptStep::InAppTunnelSend {
+            id: REQUEST,
+            data: b"0123456789abcdef".to_vec(),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::AppWritePaused { id: REQUEST }),
+        ScriptStep::ExpectNone,
+        // Sent while paused: still queued, no second pause.
+        ScriptStep::InAppTunnelSend {
+            id: REQUEST,
+            data: b"x".to_vec(),
+            fin: true,
+        },
+        ScriptStep::ExpectNone,
+    ]);
+    assert_eq!(queued(&mut h), 5 + 18 + 3);
+
+    // 26 -> 9 queued: above half the high-water mark.
+    h.run_script(&[
+        writable(17),
+        write(&[0x01, 0x03, 0x00, 0x00, 0xd9], false),
+        write(&first[..12], false),
+        ScriptStep::ExpectNone,
+    ]);
+    // 9 -> 8 queued: resumed.
+    h.run_script(&[
+        writable(1),
+        write(&first[12..13], false),
+        ScriptStep::Expect(ExpectCommand::AppWriteResumed { id: REQUEST }),
+        ScriptStep::ExpectNone,
+        writable(64),
+        write(&first[13..], false),
+        write(&frame(consts::FRAME_TYPE_DATA, b"x"), true),
+        ScriptStep::ExpectNone,
+    ]);
+    assert_eq!(queued(&mut h), 0);
+}
+
+#[test]
+fn reset_drops_queued_bytes() {
+    let mut h = blocked_tunnel(1024);
+    h.run_script(&[
+        ScriptStep::InAppTunnelSend {
+            id: REQUEST,
+            data: b"pong".to_vec(),
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InAppResetStream {
+            id: REQUEST,
+            app_error: consts::H3_CONNECT_ERROR,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStopSending {
+            id: REQUEST,
+            app_error: consts::H3_CONNECT_ERROR,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicResetStream {
+            id: REQUEST,
+            app_error: consts::H3_CONNECT_ERROR,
+        }),
+        ScriptStep::ExpectNone,
+        writable(64),
+        ScriptStep::ExpectNone,
+    ]);
+    assert_eq!(queued(&mut h), 0);
+}
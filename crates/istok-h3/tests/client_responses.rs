@@ -0,0 +1,250 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::h3::consts;
+use istok_core::qpack::HeaderField;
+use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
+use istok_h3::mock::{control_stream, frame, headers};
+use istok_h3::{H3Config, H3Engine, Role};
+use istok_transport::{StreamId, StreamKind};
+
+const PEER_CONTROL: StreamId = StreamId(3);
+const REQUEST: StreamId = StreamId(0);
+
+const GET: [(&[u8], &[u8]); 4] = [
+    (b":method", b"GET"),
+    (b":scheme", b"https"),
+    (b":authority", b"example.com"),
+    (b":path", b"/"),
+];
+
+fn owned(fields: &[(&[u8], &[u8])]) -> Vec<(Vec<u8>, Vec<u8>)> {
+    fields
+        .iter()
+        .map(|(name, value)| (name.to_vec(), value.to_vec()))
+        .collect()
+}
+
+fn block(fields: &[(&[u8], &[u8])]) -> Vec<u8> {
+    let fields: Vec<HeaderField<'_>> = fields
+        .iter()
+        .map(|&(name, value)| HeaderField { name, value })
+        .collect();
+    headers(&fields)
+}
+
+fn client() -> MockHarness<H3Engine> {
+    let config = H3Config::builder()
+        .role(Role::Client)
+        .build()
+        .expect("valid config");
+    let mut h = MockHarness::new(H3Engine::new(config));
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: PEER_CONTROL,
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::InQuicData {
+            id: PEER_CONTROL,
+            data: control_stream(&[]),
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+    ]);
+    h
+}
+
+/// Send a GET on `id` and expect its HEADERS to go out with FIN.
+fn send_get(id: StreamId) -> [ScriptStep; 2] {
+    [
+        ScriptStep::InAppSendRequest {
+            id,
+            fields: owned(&GET),
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id,
+            data_prefix: block(&GET),
+            fin: true,
+        }),
+    ]
+}
+
+fn response(data: Vec<u8>, fin: bool) -> ScriptStep {
+    ScriptStep::InQuicData {
+        id: REQUEST,
+        data,
+        fin,
+    }
+}
+
+fn aborted(app_error: u64) -> [ScriptStep; 3] {
+    [
+        ScriptStep::Expect(ExpectCommand::QuicStopSending {
+            id: REQUEST,
+            app_error,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicResetStream {
+            id: REQUEST,
+            app_error,
+        }),
+        ScriptStep::Expect(ExpectCommand::AppStreamAborted {
+            id: REQUEST,
+            app_error,
+        }),
+    ]
+}
+
+#[test]
+fn interim_and_final_responses_are_reported_separately() {
+    let mut h = client();
+    let mut script = Vec::from(send_get(REQUEST));
+    script.extend([
+        response(
+            [
+                block(&[(b":status", b"103"), (b"link", b"</a.css>; rel=preload")]),
+                block(&[(b":status", b"200"), (b"content-length", b"2")]),
+            ]
+            .concat(),
+            false,
+        ),
+        ScriptStep::Expect(ExpectCommand::AppInterimResponse {
+            id: REQUEST,
+            status: 103,
+            fields: owned(&[(b"link", b"</a.css>; rel=preload")]),
+        }),
+        ScriptStep::Expect(ExpectCommand::AppResponse {
+            id: REQUEST,
+            status: 200,
+            fields: owned(&[(b"content-length", b"2")]),
+        }),
+        ScriptStep::ExpectNone,
+        response(frame(consts::FRAME_TYPE_DATA, b"ok"), true),
+        ScriptStep::Expect(ExpectCommand::AppResponseData {
+            id: REQUEST,
+            data: b"ok".to_vec(),
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::AppResponseData {
+            id: REQUEST,
+            data: Vec::new(),
+            fin: true,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+    h.run_script(&script);
+}
+
+#[test]
+fn responses_to_concurrent_requests_are_read_independently() {
+    let second = StreamId(4);
+    let mut h = client();
+    let mut script = Vec::from(send_get(REQUEST));
+    script.extend(send_get(second));
+    script.extend([
+        ScriptStep::InQuicData {
+            id: second,
+            data: block(&[(b":status", b"204")]),
+            fin: true,
+        },
+        ScriptStep::Expect(ExpectCommand::AppResponse {
+            id: second,
+            status: 204,
+            fields: Vec::new(),
+        }),
+        ScriptStep::Expect(ExpectCommand::AppResponseData {
+            id: second,
+            data: Vec::new(),
+            fin: true,
+        }),
+        response(block(&[(b":status", b"204")]), true),
+        ScriptStep::Expect(ExpectCommand::AppResponse {
+            id: REQUEST,
+            status: 204,
+            fields: Vec::new(),
+        }),
+        ScriptStep::Expect(ExpectCommand::AppResponseData {
+            id: REQUEST,
+            data: Vec::new(),
+            fin: true,
+        }),
+        // A stream carries one request.
+        ScriptStep::InAppSendRequest {
+            id: StreamId(8),
+            fields: owned(&GET),
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: StreamId(8),
+            data_prefix: block(&GET),
+            fin: true,
+        }),
+        ScriptStep::InAppSendRequest {
+            id: StreamId(8),
+            fields: owned(&GET),
+        },
+        ScriptStep::Expect(ExpectCommand::QuicResetStream {
+            id: StreamId(8),
+            app_error: consts::H3_REQUEST_CANCELLED,
+        }),
+        ScriptStep::Expect(ExpectCommand::AppStreamAborted {
+            id: StreamId(8),
+            app_error: consts::H3_REQUEST_CANCELLED,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+    h.run_script(&script);
+}
+
+#[test]
+fn malformed_responses_are_message_errors() {
+    let malformed: [&[(&[u8], &[u8])]; 4] = [
+        &[(b"content-type", b"text/plain")],
+        &[(b":status", b"200"), (b":status", b"200")],
+        &[(b":status", b"20x")],
+        &[(b":status", b"101")],
+    ];
+    for fields in malformed {
+        let mut h = client();
+        let mut script = Vec::from(send_get(REQUEST));
+        script.push(response(block(fields), false));
+        script.extend(aborted(consts::H3_MESSAGE_ERROR));
+        script.push(ScriptStep::ExpectNone);
+        h.run_script(&script);
+    }
+}
+
+#[test]
+fn response_body_beyond_content_length_is_message_error() {
+    let mut h = client();
+    let mut script = Vec::from(send_get(REQUEST));
+    script.extend([
+        response(
+            [
+                block(&[(b":status", b"200"), (b"content-length", b"1")]),
+                frame(consts::FRAME_TYPE_DATA, b"ok"),
+            ]
+            .concat(),
+            true,
+        ),
+        ScriptStep::Expect(ExpectCommand::AppResponse {
+            id: REQUEST,
+            status: 200,
+            fields: owned(&[(b"content-length", b"1")]),
+        }),
+    ]);
+    script.extend(aborted(consts::H3_MESSAGE_ERROR));
+    script.push(ScriptStep::ExpectNone);
+    h.run_script(&script);
+}
+
+#[test]
+fn server_ignores_send_request() {
+    let mut h = MockHarness::new(H3Engine::default());
+    h.run_script(&[
+        ScriptStep::InAppSendRequest {
+            id: REQUEST,
+            fields: owned(&GET),
+        },
+        ScriptStep::ExpectNone,
+    ]);
+}
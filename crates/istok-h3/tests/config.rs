@@ -0,0 +1,446 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::codec::varint;
+use istok_core::h3::consts;
+use istok_core::h3::settings::{self, Settings};
+use istok_core::qpack::{self, HeaderField};
+use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
+use istok_h3::mock::{control_stream, frame, status, varint_bytes};
+use istok_h3::{ConfigError, DosLimits, H3Config, H3Engine, Role};
+use istok_transport::{StreamId, StreamKind};
+
+const LOCAL_CONTROL: StreamId = StreamId(2);
+const PEER_CONTROL: StreamId = StreamId(3);
+const REQUEST: StreamId = StreamId(0);
+
+/// GET with an extra `x-pad` field of `pad` bytes.
+fn get(pad: usize) -> Vec<u8> {
+    let pad = alloc::vec![b'p'; pad];
+    let fields = [
+        HeaderField {
+            name: b":method",
+            value: b"GET",
+        },
+        HeaderField {
+            name: b":scheme",
+            value: b"https",
+        },
+        HeaderField {
+            name: b":authority",
+            value: b"example.com",
+        },
+        HeaderField {
+            name: b":path",
+            value: b"/",
+        },
+        HeaderField {
+            name: b"x-pad",
+            value: &pad,
+        },
+    ];
+    let mut block = alloc::vec![0u8; 256 + pad.len()];
+    let len = qpack::encode(&fields, &mut block).expect("qpack encodes");
+    frame(consts::FRAME_TYPE_HEADERS, &block[..len])
+}
+
+fn engine(config: Result<H3Config, ConfigError>) -> MockHarness<H3Engine> {
+    MockHarness::new(H3Engine::new(config.expect("valid config")))
+}
+
+fn open_peer_control(h: &mut MockHarness<H3Engine>, frames: &[u8], expect: &[ScriptStep]) {
+    let mut data = control_stream(&[]);
+    data.extend_from_slice(frames);
+    let mut script = alloc::vec![
+        ScriptStep::InQuicOpen {
+            id: PEER_CONTROL,
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::InQuicData {
+            id: PEER_CONTROL,
+            data,
+            fin: false,
+        },
+    ];
+    script.extend_from_slice(expect);
+    script.push(ScriptStep::ExpectNone);
+    h.run_script(&script);
+}
+
+fn send_request(h: &mut MockHarness<H3Engine>, id: StreamId, data: Vec<u8>, expect: &[ScriptStep]) {
+    let mut script = alloc::vec![
+        ScriptStep::InQuicOpen {
+            id,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::InQuicData {
+            id,
+            data,
+            fin: true,
+        },
+    ];
+    script.extend_from_slice(expect);
+    script.push(ScriptStep::ExpectNone);
+    h.run_script(&script);
+}
+
+fn ok_response() -> [ScriptStep; 2] {
+    [
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00, 0xd9]),
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: frame(consts::FRAME_TYPE_DATA, &[0x01]),
+            fin: true,
+        }),
+    ]
+}
+
+fn rejected(id: StreamId, app_error: u64) -> [ScriptStep; 2] {
+    [
+        ScriptStep::Expect(ExpectCommand::QuicStopSending { id, app_error }),
+        ScriptStep::Expect(ExpectCommand::QuicResetStream { id, app_error }),
+    ]
+}
+
+fn max_push_id(push_id: u64) -> Vec<u8> {
+    frame(consts::FRAME_TYPE_MAX_PUSH_ID, &varint_bytes(push_id))
+}
+
+#[test]
+fn defaults_match_previous_limits() {
+    let config = H3Config::default();
+    assert_eq!(H3Config::builder().build(), Ok(config.clone()));
+    assert_eq!(config.role(), Role::Server);
+    assert_eq!(config.settings(), &Settings::new());
+    assert_eq!(config.max_header_frame_size(), 16 * 1024);
+    assert_eq!(config.max_request_buffer(), 16 * 1024 + 16);
+    assert_eq!(config.max_concurrent_streams(), 100);
+    assert_eq!(config.idle_timeout_ms(), None);
+    assert_eq!(config.header_read_timeout_ms(), None);
+    assert_eq!(config.body_idle_timeout_ms(), None);
+    assert_eq!(config.grease(), None);
+    assert_eq!(config.stream_high_water(), 64 * 1024);
+    assert_eq!(config.memory_budget(), 1024 * 1024);
+    assert_eq!(config.dos_limits(), &DosLimits::default());
+    assert!(!config.push_enabled());
+    assert!(!config.extended_connect_enabled());
+}
+
+#[test]
+fn invalid_options_are_rejected() {
+    let cases = [
+        (
+            H3Config::builder().max_concurrent_streams(0),
+            ConfigError::NoConcurrentStreams,
+        ),
+        (
+            H3Config::builder().stream_high_water(0),
+            ConfigError::NoStreamHighWater,
+        ),
+        (
+            H3Config::builder().max_header_frame_size(0),
+            ConfigError::NoHeaderFrameSize,
+        ),
+        (
+            H3Config::builder()
+                .max_header_frame_size(1024)
+                .max_request_buffer(1024),
+            ConfigError::RequestBufferTooSmall,
+        ),
+        (
+            H3Config::builder().memory_budget(16 * 1024),
+            ConfigError::MemoryBudgetTooSmall,
+        ),
+        (
+            H3Config::builder().idle_timeout_ms(Some(0)),
+            ConfigError::ZeroTimeout,
+        ),
+        (
+            H3Config::builder().header_read_timeout_ms(Some(0)),
+            ConfigError::ZeroTimeout,
+        ),
+        (
+            H3Config::builder().body_idle_timeout_ms(Some(0)),
+            ConfigError::ZeroTimeout,
+        ),
+        (
+            H3Config::builder().settings(Settings {
+                h3_datagram: Some(2),
+                ..Settings::default()
+            }),
+            ConfigError::InvalidSettings(settings::Error::InvalidValue {
+                id: consts::SETTINGS_H3_DATAGRAM,
+                value: 2,
+            }),
+        ),
+        (
+            H3Config::builder().max_field_section_size(u64::MAX),
+            ConfigError::InvalidSettings(settings::Error::VarInt(
+                varint::VarIntError::ValueTooLarge,
+            )),
+        ),
+        (
+            H3Config::builder()
+                .settings(Settings {
+                    enable_connect_protocol: Some(1),
+                    h3_datagram: Some(1),
+                    webtransport_max_sessions: Some(1),
+                    ..Settings::default()
+                })
+                .enable_extended_connect(false),
+            ConfigError::WebTransportRequirements,
+        ),
+    ];
+    for (builder, err) in cases {
+        assert_eq!(builder.build(), Err(err));
+    }
+}
+
+#[test]
+fn boot_advertises_configured_settings() {
+    let config = H3Config::builder()
+        .settings(Settings {
+            h3_datagram: Some(1),
+            ..Settings::default()
+        })
+        .max_field_section_size(4096)
+        .enable_extended_connect(true)
+        .build();
+    assert!(
+        config
+            .as_ref()
+            .is_ok_and(H3Config::extended_connect_enabled)
+    );
+
+    engine(config).run_script(&[
+        ScriptStep::InBoot,
+        ScriptStep::Expect(ExpectCommand::QuicOpenUni),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: LOCAL_CONTROL,
+            data_prefix: control_stream(&[0x06, 0x50, 0x00, 0x08, 0x01, 0x33, 0x01]),
+            fin: false,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn oversized_field_section_gets_431() {
+    let config = || H3Config::builder().max_field_section_size(400).build();
+
+    // 314 bytes with a 100-byte `x-pad`, 414 with a 200-byte one.
+    let mut h = engine(config());
+    open_peer_control(&mut h, &[], &[]);
+    send_request(&mut h, REQUEST, get(100), &ok_response());
+
+    let mut h = engine(config());
+    open_peer_control(&mut h, &[], &[]);
+    send_request(
+        &mut h,
+        REQUEST,
+        get(200),
+        &[
+            ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+                id: REQUEST,
+                data_prefix: status(b"431"),
+                fin: true,
+            }),
+            ScriptStep::Expect(ExpectCommand::QuicStopSending {
+                id: REQUEST,
+                app_error: consts::H3_NO_ERROR,
+            }),
+        ],
+    );
+}
+
+#[test]
+fn header_frame_size_caps_request_headers() {
+    let config = || {
+        H3Config::builder()
+            .max_header_frame_size(64)
+            .max_request_buffer(256)
+            .build()
+    };
+
+    let mut h = engine(config());
+    open_peer_control(&mut h, &[], &[]);
+    send_request(&mut h, REQUEST, get(8), &ok_response());
+
+    let mut h = engine(config());
+    open_peer_control(&mut h, &[], &[]);
+    send_request(
+        &mut h,
+        REQUEST,
+        get(64),
+        &[ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+            app_error: consts::H3_FRAME_ERROR,
+        })],
+    );
+}
+
+#[test]
+fn request_buffer_caps_early_request_bytes() {
+    let mut h = engine(
+        H3Config::builder()
+            .max_header_frame_size(64)
+            .max_request_buffer(100)
+            .build(),
+    );
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: REQUEST,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: alloc::vec![0x42; 100],
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: alloc::vec![0x42],
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+            app_error: consts::H3_FRAME_ERROR,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn streams_beyond_the_limit_are_refused() {
+    let mut h = engine(H3Config::builder().max_concurrent_streams(2).build());
+    open_peer_control(&mut h, &[], &[]);
+    let open = |id| ScriptStep::InQuicOpen {
+        id: StreamId(id),
+        kind: StreamKind::Bidi,
+    };
+    let second = StreamId(4);
+
+    let mut script = alloc::vec![open(0), open(4), ScriptStep::ExpectNone, open(8)];
+    script.extend_from_slice(&rejected(StreamId(8), consts::H3_REQUEST_REJECTED));
+    // Both open streams are served; finishing one frees its slot.
+    script.extend_from_slice(&[
+        ScriptStep::InQuicData {
+            id: second,
+            data: get(0),
+            fin: true,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: second,
+            data_prefix: frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00, 0xd9]),
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: second,
+            data_prefix: frame(consts::FRAME_TYPE_DATA, &[0x01]),
+            fin: true,
+        }),
+        open(12),
+        ScriptStep::ExpectNone,
+        open(16),
+    ]);
+    script.extend_from_slice(&rejected(StreamId(16), consts::H3_REQUEST_REJECTED));
+    script.push(ScriptStep::InQuicData {
+        id: REQUEST,
+        data: get(0),
+        fin: true,
+    });
+    script.extend_from_slice(&ok_response());
+    script.push(ScriptStep::ExpectNone);
+    h.run_script(&script);
+}
+
+#[test]
+fn push_enabled_server_tracks_max_push_id() {
+    let mut h = engine(H3Config::builder().enable_push(true).build());
+    let mut frames = max_push_id(3);
+    frames.extend_from_slice(&max_push_id(7));
+    open_peer_control(&mut h, &frames, &[]);
+    assert_eq!(h.apply(|engine, _| engine.peer_max_push_id()), Some(7));
+
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: PEER_CONTROL,
+            data: max_push_id(5),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+            app_error: consts::H3_ID_ERROR,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn push_disabled_server_ignores_max_push_id() {
+    let mut h = engine(H3Config::builder().build());
+    let mut frames = max_push_id(7);
+    frames.extend_from_slice(&max_push_id(3));
+    open_peer_control(&mut h, &frames, &[]);
+    assert_eq!(h.apply(|engine, _| engine.peer_max_push_id()), None);
+}
+
+#[test]
+fn client_rejects_max_push_id() {
+    let mut h = engine(H3Config::builder().role(Role::Client).build());
+    open_peer_control(
+        &mut h,
+        &max_push_id(0),
+        &[ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+            app_error: consts::H3_FRAME_UNEXPECTED,
+        })],
+    );
+}
+
+#[test]
+fn extended_connect_option_overrides_settings() {
+    let mut h = engine(
+        H3Config::builder()
+            .settings(Settings {
+                enable_connect_protocol: Some(1),
+                ..Settings::default()
+            })
+            .enable_extended_connect(false)
+            .build(),
+    );
+    open_peer_control(&mut h, &[], &[]);
+
+    let fields = [
+        HeaderField {
+            name: b":method",
+            value: b"CONNECT",
+        },
+        HeaderField {
+            name: b":protocol",
+            value: b"webtransport",
+        },
+        HeaderField {
+            name: b":scheme",
+            value: b"https",
+        },
+        HeaderField {
+            name: b":authority",
+            value: b"example.com",
+        },
+        HeaderField {
+            name: b":path",
+            value: b"/wt",
+        },
+    ];
+    let mut block = [0u8; 128];
+    let len = qpack::encode(&fields, &mut block).expect("qpack encodes");
+    send_request(
+        &mut h,
+        REQUEST,
+        frame(consts::FRAME_TYPE_HEADERS, &block[..len]),
+        &rejected(REQUEST, consts::H3_MESSAGE_ERROR),
+    );
+}
@@ -0,0 +1,476 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::bytes::Bytes;
+use istok_core::h3::consts;
+use istok_core::h3::settings::Settings;
+use istok_core::qpack::HeaderField;
+use istok_h3::engine::CommandSink;
+use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
+use istok_h3::mock::{control_stream, frame, headers, settings_payload};
+use istok_h3::{AppAction, Engine, EngineCommand, EngineEvent, H3Engine};
+use istok_transport::{InlineHeader, QuicCommand, StreamError, StreamId, StreamKind};
+
+const TUNNEL: StreamId = StreamId(0);
+
+fn connect_request(extra: &[HeaderField<'_>]) -> Vec<u8> {
+    let mut fields = alloc::vec![
+        HeaderField {
+            name: b":method",
+            value: b"CONNECT",
+        },
+        HeaderField {
+            name: b":authority",
+            value: b"example.com:443",
+        },
+    ];
+    fields.extend_from_slice(extra);
+    headers(&fields)
+}
+
+/// Peer control stream + CONNECT request, followed by `expect`.
+fn open_tunnel_stream(h: &mut MockHarness<H3Engine>, request: Vec<u8>, expect: &[ScriptStep]) {
+    let control = control_stream(&settings_payload(&Settings::default()));
+
+    let mut script = alloc::vec![
+        ScriptStep::InQuicOpen {
+            id: StreamId(3),
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: StreamId(3),
+            data: control,
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicOpen {
+            id: TUNNEL,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: TUNNEL,
+            data: request,
+            fin: false,
+        },
+    ];
+    script.extend_from_slice(expect);
+    h.run_script(&script);
+}
+
+fn accepted() -> MockHarness<H3Engine> {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_tunnel_stream(
+        &mut h,
+        connect_request(&[]),
+        &[
+            ScriptStep::Expect(ExpectCommand::AppConnectTunnel {
+                id: TUNNEL,
+                authority: b"example.com:443".to_vec(),
+            }),
+            ScriptStep::ExpectNone,
+            ScriptStep::InAppAcceptTunnel { id: TUNNEL },
+            ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+                id: TUNNEL,
+                data_prefix: alloc::vec![0x01, 0x03, 0x00, 0x00, 0xd9],
+                fin: false,
+            }),
+            ScriptStep::ExpectNone,
+        ],
+    );
+    h
+}
+
+#[test]
+fn connect_relays_bytes_both_ways() {
+    let mut h = accepted();
+    let mut data = frame(consts::FRAME_TYPE_DATA, b"ping");
+    data.extend_from_slice(&frame(consts::FRAME_TYPE_DATA, b"!"));
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: TUNNEL,
+            data,
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::AppTunnelData {
+            id: TUNNEL,
+            data: b"ping".to_vec(),
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::AppTunnelData {
+            id: TUNNEL,
+            data: b"!".to_vec(),
+            fin: false,
+        }),
+        ScriptStep::ExpectNone,
+        ScriptStep::InAppTunnelSend {
+            id: TUNNEL,
+            data: b"pong".to_vec(),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: TUNNEL,
+            data_prefix: frame(consts::FRAME_TYPE_DATA, b"pong"),
+            fin: false,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn connect_tunnel_half_closes_in_each_direction() {
+    let mut h = accepted();
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: TUNNEL,
+            data: Vec::new(),
+            fin: true,
+        },
+        ScriptStep::Expect(ExpectCommand::AppTunnelData {
+            id: TUNNEL,
+            data: Vec::new(),
+            fin: true,
+        }),
+        ScriptStep::ExpectNone,
+        // The target may keep talking after the peer's FIN.
+        ScriptStep::InAppTunnelSend {
+            id: TUNNEL,
+            data: b"bye".to_vec(),
+            fin: true,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: TUNNEL,
+            data_prefix: frame(consts::FRAME_TYPE_DATA, b"bye"),
+            fin: true,
+        }),
+        ScriptStep::ExpectNone,
+        ScriptStep::InAppTunnelSend {
+            id: TUNNEL,
+            data: b"late".to_vec(),
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn peer_data_before_accept_is_relayed_but_sends_are_held() {
+    let mut h = MockHarness::new(H3Engine::default());
+    let mut request = connect_request(&[]);
+    request.extend_from_slice(&frame(consts::FRAME_TYPE_DATA, b"early"));
+    open_tunnel_stream(
+        &mut h,
+        request,
+        &[
+            ScriptStep::Expect(ExpectCommand::AppConnectTunnel {
+                id: TUNNEL,
+                authority: b"example.com:443".to_vec(),
+            }),
+            ScriptStep::Expect(ExpectCommand::AppTunnelData {
+                id: TUNNEL,
+                data: b"early".to_vec(),
+                fin: false,
+            }),
+            ScriptStep::ExpectNone,
+            ScriptStep::InAppTunnelSend {
+                id: TUNNEL,
+                data: b"too soon".to_vec(),
+                fin: false,
+            },
+            ScriptStep::ExpectNone,
+        ],
+    );
+}
+
+#[test]
+fn headers_after_connect_is_frame_unexpected() {
+    let mut h = accepted();
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: TUNNEL,
+            data: frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00]),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+            app_error: consts::H3_FRAME_UNEXPECTED,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn connect_with_path_or_scheme_is_malformed() {
+    for extra in [
+        HeaderField {
+            name: b":path",
+            value: b"/",
+        },
+        HeaderField {
+            name: b":scheme",
+            value: b"https",
+        },
+    ] {
+        let mut h = MockHarness::new(H3Engine::default());
+        open_tunnel_stream(
+            &mut h,
+            connect_request(&[extra]),
+            &[
+                ScriptStep::Expect(ExpectCommand::QuicStopSending {
+                    id: TUNNEL,
+                    app_error: consts::H3_MESSAGE_ERROR,
+                }),
+                ScriptStep::Expect(ExpectCommand::QuicResetStream {
+                    id: TUNNEL,
+                    app_error: consts::H3_MESSAGE_ERROR,
+                }),
+                ScriptStep::ExpectNone,
+            ],
+        );
+    }
+}
+
+#[test]
+fn connect_without_authority_is_malformed() {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_tunnel_stream(
+        &mut h,
+        headers(&[HeaderField {
+            name: b":method",
+            value: b"CONNECT",
+        }]),
+        &[
+            ScriptStep::Expect(ExpectCommand::QuicStopSending {
+                id: TUNNEL,
+                app_error: consts::H3_MESSAGE_ERROR,
+            }),
+            ScriptStep::Expect(ExpectCommand::QuicResetStream {
+                id: TUNNEL,
+                app_error: consts::H3_MESSAGE_ERROR,
+            }),
+            ScriptStep::ExpectNone,
+        ],
+    );
+}
+
+#[test]
+fn app_reset_aborts_tunnel_with_connect_error() {
+    let mut h = accepted();
+    h.run_script(&[
+        ScriptStep::InAppResetStream {
+            id: TUNNEL,
+            app_error: consts::H3_CONNECT_ERROR,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStopSending {
+            id: TUNNEL,
+            app_error: consts::H3_CONNECT_ERROR,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicResetStream {
+            id: TUNNEL,
+            app_error: consts::H3_CONNECT_ERROR,
+        }),
+        ScriptStep::ExpectNone,
+        ScriptStep::InAppTunnelSend {
+            id: TUNNEL,
+            data: b"gone".to_vec(),
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn peer_reset_is_reported_to_app() {
+    let mut h = accepted();
+    h.run_script(&[
+        ScriptStep::InQuicStreamError {
+            id: TUNNEL,
+            err: StreamError::Reset(consts::H3_CONNECT_ERROR),
+        },
+        ScriptStep::Expect(ExpectCommand::AppTunnelReset {
+            id: TUNNEL,
+            app_error: consts::H3_CONNECT_ERROR,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStopSending {
+            id: TUNNEL,
+            app_error: consts::H3_CONNECT_ERROR,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicResetStream {
+            id: TUNNEL,
+            app_error: consts::H3_CONNECT_ERROR,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+/// Collects the vectored writes the engine issues.
+#[derive(Default)]
+struct Vectored(Vec<(InlineHeader, Bytes, bool)>);
+
+impl<'a> CommandSink<'a> for Vectored {
+    fn push(&mut self, cmd: EngineCommand<'a>) {
+        if let EngineCommand::Quic(QuicCommand::StreamWriteVectored {
+            header, body, fin, ..
+        }) = cmd
+        {
+            self.0.push((header, body, fin));
+        }
+    }
+}
+
+#[test]
+fn tunnel_bytes_are_framed_without_copying() {
+    let mut h = accepted();
+    let body = Bytes::from(alloc::vec![0xab; 1000]);
+    let writes = h.apply(|engine, _| {
+        let mut sink = Vectored::default();
+        engine.on_event(
+            EngineEvent::App(AppAction::TunnelSend {
+                id: TUNNEL,
+                data: body.clone(),
+                fin: true,
+            }),
+            &mut sink,
+        );
+        sink.0
+    });
+
+    let [(header, sent, fin)] = writes.as_slice() else {
+        panic!("expected one vectored write, got {writes:?}");
+    };
+    assert_eq!(
+        header.as_slice(),
+        &frame(consts::FRAME_TYPE_DATA, &body)[..3]
+    );
+    assert_eq!(sent.as_ptr(), body.as_ptr(), "the application's buffer");
+    assert_eq!(sent.len(), 1000);
+    assert!(*fin);
+}
+
+#[test]
+fn two_tunnels_run_alongside_a_request() {
+    let second = StreamId(4);
+    let request = StreamId(8);
+    let mut h = accepted();
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: second,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::InQuicData {
+            id: second,
+            data: headers(&[
+                HeaderField {
+                    name: b":method",
+                    value: b"CONNECT",
+                },
+                HeaderField {
+                    name: b":authority",
+                    value: b"example.net:22",
+                },
+            ]),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::AppConnectTunnel {
+            id: second,
+            authority: b"example.net:22".to_vec(),
+        }),
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicOpen {
+            id: request,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::InQuicData {
+            id: request,
+            data: headers(&[
+                HeaderField {
+                    name: b":method",
+                    value: b"GET",
+                },
+                HeaderField {
+                    name: b":scheme",
+                    value: b"https",
+                },
+                HeaderField {
+                    name: b":authority",
+                    value: b"proxy.example",
+                },
+                HeaderField {
+                    name: b":path",
+                    value: b"/",
+                },
+            ]),
+            fin: true,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: request,
+            data_prefix: headers(&[HeaderField {
+                name: b":status",
+                value: b"200",
+            }]),
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: request,
+            data_prefix: frame(consts::FRAME_TYPE_DATA, &[0x01]),
+            fin: true,
+        }),
+        ScriptStep::ExpectNone,
+        ScriptStep::InAppAcceptTunnel { id: second },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: second,
+            data_prefix: alloc::vec![0x01, 0x03, 0x00, 0x00, 0xd9],
+            fin: false,
+        }),
+        ScriptStep::ExpectNone,
+        // Each tunnel relays its own stream's bytes.
+        ScriptStep::InQuicData {
+            id: second,
+            data: frame(consts::FRAME_TYPE_DATA, b"ssh"),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::AppTunnelData {
+            id: second,
+            data: b"ssh".to_vec(),
+            fin: false,
+        }),
+        ScriptStep::InQuicData {
+            id: TUNNEL,
+            data: frame(consts::FRAME_TYPE_DATA, b"tls"),
+            fin: true,
+        },
+        ScriptStep::Expect(ExpectCommand::AppTunnelData {
+            id: TUNNEL,
+            data: b"tls".to_vec(),
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::AppTunnelData {
+            id: TUNNEL,
+            data: Vec::new(),
+            fin: true,
+        }),
+        ScriptStep::ExpectNone,
+        ScriptStep::InAppTunnelSend {
+            id: second,
+            data: b"banner".to_vec(),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: second,
+            data_prefix: frame(consts::FRAME_TYPE_DATA, b"banner"),
+            fin: false,
+        }),
+        ScriptStep::InAppTunnelSend {
+            id: TUNNEL,
+            data: b"hello".to_vec(),
+            fin: true,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: TUNNEL,
+            data_prefix: frame(consts::FRAME_TYPE_DATA, b"hello"),
+            fin: true,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
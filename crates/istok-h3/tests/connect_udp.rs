@@ -0,0 +1,467 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::codec::capsule;
+use istok_core::h3::consts;
+use istok_core::h3::settings::Settings;
+use istok_core::qpack::HeaderField;
+use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
+use istok_h3::mock::{control_stream, frame, headers, settings_payload};
+use istok_h3::{H3Config, H3Engine};
+use istok_transport::{StreamId, StreamKind};
+
+const TUNNEL: StreamId = StreamId(0);
+
+fn engine_with(settings: Settings) -> H3Engine {
+    H3Engine::new(
+        H3Config::builder()
+            .settings(settings)
+            .build()
+            .expect("valid config"),
+    )
+}
+
+fn proxy_settings() -> Settings {
+    Settings {
+        enable_connect_protocol: Some(1),
+        h3_datagram: Some(1),
+        ..Settings::default()
+    }
+}
+
+fn connect_udp_request(path: &[u8]) -> Vec<u8> {
+    headers(&[
+        HeaderField {
+            name: b":method",
+            value: b"CONNECT",
+        },
+        HeaderField {
+            name: b":protocol",
+            value: b"connect-udp",
+        },
+        HeaderField {
+            name: b":scheme",
+            value: b"https",
+        },
+        HeaderField {
+            name: b":authority",
+            value: b"proxy.example",
+        },
+        HeaderField {
+            name: b":path",
+            value: path,
+        },
+        HeaderField {
+            name: b"capsule-protocol",
+            value: b"?1",
+        },
+    ])
+}
+
+fn capsule(ty: u64, value: &[u8]) -> Vec<u8> {
+    let mut buf = [0u8; 64];
+    let header_len = capsule::encode_capsule_header(
+        capsule::CapsuleHeader {
+            ty,
+            len: value.len() as u64,
+        },
+        &mut buf,
+    )
+    .expect("capsule header encodes");
+    buf[header_len..header_len + value.len()].copy_from_slice(value);
+    buf[..header_len + value.len()].to_vec()
+}
+
+/// Peer control stream + CONNECT request, followed by `expect`.
+fn open_tunnel_stream(
+    h: &mut MockHarness<H3Engine>,
+    settings: &Settings,
+    request: Vec<u8>,
+    expect: &[ScriptStep],
+) {
+    let control = control_stream(&settings_payload(settings));
+
+    let mut script = alloc::vec![
+        ScriptStep::InQuicOpen {
+            id: StreamId(3),
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: StreamId(3),
+            data: control,
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicOpen {
+            id: TUNNEL,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: TUNNEL,
+            data: request,
+            fin: false,
+        },
+    ];
+    script.extend_from_slice(expect);
+    h.run_script(&script);
+}
+
+/// The 200 accepting a CONNECT-UDP tunnel on `id`.
+fn accept(id: StreamId) -> [ScriptStep; 2] {
+    [
+        ScriptStep::InAppAcceptTunnel { id },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id,
+            data_prefix: headers(&[
+                HeaderField {
+                    name: b":status",
+                    value: b"200",
+                },
+                HeaderField {
+                    name: b"capsule-protocol",
+                    value: b"?1",
+                },
+            ]),
+            fin: false,
+        }),
+    ]
+}
+
+fn requested() -> MockHarness<H3Engine> {
+    let mut h = MockHarness::new(engine_with(proxy_settings()));
+    open_tunnel_stream(
+        &mut h,
+        &proxy_settings(),
+        connect_udp_request(b"/.well-known/masque/udp/2001%3Adb8%3A%3A42/443/"),
+        &[
+            ScriptStep::Expect(ExpectCommand::AppConnectUdp {
+                id: TUNNEL,
+                host: b"2001:db8::42".to_vec(),
+                port: 443,
+            }),
+            // Nothing is answered before the application decides.
+            ScriptStep::ExpectNone,
+        ],
+    );
+    h
+}
+
+fn accepted() -> MockHarness<H3Engine> {
+    let mut h = requested();
+    let mut script = Vec::from(accept(TUNNEL));
+    script.push(ScriptStep::ExpectNone);
+    h.run_script(&script);
+    h
+}
+
+#[test]
+fn connect_udp_is_accepted_and_relays_datagrams() {
+    let mut h = accepted();
+    h.run_script(&[
+        // Quarter stream id 0, Context ID 0, UDP payload.
+        ScriptStep::InQuicDatagram {
+            data: alloc::vec![0x00, 0x00, b'p', b'i', b'n', b'g'],
+        },
+        ScriptStep::Expect(ExpectCommand::AppDatagram {
+            id: TUNNEL,
+            payload: alloc::vec![0x00, b'p', b'i', b'n', b'g'],
+        }),
+        ScriptStep::ExpectNone,
+        ScriptStep::InAppSendDatagram {
+            id: TUNNEL,
+            payload: alloc::vec![0x00, b'p', b'o', b'n', b'g'],
+        },
+        ScriptStep::Expect(ExpectCommand::QuicSendDatagram {
+            data: alloc::vec![0x00, 0x00, b'p', b'o', b'n', b'g'],
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn datagram_capsules_are_relayed_like_datagrams() {
+    let mut h = accepted();
+    // Context ID 0 + UDP payload, split across two DATA frames, after an
+    // unknown capsule that is skipped.
+    let stream = [
+        capsule(0x29, &[1, 2, 3]),
+        capsule(
+            consts::CAPSULE_TYPE_DATAGRAM,
+            &[0x00, b'p', b'i', b'n', b'g'],
+        ),
+    ]
+    .concat();
+    let (head, tail) = stream.split_at(7);
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: TUNNEL,
+            data: frame(consts::FRAME_TYPE_DATA, head),
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: TUNNEL,
+            data: frame(consts::FRAME_TYPE_DATA, tail),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::AppDatagram {
+            id: TUNNEL,
+            payload: alloc::vec![0x00, b'p', b'i', b'n', b'g'],
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn capsule_flood_closes_connection() {
+    let mut h = accepted();
+    // A DATAGRAM capsule announcing more than the capsule buffer holds.
+    let mut stream = alloc::vec![0u8; 16 * 1024 + 1];
+    capsule::encode_capsule_header(
+        capsule::CapsuleHeader {
+            ty: consts::CAPSULE_TYPE_DATAGRAM,
+            len: 32 * 1024,
+        },
+        &mut stream,
+    )
+    .expect("capsule header encodes");
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: TUNNEL,
+            data: frame(consts::FRAME_TYPE_DATA, &stream),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+            app_error: consts::H3_EXCESSIVE_LOAD,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn peer_fin_closes_tunnel() {
+    let mut h = accepted();
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: TUNNEL,
+            data: Vec::new(),
+            fin: true,
+        },
+        ScriptStep::Expect(ExpectCommand::AppConnectUdpClosed { id: TUNNEL }),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: TUNNEL,
+            data_prefix: Vec::new(),
+            fin: true,
+        }),
+        ScriptStep::ExpectNone,
+        // The tunnel is gone: its datagrams are dropped.
+        ScriptStep::InQuicDatagram {
+            data: alloc::vec![0x00, 0x00, 0x01],
+        },
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn app_reset_aborts_tunnel_stream() {
+    let mut h = accepted();
+    h.run_script(&[
+        ScriptStep::InAppResetStream {
+            id: TUNNEL,
+            app_error: consts::H3_CONNECT_ERROR,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStopSending {
+            id: TUNNEL,
+            app_error: consts::H3_CONNECT_ERROR,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicResetStream {
+            id: TUNNEL,
+            app_error: consts::H3_CONNECT_ERROR,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn rejected_target_is_answered_with_the_app_status() {
+    let mut h = requested();
+    h.run_script(&[
+        ScriptStep::InAppRejectTunnel {
+            id: TUNNEL,
+            status: 403,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: TUNNEL,
+            data_prefix: headers(&[HeaderField {
+                name: b":status",
+                value: b"403",
+            }]),
+            fin: true,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStopSending {
+            id: TUNNEL,
+            app_error: consts::H3_NO_ERROR,
+        }),
+        ScriptStep::ExpectNone,
+        // A late accept finds no tunnel.
+        ScriptStep::InAppAcceptTunnel { id: TUNNEL },
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn two_tunnels_run_alongside_a_request() {
+    let second = StreamId(4);
+    let request = StreamId(8);
+    let mut h = accepted();
+    let mut script = alloc::vec![
+        ScriptStep::InQuicOpen {
+            id: second,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::InQuicData {
+            id: second,
+            data: connect_udp_request(b"/.well-known/masque/udp/192.0.2.6/53/"),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::AppConnectUdp {
+            id: second,
+            host: b"192.0.2.6".to_vec(),
+            port: 53,
+        }),
+    ];
+    script.extend(accept(second));
+    script.extend([
+        ScriptStep::InQuicOpen {
+            id: request,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::InQuicData {
+            id: request,
+            data: headers(&[
+                HeaderField {
+                    name: b":method",
+                    value: b"GET",
+                },
+                HeaderField {
+                    name: b":scheme",
+                    value: b"https",
+                },
+                HeaderField {
+                    name: b":authority",
+                    value: b"proxy.example",
+                },
+                HeaderField {
+                    name: b":path",
+                    value: b"/",
+                },
+            ]),
+            fin: true,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: request,
+            data_prefix: headers(&[HeaderField {
+                name: b":status",
+                value: b"200",
+            }]),
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: request,
+            data_prefix: frame(consts::FRAME_TYPE_DATA, &[0x01]),
+            fin: true,
+        }),
+        // Each tunnel gets the datagrams naming its stream.
+        ScriptStep::InQuicDatagram {
+            data: alloc::vec![0x01, 0x00, b'b'],
+        },
+        ScriptStep::Expect(ExpectCommand::AppDatagram {
+            id: second,
+            payload: alloc::vec![0x00, b'b'],
+        }),
+        ScriptStep::InQuicDatagram {
+            data: alloc::vec![0x00, 0x00, b'a'],
+        },
+        ScriptStep::Expect(ExpectCommand::AppDatagram {
+            id: TUNNEL,
+            payload: alloc::vec![0x00, b'a'],
+        }),
+        ScriptStep::InQuicData {
+            id: TUNNEL,
+            data: Vec::new(),
+            fin: true,
+        },
+        ScriptStep::Expect(ExpectCommand::AppConnectUdpClosed { id: TUNNEL }),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: TUNNEL,
+            data_prefix: Vec::new(),
+            fin: true,
+        }),
+        ScriptStep::InQuicDatagram {
+            data: alloc::vec![0x01, 0x00, b'c'],
+        },
+        ScriptStep::Expect(ExpectCommand::AppDatagram {
+            id: second,
+            payload: alloc::vec![0x00, b'c'],
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+    h.run_script(&script);
+}
+
+#[test]
+fn path_off_template_is_answered_with_400() {
+    let mut h = MockHarness::new(engine_with(proxy_settings()));
+    open_tunnel_stream(
+        &mut h,
+        &proxy_settings(),
+        connect_udp_request(b"/.well-known/masque/udp/host/0/"),
+        &[
+            ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+                id: TUNNEL,
+                data_prefix: headers(&[HeaderField {
+                    name: b":status",
+                    value: b"400",
+                }]),
+                fin: true,
+            }),
+            ScriptStep::Expect(ExpectCommand::QuicStopSending {
+                id: TUNNEL,
+                app_error: consts::H3_NO_ERROR,
+            }),
+            ScriptStep::ExpectNone,
+        ],
+    );
+}
+
+#[test]
+fn connect_udp_without_datagrams_is_answered_with_501() {
+    let settings = Settings {
+        enable_connect_protocol: Some(1),
+        ..Settings::default()
+    };
+    let mut h = MockHarness::new(engine_with(settings.clone()));
+    open_tunnel_stream(
+        &mut h,
+        &settings,
+        connect_udp_request(b"/.well-known/masque/udp/192.0.2.6/443/"),
+        &[
+            ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+                id: TUNNEL,
+                data_prefix: headers(&[HeaderField {
+                    name: b":status",
+                    value: b"501",
+                }]),
+                fin: true,
+            }),
+            ScriptStep::Expect(ExpectCommand::QuicStopSending {
+                id: TUNNEL,
+                app_error: consts::H3_NO_ERROR,
+            }),
+            ScriptStep::ExpectNone,
+        ],
+    );
+}
@@ -0,0 +1,306 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::h3::consts;
+use istok_core::qpack::{self, HeaderField};
+use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
+use istok_h3::mock::{control_stream, frame, headers};
+use istok_h3::{FinalResponse, H3Engine, RequestInfo, ResponseHook};
+use istok_transport::{StreamId, StreamKind};
+
+const CONTROL: StreamId = StreamId(3);
+const REQUEST: StreamId = StreamId(0);
+
+/// HEADERS for a POST, with `content-length` when given.
+fn post(content_length: Option<&[u8]>) -> Vec<u8> {
+    let mut fields = alloc::vec![
+        HeaderField {
+            name: b":method",
+            value: b"POST",
+        },
+        HeaderField {
+            name: b":scheme",
+            value: b"https",
+        },
+        HeaderField {
+            name: b":authority",
+            value: b"example.com",
+        },
+        HeaderField {
+            name: b":path",
+            value: b"/upload",
+        },
+    ];
+    if let Some(value) = content_length {
+        fields.push(HeaderField {
+            name: b"content-length",
+            value,
+        });
+    }
+    let mut block = [0u8; 128];
+    let len = qpack::encode(&fields, &mut block).expect("qpack encodes");
+    frame(consts::FRAME_TYPE_HEADERS, &block[..len])
+}
+
+fn data(len: usize) -> Vec<u8> {
+    frame(consts::FRAME_TYPE_DATA, &alloc::vec![0xab; len])
+}
+
+/// Open the control and request streams and send `request` without FIN;
+/// the auto-response goes out right after HEADERS.
+fn open_request(request: Vec<u8>) -> MockHarness<H3Engine> {
+    let control = control_stream(&[]);
+
+    let mut h = MockHarness::new(H3Engine::default());
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: CONTROL,
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::InQuicData {
+            id: CONTROL,
+            data: control,
+            fin: false,
+        },
+        ScriptStep::InQuicOpen {
+            id: REQUEST,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: request,
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00, 0xd9]),
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: frame(consts::FRAME_TYPE_DATA, &[0x01]),
+            fin: true,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+    h
+}
+
+fn message_error() -> [ScriptStep; 3] {
+    [
+        ScriptStep::Expect(ExpectCommand::QuicStopSending {
+            id: REQUEST,
+            app_error: consts::H3_MESSAGE_ERROR,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicResetStream {
+            id: REQUEST,
+            app_error: consts::H3_MESSAGE_ERROR,
+        }),
+        ScriptStep::ExpectNone,
+    ]
+}
+
+fn send(h: &mut MockHarness<H3Engine>, data: Vec<u8>, fin: bool, expect: &[ScriptStep]) {
+    let mut script = alloc::vec![ScriptStep::InQuicData {
+        id: REQUEST,
+        data,
+        fin,
+    }];
+    script.extend_from_slice(expect);
+    h.run_script(&script);
+}
+
+#[test]
+fn body_matching_content_length_is_accepted() {
+    let mut request = post(Some(b"7"));
+    request.extend_from_slice(&data(3));
+    let mut h = open_request(request);
+
+    send(&mut h, data(4), false, &[ScriptStep::ExpectNone]);
+    send(&mut h, Vec::new(), true, &[ScriptStep::ExpectNone]);
+}
+
+#[test]
+fn body_beyond_content_length_is_message_error() {
+    let mut h = open_request(post(Some(b"4")));
+    send(&mut h, data(3), false, &[ScriptStep::ExpectNone]);
+    // Rejected on the frame header, before the excess payload arrives.
+    send(&mut h, data(2)[..2].to_vec(), false, &message_error());
+}
+
+#[test]
+fn body_short_of_content_length_at_fin_is_message_error() {
+    let mut h = open_request(post(Some(b"10")));
+    send(&mut h, data(9), true, &message_error());
+}
+
+#[test]
+fn data_with_zero_content_length_is_message_error() {
+    let mut h = open_request(post(Some(b"0")));
+    send(&mut h, data(1), false, &message_error());
+}
+
+#[test]
+fn empty_data_frames_count_nothing() {
+    let mut h = open_request(post(Some(b"0")));
+    send(&mut h, data(0), true, &[ScriptStep::ExpectNone]);
+}
+
+#[test]
+fn body_without_content_length_is_unbounded() {
+    let mut h = open_request(post(None));
+    send(&mut h, data(1000), false, &[ScriptStep::ExpectNone]);
+    send(&mut h, data(1), true, &[ScriptStep::ExpectNone]);
+}
+
+#[test]
+fn unparsable_or_conflicting_content_length_is_message_error() {
+    for value in [&b"ten"[..], b"-1", b"5, 6"] {
+        let control = control_stream(&[]);
+
+        let mut script = alloc::vec![
+            ScriptStep::InQuicOpen {
+                id: CONTROL,
+                kind: StreamKind::Uni,
+            },
+            ScriptStep::InQuicData {
+                id: CONTROL,
+                data: control,
+                fin: false,
+            },
+            ScriptStep::InQuicOpen {
+                id: REQUEST,
+                kind: StreamKind::Bidi,
+            },
+            ScriptStep::InQuicData {
+                id: REQUEST,
+                data: post(Some(value)),
+                fin: false,
+            },
+        ];
+        script.extend_from_slice(&message_error());
+        MockHarness::new(H3Engine::default()).run_script(&script);
+    }
+}
+
+#[test]
+fn trailers_end_the_body() {
+    let mut h = open_request(post(Some(b"2")));
+    let mut body = data(2);
+    body.extend_from_slice(&frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00]));
+    send(&mut h, body, false, &[ScriptStep::ExpectNone]);
+    send(
+        &mut h,
+        data(0),
+        false,
+        &[
+            ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+                app_error: consts::H3_FRAME_UNEXPECTED,
+            }),
+            ScriptStep::ExpectNone,
+        ],
+    );
+}
+
+#[test]
+fn trailers_before_declared_length_is_message_error_at_fin() {
+    let mut h = open_request(post(Some(b"2")));
+    let mut body = data(1);
+    body.extend_from_slice(&frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00]));
+    send(&mut h, body, true, &message_error());
+}
+
+/// Answers every request with the same response.
+struct Respond(FinalResponse);
+
+impl ResponseHook for Respond {
+    fn final_response(&mut self, _request: &RequestInfo<'_>) -> Option<FinalResponse> {
+        Some(self.0.clone())
+    }
+}
+
+/// Send a bodyless POST to an engine answering with `response`.
+fn respond_with(response: FinalResponse, expect: &[ScriptStep]) {
+    let mut engine = H3Engine::default();
+    engine.set_response_hook(Respond(response));
+    let mut script = alloc::vec![
+        ScriptStep::InQuicOpen {
+            id: CONTROL,
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::InQuicData {
+            id: CONTROL,
+            data: control_stream(&[]),
+            fin: false,
+        },
+        ScriptStep::InQuicOpen {
+            id: REQUEST,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: post(None),
+            fin: true,
+        },
+    ];
+    script.extend_from_slice(expect);
+    script.push(ScriptStep::ExpectNone);
+    MockHarness::new(engine).run_script(&script);
+}
+
+#[test]
+fn response_matching_its_content_length_is_sent() {
+    respond_with(
+        FinalResponse::new(201)
+            .with_field(b"content-length", b"3")
+            .with_body(b"abc"),
+        &[
+            ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+                id: REQUEST,
+                data_prefix: headers(&[
+                    HeaderField {
+                        name: b":status",
+                        value: b"201",
+                    },
+                    HeaderField {
+                        name: b"content-length",
+                        value: b"3",
+                    },
+                ]),
+                fin: false,
+            }),
+            ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+                id: REQUEST,
+                data_prefix: frame(consts::FRAME_TYPE_DATA, b"abc"),
+                fin: true,
+            }),
+        ],
+    );
+}
+
+#[test]
+fn response_body_disagreeing_with_content_length_is_never_sent() {
+    let bodies: [&[u8]; 3] = [b"abc", b"abcdef", b""];
+    for body in bodies {
+        respond_with(
+            FinalResponse::new(200)
+                .with_field(b"content-length", b"5")
+                .with_body(body),
+            &message_error()[..2],
+        );
+    }
+}
+
+#[test]
+fn conflicting_response_content_length_closes_connection() {
+    respond_with(
+        FinalResponse::new(200)
+            .with_field(b"content-length", b"1")
+            .with_field(b"content-length", b"2")
+            .with_body(b"a"),
+        &[ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+            app_error: consts::H3_INTERNAL_ERROR,
+        })],
+    );
+}
@@ -0,0 +1,112 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::h3::consts;
+use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
+use istok_h3::mock::{control_stream, frame, varint_bytes};
+use istok_h3::{H3Config, H3Engine, Role};
+use istok_transport::{StreamId, StreamKind};
+
+const PEER_CONTROL: StreamId = StreamId(3);
+
+/// Peer control stream carrying `frames` after an empty SETTINGS.
+fn open_control(h: &mut MockHarness<H3Engine>, frames: &[u8], expect: &[ScriptStep]) {
+    let mut script = alloc::vec![
+        ScriptStep::InQuicOpen {
+            id: PEER_CONTROL,
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::InQuicData {
+            id: PEER_CONTROL,
+            data: [control_stream(&[]).as_slice(), frames].concat(),
+            fin: false,
+        },
+    ];
+    script.extend_from_slice(expect);
+    script.push(ScriptStep::ExpectNone);
+    h.run_script(&script);
+}
+
+fn goaway(id: u64) -> Vec<u8> {
+    frame(consts::FRAME_TYPE_GOAWAY, &varint_bytes(id))
+}
+
+fn close(app_error: u64) -> ScriptStep {
+    ScriptStep::Expect(ExpectCommand::QuicCloseConnection { app_error })
+}
+
+fn client() -> H3Engine {
+    H3Engine::new(
+        H3Config::builder()
+            .role(Role::Client)
+            .build()
+            .expect("valid config"),
+    )
+}
+
+#[test]
+fn goaway_is_recorded_and_may_decrease() {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_control(&mut h, &[goaway(9), goaway(4)].concat(), &[]);
+    assert_eq!(h.apply(|engine, _| engine.peer_goaway()), Some(4));
+}
+
+#[test]
+fn increasing_goaway_is_id_error() {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_control(
+        &mut h,
+        &[goaway(4), goaway(8)].concat(),
+        &[close(consts::H3_ID_ERROR)],
+    );
+}
+
+#[test]
+fn server_goaway_must_name_a_client_request_stream() {
+    let mut h = MockHarness::new(client());
+    open_control(&mut h, &goaway(8), &[]);
+    assert_eq!(h.apply(|engine, _| engine.peer_goaway()), Some(8));
+
+    let mut h = MockHarness::new(client());
+    open_control(&mut h, &goaway(3), &[close(consts::H3_ID_ERROR)]);
+}
+
+#[test]
+fn malformed_goaway_is_frame_error() {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_control(
+        &mut h,
+        &frame(consts::FRAME_TYPE_GOAWAY, &[0x04, 0x00]),
+        &[close(consts::H3_FRAME_ERROR)],
+    );
+}
+
+#[test]
+fn cancel_push_for_a_push_never_promised_is_id_error() {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_control(
+        &mut h,
+        &frame(consts::FRAME_TYPE_CANCEL_PUSH, &[0x00]),
+        &[close(consts::H3_ID_ERROR)],
+    );
+}
+
+#[test]
+fn second_control_stream_is_stream_creation_error() {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_control(&mut h, &[], &[]);
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: StreamId(7),
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::InQuicData {
+            id: StreamId(7),
+            data: control_stream(&[]),
+            fin: false,
+        },
+        close(consts::H3_STREAM_CREATION_ERROR),
+        ScriptStep::ExpectNone,
+    ]);
+}
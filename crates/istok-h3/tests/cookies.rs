@@ -0,0 +1,193 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::h3::consts;
+use istok_core::qpack::{self, HeaderField};
+use istok_h3::mock::{Collect, ExpectCommand, MockHarness, ScriptStep};
+use istok_h3::mock::{control_stream, frame, headers, status, written};
+use istok_h3::{AppAction, Engine, EngineEvent, H3Config, H3ConfigBuilder, H3Engine, Role};
+use istok_transport::{StreamId, StreamKind};
+
+const CONTROL: StreamId = StreamId(3);
+const REQUEST: StreamId = StreamId(0);
+
+const FIELDS: [HeaderField<'static>; 5] = [
+    HeaderField {
+        name: b":method",
+        value: b"GET",
+    },
+    HeaderField {
+        name: b":scheme",
+        value: b"https",
+    },
+    HeaderField {
+        name: b":authority",
+        value: b"example.com",
+    },
+    HeaderField {
+        name: b":path",
+        value: b"/",
+    },
+    HeaderField {
+        name: b"cookie",
+        value: b"a=1; b=2; c=3",
+    },
+];
+
+fn owned(fields: &[HeaderField<'_>]) -> Vec<(Vec<u8>, Vec<u8>)> {
+    fields
+        .iter()
+        .map(|field| (field.name.to_vec(), field.value.to_vec()))
+        .collect()
+}
+
+fn crumbled(fields: &[HeaderField<'_>]) -> Vec<u8> {
+    let mut block = [0u8; 256];
+    let len = qpack::cookie::encode_crumbled(fields, &mut block).expect("qpack encodes");
+    frame(consts::FRAME_TYPE_HEADERS, &block[..len])
+}
+
+fn engine(configure: impl FnOnce(H3ConfigBuilder) -> H3ConfigBuilder) -> H3Engine {
+    H3Engine::new(
+        configure(H3Config::builder())
+            .build()
+            .expect("valid config"),
+    )
+}
+
+/// Serve `request` on an engine reporting requests.
+fn serve(request: Vec<u8>, expect: &[ScriptStep]) {
+    let mut script = alloc::vec![
+        ScriptStep::InQuicOpen {
+            id: CONTROL,
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::InQuicData {
+            id: CONTROL,
+            data: control_stream(&[]),
+            fin: false,
+        },
+        ScriptStep::InQuicOpen {
+            id: REQUEST,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: request,
+            fin: true,
+        },
+    ];
+    script.extend_from_slice(expect);
+    script.extend([
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: status(b"200"),
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: frame(consts::FRAME_TYPE_DATA, &[0x01]),
+            fin: true,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+    MockHarness::new(engine(|builder| builder.report_requests(true))).run_script(&script);
+}
+
+#[test]
+fn crumbled_cookie_reaches_the_application_joined() {
+    serve(
+        crumbled(&FIELDS),
+        &[ScriptStep::Expect(ExpectCommand::AppRequest {
+            id: REQUEST,
+            fields: owned(&FIELDS),
+        })],
+    );
+}
+
+#[test]
+fn cookie_lines_apart_are_joined_after_the_other_fields() {
+    let mut fields = FIELDS[..4].to_vec();
+    fields.extend([
+        HeaderField {
+            name: b"cookie",
+            value: b"a=1",
+        },
+        HeaderField {
+            name: b"accept",
+            value: b"*/*",
+        },
+        HeaderField {
+            name: b"cookie",
+            value: b"b=2",
+        },
+    ]);
+    let mut expected = owned(&fields[..4]);
+    expected.extend(owned(&[
+        HeaderField {
+            name: b"accept",
+            value: b"*/*",
+        },
+        HeaderField {
+            name: b"cookie",
+            value: b"a=1; b=2",
+        },
+    ]));
+    serve(
+        headers(&fields),
+        &[ScriptStep::Expect(ExpectCommand::AppRequest {
+            id: REQUEST,
+            fields: expected,
+        })],
+    );
+}
+
+#[test]
+fn client_crumbles_cookies_and_the_server_joins_them() {
+    let mut client = engine(|builder| builder.role(Role::Client).crumble_cookies(true));
+    let mut out = Collect::default();
+    client.on_event(
+        EngineEvent::App(AppAction::SendRequest {
+            id: REQUEST,
+            fields: FIELDS.to_vec(),
+        }),
+        &mut out,
+    );
+    let request: Vec<u8> = out
+        .0
+        .iter()
+        .filter_map(written)
+        .flat_map(|(_, data, _)| data)
+        .collect();
+    assert_eq!(request, crumbled(&FIELDS));
+    assert_ne!(request, headers(&FIELDS));
+
+    serve(
+        request,
+        &[ScriptStep::Expect(ExpectCommand::AppRequest {
+            id: REQUEST,
+            fields: owned(&FIELDS),
+        })],
+    );
+}
+
+#[test]
+fn cookies_are_sent_whole_unless_crumbling_is_on() {
+    let mut client = engine(|builder| builder.role(Role::Client));
+    let mut out = Collect::default();
+    client.on_event(
+        EngineEvent::App(AppAction::SendRequest {
+            id: REQUEST,
+            fields: FIELDS.to_vec(),
+        }),
+        &mut out,
+    );
+    let request: Vec<u8> = out
+        .0
+        .iter()
+        .filter_map(written)
+        .flat_map(|(_, data, _)| data)
+        .collect();
+    assert_eq!(request, headers(&FIELDS));
+}
@@ -0,0 +1,371 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::h3::consts;
+use istok_core::qpack::{self, HeaderField};
+use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
+use istok_h3::mock::{control_stream, frame};
+use istok_h3::{ConfigError, DosLimits, H3Config, H3Engine};
+use istok_transport::{StreamError, StreamId, StreamKind};
+
+const PEER_CONTROL: StreamId = StreamId(3);
+const REQUEST: StreamId = StreamId(0);
+
+/// A reserved frame type (RFC 9114 §7.2.8).
+const RESERVED_FRAME: u64 = 0x21;
+
+const GET: [HeaderField<'static>; 4] = [
+    HeaderField {
+        name: b":method",
+        value: b"GET",
+    },
+    HeaderField {
+        name: b":scheme",
+        value: b"https",
+    },
+    HeaderField {
+        name: b":authority",
+        value: b"example.com",
+    },
+    HeaderField {
+        name: b":path",
+        value: b"/",
+    },
+];
+
+/// GET carrying `extra` after its pseudo-headers.
+fn get_with(extra: &[HeaderField<'_>]) -> Vec<u8> {
+    let mut fields = GET.to_vec();
+    fields.extend_from_slice(extra);
+    let mut block = alloc::vec![0u8; 16 * 1024];
+    let len = qpack::encode(&fields, &mut block).expect("qpack encodes");
+    frame(consts::FRAME_TYPE_HEADERS, &block[..len])
+}
+
+fn harness(limits: DosLimits) -> MockHarness<H3Engine> {
+    let config = H3Config::builder()
+        .dos_limits(limits)
+        .build()
+        .expect("valid config");
+    let mut h = MockHarness::new(H3Engine::new(config));
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: PEER_CONTROL,
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::InQuicData {
+            id: PEER_CONTROL,
+            data: control_stream(&[]),
+            fin: false,
+        },
+        ScriptStep::InQuicOpen {
+            id: REQUEST,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::ExpectNone,
+    ]);
+    h
+}
+
+/// Request whose body stays open, answered before its body is read.
+fn open_body(h: &mut MockHarness<H3Engine>) {
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: get_with(&[]),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00, 0xd9]),
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: frame(consts::FRAME_TYPE_DATA, &[0x01]),
+            fin: true,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+fn excessive_load_close() -> ScriptStep {
+    ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+        app_error: consts::H3_EXCESSIVE_LOAD,
+    })
+}
+
+fn excessive_load_reset() -> [ScriptStep; 3] {
+    [
+        ScriptStep::Expect(ExpectCommand::QuicStopSending {
+            id: REQUEST,
+            app_error: consts::H3_EXCESSIVE_LOAD,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicResetStream {
+            id: REQUEST,
+            app_error: consts::H3_EXCESSIVE_LOAD,
+        }),
+        ScriptStep::ExpectNone,
+    ]
+}
+
+#[test]
+fn rapid_reset_closes_the_connection() {
+    let mut h = harness(DosLimits {
+        rapid_resets: 3,
+        ..DosLimits::default()
+    });
+    // One finished request earns one more reset.
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: get_with(&[]),
+            fin: true,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00, 0xd9]),
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: frame(consts::FRAME_TYPE_DATA, &[0x01]),
+            fin: true,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+
+    let mut script = Vec::new();
+    for n in 1..=5u64 {
+        let id = StreamId(4 * n);
+        script.push(ScriptStep::InQuicOpen {
+            id,
+            kind: StreamKind::Bidi,
+        });
+        script.push(ScriptStep::InQuicStreamError {
+            id,
+            err: StreamError::Reset(consts::H3_REQUEST_CANCELLED),
+        });
+        if n < 5 {
+            script.push(ScriptStep::ExpectNone);
+        }
+    }
+    // Only the fifth reset is over 1 + 3.
+    script.push(excessive_load_close());
+    script.push(ScriptStep::ExpectNone);
+    h.run_script(&script);
+}
+
+#[test]
+fn resets_of_finished_streams_are_not_counted() {
+    let mut h = harness(DosLimits {
+        rapid_resets: 0,
+        ..DosLimits::default()
+    });
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: get_with(&[]),
+            fin: true,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00, 0xd9]),
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: frame(consts::FRAME_TYPE_DATA, &[0x01]),
+            fin: true,
+        }),
+        ScriptStep::InQuicStreamError {
+            id: REQUEST,
+            err: StreamError::StopSending(consts::H3_NO_ERROR),
+        },
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn unknown_frame_flood_closes_the_connection() {
+    let mut h = harness(DosLimits {
+        unknown_frames_per_stream: 4,
+        ..DosLimits::default()
+    });
+    open_body(&mut h);
+
+    let grease = frame(RESERVED_FRAME, &[]);
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: grease.repeat(4),
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: grease,
+            fin: false,
+        },
+        excessive_load_close(),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn tiny_data_frames_close_the_connection() {
+    let mut h = harness(DosLimits {
+        data_frame_burst: 8,
+        min_data_frame_avg: 16,
+        ..DosLimits::default()
+    });
+    open_body(&mut h);
+
+    let tiny = frame(consts::FRAME_TYPE_DATA, b"x");
+    h.run_script(&[
+        // Within the burst anything goes.
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: tiny.repeat(8),
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: tiny,
+            fin: false,
+        },
+        excessive_load_close(),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn large_data_frames_keep_the_average_up() {
+    let mut h = harness(DosLimits {
+        data_frame_burst: 2,
+        min_data_frame_avg: 16,
+        ..DosLimits::default()
+    });
+    open_body(&mut h);
+
+    let mut body = frame(consts::FRAME_TYPE_DATA, &[0; 128]);
+    for _ in 0..4 {
+        body.extend_from_slice(&frame(consts::FRAME_TYPE_DATA, b"x"));
+    }
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: body,
+            fin: true,
+        },
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn qpack_bomb_resets_the_stream() {
+    let mut h = harness(DosLimits::default());
+    // Each 2-byte reference to static entry 85 expands to 76 bytes.
+    let csp = HeaderField {
+        name: b"content-security-policy",
+        value: b"script-src 'none'; object-src 'none'; base-uri 'none'",
+    };
+    let mut script = alloc::vec![ScriptStep::InQuicData {
+        id: REQUEST,
+        data: get_with(&[csp; 64]),
+        fin: true,
+    }];
+    script.extend_from_slice(&excessive_load_reset());
+    h.run_script(&script);
+}
+
+#[test]
+fn expansion_below_the_floor_is_allowed() {
+    let mut h = harness(DosLimits::default());
+    let csp = HeaderField {
+        name: b"content-security-policy",
+        value: b"script-src 'none'; object-src 'none'; base-uri 'none'",
+    };
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: get_with(&[csp; 8]),
+            fin: true,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00, 0xd9]),
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: frame(consts::FRAME_TYPE_DATA, &[0x01]),
+            fin: true,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn too_many_fields_reset_the_stream() {
+    let mut h = harness(DosLimits {
+        max_header_fields: 8,
+        ..DosLimits::default()
+    });
+    let accept = HeaderField {
+        name: b"accept",
+        value: b"*/*",
+    };
+    let mut script = alloc::vec![ScriptStep::InQuicData {
+        id: REQUEST,
+        data: get_with(&[accept; 5]),
+        fin: true,
+    }];
+    script.extend_from_slice(&excessive_load_reset());
+    h.run_script(&script);
+}
+
+#[test]
+fn cookie_crumb_flood_counts_every_crumb() {
+    let crumbs: Vec<u8> = (0..300)
+        .flat_map(|i: u32| alloc::format!("c{i}=1; ").into_bytes())
+        .collect();
+    let mut fields = GET.to_vec();
+    fields.push(HeaderField {
+        name: b"cookie",
+        value: &crumbs,
+    });
+    let mut block = alloc::vec![0u8; 16 * 1024];
+    let len = qpack::cookie::encode_crumbled(&fields, &mut block).expect("qpack encodes");
+
+    // Joined, the flood would be five fields, well under the default 256.
+    let mut h = harness(DosLimits::default());
+    let mut script = alloc::vec![ScriptStep::InQuicData {
+        id: REQUEST,
+        data: frame(consts::FRAME_TYPE_HEADERS, &block[..len]),
+        fin: true,
+    }];
+    script.extend_from_slice(&excessive_load_reset());
+    h.run_script(&script);
+}
+
+#[test]
+fn limits_must_admit_some_request() {
+    for limits in [
+        DosLimits {
+            max_header_fields: 0,
+            ..DosLimits::default()
+        },
+        DosLimits {
+            max_header_expansion: 0,
+            ..DosLimits::default()
+        },
+    ] {
+        assert_eq!(
+            H3Config::builder().dos_limits(limits).build(),
+            Err(ConfigError::InvalidDosLimits)
+        );
+    }
+}
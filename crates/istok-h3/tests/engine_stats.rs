@@ -0,0 +1,227 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::h3::consts;
+use istok_h3::mock::Discard;
+use istok_h3::mock::{control_stream, frame};
+use istok_h3::stats::{FrameCounts, QpackCounts};
+use istok_h3::{Engine, EngineEvent, EngineStats, H3Engine};
+use istok_transport::{QuicEvent, StreamError, StreamId, StreamKind};
+
+const PEER_CONTROL: StreamId = StreamId(3);
+const REQUEST: StreamId = StreamId(0);
+
+/// `:method GET`, `:scheme https`, `:path /`, `:authority a`.
+const GET_BLOCK: [u8; 8] = [0x00, 0x00, 0xd1, 0xd7, 0xc1, 0x50, 0x01, b'a'];
+
+fn feed<const N: usize>(engine: &mut H3Engine, events: [QuicEvent<'_>; N]) {
+    engine.on_event(EngineEvent::Boot, &mut Discard);
+    for ev in events {
+        engine.on_event(EngineEvent::Quic(ev), &mut Discard);
+    }
+}
+
+/// Peer control stream, then a GET split over two reads.
+fn serve_get(engine: &mut H3Engine) {
+    let control = control_stream(&[]);
+    let request = frame(consts::FRAME_TYPE_HEADERS, &GET_BLOCK);
+    let (head, tail) = request.split_at(4);
+    feed(
+        engine,
+        [
+            QuicEvent::StreamOpened {
+                id: PEER_CONTROL,
+                kind: StreamKind::Uni,
+            },
+            QuicEvent::StreamReadable {
+                id: PEER_CONTROL,
+                data: &control,
+                fin: false,
+            },
+            QuicEvent::StreamOpened {
+                id: REQUEST,
+                kind: StreamKind::Bidi,
+            },
+            QuicEvent::StreamReadable {
+                id: REQUEST,
+                data: head,
+                fin: false,
+            },
+            QuicEvent::StreamReadable {
+                id: REQUEST,
+                data: tail,
+                fin: true,
+            },
+        ],
+    );
+}
+
+#[test]
+fn a_served_request_is_counted() {
+    let mut engine = H3Engine::default();
+    serve_get(&mut engine);
+    let stats = engine.stats();
+
+    assert_eq!(
+        (
+            stats.streams_opened,
+            stats.streams_completed,
+            stats.streams_reset
+        ),
+        (1, 1, 0)
+    );
+    assert_eq!(
+        stats.frames_in,
+        FrameCounts {
+            settings: 1,
+            headers: 1,
+            ..FrameCounts::default()
+        }
+    );
+    assert_eq!(
+        stats.frames_out,
+        FrameCounts {
+            settings: 1,
+            headers: 1,
+            data: 1,
+            ..FrameCounts::default()
+        }
+    );
+    assert_eq!((stats.header_bytes_in, stats.body_bytes_in), (8, 0));
+    assert_eq!((stats.header_bytes_out, stats.body_bytes_out), (3, 1));
+    assert_eq!(
+        stats.qpack_in,
+        QpackCounts {
+            blocks: 1,
+            encoded_bytes: 8,
+            decoded_bytes: 39,
+        }
+    );
+    assert_eq!(stats.qpack_out.compression_ratio(), Some(10.0 / 3.0));
+    assert!(stats.close_codes.is_empty() && stats.reset_codes.is_empty());
+    assert!(stats.peak_buffered_bytes > 0);
+    assert_eq!(engine.memory_usage(), 0);
+}
+
+#[test]
+fn partial_control_frames_are_counted_once_complete() {
+    let mut engine = H3Engine::default();
+    let control = control_stream(&[]);
+    let update = frame(consts::FRAME_TYPE_PRIORITY_UPDATE_REQUEST, b"\x00u=1");
+    let (head, tail) = update.split_at(update.len() - 1);
+    feed(
+        &mut engine,
+        [
+            QuicEvent::StreamOpened {
+                id: PEER_CONTROL,
+                kind: StreamKind::Uni,
+            },
+            QuicEvent::StreamReadable {
+                id: PEER_CONTROL,
+                data: &control,
+                fin: false,
+            },
+            QuicEvent::StreamReadable {
+                id: PEER_CONTROL,
+                data: head,
+                fin: false,
+            },
+        ],
+    );
+    assert_eq!(engine.stats().frames_in.priority_update, 0);
+
+    engine.on_event(
+        EngineEvent::Quic(QuicEvent::StreamReadable {
+            id: PEER_CONTROL,
+            data: tail,
+            fin: false,
+        }),
+        &mut Discard,
+    );
+    assert_eq!(engine.stats().frames_in.priority_update, 1);
+    assert_eq!(engine.stats().frames_in.total(), 2);
+}
+
+#[test]
+fn resets_and_closes_are_counted_by_code() {
+    let mut engine = H3Engine::default();
+    let control = control_stream(&[]);
+    // No `:path`: malformed, so the stream is reset.
+    let malformed = frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00, 0xd1, 0xd7]);
+    let data_on_control = frame(consts::FRAME_TYPE_DATA, &[0x00]);
+    feed(
+        &mut engine,
+        [
+            QuicEvent::StreamOpened {
+                id: PEER_CONTROL,
+                kind: StreamKind::Uni,
+            },
+            QuicEvent::StreamReadable {
+                id: PEER_CONTROL,
+                data: &control,
+                fin: false,
+            },
+            QuicEvent::StreamOpened {
+                id: REQUEST,
+                kind: StreamKind::Bidi,
+            },
+            QuicEvent::StreamReadable {
+                id: REQUEST,
+                data: &malformed,
+                fin: false,
+            },
+            QuicEvent::StreamOpened {
+                id: StreamId(4),
+                kind: StreamKind::Bidi,
+            },
+            QuicEvent::StreamError {
+                id: StreamId(4),
+                err: StreamError::Reset(consts::H3_REQUEST_CANCELLED),
+            },
+            QuicEvent::StreamReadable {
+                id: PEER_CONTROL,
+                data: &data_on_control,
+                fin: false,
+            },
+        ],
+    );
+
+    let stats = engine.stats();
+    assert_eq!((stats.streams_opened, stats.streams_reset), (2, 1));
+    assert_eq!(
+        stats.reset_codes.iter().collect::<Vec<_>>(),
+        [(&consts::H3_MESSAGE_ERROR, &1)]
+    );
+    assert_eq!(
+        stats.close_codes.iter().collect::<Vec<_>>(),
+        [(&consts::H3_FRAME_UNEXPECTED, &1)]
+    );
+}
+
+#[test]
+fn merged_stats_add_up_across_connections() {
+    let mut total = EngineStats::default();
+    for _ in 0..3 {
+        let mut engine = H3Engine::default();
+        serve_get(&mut engine);
+        total.merge(engine.stats());
+    }
+    let mut single = H3Engine::default();
+    serve_get(&mut single);
+
+    assert_eq!(total.streams_completed, 3);
+    assert_eq!(
+        total.frames_out.total(),
+        3 * single.stats().frames_out.total()
+    );
+    assert_eq!(total.qpack_in.decoded_bytes, 3 * 39);
+    assert_eq!(
+        total.qpack_in.compression_ratio(),
+        single.stats().qpack_in.compression_ratio()
+    );
+    assert_eq!(
+        total.peak_buffered_bytes,
+        single.stats().peak_buffered_bytes
+    );
+}
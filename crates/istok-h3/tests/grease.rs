@@ -0,0 +1,387 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::codec::{h3_frame, varint};
+use istok_core::h3::settings::Settings;
+use istok_core::h3::{consts, grease};
+use istok_h3::engine::CommandSink;
+use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
+use istok_h3::mock::{control_stream, frame, varint_bytes, written};
+use istok_h3::{Engine, EngineCommand, EngineEvent, H3Config, H3Engine};
+use istok_transport::{QuicCommand, QuicEvent, StreamId, StreamKind};
+
+const LOCAL_CONTROL: StreamId = StreamId(2);
+const PEER_CONTROL: StreamId = StreamId(3);
+const REQUEST: StreamId = StreamId(0);
+
+/// Everything the engine asked QUIC to do, with write payloads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Cmd {
+    OpenUni(Option<StreamId>),
+    Write(StreamId, Vec<u8>, bool),
+    StopSending(StreamId, u64),
+    Close(u64),
+    Other,
+}
+
+#[derive(Default)]
+struct Record(Vec<Cmd>);
+
+impl<'a> CommandSink<'a> for Record {
+    fn push(&mut self, cmd: EngineCommand<'a>) {
+        self.0.push(match cmd {
+            EngineCommand::Quic(QuicCommand::OpenUni { id_hint }) => Cmd::OpenUni(id_hint),
+            EngineCommand::Quic(QuicCommand::StopSending { id, app_error }) => {
+                Cmd::StopSending(id, app_error)
+            }
+            EngineCommand::Quic(QuicCommand::CloseConnection { app_error }) => {
+                Cmd::Close(app_error)
+            }
+            cmd => written(&cmd).map_or(Cmd::Other, |(id, data, fin)| Cmd::Write(id, data, fin)),
+        });
+    }
+}
+
+fn greased(seed: u64) -> H3Engine {
+    H3Engine::new(
+        H3Config::builder()
+            .grease(Some(seed))
+            .build()
+            .expect("valid config"),
+    )
+}
+
+/// Boot, then a GET on the request stream; returns every command.
+fn run(engine: H3Engine) -> Vec<Cmd> {
+    serve(engine, &control_stream(&[]), &get())
+}
+
+/// `:method GET`, `:scheme https`, `:path /`, `:authority a`.
+fn get() -> Vec<u8> {
+    frame(
+        consts::FRAME_TYPE_HEADERS,
+        &[0x00, 0x00, 0xd1, 0xd7, 0xc1, 0x50, 0x01, b'a'],
+    )
+}
+
+/// Boot, then `control` on the peer control stream and `request` on the
+/// request stream; returns every command.
+fn serve(mut engine: H3Engine, control: &[u8], request: &[u8]) -> Vec<Cmd> {
+    let mut sink = Record::default();
+    engine.on_event(EngineEvent::Boot, &mut sink);
+    let events = [
+        QuicEvent::StreamOpened {
+            id: PEER_CONTROL,
+            kind: StreamKind::Uni,
+        },
+        QuicEvent::StreamReadable {
+            id: PEER_CONTROL,
+            data: control,
+            fin: false,
+        },
+        QuicEvent::StreamOpened {
+            id: REQUEST,
+            kind: StreamKind::Bidi,
+        },
+        QuicEvent::StreamReadable {
+            id: REQUEST,
+            data: request,
+            fin: true,
+        },
+    ];
+    for ev in events {
+        engine.on_event(EngineEvent::Quic(ev), &mut sink);
+    }
+    sink.0
+}
+
+/// Split `data` into `(type, payload)` frames.
+fn frames(mut data: &[u8]) -> Vec<(u64, Vec<u8>)> {
+    let mut out = Vec::new();
+    while !data.is_empty() {
+        let (header, payload, consumed) = h3_frame::decode_frame(data).expect("whole frame");
+        out.push((header.ty, payload.to_vec()));
+        data = &data[consumed..];
+    }
+    out
+}
+
+fn writes_on(cmds: &[Cmd], id: StreamId) -> Vec<(Vec<u8>, bool)> {
+    cmds.iter()
+        .filter_map(|cmd| match cmd {
+            Cmd::Write(w, data, fin) if *w == id => Some((data.clone(), *fin)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn grease_is_off_by_default() {
+    let cmds = run(H3Engine::default());
+    let control = writes_on(&cmds, LOCAL_CONTROL);
+    assert_eq!(control.len(), 1);
+    assert_eq!(control[0].0, [0x00, 0x04, 0x00]);
+    assert_eq!(
+        cmds.iter().filter(|c| matches!(c, Cmd::OpenUni(_))).count(),
+        1
+    );
+
+    let response = writes_on(&cmds, REQUEST);
+    assert_eq!(
+        response[0].0,
+        frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00, 0xd9])
+    );
+}
+
+#[test]
+fn same_seed_produces_identical_output() {
+    assert_eq!(run(greased(42)), run(greased(42)));
+    assert_ne!(run(greased(42)), run(greased(43)));
+}
+
+#[test]
+fn settings_carry_a_reserved_setting() {
+    for seed in 0..8 {
+        let cmds = run(greased(seed));
+        let control = writes_on(&cmds, LOCAL_CONTROL);
+        let (stream_type, type_len) = varint::decode(&control[0].0).expect("stream type");
+        assert_eq!(stream_type, consts::STREAM_TYPE_CONTROL);
+
+        let settings = frames(&control[0].0[type_len..]);
+        assert_eq!(settings.len(), 1);
+        assert_eq!(settings[0].0, consts::FRAME_TYPE_SETTINGS);
+        let (id, _) = varint::decode(&settings[0].1).expect("setting id");
+        assert!(grease::is_reserved(id), "seed {seed}: {id:#x}");
+        assert_eq!(
+            Settings::decode_payload(&settings[0].1),
+            Ok(Settings::new())
+        );
+    }
+}
+
+#[test]
+fn control_stream_gets_a_reserved_frame_after_settings() {
+    let cmds = run(greased(1));
+    let control = writes_on(&cmds, LOCAL_CONTROL);
+    assert_eq!(control.len(), 2);
+    let reserved = frames(&control[1].0);
+    assert_eq!(reserved.len(), 1);
+    assert!(grease::is_reserved(reserved[0].0));
+    assert!(reserved[0].1.len() <= 8);
+    assert!(!control[1].1, "control stream stays open");
+}
+
+#[test]
+fn response_starts_with_a_reserved_frame() {
+    let cmds = run(greased(5));
+    let response: Vec<(u64, Vec<u8>)> = writes_on(&cmds, REQUEST)
+        .iter()
+        .flat_map(|(data, _)| frames(data))
+        .collect();
+    let types: Vec<u64> = response.iter().map(|(ty, _)| *ty).collect();
+    assert_eq!(types.len(), 3);
+    assert!(grease::is_reserved(types[0]));
+    assert_eq!(
+        types[1..],
+        [consts::FRAME_TYPE_HEADERS, consts::FRAME_TYPE_DATA]
+    );
+}
+
+#[test]
+fn reserved_uni_stream_is_opened_and_finished_on_some_seeds() {
+    let mut opened = 0;
+    for seed in 0..16 {
+        let cmds = run(greased(seed));
+        let hints: Vec<Option<StreamId>> = cmds
+            .iter()
+            .filter_map(|cmd| match cmd {
+                Cmd::OpenUni(hint) => Some(*hint),
+                _ => None,
+            })
+            .collect();
+        if hints.len() == 1 {
+            continue;
+        }
+        opened += 1;
+        assert_eq!(hints, [Some(LOCAL_CONTROL), Some(StreamId(6))]);
+        let writes = writes_on(&cmds, StreamId(6));
+        assert_eq!(writes.len(), 1);
+        let (stream_type, len) = varint::decode(&writes[0].0).expect("stream type");
+        assert_eq!(len, writes[0].0.len(), "no bytes after the type");
+        assert!(grease::is_reserved(stream_type));
+        assert!(writes[0].1, "finished right away");
+    }
+    assert!(0 < opened && opened < 16, "opened on {opened} of 16 seeds");
+}
+
+#[test]
+fn greased_boot_is_scriptable_in_mock_harness() {
+    let any_write = |id| {
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id,
+            data_prefix: Vec::new(),
+            fin: false,
+        })
+    };
+    let uni_opened = run(greased(3)).contains(&Cmd::OpenUni(Some(StreamId(6))));
+
+    let mut script = alloc::vec![
+        ScriptStep::InBoot,
+        ScriptStep::Expect(ExpectCommand::QuicOpenUni),
+        any_write(LOCAL_CONTROL),
+        any_write(LOCAL_CONTROL),
+    ];
+    if uni_opened {
+        script.push(ScriptStep::Expect(ExpectCommand::QuicOpenUni));
+        script.push(ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: StreamId(6),
+            data_prefix: Vec::new(),
+            fin: true,
+        }));
+    }
+    script.push(ScriptStep::ExpectNone);
+    MockHarness::new(greased(3)).run_script(&script);
+}
+
+/// A reserved frame of random type and payload, as a greasing peer sends.
+fn reserved_frame(rng: &mut grease::Rng) -> Vec<u8> {
+    let mut payload = alloc::vec![0u8; rng.below(64) as usize];
+    rng.fill(&mut payload);
+    frame(rng.reserved(), &payload)
+}
+
+fn closes(cmds: &[Cmd]) -> Vec<u64> {
+    cmds.iter()
+        .filter_map(|cmd| match cmd {
+            Cmd::Close(code) => Some(*code),
+            _ => None,
+        })
+        .collect()
+}
+
+fn response_types(cmds: &[Cmd]) -> Vec<u64> {
+    writes_on(cmds, REQUEST)
+        .iter()
+        .flat_map(|(data, _)| frames(data))
+        .map(|(ty, _)| ty)
+        .collect()
+}
+
+#[test]
+fn reserved_frames_from_the_peer_are_skipped() {
+    for seed in 0..16 {
+        let mut rng = grease::Rng::new(seed);
+        let mut control = control_stream(&[]);
+        control.extend(reserved_frame(&mut rng));
+        control.extend(reserved_frame(&mut rng));
+        let mut request = reserved_frame(&mut rng);
+        request.extend(get());
+        request.extend(reserved_frame(&mut rng));
+
+        let cmds = serve(H3Engine::default(), &control, &request);
+        assert_eq!(closes(&cmds), [], "seed {seed}");
+        assert_eq!(
+            response_types(&cmds),
+            [consts::FRAME_TYPE_HEADERS, consts::FRAME_TYPE_DATA],
+            "seed {seed}"
+        );
+    }
+}
+
+#[test]
+fn reserved_control_frame_is_skipped_across_reads() {
+    let mut rng = grease::Rng::new(9);
+    let mut engine = H3Engine::default();
+    let mut sink = Record::default();
+    let mut control = control_stream(&[]);
+    control.extend(frame(rng.reserved(), &[0xaa; 300]));
+    control.extend(frame(
+        consts::FRAME_TYPE_PRIORITY_UPDATE_REQUEST,
+        b"\x00u=1,",
+    ));
+
+    engine.on_event(
+        EngineEvent::Quic(QuicEvent::StreamOpened {
+            id: PEER_CONTROL,
+            kind: StreamKind::Uni,
+        }),
+        &mut sink,
+    );
+    for chunk in control.chunks(7) {
+        engine.on_event(
+            EngineEvent::Quic(QuicEvent::StreamReadable {
+                id: PEER_CONTROL,
+                data: chunk,
+                fin: false,
+            }),
+            &mut sink,
+        );
+    }
+    // The frame after the skipped one is parsed: its field is malformed.
+    assert_eq!(closes(&sink.0), [consts::H3_GENERAL_PROTOCOL_ERROR]);
+}
+
+#[test]
+fn reserved_uni_stream_from_the_peer_is_stopped() {
+    let mut rng = grease::Rng::new(4);
+    let reserved_uni = StreamId(7);
+    let mut engine = H3Engine::default();
+    let mut sink = Record::default();
+    let mut data = varint_bytes(rng.reserved());
+    data.extend(reserved_frame(&mut rng));
+
+    engine.on_event(
+        EngineEvent::Quic(QuicEvent::StreamOpened {
+            id: reserved_uni,
+            kind: StreamKind::Uni,
+        }),
+        &mut sink,
+    );
+    engine.on_event(
+        EngineEvent::Quic(QuicEvent::StreamReadable {
+            id: reserved_uni,
+            data: &data,
+            fin: false,
+        }),
+        &mut sink,
+    );
+    engine.on_event(
+        EngineEvent::Quic(QuicEvent::StreamReadable {
+            id: reserved_uni,
+            data: &[0xff; 32],
+            fin: true,
+        }),
+        &mut sink,
+    );
+    assert_eq!(
+        sink.0,
+        [Cmd::StopSending(
+            reserved_uni,
+            consts::H3_STREAM_CREATION_ERROR
+        )]
+    );
+
+    // The control stream opened after it still works.
+    let cmds = serve(engine, &control_stream(&[]), &get());
+    assert_eq!(closes(&cmds), []);
+    assert_eq!(
+        response_types(&cmds),
+        [consts::FRAME_TYPE_HEADERS, consts::FRAME_TYPE_DATA]
+    );
+}
+
+#[test]
+fn http2_frame_types_are_unexpected() {
+    for ty in consts::FRAME_TYPES_HTTP2_RESERVED {
+        let mut control = control_stream(&[]);
+        control.extend(frame(ty, &[]));
+        let cmds = serve(H3Engine::default(), &control, &get());
+        assert_eq!(closes(&cmds), [consts::H3_FRAME_UNEXPECTED], "{ty:#x}");
+
+        let mut request = frame(ty, &[]);
+        request.extend(get());
+        let cmds = serve(H3Engine::default(), &control_stream(&[]), &request);
+        assert_eq!(closes(&cmds), [consts::H3_FRAME_UNEXPECTED], "{ty:#x}");
+    }
+}
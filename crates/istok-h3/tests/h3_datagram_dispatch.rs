@@ -0,0 +1,178 @@
+extern crate alloc;
+
+use istok_core::h3::consts;
+use istok_core::h3::settings::Settings;
+use istok_h3::mock::control_stream;
+use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
+use istok_h3::{H3Config, H3Engine};
+use istok_transport::{StreamId, StreamKind};
+
+fn engine_with(settings: Settings) -> H3Engine {
+    H3Engine::new(
+        H3Config::builder()
+            .settings(settings)
+            .build()
+            .expect("valid config"),
+    )
+}
+
+fn datagram_settings() -> Settings {
+    Settings {
+        h3_datagram: Some(1),
+        ..Settings::default()
+    }
+}
+
+fn open_control(payload: &[u8]) -> [ScriptStep; 4] {
+    let control_stream_id = StreamId(3);
+    [
+        ScriptStep::InQuicOpen {
+            id: control_stream_id,
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: control_stream_id,
+            data: control_stream(payload),
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+    ]
+}
+
+#[test]
+fn boot_advertises_h3_datagram_setting() {
+    let mut h = MockHarness::new(engine_with(datagram_settings()));
+
+    // Stream type + SETTINGS header + payload: the whole write is the prefix.
+    let expected = control_stream(&[0x33, 0x01]);
+
+    h.run_script(&[
+        ScriptStep::InBoot,
+        ScriptStep::Expect(ExpectCommand::QuicOpenUni),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: StreamId(2),
+            data_prefix: expected,
+            fin: false,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn negotiated_datagram_is_dispatched_to_request_stream() {
+    let mut h = MockHarness::new(engine_with(datagram_settings()));
+    let request_stream_id = StreamId(0);
+
+    h.run_script(&open_control(&[0x33, 0x01]));
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: request_stream_id,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::ExpectNone,
+        // Quarter stream id 0 -> request stream 0.
+        ScriptStep::InQuicDatagram {
+            data: alloc::vec![0x00, 0xaa, 0xbb],
+        },
+        ScriptStep::Expect(ExpectCommand::AppDatagram {
+            id: request_stream_id,
+            payload: alloc::vec![0xaa, 0xbb],
+        }),
+        ScriptStep::ExpectNone,
+        ScriptStep::InAppSendDatagram {
+            id: request_stream_id,
+            payload: alloc::vec![0xcc],
+        },
+        ScriptStep::Expect(ExpectCommand::QuicSendDatagram {
+            data: alloc::vec![0x00, 0xcc],
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn datagram_for_untracked_stream_is_dropped() {
+    let mut h = MockHarness::new(engine_with(datagram_settings()));
+
+    h.run_script(&open_control(&[0x33, 0x01]));
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: StreamId(0),
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::ExpectNone,
+        // Quarter stream id 1 -> stream 4, which was never opened.
+        ScriptStep::InQuicDatagram {
+            data: alloc::vec![0x01, 0xaa],
+        },
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn datagram_without_local_support_closes_datagram_error() {
+    let mut h = MockHarness::new(H3Engine::default());
+
+    h.run_script(&[
+        ScriptStep::InQuicDatagram {
+            data: alloc::vec![0x00, 0xaa],
+        },
+        ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+            app_error: consts::H3_DATAGRAM_ERROR,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn malformed_quarter_stream_id_closes_datagram_error() {
+    let mut h = MockHarness::new(engine_with(datagram_settings()));
+
+    h.run_script(&[
+        // 8-byte varint announced, only one byte present.
+        ScriptStep::InQuicDatagram {
+            data: alloc::vec![0xc0],
+        },
+        ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+            app_error: consts::H3_DATAGRAM_ERROR,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn send_datagram_is_dropped_when_peer_did_not_advertise() {
+    let mut h = MockHarness::new(engine_with(datagram_settings()));
+
+    h.run_script(&open_control(&[]));
+    h.run_script(&[
+        ScriptStep::InAppSendDatagram {
+            id: StreamId(0),
+            payload: alloc::vec![0xcc],
+        },
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn peer_h3_datagram_value_out_of_range_closes_settings_error() {
+    let mut h = MockHarness::new(engine_with(datagram_settings()));
+
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: StreamId(3),
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: StreamId(3),
+            data: control_stream(&[0x33, 0x02]),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+            app_error: consts::H3_SETTINGS_ERROR,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
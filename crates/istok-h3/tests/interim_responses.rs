@@ -0,0 +1,259 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::h3::consts;
+use istok_core::qpack::HeaderField;
+use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
+use istok_h3::mock::{control_stream, frame, headers, status};
+use istok_h3::{FinalResponse, H3Engine, InterimResponse, RequestInfo, ResponseHook};
+use istok_transport::{StreamId, StreamKind};
+
+const CONTROL: StreamId = StreamId(3);
+const REQUEST: StreamId = StreamId(0);
+
+const LINK: &[u8] = b"</style.css>; rel=preload; as=style";
+const SCRIPT_LINK: &[u8] = b"</app.js>; rel=preload";
+
+/// POST with `content-length: 3`, plus `expect: 100-continue` if asked.
+fn post(expect_continue: bool) -> Vec<u8> {
+    let mut fields = alloc::vec![
+        HeaderField {
+            name: b":method",
+            value: b"POST",
+        },
+        HeaderField {
+            name: b":scheme",
+            value: b"https",
+        },
+        HeaderField {
+            name: b":authority",
+            value: b"example.com",
+        },
+        HeaderField {
+            name: b":path",
+            value: b"/upload",
+        },
+        HeaderField {
+            name: b"content-length",
+            value: b"3",
+        },
+    ];
+    if expect_continue {
+        fields.push(HeaderField {
+            name: b"expect",
+            value: b"100-Continue",
+        });
+    }
+    headers(&fields)
+}
+
+fn write(data: Vec<u8>, fin: bool) -> ScriptStep {
+    ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+        id: REQUEST,
+        data_prefix: data,
+        fin,
+    })
+}
+
+fn final_response() -> [ScriptStep; 2] {
+    [
+        write(status(b"200"), false),
+        write(frame(consts::FRAME_TYPE_DATA, &[0x01]), true),
+    ]
+}
+
+/// Open the control and request streams and send `request` without FIN.
+fn send_request(
+    engine: H3Engine,
+    request: Vec<u8>,
+    expect: &[ScriptStep],
+) -> MockHarness<H3Engine> {
+    let control = control_stream(&[]);
+
+    let mut script = alloc::vec![
+        ScriptStep::InQuicOpen {
+            id: CONTROL,
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::InQuicData {
+            id: CONTROL,
+            data: control,
+            fin: false,
+        },
+        ScriptStep::InQuicOpen {
+            id: REQUEST,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: request,
+            fin: false,
+        },
+    ];
+    script.extend_from_slice(expect);
+    script.push(ScriptStep::ExpectNone);
+
+    let mut h = MockHarness::new(engine);
+    h.run_script(&script);
+    h
+}
+
+fn with_hook(hook: impl ResponseHook + 'static) -> H3Engine {
+    let mut engine = H3Engine::default();
+    engine.set_response_hook(hook);
+    engine
+}
+
+struct EarlyHints;
+
+impl ResponseHook for EarlyHints {
+    fn interim_responses(&mut self, request: &RequestInfo<'_>) -> Vec<InterimResponse> {
+        assert_eq!(request.id, REQUEST);
+        assert_eq!(request.path, Some(&b"/upload"[..]));
+        alloc::vec![
+            InterimResponse::new(103).with_field(b"link", LINK),
+            InterimResponse::new(103).with_field(b"link", SCRIPT_LINK),
+        ]
+    }
+}
+
+struct Continue(bool);
+
+impl ResponseHook for Continue {
+    fn expect_continue(&mut self, request: &RequestInfo<'_>) -> bool {
+        assert!(request.expect_continue);
+        assert_eq!(request.content_length, Some(3));
+        self.0
+    }
+}
+
+struct Interim(u16);
+
+impl ResponseHook for Interim {
+    fn interim_responses(&mut self, _request: &RequestInfo<'_>) -> Vec<InterimResponse> {
+        alloc::vec![InterimResponse::new(self.0)]
+    }
+}
+
+struct Final(u16);
+
+impl ResponseHook for Final {
+    fn final_response(&mut self, _request: &RequestInfo<'_>) -> Option<FinalResponse> {
+        Some(FinalResponse::new(self.0))
+    }
+}
+
+#[test]
+fn early_hints_precede_final_response() {
+    let hints = |link| {
+        headers(&[
+            HeaderField {
+                name: b":status",
+                value: b"103",
+            },
+            HeaderField {
+                name: b"link",
+                value: link,
+            },
+        ])
+    };
+    let mut expect = alloc::vec![write(hints(LINK), false), write(hints(SCRIPT_LINK), false)];
+    expect.extend_from_slice(&final_response());
+    send_request(with_hook(EarlyHints), post(false), &expect);
+}
+
+#[test]
+fn expect_continue_sends_100_then_answers_after_the_body() {
+    let mut h = send_request(
+        with_hook(Continue(true)),
+        post(true),
+        &[write(status(b"100"), false)],
+    );
+
+    let mut script = alloc::vec![ScriptStep::InQuicData {
+        id: REQUEST,
+        data: frame(consts::FRAME_TYPE_DATA, b"abc"),
+        fin: true,
+    }];
+    script.extend_from_slice(&final_response());
+    script.push(ScriptStep::ExpectNone);
+    h.run_script(&script);
+}
+
+#[test]
+fn short_body_after_100_continue_gets_no_final_response() {
+    let mut h = send_request(
+        with_hook(Continue(true)),
+        post(true),
+        &[write(status(b"100"), false)],
+    );
+
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: frame(consts::FRAME_TYPE_DATA, b"ab"),
+            fin: true,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStopSending {
+            id: REQUEST,
+            app_error: consts::H3_MESSAGE_ERROR,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicResetStream {
+            id: REQUEST,
+            app_error: consts::H3_MESSAGE_ERROR,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn declined_expect_continue_answers_417() {
+    send_request(
+        with_hook(Continue(false)),
+        post(true),
+        &[
+            write(status(b"417"), true),
+            ScriptStep::Expect(ExpectCommand::QuicStopSending {
+                id: REQUEST,
+                app_error: consts::H3_NO_ERROR,
+            }),
+        ],
+    );
+}
+
+#[test]
+fn expect_continue_without_hook_gets_final_response_only() {
+    send_request(H3Engine::default(), post(true), &final_response());
+}
+
+#[test]
+fn hook_without_expectation_sends_no_100() {
+    send_request(with_hook(Continue(false)), post(false), &final_response());
+}
+
+#[test]
+fn non_interim_status_closes_connection() {
+    for code in [101, 200, 99] {
+        send_request(
+            with_hook(Interim(code)),
+            post(false),
+            &[ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+                app_error: consts::H3_INTERNAL_ERROR,
+            })],
+        );
+    }
+}
+
+#[test]
+fn non_final_status_as_final_response_closes_connection() {
+    for code in [103, 600] {
+        send_request(
+            with_hook(Final(code)),
+            post(false),
+            &[ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+                app_error: consts::H3_INTERNAL_ERROR,
+            })],
+        );
+    }
+}
@@ -8,7 +8,7 @@ use istok_transport::{StreamId, StreamKind};
 
 #[test]
 fn response_is_headers_fin_false_then_data_fin_true() {
-    let engine = H3Engine::new();
+    let engine = H3Engine::default();
     let mut h = MockHarness::new(engine);
 
     let control_stream_id = StreamId(3);
@@ -28,7 +28,9 @@ fn response_is_headers_fin_false_then_data_fin_true() {
     let control_total = control_type_len + control_frame_len;
 
     let mut request_header_buf = [0u8; 16];
-    let request_payload = [0xaa, 0xbb];
+    // Minimal GET: static refs for :method GET, :scheme https and :path /,
+    // plus a literal `:authority a`.
+    let request_payload = [0x00, 0x00, 0xd1, 0xd7, 0xc1, 0x50, 0x01, b'a'];
     let request_header_len = h3_frame::encode_frame_header(
         h3_frame::FrameHeader {
             ty: consts::FRAME_TYPE_HEADERS,
@@ -47,17 +49,17 @@ fn response_is_headers_fin_false_then_data_fin_true() {
     let response_headers_header_len = h3_frame::encode_frame_header(
         h3_frame::FrameHeader {
             ty: consts::FRAME_TYPE_HEADERS,
-            len: 1,
+            len: 3,
         },
         &mut response_headers_header_buf,
     )
     .expect("response headers frame header encodes");
 
     let mut response_headers_prefix =
-        alloc::vec::Vec::with_capacity(response_headers_header_len + 1);
+        alloc::vec::Vec::with_capacity(response_headers_header_len + 3);
     response_headers_prefix
         .extend_from_slice(&response_headers_header_buf[..response_headers_header_len]);
-    response_headers_prefix.push(0x00);
+    response_headers_prefix.extend_from_slice(&[0x00, 0x00, 0xd9]);
 
     let mut response_data_header_buf = [0u8; 16];
     let response_data_header_len = h3_frame::encode_frame_header(
@@ -111,7 +113,7 @@ fn response_is_headers_fin_false_then_data_fin_true() {
 
 #[test]
 fn fin_only_readable_after_m1_6_response_is_ignored() {
-    let engine = H3Engine::new();
+    let engine = H3Engine::default();
     let mut h = MockHarness::new(engine);
 
     let control_stream_id = StreamId(3);
@@ -131,7 +133,9 @@ fn fin_only_readable_after_m1_6_response_is_ignored() {
     let control_total = control_type_len + control_frame_len;
 
     let mut request_header_buf = [0u8; 16];
-    let request_payload = [0xaa, 0xbb];
+    // Minimal GET: static refs for :method GET, :scheme https and :path /,
+    // plus a literal `:authority a`.
+    let request_payload = [0x00, 0x00, 0xd1, 0xd7, 0xc1, 0x50, 0x01, b'a'];
     let request_header_len = h3_frame::encode_frame_header(
         h3_frame::FrameHeader {
             ty: consts::FRAME_TYPE_HEADERS,
@@ -150,17 +154,17 @@ fn fin_only_readable_after_m1_6_response_is_ignored() {
     let response_headers_header_len = h3_frame::encode_frame_header(
         h3_frame::FrameHeader {
             ty: consts::FRAME_TYPE_HEADERS,
-            len: 1,
+            len: 3,
         },
         &mut response_headers_header_buf,
     )
     .expect("response headers frame header encodes");
 
     let mut response_headers_prefix =
-        alloc::vec::Vec::with_capacity(response_headers_header_len + 1);
+        alloc::vec::Vec::with_capacity(response_headers_header_len + 3);
     response_headers_prefix
         .extend_from_slice(&response_headers_header_buf[..response_headers_header_len]);
-    response_headers_prefix.push(0x00);
+    response_headers_prefix.extend_from_slice(&[0x00, 0x00, 0xd9]);
 
     let mut response_data_header_buf = [0u8; 16];
     let response_data_header_len = h3_frame::encode_frame_header(
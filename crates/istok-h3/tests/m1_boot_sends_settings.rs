@@ -8,7 +8,7 @@ use istok_transport::StreamId;
 
 #[test]
 fn boot_opens_control_and_sends_settings() {
-    let engine = H3Engine::new();
+    let engine = H3Engine::default();
     let mut h = MockHarness::new(engine);
 
     let control_id = StreamId(2);
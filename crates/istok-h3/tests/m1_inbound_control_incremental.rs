@@ -8,7 +8,7 @@ use istok_transport::{StreamId, StreamKind};
 
 #[test]
 fn inbound_control_stream_accepts_stream_type_then_frame_header() {
-    let engine = H3Engine::new();
+    let engine = H3Engine::default();
     let mut h = MockHarness::new(engine);
 
     let peer_uni_id = StreamId(3);
@@ -48,7 +48,7 @@ fn inbound_control_stream_accepts_stream_type_then_frame_header() {
 
 #[test]
 fn inbound_control_stream_accepts_split_frame_header() {
-    let engine = H3Engine::new();
+    let engine = H3Engine::default();
     let mut h = MockHarness::new(engine);
 
     let peer_uni_id = StreamId(3);
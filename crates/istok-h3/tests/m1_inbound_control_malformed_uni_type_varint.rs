@@ -7,7 +7,7 @@ use istok_transport::{StreamId, StreamKind};
 
 #[test]
 fn inbound_control_stream_type_truncated_with_fin_closes_general_protocol_error() {
-    let engine = H3Engine::new();
+    let engine = H3Engine::default();
     let mut h = MockHarness::new(engine);
 
     let peer_uni_id = StreamId(3);
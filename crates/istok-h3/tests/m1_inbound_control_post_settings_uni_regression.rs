@@ -8,7 +8,7 @@ use istok_transport::{StreamId, StreamKind};
 
 #[test]
 fn inbound_uni_stream_after_settings_is_still_validated() {
-    let engine = H3Engine::new();
+    let engine = H3Engine::default();
     let mut h = MockHarness::new(engine);
 
     let control_stream_id = StreamId(3);
@@ -8,7 +8,7 @@ use istok_transport::{StreamId, StreamKind};
 
 #[test]
 fn inbound_control_stream_accepts_empty_settings_without_closing() {
-    let engine = H3Engine::new();
+    let engine = H3Engine::default();
     let mut h = MockHarness::new(engine);
 
     let peer_uni_id = StreamId(3);
@@ -42,7 +42,7 @@ fn inbound_control_stream_accepts_empty_settings_without_closing() {
 
 #[test]
 fn inbound_control_stream_accepts_empty_settings_with_extra_bytes() {
-    let engine = H3Engine::new();
+    let engine = H3Engine::default();
     let mut h = MockHarness::new(engine);
 
     let peer_uni_id = StreamId(3);
@@ -88,8 +88,8 @@ fn inbound_control_stream_accepts_empty_settings_with_extra_bytes() {
 }
 
 #[test]
-fn inbound_control_stream_fin_only_empty_after_settings_is_ignored() {
-    let engine = H3Engine::new();
+fn inbound_control_stream_fin_after_settings_closes_critical_stream() {
+    let engine = H3Engine::default();
     let mut h = MockHarness::new(engine);
 
     let peer_uni_id = StreamId(3);
@@ -123,13 +123,15 @@ fn inbound_control_stream_fin_only_empty_after_settings_is_ignored() {
             data: alloc::vec::Vec::new(),
             fin: true,
         },
-        ScriptStep::ExpectNone,
+        ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+            app_error: consts::H3_CLOSED_CRITICAL_STREAM,
+        }),
     ]);
 }
 
 #[test]
 fn inbound_pending_stream_type_is_not_overwritten_by_second_uni_open() {
-    let engine = H3Engine::new();
+    let engine = H3Engine::default();
     let mut h = MockHarness::new(engine);
 
     let first_uni_id = StreamId(3);
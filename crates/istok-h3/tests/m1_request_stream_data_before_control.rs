@@ -8,14 +8,16 @@ use istok_transport::{StreamId, StreamKind};
 
 #[test]
 fn request_data_buffered_before_control_is_processed_after_settings() {
-    let engine = H3Engine::new();
+    let engine = H3Engine::default();
     let mut h = MockHarness::new(engine);
 
     let request_stream_id = StreamId(0);
     let control_stream_id = StreamId(3);
 
     let mut req_header_buf = [0u8; 16];
-    let req_payload = [0xaa, 0xbb];
+    // Minimal GET: static refs for :method GET, :scheme https and :path /,
+    // plus a literal `:authority a`.
+    let req_payload = [0x00, 0x00, 0xd1, 0xd7, 0xc1, 0x50, 0x01, b'a'];
     let req_header_len = h3_frame::encode_frame_header(
         h3_frame::FrameHeader {
             ty: consts::FRAME_TYPE_HEADERS,
@@ -45,14 +47,14 @@ fn request_data_buffered_before_control_is_processed_after_settings() {
     let resp_headers_header_len = h3_frame::encode_frame_header(
         h3_frame::FrameHeader {
             ty: consts::FRAME_TYPE_HEADERS,
-            len: 1,
+            len: 3,
         },
         &mut resp_headers_header_buf,
     )
     .expect("response frame header encodes");
-    let mut resp_headers_prefix = alloc::vec::Vec::with_capacity(resp_headers_header_len + 1);
+    let mut resp_headers_prefix = alloc::vec::Vec::with_capacity(resp_headers_header_len + 3);
     resp_headers_prefix.extend_from_slice(&resp_headers_header_buf[..resp_headers_header_len]);
-    resp_headers_prefix.push(0x00);
+    resp_headers_prefix.extend_from_slice(&[0x00, 0x00, 0xd9]);
 
     let mut resp_data_header_buf = [0u8; 16];
     let resp_data_header_len = h3_frame::encode_frame_header(
@@ -7,7 +7,7 @@ use istok_transport::{StreamId, StreamKind};
 
 #[test]
 fn early_request_buffer_over_cap_closes_connection() {
-    let engine = H3Engine::new();
+    let engine = H3Engine::default();
     let mut h = MockHarness::new(engine);
 
     let request_stream_id = StreamId(0);
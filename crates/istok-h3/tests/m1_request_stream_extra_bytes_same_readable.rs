@@ -7,8 +7,8 @@ use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
 use istok_transport::{StreamId, StreamKind};
 
 #[test]
-fn extra_bytes_after_declared_headers_payload_closes_connection() {
-    let engine = H3Engine::new();
+fn truncated_frame_after_headers_payload_closes_connection_at_fin() {
+    let engine = H3Engine::default();
     let mut h = MockHarness::new(engine);
 
     let control_stream_id = StreamId(3);
@@ -31,15 +31,17 @@ fn extra_bytes_after_declared_headers_payload_closes_connection() {
     let request_header_len = h3_frame::encode_frame_header(
         h3_frame::FrameHeader {
             ty: consts::FRAME_TYPE_HEADERS,
-            len: 2,
+            len: 8,
         },
         &mut request_header,
     )
     .expect("request headers frame header encodes");
 
-    let mut request_data = alloc::vec::Vec::with_capacity(request_header_len + 3);
+    let mut request_data = alloc::vec::Vec::with_capacity(request_header_len + 9);
     request_data.extend_from_slice(&request_header[..request_header_len]);
-    request_data.extend_from_slice(&[0xaa, 0xbb, 0xcc]);
+    // Minimal GET (see m1_request_stream_happy_path.rs), then the first byte
+    // of an 8-byte varint: a body frame header that never completes.
+    request_data.extend_from_slice(&[0x00, 0x00, 0xd1, 0xd7, 0xc1, 0x50, 0x01, b'a', 0xcc]);
 
     h.run_script(&[
         ScriptStep::InQuicOpen {
@@ -61,8 +63,18 @@ fn extra_bytes_after_declared_headers_payload_closes_connection() {
         ScriptStep::InQuicData {
             id: request_stream_id,
             data: request_data,
-            fin: false,
+            fin: true,
         },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: request_stream_id,
+            data_prefix: alloc::vec![consts::FRAME_TYPE_HEADERS as u8, 0x03, 0x00, 0x00, 0xd9],
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: request_stream_id,
+            data_prefix: alloc::vec![consts::FRAME_TYPE_DATA as u8, 0x01, 0x01],
+            fin: true,
+        }),
         ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
             app_error: consts::H3_FRAME_ERROR,
         }),
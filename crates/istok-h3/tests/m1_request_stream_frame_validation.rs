@@ -16,7 +16,7 @@ fn control_settings_bytes() -> alloc::vec::Vec<u8> {
 
 #[test]
 fn request_first_frame_data_closes_frame_unexpected() {
-    let engine = H3Engine::new();
+    let engine = H3Engine::default();
     let mut h = MockHarness::new(engine);
 
     let control_stream_id = StreamId(3);
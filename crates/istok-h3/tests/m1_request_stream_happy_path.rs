@@ -8,7 +8,7 @@ use istok_transport::{StreamId, StreamKind};
 
 #[test]
 fn active_request_stream_accepts_headers_and_writes_headers_response() {
-    let engine = H3Engine::new();
+    let engine = H3Engine::default();
     let mut h = MockHarness::new(engine);
 
     let control_stream_id = StreamId(3);
@@ -28,7 +28,9 @@ fn active_request_stream_accepts_headers_and_writes_headers_response() {
     let control_total = control_type_len + control_frame_len;
 
     let mut header_buf = [0u8; 16];
-    let payload = [0xaa, 0xbb];
+    // Minimal GET: static refs for :method GET, :scheme https and :path /,
+    // plus a literal `:authority a`.
+    let payload = [0x00, 0x00, 0xd1, 0xd7, 0xc1, 0x50, 0x01, b'a'];
     let header_len = h3_frame::encode_frame_header(
         h3_frame::FrameHeader {
             ty: consts::FRAME_TYPE_HEADERS,
@@ -46,17 +48,17 @@ fn active_request_stream_accepts_headers_and_writes_headers_response() {
     let response_headers_header_len = h3_frame::encode_frame_header(
         h3_frame::FrameHeader {
             ty: consts::FRAME_TYPE_HEADERS,
-            len: 1,
+            len: 3,
         },
         &mut response_headers_header_buf,
     )
     .expect("response frame header encodes");
 
     let mut response_headers_prefix =
-        alloc::vec::Vec::with_capacity(response_headers_header_len + 1);
+        alloc::vec::Vec::with_capacity(response_headers_header_len + 3);
     response_headers_prefix
         .extend_from_slice(&response_headers_header_buf[..response_headers_header_len]);
-    response_headers_prefix.push(0x00);
+    response_headers_prefix.extend_from_slice(&[0x00, 0x00, 0xd9]);
 
     let mut response_data_header_buf = [0u8; 16];
     let response_data_header_len = h3_frame::encode_frame_header(
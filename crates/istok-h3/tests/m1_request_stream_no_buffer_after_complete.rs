@@ -7,8 +7,8 @@ use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
 use istok_transport::{StreamId, StreamKind};
 
 #[test]
-fn readable_after_request_completion_closes_connection() {
-    let engine = H3Engine::new();
+fn large_request_body_after_response_is_drained() {
+    let engine = H3Engine::default();
     let mut h = MockHarness::new(engine);
 
     let control_stream_id = StreamId(3);
@@ -28,7 +28,9 @@ fn readable_after_request_completion_closes_connection() {
     let control_total = control_type_len + control_frame_len;
 
     let mut request_buf = [0u8; 16];
-    let request_payload = [0xaa, 0xbb];
+    // Minimal GET: static refs for :method GET, :scheme https and :path /,
+    // plus a literal `:authority a`.
+    let request_payload = [0x00, 0x00, 0xd1, 0xd7, 0xc1, 0x50, 0x01, b'a'];
     let request_header_len = h3_frame::encode_frame_header(
         h3_frame::FrameHeader {
             ty: consts::FRAME_TYPE_HEADERS,
@@ -46,16 +48,16 @@ fn readable_after_request_completion_closes_connection() {
     let response_headers_header_len = h3_frame::encode_frame_header(
         h3_frame::FrameHeader {
             ty: consts::FRAME_TYPE_HEADERS,
-            len: 1,
+            len: 3,
         },
         &mut response_headers_buf,
     )
     .expect("response header encodes");
 
     let mut response_headers_prefix =
-        alloc::vec::Vec::with_capacity(response_headers_header_len + 1);
+        alloc::vec::Vec::with_capacity(response_headers_header_len + 3);
     response_headers_prefix.extend_from_slice(&response_headers_buf[..response_headers_header_len]);
-    response_headers_prefix.push(0x00);
+    response_headers_prefix.extend_from_slice(&[0x00, 0x00, 0xd9]);
 
     let mut response_data_buf = [0u8; 16];
     let response_data_header_len = h3_frame::encode_frame_header(
@@ -71,7 +73,19 @@ fn readable_after_request_completion_closes_connection() {
     response_data_prefix.extend_from_slice(&response_data_buf[..response_data_header_len]);
     response_data_prefix.push(0x01);
 
-    let extra = alloc::vec![0x11; 64 * 1024];
+    // One DATA frame far larger than the request buffer cap.
+    let body_len = 64 * 1024;
+    let mut body_header = [0u8; 16];
+    let body_header_len = h3_frame::encode_frame_header(
+        h3_frame::FrameHeader {
+            ty: consts::FRAME_TYPE_DATA,
+            len: body_len as u64,
+        },
+        &mut body_header,
+    )
+    .expect("body header encodes");
+    let mut extra = alloc::vec::Vec::from(&body_header[..body_header_len]);
+    extra.resize(body_header_len + body_len, 0x11);
 
     h.run_script(&[
         ScriptStep::InQuicOpen {
@@ -111,16 +125,19 @@ fn readable_after_request_completion_closes_connection() {
             data: extra,
             fin: false,
         },
-        ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
-            app_error: consts::H3_GENERAL_PROTOCOL_ERROR,
-        }),
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: request_stream_id,
+            data: alloc::vec::Vec::new(),
+            fin: true,
+        },
         ScriptStep::ExpectNone,
     ]);
 }
 
 #[test]
 fn fin_only_readable_after_request_completion_is_ignored() {
-    let engine = H3Engine::new();
+    let engine = H3Engine::default();
     let mut h = MockHarness::new(engine);
 
     let control_stream_id = StreamId(3);
@@ -140,7 +157,9 @@ fn fin_only_readable_after_request_completion_is_ignored() {
     let control_total = control_type_len + control_frame_len;
 
     let mut request_buf = [0u8; 16];
-    let request_payload = [0xaa, 0xbb];
+    // Minimal GET: static refs for :method GET, :scheme https and :path /,
+    // plus a literal `:authority a`.
+    let request_payload = [0x00, 0x00, 0xd1, 0xd7, 0xc1, 0x50, 0x01, b'a'];
     let request_header_len = h3_frame::encode_frame_header(
         h3_frame::FrameHeader {
             ty: consts::FRAME_TYPE_HEADERS,
@@ -158,16 +177,16 @@ fn fin_only_readable_after_request_completion_is_ignored() {
     let response_headers_header_len = h3_frame::encode_frame_header(
         h3_frame::FrameHeader {
             ty: consts::FRAME_TYPE_HEADERS,
-            len: 1,
+            len: 3,
         },
         &mut response_headers_buf,
     )
     .expect("response header encodes");
 
     let mut response_headers_prefix =
-        alloc::vec::Vec::with_capacity(response_headers_header_len + 1);
+        alloc::vec::Vec::with_capacity(response_headers_header_len + 3);
     response_headers_prefix.extend_from_slice(&response_headers_buf[..response_headers_header_len]);
-    response_headers_prefix.push(0x00);
+    response_headers_prefix.extend_from_slice(&[0x00, 0x00, 0xd9]);
 
     let mut response_data_buf = [0u8; 16];
     let response_data_header_len = h3_frame::encode_frame_header(
@@ -8,7 +8,7 @@ use istok_transport::{StreamId, StreamKind};
 
 #[test]
 fn request_stream_opened_before_control_is_promoted_after_settings() {
-    let engine = H3Engine::new();
+    let engine = H3Engine::default();
     let mut h = MockHarness::new(engine);
 
     let request_stream_id = StreamId(0);
@@ -28,7 +28,9 @@ fn request_stream_opened_before_control_is_promoted_after_settings() {
     let control_total = control_type_len + control_frame_len;
 
     let mut request_header_buf = [0u8; 16];
-    let request_payload = [0xaa, 0xbb];
+    // Minimal GET: static refs for :method GET, :scheme https and :path /,
+    // plus a literal `:authority a`.
+    let request_payload = [0x00, 0x00, 0xd1, 0xd7, 0xc1, 0x50, 0x01, b'a'];
     let request_header_len = h3_frame::encode_frame_header(
         h3_frame::FrameHeader {
             ty: consts::FRAME_TYPE_HEADERS,
@@ -47,17 +49,17 @@ fn request_stream_opened_before_control_is_promoted_after_settings() {
     let response_headers_header_len = h3_frame::encode_frame_header(
         h3_frame::FrameHeader {
             ty: consts::FRAME_TYPE_HEADERS,
-            len: 1,
+            len: 3,
         },
         &mut response_headers_header,
     )
     .expect("response frame header encodes");
 
     let mut response_headers_prefix =
-        alloc::vec::Vec::with_capacity(response_headers_header_len + 1);
+        alloc::vec::Vec::with_capacity(response_headers_header_len + 3);
     response_headers_prefix
         .extend_from_slice(&response_headers_header[..response_headers_header_len]);
-    response_headers_prefix.push(0x00);
+    response_headers_prefix.extend_from_slice(&[0x00, 0x00, 0xd9]);
 
     let mut response_data_header = [0u8; 16];
     let response_data_header_len = h3_frame::encode_frame_header(
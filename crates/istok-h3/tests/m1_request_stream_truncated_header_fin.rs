@@ -7,7 +7,7 @@ use istok_transport::{StreamId, StreamKind};
 
 #[test]
 fn request_headers_header_truncated_with_fin_closes_connection() {
-    let engine = H3Engine::new();
+    let engine = H3Engine::default();
     let mut h = MockHarness::new(engine);
 
     let control_stream_id = StreamId(3);
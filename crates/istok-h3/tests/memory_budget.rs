@@ -0,0 +1,252 @@
+extern crate alloc;
+
+use istok_core::h3::consts;
+use istok_core::h3::settings::Settings;
+use istok_core::qpack::HeaderField;
+use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
+use istok_h3::mock::{control_stream, frame, headers, settings_payload};
+use istok_h3::webtransport::Error;
+use istok_h3::{H3Config, H3ConfigBuilder, H3Engine};
+use istok_transport::{StreamId, StreamKind};
+
+const PEER_CONTROL: StreamId = StreamId(3);
+const REQUEST: StreamId = StreamId(0);
+
+fn wt_settings() -> Settings {
+    Settings {
+        enable_connect_protocol: Some(1),
+        h3_datagram: Some(1),
+        webtransport_max_sessions: Some(1),
+        ..Settings::default()
+    }
+}
+
+/// 1 KiB budget: room for one 528-byte request buffer plus a little.
+fn small_budget() -> H3ConfigBuilder {
+    H3Config::builder()
+        .max_header_frame_size(512)
+        .max_request_buffer(528)
+        .memory_budget(1024)
+}
+
+fn harness(config: H3ConfigBuilder, settings: &Settings) -> MockHarness<H3Engine> {
+    let mut h = MockHarness::new(H3Engine::new(config.build().expect("valid config")));
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: PEER_CONTROL,
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::InQuicData {
+            id: PEER_CONTROL,
+            data: control_stream(&settings_payload(settings)),
+            fin: false,
+        },
+        ScriptStep::InQuicOpen {
+            id: REQUEST,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::ExpectNone,
+    ]);
+    h
+}
+
+/// Accepted CONNECT tunnel on `REQUEST` whose peer takes no bytes.
+fn blocked_tunnel() -> MockHarness<H3Engine> {
+    let mut h = harness(small_budget(), &Settings::default());
+    h.run_script(&[
+        ScriptStep::InQuicWritable {
+            id: REQUEST,
+            capacity: 0,
+        },
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: headers(&[
+                HeaderField {
+                    name: b":method",
+                    value: b"CONNECT",
+                },
+                HeaderField {
+                    name: b":authority",
+                    value: b"example.com:443",
+                },
+            ]),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::AppConnectTunnel {
+            id: REQUEST,
+            authority: b"example.com:443".to_vec(),
+        }),
+        ScriptStep::InAppAcceptTunnel { id: REQUEST },
+        ScriptStep::ExpectNone,
+    ]);
+    h
+}
+
+fn tunnel_send(len: usize) -> ScriptStep {
+    ScriptStep::InAppTunnelSend {
+        id: REQUEST,
+        data: alloc::vec![b'x'; len],
+        fin: false,
+    }
+}
+
+fn memory_usage(h: &mut MockHarness<H3Engine>) -> usize {
+    h.apply(|engine, _| engine.memory_usage())
+}
+
+#[test]
+fn usage_counts_queued_writes() {
+    let mut h = blocked_tunnel();
+    // The 200 response HEADERS wait for credit too.
+    assert_eq!(memory_usage(&mut h), 5);
+    h.run_script(&[tunnel_send(100), ScriptStep::ExpectNone]);
+    assert_eq!(memory_usage(&mut h), 5 + 103);
+}
+
+#[test]
+fn new_streams_are_refused_without_room_for_a_request() {
+    let mut h = blocked_tunnel();
+    h.run_script(&[
+        tunnel_send(600),
+        ScriptStep::ExpectNone,
+        // 608 queued: a 528-byte request buffer no longer fits in 1 KiB.
+        ScriptStep::InQuicOpen {
+            id: StreamId(4),
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStopSending {
+            id: StreamId(4),
+            app_error: consts::H3_REQUEST_REJECTED,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicResetStream {
+            id: StreamId(4),
+            app_error: consts::H3_REQUEST_REJECTED,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn exceeding_the_budget_sheds_the_stream() {
+    let mut h = blocked_tunnel();
+    h.run_script(&[
+        tunnel_send(600),
+        ScriptStep::ExpectNone,
+        tunnel_send(500),
+        ScriptStep::Expect(ExpectCommand::QuicStopSending {
+            id: REQUEST,
+            app_error: consts::H3_EXCESSIVE_LOAD,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicResetStream {
+            id: REQUEST,
+            app_error: consts::H3_EXCESSIVE_LOAD,
+        }),
+        ScriptStep::Expect(ExpectCommand::AppStreamAborted {
+            id: REQUEST,
+            app_error: consts::H3_EXCESSIVE_LOAD,
+        }),
+        ScriptStep::ExpectNone,
+        // The tunnel is gone and its memory released.
+        tunnel_send(10),
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicOpen {
+            id: StreamId(8),
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::ExpectNone,
+    ]);
+    assert_eq!(memory_usage(&mut h), 0);
+}
+
+#[test]
+fn largest_webtransport_stream_is_shed_first() {
+    let settings = wt_settings();
+    let mut h = harness(small_budget().settings(settings.clone()), &settings);
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: headers(&[
+                HeaderField {
+                    name: b":method",
+                    value: b"CONNECT",
+                },
+                HeaderField {
+                    name: b":protocol",
+                    value: b"webtransport",
+                },
+                HeaderField {
+                    name: b":scheme",
+                    value: b"https",
+                },
+                HeaderField {
+                    name: b":authority",
+                    value: b"example.com",
+                },
+                HeaderField {
+                    name: b":path",
+                    value: b"/game",
+                },
+            ]),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00, 0xd9]),
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::AppWebTransportSession { id: REQUEST }),
+    ]);
+
+    let (big, small) = h.apply(|engine, out| {
+        let mut session = engine.webtransport_session(REQUEST).expect("session");
+        (session.open_uni(out), session.open_uni(out))
+    });
+    h.run_script(&[
+        ScriptStep::Expect(ExpectCommand::QuicOpenUni),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: big,
+            data_prefix: alloc::vec![0x40, 0x54, 0x00],
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicOpenUni),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: small,
+            data_prefix: alloc::vec![0x40, 0x54, 0x00],
+            fin: false,
+        }),
+        ScriptStep::InQuicWritable {
+            id: big,
+            capacity: 0,
+        },
+        ScriptStep::InQuicWritable {
+            id: small,
+            capacity: 0,
+        },
+        ScriptStep::ExpectNone,
+    ]);
+
+    // 700 + 400 bytes queued: over the budget, the 700-byte stream goes.
+    h.apply(|engine, out| {
+        let mut session = engine.webtransport_session(REQUEST).expect("session");
+        session.send(big, &[0; 700], false, out).expect("sent");
+        session.send(small, &[0; 400], false, out).expect("sent");
+    });
+    h.run_script(&[
+        ScriptStep::Expect(ExpectCommand::QuicResetStream {
+            id: big,
+            app_error: consts::H3_EXCESSIVE_LOAD,
+        }),
+        ScriptStep::Expect(ExpectCommand::AppStreamAborted {
+            id: big,
+            app_error: consts::H3_EXCESSIVE_LOAD,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+    assert_eq!(memory_usage(&mut h), 400);
+
+    let resent = h.apply(|engine, out| {
+        let mut session = engine.webtransport_session(REQUEST).expect("session");
+        session.send(big, b"more", false, out)
+    });
+    assert_eq!(resent, Err(Error::UnknownStream));
+}
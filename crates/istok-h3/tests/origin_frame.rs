@@ -0,0 +1,192 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::codec::origin;
+use istok_core::h3::consts;
+use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
+use istok_h3::mock::{control_stream, frame};
+use istok_h3::{H3Config, H3Engine, Role};
+use istok_transport::{StreamId, StreamKind};
+
+const LOCAL_CONTROL: StreamId = StreamId(2);
+const PEER_CONTROL: StreamId = StreamId(3);
+const REQUEST: StreamId = StreamId(0);
+
+fn origin_frame(origins: &[&[u8]]) -> Vec<u8> {
+    let mut payload = alloc::vec![0u8; origin::encoded_len(origins)];
+    let len = origin::encode_origin(origins, &mut payload).expect("origins encode");
+    frame(consts::FRAME_TYPE_ORIGIN, &payload[..len])
+}
+
+fn open_peer_control(h: &mut MockHarness<H3Engine>, frames: &[u8], expect: &[ScriptStep]) {
+    let mut script = alloc::vec![
+        ScriptStep::InQuicOpen {
+            id: PEER_CONTROL,
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::InQuicData {
+            id: PEER_CONTROL,
+            data: [control_stream(&[]).as_slice(), frames].concat(),
+            fin: false,
+        },
+    ];
+    script.extend_from_slice(expect);
+    script.push(ScriptStep::ExpectNone);
+    h.run_script(&script);
+}
+
+fn client() -> H3Engine {
+    H3Engine::new(
+        H3Config::builder()
+            .role(Role::Client)
+            .build()
+            .expect("valid config"),
+    )
+}
+
+fn peer_origins(h: &mut MockHarness<H3Engine>) -> Vec<Vec<u8>> {
+    h.apply(|engine, _| engine.peer_origins().to_vec())
+}
+
+#[test]
+fn server_sends_origin_set_after_settings() {
+    let mut engine = H3Engine::default();
+    engine.set_origin_set(alloc::vec![
+        b"https://a.example".to_vec(),
+        b"https://b.example".to_vec(),
+    ]);
+
+    let settings = control_stream(&[]);
+
+    MockHarness::new(engine).run_script(&[
+        ScriptStep::InBoot,
+        ScriptStep::Expect(ExpectCommand::QuicOpenUni),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: LOCAL_CONTROL,
+            data_prefix: settings,
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: LOCAL_CONTROL,
+            data_prefix: origin_frame(&[b"https://a.example", b"https://b.example"]),
+            fin: false,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn client_does_not_send_origin_set() {
+    let mut engine = client();
+    engine.set_origin_set(alloc::vec![b"https://a.example".to_vec()]);
+    MockHarness::new(engine).run_script(&[
+        ScriptStep::InBoot,
+        ScriptStep::Expect(ExpectCommand::QuicOpenUni),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: LOCAL_CONTROL,
+            data_prefix: Vec::new(),
+            fin: false,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn client_records_received_origins() {
+    let mut h = MockHarness::new(client());
+    let mut frames = origin_frame(&[b"https://a.example", b"https://b.example:8443"]);
+    frames.extend_from_slice(&origin_frame(&[b"https://a.example", b"https://c.example"]));
+    open_peer_control(&mut h, &frames, &[]);
+
+    assert_eq!(
+        peer_origins(&mut h),
+        [
+            b"https://a.example".to_vec(),
+            b"https://b.example:8443".to_vec(),
+            b"https://c.example".to_vec(),
+        ]
+    );
+}
+
+#[test]
+fn client_closes_on_truncated_origin_entry() {
+    let mut h = MockHarness::new(client());
+    let frames = frame(consts::FRAME_TYPE_ORIGIN, b"\x00\x10https://a");
+    open_peer_control(
+        &mut h,
+        &frames,
+        &[ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+            app_error: consts::H3_FRAME_ERROR,
+        })],
+    );
+    assert!(peer_origins(&mut h).is_empty());
+}
+
+#[test]
+fn server_ignores_origin_frame() {
+    let mut h = MockHarness::new(H3Engine::default());
+    // Even a malformed one: the payload is never parsed.
+    let mut frames = frame(consts::FRAME_TYPE_ORIGIN, b"\x00\x10https://a");
+    frames.extend_from_slice(&origin_frame(&[b"https://a.example"]));
+    open_peer_control(&mut h, &frames, &[]);
+    assert!(peer_origins(&mut h).is_empty());
+}
+
+#[test]
+fn origin_frame_on_request_stream_is_unexpected() {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_peer_control(&mut h, &[], &[]);
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: REQUEST,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: origin_frame(&[b"https://a.example"]),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+            app_error: consts::H3_FRAME_UNEXPECTED,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn origin_frame_after_request_headers_is_unexpected() {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_peer_control(&mut h, &[], &[]);
+
+    let mut request = frame(
+        consts::FRAME_TYPE_HEADERS,
+        &[0x00, 0x00, 0xd1, 0xd7, 0xc1, 0x50, 0x01, b'a'],
+    );
+    request.extend_from_slice(&origin_frame(&[b"https://a.example"]));
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: REQUEST,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: request,
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00, 0xd9]),
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: frame(consts::FRAME_TYPE_DATA, &[0x01]),
+            fin: true,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+            app_error: consts::H3_FRAME_UNEXPECTED,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
@@ -0,0 +1,212 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::bytes::Bytes;
+use istok_core::h3::consts;
+use istok_core::h3::settings::Settings;
+use istok_h3::mock::{control_stream, frame};
+use istok_h3::timers::{IDLE_TIMER, stream_timer};
+use istok_h3::{AppEvent, Deadline, EngineEvent, H3Config, H3Engine, PollEngine};
+use istok_transport::{InlineHeader, QuicCommand, QuicEvent, StreamId, StreamKind};
+
+const PEER_CONTROL: StreamId = StreamId(3);
+const REQUEST: StreamId = StreamId(0);
+
+fn get() -> Vec<u8> {
+    frame(
+        consts::FRAME_TYPE_HEADERS,
+        &[0x00, 0x00, 0xd1, 0xd7, 0xc1, 0x50, 0x01, b'a'],
+    )
+}
+
+fn engine(config: H3Config) -> PollEngine<H3Engine> {
+    PollEngine::new(H3Engine::new(config))
+}
+
+fn commands(e: &mut PollEngine<H3Engine>) -> Vec<QuicCommand<'static>> {
+    core::iter::from_fn(|| e.poll_command()).collect()
+}
+
+fn open(e: &mut PollEngine<H3Engine>, now_ms: u64, id: StreamId, kind: StreamKind) {
+    e.handle_event(
+        now_ms,
+        EngineEvent::Quic(QuicEvent::StreamOpened { id, kind }),
+    );
+}
+
+fn data(e: &mut PollEngine<H3Engine>, now_ms: u64, id: StreamId, data: &[u8], fin: bool) {
+    e.handle_event(
+        now_ms,
+        EngineEvent::Quic(QuicEvent::StreamReadable { id, data, fin }),
+    );
+}
+
+#[test]
+fn boot_commands_are_polled_in_order() {
+    let mut e = engine(H3Config::default());
+    assert!(e.poll_command().is_none());
+
+    e.handle_event(0, EngineEvent::Boot);
+    let cmds = commands(&mut e);
+    assert_eq!(cmds.len(), 2);
+    assert!(matches!(cmds[0], QuicCommand::OpenUni { .. }));
+    assert!(matches!(
+        &cmds[1],
+        QuicCommand::StreamWriteOwned { id: StreamId(2), data, fin: false }
+            if data.starts_with(&control_stream(&[])[..1])
+    ));
+    assert!(e.poll_app_event().is_none());
+    assert!(e.poll_timeout().is_none());
+}
+
+#[test]
+fn responses_outlive_the_input_buffer() {
+    let mut e = engine(H3Config::default());
+    open(&mut e, 0, PEER_CONTROL, StreamKind::Uni);
+    data(&mut e, 0, PEER_CONTROL, &control_stream(&[]), false);
+    open(&mut e, 0, REQUEST, StreamKind::Bidi);
+    {
+        let request = get();
+        data(&mut e, 0, REQUEST, &request, true);
+    }
+
+    let cmds = commands(&mut e);
+    assert_eq!(
+        cmds,
+        [
+            QuicCommand::StreamWriteOwned {
+                id: REQUEST,
+                data: frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00, 0xd9]),
+                fin: false,
+            },
+            QuicCommand::StreamWriteVectored {
+                id: REQUEST,
+                header: InlineHeader::new(&[0x00, 0x01]).expect("fits"),
+                body: Bytes::from(alloc::vec![0x01]),
+                fin: true,
+            },
+        ]
+    );
+}
+
+#[test]
+fn datagram_payload_is_copied_out_of_the_event() {
+    let datagrams = Settings {
+        h3_datagram: Some(1),
+        ..Settings::default()
+    };
+    let mut e = engine(
+        H3Config::builder()
+            .settings(datagrams)
+            .build()
+            .expect("valid config"),
+    );
+    open(&mut e, 0, PEER_CONTROL, StreamKind::Uni);
+    data(
+        &mut e,
+        0,
+        PEER_CONTROL,
+        &control_stream(&[0x33, 0x01]),
+        false,
+    );
+    open(&mut e, 0, REQUEST, StreamKind::Bidi);
+    {
+        let datagram = alloc::vec![0x00, 0xaa, 0xbb];
+        e.handle_event(
+            0,
+            EngineEvent::Quic(QuicEvent::Datagram { data: &datagram }),
+        );
+    }
+
+    match e.poll_app_event() {
+        Some(AppEvent::Datagram { id, payload }) => {
+            assert_eq!(id, REQUEST);
+            assert_eq!(*payload, [0xaa, 0xbb]);
+        }
+        _ => panic!("expected a datagram"),
+    }
+    assert!(e.poll_app_event().is_none());
+}
+
+#[test]
+fn deadlines_are_absolute_and_fire_when_due() {
+    let mut e = engine(
+        H3Config::builder()
+            .idle_timeout_ms(Some(30_000))
+            .build()
+            .expect("valid config"),
+    );
+    e.handle_event(1_000, EngineEvent::Boot);
+    let _ = commands(&mut e);
+    assert_eq!(
+        e.poll_timeout(),
+        Some(Deadline {
+            at_ms: 31_000,
+            id: IDLE_TIMER,
+        })
+    );
+
+    // Activity pushes the deadline out.
+    open(&mut e, 5_000, PEER_CONTROL, StreamKind::Uni);
+    assert_eq!(e.poll_timeout().map(|d| d.at_ms), Some(35_000));
+
+    e.handle_timeout(34_999);
+    assert!(e.poll_command().is_none());
+
+    e.handle_timeout(35_000);
+    assert_eq!(
+        commands(&mut e),
+        [QuicCommand::CloseConnection {
+            app_error: consts::H3_NO_ERROR,
+        }]
+    );
+    assert!(e.poll_timeout().is_none());
+}
+
+#[test]
+fn earliest_deadline_is_reported_first() {
+    let mut e = engine(
+        H3Config::builder()
+            .idle_timeout_ms(Some(30_000))
+            .header_read_timeout_ms(Some(5_000))
+            .build()
+            .expect("valid config"),
+    );
+    open(&mut e, 0, PEER_CONTROL, StreamKind::Uni);
+    data(&mut e, 0, PEER_CONTROL, &control_stream(&[]), false);
+    open(&mut e, 100, REQUEST, StreamKind::Bidi);
+    let request_timer = stream_timer(REQUEST).expect("request stream has a timer");
+    assert_eq!(
+        e.poll_timeout(),
+        Some(Deadline {
+            at_ms: 5_100,
+            id: request_timer,
+        })
+    );
+
+    // The request timer fires alone; the idle timer stays armed.
+    e.handle_timeout(6_000);
+    assert!(!commands(&mut e).is_empty());
+    assert_eq!(e.poll_timeout().map(|d| d.id), Some(IDLE_TIMER));
+}
+
+#[test]
+fn time_never_moves_backwards() {
+    let mut e = engine(
+        H3Config::builder()
+            .idle_timeout_ms(Some(1_000))
+            .build()
+            .expect("valid config"),
+    );
+    e.handle_event(10_000, EngineEvent::Boot);
+    e.handle_event(
+        5_000,
+        EngineEvent::Quic(QuicEvent::StreamOpened {
+            id: PEER_CONTROL,
+            kind: StreamKind::Uni,
+        }),
+    );
+    assert_eq!(e.now_ms(), 10_000);
+    assert_eq!(e.poll_timeout().map(|d| d.at_ms), Some(11_000));
+}
@@ -0,0 +1,325 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::codec::priority::{self, Priority, PriorityUpdate};
+use istok_core::h3::consts;
+use istok_core::qpack::{self, HeaderField};
+use istok_h3::H3Engine;
+use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
+use istok_h3::mock::{control_stream, frame};
+use istok_transport::{StreamId, StreamKind};
+
+const CONTROL: StreamId = StreamId(3);
+const REQUEST: StreamId = StreamId(0);
+
+fn priority_update(ty: u64, element_id: u64, field_value: &[u8]) -> Vec<u8> {
+    let mut payload = [0u8; 64];
+    let len = priority::encode_priority_update(
+        &PriorityUpdate {
+            element_id,
+            field_value,
+        },
+        &mut payload,
+    )
+    .expect("priority update encodes");
+    frame(ty, &payload[..len])
+}
+
+fn open_control(h: &mut MockHarness<H3Engine>) {
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: CONTROL,
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::InQuicData {
+            id: CONTROL,
+            data: control_stream(&[]),
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+/// GET request HEADERS, with a `priority` header when given.
+fn get(priority: Option<&[u8]>) -> Vec<u8> {
+    let mut fields = alloc::vec![
+        HeaderField {
+            name: b":method",
+            value: b"GET",
+        },
+        HeaderField {
+            name: b":scheme",
+            value: b"https",
+        },
+        HeaderField {
+            name: b":authority",
+            value: b"example.com",
+        },
+        HeaderField {
+            name: b":path",
+            value: b"/",
+        },
+    ];
+    if let Some(value) = priority {
+        fields.push(HeaderField {
+            name: b"priority",
+            value,
+        });
+    }
+    let mut block = [0u8; 64];
+    let len = qpack::encode(&fields, &mut block).expect("qpack encodes");
+    frame(consts::FRAME_TYPE_HEADERS, &block[..len])
+}
+
+/// Send a request on `id` whose response is held back by zero stream
+/// credit, so its priority stays observable.
+fn held_request(h: &mut MockHarness<H3Engine>, id: StreamId, priority: Option<&[u8]>) {
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::InQuicWritable { id, capacity: 0 },
+        ScriptStep::InQuicData {
+            id,
+            data: get(priority),
+            fin: true,
+        },
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+fn send_update(h: &mut MockHarness<H3Engine>, element_id: u64, field_value: &[u8]) {
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: CONTROL,
+            data: priority_update(
+                consts::FRAME_TYPE_PRIORITY_UPDATE_REQUEST,
+                element_id,
+                field_value,
+            ),
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+fn urgency(urgency: u8) -> Option<Priority> {
+    Some(Priority {
+        urgency,
+        incremental: false,
+    })
+}
+
+fn expect_close(h: &mut MockHarness<H3Engine>, data: Vec<u8>, app_error: u64) {
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: CONTROL,
+            data,
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicCloseConnection { app_error }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn priority_update_is_accepted_on_control_stream() {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_control(&mut h);
+
+    let mut updates = priority_update(consts::FRAME_TYPE_PRIORITY_UPDATE_REQUEST, 0, b"u=1, i");
+    updates.extend_from_slice(&priority_update(
+        consts::FRAME_TYPE_PRIORITY_UPDATE_REQUEST,
+        4,
+        b"u=7, unknown=\"x\"",
+    ));
+    // Byte-at-a-time delivery exercises partial frame buffering.
+    let mut script = Vec::new();
+    for byte in updates {
+        script.push(ScriptStep::InQuicData {
+            id: CONTROL,
+            data: alloc::vec![byte],
+            fin: false,
+        });
+        script.push(ScriptStep::ExpectNone);
+    }
+    h.run_script(&script);
+}
+
+#[test]
+fn prioritized_request_still_gets_its_response() {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_control(&mut h);
+
+    let mut block = [0u8; 64];
+    let block_len = qpack::encode(
+        &[
+            HeaderField {
+                name: b":method",
+                value: b"GET",
+            },
+            HeaderField {
+                name: b":scheme",
+                value: b"https",
+            },
+            HeaderField {
+                name: b":authority",
+                value: b"example.com",
+            },
+            HeaderField {
+                name: b":path",
+                value: b"/",
+            },
+            HeaderField {
+                name: b"priority",
+                value: b"u=0",
+            },
+        ],
+        &mut block,
+    )
+    .expect("qpack encodes");
+
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: CONTROL,
+            data: priority_update(consts::FRAME_TYPE_PRIORITY_UPDATE_REQUEST, 0, b"u=6"),
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicOpen {
+            id: REQUEST,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: frame(consts::FRAME_TYPE_HEADERS, &block[..block_len]),
+            fin: true,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00, 0xd9]),
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: frame(consts::FRAME_TYPE_DATA, &[0x01]),
+            fin: true,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn push_priority_update_is_id_error() {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_control(&mut h);
+    expect_close(
+        &mut h,
+        priority_update(consts::FRAME_TYPE_PRIORITY_UPDATE_PUSH, 0, b"u=1"),
+        consts::H3_ID_ERROR,
+    );
+}
+
+#[test]
+fn priority_update_for_non_request_stream_is_id_error() {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_control(&mut h);
+    expect_close(
+        &mut h,
+        priority_update(consts::FRAME_TYPE_PRIORITY_UPDATE_REQUEST, 2, b"u=1"),
+        consts::H3_ID_ERROR,
+    );
+}
+
+#[test]
+fn unparsable_priority_field_is_general_protocol_error() {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_control(&mut h);
+    expect_close(
+        &mut h,
+        priority_update(consts::FRAME_TYPE_PRIORITY_UPDATE_REQUEST, 0, b"u=1,"),
+        consts::H3_GENERAL_PROTOCOL_ERROR,
+    );
+}
+
+#[test]
+fn empty_priority_update_payload_is_frame_error() {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_control(&mut h);
+    expect_close(
+        &mut h,
+        frame(consts::FRAME_TYPE_PRIORITY_UPDATE_REQUEST, &[]),
+        consts::H3_FRAME_ERROR,
+    );
+}
+
+#[test]
+fn priority_update_on_request_stream_is_frame_unexpected() {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_control(&mut h);
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: REQUEST,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: priority_update(consts::FRAME_TYPE_PRIORITY_UPDATE_REQUEST, 0, b"u=1"),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+            app_error: consts::H3_FRAME_UNEXPECTED,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn update_before_the_request_overrides_its_header() {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_control(&mut h);
+    send_update(&mut h, 0, b"u=6");
+    assert_eq!(h.apply(|engine, _| engine.stream_priority(REQUEST)), None);
+
+    held_request(&mut h, REQUEST, Some(b"u=0"));
+    assert_eq!(
+        h.apply(|engine, _| engine.stream_priority(REQUEST)),
+        urgency(6)
+    );
+
+    // Once the request set a priority, updates apply at once.
+    send_update(&mut h, 0, b"u=2");
+    assert_eq!(
+        h.apply(|engine, _| engine.stream_priority(REQUEST)),
+        urgency(2)
+    );
+}
+
+#[test]
+fn stream_credit_does_not_count_as_a_priority() {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_control(&mut h);
+    held_request(&mut h, REQUEST, Some(b"u=1"));
+    assert_eq!(
+        h.apply(|engine, _| engine.stream_priority(REQUEST)),
+        urgency(1)
+    );
+}
+
+#[test]
+fn updates_for_streams_that_never_open_are_evicted() {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_control(&mut h);
+    // One more update than is kept: the lowest stream id is dropped.
+    for n in 0..=64 {
+        send_update(&mut h, n * 4, b"u=1");
+    }
+
+    held_request(&mut h, StreamId(0), None);
+    held_request(&mut h, StreamId(4), None);
+    let priorities =
+        h.apply(|engine, _| [StreamId(0), StreamId(4)].map(|id| engine.stream_priority(id)));
+    assert_eq!(priorities, [Some(Priority::default()), urgency(1)]);
+}
@@ -0,0 +1,160 @@
+//! qlog traces of scripted connections, compared against the golden files
+//! in `tests/golden`. Run with `BLESS=1` to rewrite them after an
+//! intended change.
+
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use istok_core::h3::consts;
+use istok_core::h3::settings::Settings;
+use istok_h3::mock::Discard;
+use istok_h3::mock::{control_stream, frame, settings_payload};
+use istok_h3::{Engine, EngineEvent, H3Config, H3Engine, Qlog, QlogWriter};
+use istok_transport::{QuicEvent, StreamId, StreamKind};
+
+const PEER_CONTROL: StreamId = StreamId(3);
+const REQUEST: StreamId = StreamId(0);
+
+/// Trace shared between the engine and the test.
+#[derive(Clone, Default)]
+struct Trace(Rc<RefCell<Vec<u8>>>);
+
+impl QlogWriter for Trace {
+    fn write_record(&mut self, record: &[u8]) {
+        self.0.borrow_mut().extend_from_slice(record);
+    }
+}
+
+/// Run `events` through an engine tracing to qlog, with a clock that
+/// advances by 0.5 ms per event.
+fn trace(config: H3Config, events: &[QuicEvent<'_>]) -> Vec<u8> {
+    let out = Trace::default();
+    let mut now = 0.0;
+    let clock = move || {
+        now += 0.5;
+        now
+    };
+    let mut engine = H3Engine::new(config);
+    engine.set_qlog(Qlog::new("istok test", out.clone(), clock));
+    engine.on_event(EngineEvent::Boot, &mut Discard);
+    for ev in events {
+        engine.on_event(EngineEvent::Quic(ev.clone()), &mut Discard);
+    }
+    out.0.take()
+}
+
+fn check_golden(name: &str, trace: &[u8]) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    if std::env::var_os("BLESS").is_some() {
+        std::fs::write(&path, trace).expect("golden file written");
+        return;
+    }
+    let golden = std::fs::read(&path).expect("golden file exists");
+    assert!(
+        golden == trace,
+        "{name} differs; rerun with BLESS=1 if intended:\n{}",
+        String::from_utf8_lossy(trace)
+    );
+}
+
+fn get_request_events<'a>(control: &'a [u8], request: &'a [u8]) -> [QuicEvent<'a>; 4] {
+    [
+        QuicEvent::StreamOpened {
+            id: PEER_CONTROL,
+            kind: StreamKind::Uni,
+        },
+        QuicEvent::StreamReadable {
+            id: PEER_CONTROL,
+            data: control,
+            fin: false,
+        },
+        QuicEvent::StreamOpened {
+            id: REQUEST,
+            kind: StreamKind::Bidi,
+        },
+        QuicEvent::StreamReadable {
+            id: REQUEST,
+            data: request,
+            fin: true,
+        },
+    ]
+}
+
+#[test]
+fn get_request_trace_matches_golden() {
+    let control = control_stream(&settings_payload(&Settings {
+        max_field_section_size: Some(4096),
+        ..Settings::default()
+    }));
+    // `:method GET`, `:scheme https`, `:path /`, `:authority a`.
+    let request = frame(
+        consts::FRAME_TYPE_HEADERS,
+        &[0x00, 0x00, 0xd1, 0xd7, 0xc1, 0x50, 0x01, b'a'],
+    );
+    let events = get_request_events(&control, &request);
+
+    let first = trace(H3Config::default(), &events);
+    assert_eq!(first, trace(H3Config::default(), &events), "deterministic");
+    check_golden("get_request.sqlog", &first);
+}
+
+#[test]
+fn grease_and_escaped_values_trace_matches_golden() {
+    let config = H3Config::builder()
+        .grease(Some(7))
+        .build()
+        .expect("valid config");
+    let control = control_stream(&settings_payload(&Settings::default()));
+    // `:authority` with a quote, a backslash and a non-ASCII byte.
+    let request = frame(
+        consts::FRAME_TYPE_HEADERS,
+        &[
+            0x00, 0x00, 0xd1, 0xd7, 0xc1, 0x50, 0x04, b'"', b'\\', 0xff, b'a',
+        ],
+    );
+    let events = get_request_events(&control, &request);
+
+    let trace = trace(config, &events);
+    check_golden("grease_escaped.sqlog", &trace);
+}
+
+#[test]
+fn records_are_json_seq() {
+    let control = control_stream(&settings_payload(&Settings::default()));
+    let trace = trace(
+        H3Config::default(),
+        &[
+            QuicEvent::StreamOpened {
+                id: PEER_CONTROL,
+                kind: StreamKind::Uni,
+            },
+            QuicEvent::StreamReadable {
+                id: PEER_CONTROL,
+                data: &control,
+                fin: false,
+            },
+        ],
+    );
+    let text = String::from_utf8(trace).expect("UTF-8");
+    let records: Vec<&str> = text.split_terminator('\n').collect();
+    assert!(records.iter().all(|record| record.starts_with("\u{1e}{")));
+    assert!(records[0].contains("\"qlog_format\":\"JSON-SEQ\""));
+    let names: Vec<&str> = records[1..]
+        .iter()
+        .filter_map(|record| record.split("\"name\":\"").nth(1)?.split('"').next())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "http:stream_type_set",
+            "http:frame_created",
+            "http:parameters_set",
+            "http:stream_type_set",
+            "http:frame_parsed",
+            "http:parameters_set",
+        ]
+    );
+}
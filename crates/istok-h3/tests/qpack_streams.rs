@@ -0,0 +1,155 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::h3::consts;
+use istok_h3::H3Engine;
+use istok_h3::mock::control_stream;
+use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
+use istok_transport::{StreamError, StreamId, StreamKind};
+
+const ENCODER: StreamId = StreamId(7);
+const DECODER: StreamId = StreamId(11);
+
+fn open(id: StreamId) -> ScriptStep {
+    ScriptStep::InQuicOpen {
+        id,
+        kind: StreamKind::Uni,
+    }
+}
+
+fn data(id: StreamId, bytes: &[u8], fin: bool) -> ScriptStep {
+    ScriptStep::InQuicData {
+        id,
+        data: Vec::from(bytes),
+        fin,
+    }
+}
+
+fn close(app_error: u64) -> ScriptStep {
+    ScriptStep::Expect(ExpectCommand::QuicCloseConnection { app_error })
+}
+
+#[test]
+fn zero_capacity_and_stream_cancellation_are_accepted() {
+    let mut h = MockHarness::new(H3Engine::default());
+    h.run_script(&[
+        open(ENCODER),
+        data(
+            ENCODER,
+            &[consts::STREAM_TYPE_QPACK_ENCODER as u8, 0x20],
+            false,
+        ),
+        data(ENCODER, &[0x20], false),
+        open(DECODER),
+        // Stream Cancellation for stream 4, then one whose id spans reads.
+        data(
+            DECODER,
+            &[consts::STREAM_TYPE_QPACK_DECODER as u8, 0x44, 0x7f, 0x81],
+            false,
+        ),
+        data(DECODER, &[0x01], false),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn non_zero_capacity_is_encoder_stream_error() {
+    let mut h = MockHarness::new(H3Engine::default());
+    h.run_script(&[
+        open(ENCODER),
+        data(
+            ENCODER,
+            &[consts::STREAM_TYPE_QPACK_ENCODER as u8, 0x3f, 0x01],
+            false,
+        ),
+        close(consts::H3_QPACK_ENCODER_STREAM_ERROR),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn insert_is_encoder_stream_error() {
+    let mut h = MockHarness::new(H3Engine::default());
+    h.run_script(&[
+        open(ENCODER),
+        data(ENCODER, &[consts::STREAM_TYPE_QPACK_ENCODER as u8], false),
+        // Insert With Name Reference, static entry 1.
+        data(ENCODER, &[0xc1, 0x01, b'a'], false),
+        close(consts::H3_QPACK_ENCODER_STREAM_ERROR),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn section_ack_and_insert_count_increment_are_decoder_stream_errors() {
+    for instruction in [0x80, 0x01] {
+        let mut h = MockHarness::new(H3Engine::default());
+        h.run_script(&[
+            open(DECODER),
+            data(
+                DECODER,
+                &[consts::STREAM_TYPE_QPACK_DECODER as u8, instruction],
+                false,
+            ),
+            close(consts::H3_QPACK_DECODER_STREAM_ERROR),
+            ScriptStep::ExpectNone,
+        ]);
+    }
+}
+
+#[test]
+fn second_qpack_stream_of_a_type_is_stream_creation_error() {
+    let mut h = MockHarness::new(H3Engine::default());
+    h.run_script(&[
+        open(ENCODER),
+        data(ENCODER, &[consts::STREAM_TYPE_QPACK_ENCODER as u8], false),
+        open(StreamId(15)),
+        data(
+            StreamId(15),
+            &[consts::STREAM_TYPE_QPACK_ENCODER as u8],
+            false,
+        ),
+        close(consts::H3_STREAM_CREATION_ERROR),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn closing_a_qpack_stream_is_closed_critical_stream() {
+    let mut h = MockHarness::new(H3Engine::default());
+    h.run_script(&[
+        open(DECODER),
+        data(DECODER, &[consts::STREAM_TYPE_QPACK_DECODER as u8], false),
+        data(DECODER, &[], true),
+        close(consts::H3_CLOSED_CRITICAL_STREAM),
+        ScriptStep::ExpectNone,
+    ]);
+
+    let mut h = MockHarness::new(H3Engine::default());
+    h.run_script(&[
+        open(ENCODER),
+        data(ENCODER, &[consts::STREAM_TYPE_QPACK_ENCODER as u8], false),
+        ScriptStep::InQuicStreamError {
+            id: ENCODER,
+            err: StreamError::Reset(consts::H3_NO_ERROR),
+        },
+        close(consts::H3_CLOSED_CRITICAL_STREAM),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn resetting_the_control_stream_is_closed_critical_stream() {
+    let mut h = MockHarness::new(H3Engine::default());
+    h.run_script(&[
+        open(StreamId(3)),
+        data(StreamId(3), &control_stream(&[]), false),
+        ScriptStep::InQuicStreamError {
+            id: StreamId(3),
+            err: StreamError::Reset(consts::H3_NO_ERROR),
+        },
+        close(consts::H3_CLOSED_CRITICAL_STREAM),
+        ScriptStep::ExpectNone,
+    ]);
+}
@@ -0,0 +1,273 @@
+use istok_core::h3::consts;
+use istok_core::h3::settings::Settings;
+use istok_core::qpack::HeaderField;
+use istok_h3::mock::{Discard, ExpectCommand, MockHarness, ScriptStep};
+use istok_h3::mock::{control_stream, frame, headers};
+use istok_h3::record::{self, RecordError};
+use istok_h3::{
+    AppAction, ConfigError, DosLimits, Engine, EngineEvent, H3Config, H3Engine, Recorder, Role,
+};
+use istok_transport::{QuicEvent, StreamError, StreamId, StreamKind};
+
+const PEER_CONTROL: StreamId = StreamId(3);
+const REQUEST: StreamId = StreamId(0);
+
+/// Record a connection that serves a GET, sees a reset stream and closes.
+fn record_session(recorder: &mut Recorder<H3Engine>) {
+    let control = control_stream(&[]);
+    // `:method GET`, `:scheme https`, `:path /`, `:authority a`.
+    let request = frame(
+        consts::FRAME_TYPE_HEADERS,
+        &[0x00, 0x00, 0xd1, 0xd7, 0xc1, 0x50, 0x01, b'a'],
+    );
+    recorder.on_event(EngineEvent::Boot, &mut Discard);
+    for ev in [
+        QuicEvent::StreamOpened {
+            id: PEER_CONTROL,
+            kind: StreamKind::Uni,
+        },
+        QuicEvent::StreamReadable {
+            id: PEER_CONTROL,
+            data: &control,
+            fin: false,
+        },
+        QuicEvent::StreamOpened {
+            id: REQUEST,
+            kind: StreamKind::Bidi,
+        },
+        QuicEvent::StreamWritable {
+            id: REQUEST,
+            capacity: u64::MAX,
+        },
+        QuicEvent::StreamReadable {
+            id: REQUEST,
+            data: &request,
+            fin: true,
+        },
+        QuicEvent::StreamOpened {
+            id: StreamId(4),
+            kind: StreamKind::Bidi,
+        },
+        QuicEvent::StreamError {
+            id: StreamId(4),
+            err: StreamError::Reset(consts::H3_REQUEST_CANCELLED),
+        },
+        QuicEvent::ConnectionClosed { app_error: None },
+    ] {
+        recorder.on_event(EngineEvent::Quic(ev), &mut Discard);
+    }
+    recorder.on_event(EngineEvent::Shutdown, &mut Discard);
+}
+
+#[test]
+fn a_recording_replays_as_a_mock_script() {
+    let mut recorder = Recorder::new(H3Engine::default());
+    record_session(&mut recorder);
+    let recording = record::decode(&recorder.take_recording()).expect("decodes");
+    assert_eq!(recording.config, H3Config::default());
+    let script = recording.script;
+
+    let inputs = script.iter().filter(|step| step.event().is_some()).count();
+    assert_eq!(inputs, 10);
+    assert!(matches!(
+        script
+            .iter()
+            .find(|step| matches!(step, ScriptStep::InQuicWritable { .. })),
+        Some(ScriptStep::InQuicWritable {
+            capacity: u64::MAX,
+            ..
+        })
+    ));
+    // The response went out as recorded: HEADERS then DATA, both on stream 0.
+    let writes: Vec<_> = script
+        .iter()
+        .filter_map(|step| match step {
+            ScriptStep::Expect(ExpectCommand::QuicStreamWrite { id, .. }) => Some(*id),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(writes, [StreamId(2), REQUEST, REQUEST]);
+
+    MockHarness::new(H3Engine::default()).run_script(&script);
+    assert!(record::replay(H3Engine::default(), &script).is_empty());
+}
+
+#[test]
+fn the_recorded_config_replays_without_diffs() {
+    let config = H3Config::builder()
+        .settings(Settings {
+            h3_datagram: Some(1),
+            ..Settings::default()
+        })
+        .max_field_section_size(8 * 1024)
+        .enable_extended_connect(true)
+        .max_concurrent_streams(8)
+        .idle_timeout_ms(Some(30_000))
+        .header_read_timeout_ms(Some(5_000))
+        .grease(Some(7))
+        .memory_budget(256 * 1024)
+        .dos_limits(DosLimits {
+            max_header_fields: 32,
+            ..DosLimits::default()
+        })
+        .report_requests(true)
+        .build()
+        .expect("valid config");
+    let mut recorder = Recorder::new(H3Engine::new(config.clone()));
+    record_session(&mut recorder);
+    let recording = record::decode(&recorder.take_recording()).expect("decodes");
+
+    assert_eq!(recording.config, config);
+    assert!(record::replay(recording.engine(), &recording.script).is_empty());
+    // A default engine writes other SETTINGS and no GREASE.
+    assert!(!record::replay(H3Engine::default(), &recording.script).is_empty());
+}
+
+#[test]
+fn a_client_exchange_replays() {
+    let client = || {
+        let config = H3Config::builder()
+            .role(Role::Client)
+            .build()
+            .expect("valid config");
+        H3Engine::new(config)
+    };
+    let field = |name, value| HeaderField { name, value };
+    let control = control_stream(&[]);
+    let response = [
+        headers(&[field(b":status", b"103"), field(b"link", b"</a>")]),
+        headers(&[field(b":status", b"200")]),
+        frame(consts::FRAME_TYPE_DATA, b"ok"),
+    ]
+    .concat();
+
+    let mut recorder = Recorder::new(client());
+    recorder.on_event(EngineEvent::Boot, &mut Discard);
+    recorder.on_event(
+        EngineEvent::App(AppAction::SendRequest {
+            id: REQUEST,
+            fields: vec![field(b":method", b"GET"), field(b":path", b"/")],
+        }),
+        &mut Discard,
+    );
+    for ev in [
+        QuicEvent::StreamOpened {
+            id: PEER_CONTROL,
+            kind: StreamKind::Uni,
+        },
+        QuicEvent::StreamReadable {
+            id: PEER_CONTROL,
+            data: &control,
+            fin: false,
+        },
+        QuicEvent::StreamReadable {
+            id: REQUEST,
+            data: &response,
+            fin: true,
+        },
+    ] {
+        recorder.on_event(EngineEvent::Quic(ev), &mut Discard);
+    }
+    let recording = record::decode(&recorder.take_recording()).expect("decodes");
+    assert_eq!(recording.config.role(), Role::Client);
+    let script = &recording.script;
+
+    let responses = script
+        .iter()
+        .filter(|step| {
+            matches!(
+                step,
+                ScriptStep::Expect(
+                    ExpectCommand::AppInterimResponse { .. }
+                        | ExpectCommand::AppResponse { .. }
+                        | ExpectCommand::AppResponseData { .. }
+                )
+            )
+        })
+        .count();
+    assert_eq!(responses, 4);
+    MockHarness::new(client()).run_script(script);
+    assert!(record::replay(recording.engine(), script).is_empty());
+}
+
+#[test]
+fn chunks_concatenate_into_the_recording() {
+    let mut whole = Recorder::new(H3Engine::default());
+    record_session(&mut whole);
+
+    let mut chunked = Recorder::new(H3Engine::default());
+    let mut file = chunked.take_recording();
+    // The header alone is a recording without events.
+    assert!(record::decode(&file).expect("decodes").script.is_empty());
+    record_session(&mut chunked);
+    file.extend(chunked.take_recording());
+    assert!(chunked.take_recording().is_empty());
+
+    assert_eq!(file, whole.take_recording());
+}
+
+#[test]
+fn replay_reports_events_that_diverge() {
+    let mut recorder = Recorder::new(H3Engine::default());
+    record_session(&mut recorder);
+    let script = record::decode(&recorder.take_recording())
+        .expect("decodes")
+        .script;
+
+    // GREASE changes what Boot writes on the control stream, and every
+    // difference is reported, not only the first.
+    let config = H3Config::builder()
+        .grease(Some(7))
+        .build()
+        .expect("valid config");
+    let diffs = record::replay(H3Engine::new(config), &script);
+    assert_eq!(diffs[0].event, 0);
+    assert_ne!(diffs[0].expected, diffs[0].actual);
+    assert!(diffs.len() > 1);
+    assert!(diffs.windows(2).all(|pair| pair[0].event < pair[1].event));
+}
+
+#[test]
+fn malformed_recordings_are_rejected() {
+    let mut recorder = Recorder::new(H3Engine::default());
+    record_session(&mut recorder);
+    let recording = recorder.take_recording();
+
+    let decode = |bytes: &[u8]| record::decode(bytes).err();
+    let header_len = Recorder::new(H3Engine::default()).take_recording().len();
+    let version_at = record::MAGIC.len();
+    // The configuration entry's tag, then the role.
+    let role_at = version_at + 2;
+
+    assert_eq!(decode(b"not a rec"), Some(RecordError::BadMagic));
+    let mut newer = recording.clone();
+    newer[version_at] = record::VERSION + 1;
+    assert_eq!(
+        decode(&newer),
+        Some(RecordError::UnsupportedVersion(record::VERSION + 1))
+    );
+    // Boot, OpenUni, then a write cut off after its stream id.
+    assert_eq!(
+        decode(&recording[..header_len + 4]),
+        Some(RecordError::Truncated)
+    );
+
+    let mut unknown = recording[..header_len].to_vec();
+    unknown.push(0x3f);
+    assert_eq!(decode(&unknown), Some(RecordError::UnknownTag(0x3f)));
+    let mut orphan = recording[..header_len].to_vec();
+    orphan.push(0x40);
+    assert_eq!(decode(&orphan), Some(RecordError::CommandBeforeEvent));
+
+    assert_eq!(decode(&recording[..role_at]), Some(RecordError::Truncated));
+    let mut bad_role = recording.clone();
+    bad_role[role_at] = 2;
+    assert_eq!(decode(&bad_role), Some(RecordError::InvalidField(0x00)));
+    // SETTINGS_ENABLE_CONNECT_PROTOCOL = 2 does not build.
+    let mut bad_settings = recording.clone();
+    bad_settings.splice(role_at + 2..role_at + 3, [1, 2]);
+    assert!(matches!(
+        decode(&bad_settings),
+        Some(RecordError::InvalidConfig(ConfigError::InvalidSettings(_)))
+    ));
+}
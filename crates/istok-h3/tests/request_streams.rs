@@ -0,0 +1,164 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::h3::consts;
+use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
+use istok_h3::mock::{control_stream, frame, status};
+use istok_h3::{H3Config, H3Engine, Role};
+use istok_transport::{StreamId, StreamKind};
+
+const PEER_CONTROL: StreamId = StreamId(3);
+
+fn get() -> Vec<u8> {
+    frame(
+        consts::FRAME_TYPE_HEADERS,
+        &[0x00, 0x00, 0xd1, 0xd7, 0xc1, 0x50, 0x01, b'a'],
+    )
+}
+
+fn harness(engine: H3Engine) -> MockHarness<H3Engine> {
+    let mut h = MockHarness::new(engine);
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: PEER_CONTROL,
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::InQuicData {
+            id: PEER_CONTROL,
+            data: control_stream(&[]),
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+    ]);
+    h
+}
+
+fn open(id: StreamId) -> ScriptStep {
+    ScriptStep::InQuicOpen {
+        id,
+        kind: StreamKind::Bidi,
+    }
+}
+
+fn request(id: StreamId, fin: bool) -> ScriptStep {
+    ScriptStep::InQuicData {
+        id,
+        data: get(),
+        fin,
+    }
+}
+
+fn answered(id: StreamId) -> [ScriptStep; 2] {
+    [
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id,
+            data_prefix: status(b"200"),
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id,
+            data_prefix: frame(consts::FRAME_TYPE_DATA, &[0x01]),
+            fin: true,
+        }),
+    ]
+}
+
+#[test]
+fn two_requests_on_one_connection_are_both_answered() {
+    let mut h = harness(H3Engine::default());
+    let mut script = alloc::vec![open(StreamId(0)), request(StreamId(0), true)];
+    script.extend(answered(StreamId(0)));
+    script.extend([open(StreamId(4)), request(StreamId(4), true)]);
+    script.extend(answered(StreamId(4)));
+    script.push(ScriptStep::ExpectNone);
+    h.run_script(&script);
+}
+
+#[test]
+fn interleaved_requests_are_read_concurrently() {
+    let mut h = harness(H3Engine::default());
+    let mut head = get();
+    let tail = head.split_off(4);
+    let mut script = alloc::vec![
+        open(StreamId(0)),
+        open(StreamId(4)),
+        ScriptStep::InQuicData {
+            id: StreamId(0),
+            data: head,
+            fin: false,
+        },
+        request(StreamId(4), true),
+    ];
+    script.extend(answered(StreamId(4)));
+    script.push(ScriptStep::InQuicData {
+        id: StreamId(0),
+        data: tail,
+        fin: true,
+    });
+    script.extend(answered(StreamId(0)));
+    script.push(ScriptStep::ExpectNone);
+    h.run_script(&script);
+}
+
+#[test]
+fn streams_up_to_the_limit_are_served_at_once() {
+    let config = H3Config::builder()
+        .max_concurrent_streams(3)
+        .build()
+        .expect("valid config");
+    let mut h = harness(H3Engine::new(config));
+    let ids = [StreamId(0), StreamId(4), StreamId(8)];
+    let mut head = get();
+    let tail = head.split_off(4);
+
+    // Every stream holds half a HEADERS frame at the same time.
+    let mut script = Vec::new();
+    for id in ids {
+        script.push(open(id));
+        script.push(ScriptStep::InQuicData {
+            id,
+            data: head.clone(),
+            fin: false,
+        });
+    }
+    script.push(ScriptStep::ExpectNone);
+    script.push(open(StreamId(12)));
+    script.extend([
+        ScriptStep::Expect(ExpectCommand::QuicStopSending {
+            id: StreamId(12),
+            app_error: consts::H3_REQUEST_REJECTED,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicResetStream {
+            id: StreamId(12),
+            app_error: consts::H3_REQUEST_REJECTED,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+    for id in ids.into_iter().rev() {
+        script.push(ScriptStep::InQuicData {
+            id,
+            data: tail.clone(),
+            fin: true,
+        });
+        script.extend(answered(id));
+    }
+    script.push(ScriptStep::ExpectNone);
+    h.run_script(&script);
+}
+
+#[test]
+fn client_refuses_server_initiated_bidirectional_streams() {
+    let config = H3Config::builder()
+        .role(Role::Client)
+        .build()
+        .expect("valid config");
+    let mut h = harness(H3Engine::new(config));
+    h.run_script(&[
+        open(StreamId(1)),
+        ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+            app_error: consts::H3_STREAM_CREATION_ERROR,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
@@ -0,0 +1,213 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::h3::consts;
+use istok_core::qpack::{self, HeaderField};
+use istok_h3::H3Engine;
+use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
+use istok_h3::mock::{control_stream, frame, headers};
+use istok_transport::{StreamId, StreamKind};
+
+const CONTROL: StreamId = StreamId(3);
+const REQUEST: StreamId = StreamId(0);
+
+const GET: [HeaderField<'static>; 4] = [
+    HeaderField {
+        name: b":method",
+        value: b"GET",
+    },
+    HeaderField {
+        name: b":scheme",
+        value: b"https",
+    },
+    HeaderField {
+        name: b":authority",
+        value: b"example.com",
+    },
+    HeaderField {
+        name: b":path",
+        value: b"/",
+    },
+];
+
+/// GET with `extra` appended after the pseudo-headers.
+fn get_with(extra: &[HeaderField<'_>]) -> Vec<u8> {
+    let mut fields = GET.to_vec();
+    fields.extend_from_slice(extra);
+    headers(&fields)
+}
+
+/// Open the peer control stream and the request stream, then send `request`.
+fn send_request(request: Vec<u8>, expect: &[ScriptStep]) {
+    let control = control_stream(&[]);
+
+    let mut script = alloc::vec![
+        ScriptStep::InQuicOpen {
+            id: CONTROL,
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::InQuicData {
+            id: CONTROL,
+            data: control,
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicOpen {
+            id: REQUEST,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::InQuicData {
+            id: REQUEST,
+            data: request,
+            fin: true,
+        },
+    ];
+    script.extend_from_slice(expect);
+    MockHarness::new(H3Engine::default()).run_script(&script);
+}
+
+fn expect_message_error(request: Vec<u8>) {
+    send_request(
+        request,
+        &[
+            ScriptStep::Expect(ExpectCommand::QuicStopSending {
+                id: REQUEST,
+                app_error: consts::H3_MESSAGE_ERROR,
+            }),
+            ScriptStep::Expect(ExpectCommand::QuicResetStream {
+                id: REQUEST,
+                app_error: consts::H3_MESSAGE_ERROR,
+            }),
+            ScriptStep::ExpectNone,
+        ],
+    );
+}
+
+#[test]
+fn well_formed_request_with_regular_fields_gets_response() {
+    send_request(
+        get_with(&[
+            HeaderField {
+                name: b"te",
+                value: b"trailers",
+            },
+            HeaderField {
+                name: b"accept",
+                value: b"*/*",
+            },
+        ]),
+        &[
+            ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+                id: REQUEST,
+                data_prefix: frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00, 0xd9]),
+                fin: false,
+            }),
+            ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+                id: REQUEST,
+                data_prefix: frame(consts::FRAME_TYPE_DATA, &[0x01]),
+                fin: true,
+            }),
+            ScriptStep::ExpectNone,
+        ],
+    );
+}
+
+#[test]
+fn crumbled_cookie_request_gets_response() {
+    let mut fields = GET.to_vec();
+    fields.push(HeaderField {
+        name: b"cookie",
+        value: b"a=1; b=2; c=3",
+    });
+    let mut block = [0u8; 256];
+    let len = qpack::cookie::encode_crumbled(&fields, &mut block).expect("qpack encodes");
+    send_request(
+        frame(consts::FRAME_TYPE_HEADERS, &block[..len]),
+        &[
+            ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+                id: REQUEST,
+                data_prefix: frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00, 0xd9]),
+                fin: false,
+            }),
+            ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+                id: REQUEST,
+                data_prefix: frame(consts::FRAME_TYPE_DATA, &[0x01]),
+                fin: true,
+            }),
+            ScriptStep::ExpectNone,
+        ],
+    );
+}
+
+#[test]
+fn uppercase_field_name_is_message_error() {
+    expect_message_error(get_with(&[HeaderField {
+        name: b"Accept",
+        value: b"*/*",
+    }]));
+}
+
+#[test]
+fn connection_specific_fields_are_message_error() {
+    for name in [
+        &b"connection"[..],
+        b"keep-alive",
+        b"transfer-encoding",
+        b"upgrade",
+    ] {
+        expect_message_error(get_with(&[HeaderField { name, value: b"x" }]));
+    }
+}
+
+#[test]
+fn te_other_than_trailers_is_message_error() {
+    expect_message_error(get_with(&[HeaderField {
+        name: b"te",
+        value: b"gzip",
+    }]));
+}
+
+#[test]
+fn duplicate_pseudo_header_is_message_error() {
+    expect_message_error(get_with(&[HeaderField {
+        name: b":path",
+        value: b"/other",
+    }]));
+}
+
+#[test]
+fn missing_pseudo_header_is_message_error() {
+    expect_message_error(headers(&GET[..3]));
+    expect_message_error(headers(&[]));
+}
+
+#[test]
+fn pseudo_header_after_regular_field_is_message_error() {
+    let fields = [
+        GET[0],
+        GET[1],
+        GET[2],
+        HeaderField {
+            name: b"accept",
+            value: b"*/*",
+        },
+        GET[3],
+    ];
+    expect_message_error(headers(&fields));
+}
+
+#[test]
+fn response_pseudo_header_in_request_is_message_error() {
+    let fields = [
+        HeaderField {
+            name: b":status",
+            value: b"200",
+        },
+        GET[0],
+        GET[1],
+        GET[2],
+        GET[3],
+    ];
+    expect_message_error(headers(&fields));
+}
@@ -0,0 +1,302 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::h3::consts;
+use istok_core::qpack::{self, HeaderField};
+use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
+use istok_h3::mock::{control_stream, frame};
+use istok_h3::timers::{IDLE_TIMER, stream_timer};
+use istok_h3::{H3Config, H3Engine, TimerId};
+use istok_transport::{StreamId, StreamKind};
+
+const PEER_CONTROL: StreamId = StreamId(3);
+const REQUEST: StreamId = StreamId(0);
+
+const IDLE_MS: u64 = 30_000;
+const HEADER_MS: u64 = 5_000;
+const BODY_MS: u64 = 10_000;
+
+fn get() -> Vec<u8> {
+    frame(
+        consts::FRAME_TYPE_HEADERS,
+        &[0x00, 0x00, 0xd1, 0xd7, 0xc1, 0x50, 0x01, b'a'],
+    )
+}
+
+/// POST with `content-length: 3`.
+fn post() -> Vec<u8> {
+    let fields = [
+        HeaderField {
+            name: b":method",
+            value: b"POST",
+        },
+        HeaderField {
+            name: b":scheme",
+            value: b"https",
+        },
+        HeaderField {
+            name: b":authority",
+            value: b"example.com",
+        },
+        HeaderField {
+            name: b":path",
+            value: b"/upload",
+        },
+        HeaderField {
+            name: b"content-length",
+            value: b"3",
+        },
+    ];
+    let mut block = [0u8; 128];
+    let len = qpack::encode(&fields, &mut block).expect("qpack encodes");
+    frame(consts::FRAME_TYPE_HEADERS, &block[..len])
+}
+
+fn request_timer() -> TimerId {
+    stream_timer(REQUEST).expect("request stream has a timer")
+}
+
+fn arm(id: TimerId, deadline_ms_from_now: u64) -> ScriptStep {
+    ScriptStep::Expect(ExpectCommand::ArmTimer {
+        id,
+        deadline_ms_from_now,
+    })
+}
+
+fn cancel(id: TimerId) -> ScriptStep {
+    ScriptStep::Expect(ExpectCommand::CancelTimer { id })
+}
+
+fn data(id: StreamId, data: Vec<u8>, fin: bool) -> ScriptStep {
+    ScriptStep::InQuicData { id, data, fin }
+}
+
+fn open(id: StreamId, kind: StreamKind) -> ScriptStep {
+    ScriptStep::InQuicOpen { id, kind }
+}
+
+fn response() -> [ScriptStep; 2] {
+    [
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00, 0xd9]),
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: REQUEST,
+            data_prefix: frame(consts::FRAME_TYPE_DATA, &[0x01]),
+            fin: true,
+        }),
+    ]
+}
+
+fn incomplete() -> [ScriptStep; 2] {
+    [
+        ScriptStep::Expect(ExpectCommand::QuicStopSending {
+            id: REQUEST,
+            app_error: consts::H3_REQUEST_INCOMPLETE,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicResetStream {
+            id: REQUEST,
+            app_error: consts::H3_REQUEST_INCOMPLETE,
+        }),
+    ]
+}
+
+fn harness(config: H3Config) -> MockHarness<H3Engine> {
+    MockHarness::new(H3Engine::new(config))
+}
+
+fn request_timeouts() -> MockHarness<H3Engine> {
+    harness(
+        H3Config::builder()
+            .header_read_timeout_ms(Some(HEADER_MS))
+            .body_idle_timeout_ms(Some(BODY_MS))
+            .build()
+            .expect("valid config"),
+    )
+}
+
+/// Peer control stream, then the request stream opened.
+fn open_request(h: &mut MockHarness<H3Engine>) {
+    h.run_script(&[
+        open(PEER_CONTROL, StreamKind::Uni),
+        data(PEER_CONTROL, control_stream(&[]), false),
+        ScriptStep::ExpectNone,
+        open(REQUEST, StreamKind::Bidi),
+        arm(request_timer(), HEADER_MS),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn timer_ids_are_stable() {
+    assert_eq!(stream_timer(StreamId(0)), Some(TimerId(1)));
+    assert_eq!(stream_timer(StreamId(8)), Some(TimerId(3)));
+    assert_eq!(stream_timer(StreamId(2)), None);
+    assert_eq!(stream_timer(StreamId(4 * u64::from(u32::MAX))), None);
+    assert_ne!(stream_timer(StreamId(0)), Some(IDLE_TIMER));
+}
+
+#[test]
+fn no_timers_by_default() {
+    harness(H3Config::default()).run_script(&[
+        open(PEER_CONTROL, StreamKind::Uni),
+        data(PEER_CONTROL, control_stream(&[]), false),
+        open(REQUEST, StreamKind::Bidi),
+        data(REQUEST, post(), false),
+        response()[0].clone(),
+        response()[1].clone(),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn idle_connection_is_closed() {
+    let mut h = harness(
+        H3Config::builder()
+            .idle_timeout_ms(Some(IDLE_MS))
+            .build()
+            .expect("valid config"),
+    );
+    h.run_script(&[
+        ScriptStep::InBoot,
+        ScriptStep::Expect(ExpectCommand::QuicOpenUni),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: StreamId(2),
+            data_prefix: Vec::new(),
+            fin: false,
+        }),
+        arm(IDLE_TIMER, IDLE_MS),
+        ScriptStep::ExpectNone,
+        // Every event pushes the deadline out again.
+        open(PEER_CONTROL, StreamKind::Uni),
+        arm(IDLE_TIMER, IDLE_MS),
+        data(PEER_CONTROL, control_stream(&[]), false),
+        arm(IDLE_TIMER, IDLE_MS),
+        ScriptStep::ExpectNone,
+        ScriptStep::InTimer(IDLE_TIMER),
+        ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+            app_error: consts::H3_NO_ERROR,
+        }),
+        ScriptStep::ExpectNone,
+        // Nothing is armed after the close.
+        open(REQUEST, StreamKind::Bidi),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn slow_headers_reset_the_stream() {
+    let mut h = request_timeouts();
+    open_request(&mut h);
+
+    // A partial HEADERS frame does not extend the header deadline.
+    let request = get();
+    let mut script = alloc::vec![
+        data(REQUEST, request[..4].to_vec(), false),
+        ScriptStep::ExpectNone,
+        ScriptStep::InTimer(request_timer()),
+    ];
+    script.extend_from_slice(&incomplete());
+    script.extend_from_slice(&[
+        ScriptStep::ExpectNone,
+        data(REQUEST, request[4..].to_vec(), true),
+        ScriptStep::ExpectNone,
+    ]);
+    h.run_script(&script);
+}
+
+#[test]
+fn header_timer_armed_before_control_stream() {
+    let mut h = request_timeouts();
+    let mut script = alloc::vec![
+        open(REQUEST, StreamKind::Bidi),
+        arm(request_timer(), HEADER_MS),
+        data(REQUEST, get(), false),
+        ScriptStep::ExpectNone,
+        ScriptStep::InTimer(request_timer()),
+    ];
+    script.extend_from_slice(&incomplete());
+    script.push(ScriptStep::ExpectNone);
+    h.run_script(&script);
+}
+
+#[test]
+fn complete_request_cancels_its_timer() {
+    let mut h = request_timeouts();
+    open_request(&mut h);
+
+    let mut script = alloc::vec![data(REQUEST, get(), true)];
+    script.extend_from_slice(&response());
+    script.extend_from_slice(&[
+        cancel(request_timer()),
+        ScriptStep::ExpectNone,
+        // A late firing is ignored.
+        ScriptStep::InTimer(request_timer()),
+        ScriptStep::ExpectNone,
+    ]);
+    h.run_script(&script);
+}
+
+#[test]
+fn stalled_body_resets_the_stream() {
+    let mut h = request_timeouts();
+    open_request(&mut h);
+
+    let mut script = alloc::vec![data(REQUEST, post(), false)];
+    script.extend_from_slice(&response());
+    script.extend_from_slice(&[
+        arm(request_timer(), BODY_MS),
+        ScriptStep::ExpectNone,
+        // Each read pushes the body deadline out.
+        data(REQUEST, frame(consts::FRAME_TYPE_DATA, b"a"), false),
+        arm(request_timer(), BODY_MS),
+        ScriptStep::ExpectNone,
+        // Unrelated timers are ignored.
+        ScriptStep::InTimer(TimerId(7)),
+        ScriptStep::ExpectNone,
+        ScriptStep::InTimer(request_timer()),
+    ]);
+    script.extend_from_slice(&incomplete());
+    script.push(ScriptStep::ExpectNone);
+    h.run_script(&script);
+}
+
+#[test]
+fn finished_body_cancels_its_timer() {
+    let mut h = request_timeouts();
+    open_request(&mut h);
+
+    let mut script = alloc::vec![data(REQUEST, post(), false)];
+    script.extend_from_slice(&response());
+    script.extend_from_slice(&[
+        arm(request_timer(), BODY_MS),
+        data(REQUEST, frame(consts::FRAME_TYPE_DATA, b"abc"), true),
+        cancel(request_timer()),
+        ScriptStep::ExpectNone,
+    ]);
+    h.run_script(&script);
+}
+
+#[test]
+fn shutdown_cancels_armed_timers() {
+    let mut h = harness(
+        H3Config::builder()
+            .idle_timeout_ms(Some(IDLE_MS))
+            .header_read_timeout_ms(Some(HEADER_MS))
+            .build()
+            .expect("valid config"),
+    );
+    h.run_script(&[
+        open(REQUEST, StreamKind::Bidi),
+        arm(IDLE_TIMER, IDLE_MS),
+        arm(request_timer(), HEADER_MS),
+        ScriptStep::ExpectNone,
+        ScriptStep::InShutdown,
+        cancel(IDLE_TIMER),
+        cancel(request_timer()),
+        ScriptStep::ExpectNone,
+    ]);
+}
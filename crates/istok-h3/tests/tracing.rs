@@ -0,0 +1,241 @@
+//! Spans and close/reset events under the `tracing` feature, collected by
+//! a minimal subscriber.
+#![cfg(feature = "tracing")]
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use istok_core::h3::consts;
+use istok_h3::mock::Discard;
+use istok_h3::mock::{control_stream, frame};
+use istok_h3::{Engine, EngineEvent, H3Engine};
+use istok_transport::{QuicEvent, StreamId, StreamKind};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+const PEER_CONTROL: StreamId = StreamId(3);
+const REQUEST: StreamId = StreamId(0);
+
+type Fields = BTreeMap<String, String>;
+
+#[derive(Debug, Clone)]
+struct Span {
+    name: &'static str,
+    fields: Fields,
+    parent: Option<u64>,
+}
+
+/// An event with the spans it was emitted in, innermost first.
+#[derive(Debug)]
+struct Recorded {
+    fields: Fields,
+    spans: Vec<Span>,
+}
+
+#[derive(Default)]
+struct State {
+    spans: BTreeMap<u64, Span>,
+    stack: Vec<u64>,
+    events: Vec<Recorded>,
+}
+
+#[derive(Clone, Default)]
+struct Collector {
+    next_id: Arc<AtomicU64>,
+    state: Arc<Mutex<State>>,
+}
+
+struct FieldVisitor<'f>(&'f mut Fields);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}"));
+    }
+}
+
+impl Subscriber for Collector {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut fields = Fields::new();
+        attrs.record(&mut FieldVisitor(&mut fields));
+        let mut state = self.state.lock().expect("not poisoned");
+        let parent = match attrs.parent() {
+            Some(parent) => Some(parent.into_u64()),
+            None if attrs.is_contextual() => state.stack.last().copied(),
+            None => None,
+        };
+        state.spans.insert(
+            id,
+            Span {
+                name: attrs.metadata().name(),
+                fields,
+                parent,
+            },
+        );
+        Id::from_u64(id)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::new();
+        event.record(&mut FieldVisitor(&mut fields));
+        let mut state = self.state.lock().expect("not poisoned");
+        let mut spans = Vec::new();
+        let mut next = state.stack.last().copied();
+        while let Some(id) = next {
+            let span = state.spans[&id].clone();
+            next = span.parent;
+            spans.push(span);
+        }
+        state.events.push(Recorded { fields, spans });
+    }
+
+    fn enter(&self, span: &Id) {
+        let mut state = self.state.lock().expect("not poisoned");
+        state.stack.push(span.into_u64());
+    }
+
+    fn exit(&self, _span: &Id) {
+        let mut state = self.state.lock().expect("not poisoned");
+        state.stack.pop();
+    }
+}
+
+/// Run `events` through a fresh engine and return what was traced.
+fn traced(events: &[QuicEvent<'_>]) -> Vec<Recorded> {
+    let collector = Collector::default();
+    tracing::subscriber::with_default(collector.clone(), || {
+        let mut engine = H3Engine::default();
+        engine.on_event(EngineEvent::Boot, &mut Discard);
+        for ev in events {
+            engine.on_event(EngineEvent::Quic(ev.clone()), &mut Discard);
+        }
+    });
+    let mut state = collector.state.lock().expect("not poisoned");
+    std::mem::take(&mut state.events)
+}
+
+fn span_names(event: &Recorded) -> Vec<&'static str> {
+    event.spans.iter().map(|span| span.name).collect()
+}
+
+#[test]
+fn close_is_traced_with_reason_and_offset() {
+    let control = control_stream(&[]);
+    let data_on_control = frame(consts::FRAME_TYPE_DATA, &[0x00]);
+    let events = traced(&[
+        QuicEvent::StreamOpened {
+            id: PEER_CONTROL,
+            kind: StreamKind::Uni,
+        },
+        QuicEvent::StreamReadable {
+            id: PEER_CONTROL,
+            data: &control,
+            fin: false,
+        },
+        QuicEvent::StreamReadable {
+            id: PEER_CONTROL,
+            data: &data_on_control,
+            fin: false,
+        },
+    ]);
+
+    let [event] = events.as_slice() else {
+        panic!("expected one event, got {events:?}");
+    };
+    let fields = &event.fields;
+    assert_eq!(fields["message"], "closing connection");
+    assert_eq!(fields["reason"], "frame not allowed on the control stream");
+    assert_eq!(fields["app_error"], consts::H3_FRAME_UNEXPECTED.to_string());
+    assert_eq!(fields["stream"], "3");
+    // The DATA frame starts right after the stream type and SETTINGS.
+    assert_eq!(fields["offset"], control.len().to_string());
+
+    assert_eq!(span_names(event), ["h3_stream", "h3_connection"]);
+    assert_eq!(event.spans[0].fields["id"], "3");
+    assert_eq!(event.spans[1].fields["role"], "Server");
+}
+
+#[test]
+fn reset_is_traced_in_the_stream_span() {
+    let control = control_stream(&[]);
+    // No `:path`: malformed, so the stream is reset.
+    let malformed = frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00, 0xd1, 0xd7]);
+    let events = traced(&[
+        QuicEvent::StreamOpened {
+            id: PEER_CONTROL,
+            kind: StreamKind::Uni,
+        },
+        QuicEvent::StreamReadable {
+            id: PEER_CONTROL,
+            data: &control,
+            fin: false,
+        },
+        QuicEvent::StreamOpened {
+            id: REQUEST,
+            kind: StreamKind::Bidi,
+        },
+        QuicEvent::StreamReadable {
+            id: REQUEST,
+            data: &malformed,
+            fin: false,
+        },
+    ]);
+
+    let [event] = events.as_slice() else {
+        panic!("expected one event, got {events:?}");
+    };
+    let fields = &event.fields;
+    assert_eq!(fields["message"], "resetting stream");
+    assert_eq!(fields["reason"], "malformed request");
+    assert_eq!(fields["app_error"], consts::H3_MESSAGE_ERROR.to_string());
+    assert_eq!(fields["stream"], "0");
+    assert_eq!(fields["offset"], "2");
+    assert_eq!(span_names(event), ["h3_stream", "h3_connection"]);
+    assert_eq!(event.spans[0].fields["id"], "0");
+}
+
+#[test]
+fn a_served_request_traces_no_decisions() {
+    let control = control_stream(&[]);
+    let request = frame(
+        consts::FRAME_TYPE_HEADERS,
+        &[0x00, 0x00, 0xd1, 0xd7, 0xc1, 0x50, 0x01, b'a'],
+    );
+    let events = traced(&[
+        QuicEvent::StreamOpened {
+            id: PEER_CONTROL,
+            kind: StreamKind::Uni,
+        },
+        QuicEvent::StreamReadable {
+            id: PEER_CONTROL,
+            data: &control,
+            fin: false,
+        },
+        QuicEvent::StreamOpened {
+            id: REQUEST,
+            kind: StreamKind::Bidi,
+        },
+        QuicEvent::StreamReadable {
+            id: REQUEST,
+            data: &request,
+            fin: true,
+        },
+    ]);
+    assert!(events.is_empty(), "{events:?}");
+}
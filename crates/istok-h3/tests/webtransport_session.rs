@@ -0,0 +1,562 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::codec::capsule;
+use istok_core::h3::consts;
+use istok_core::h3::settings::Settings;
+use istok_core::qpack::{self, HeaderField};
+use istok_h3::mock::{ExpectCommand, MockHarness, ScriptStep};
+use istok_h3::mock::{control_stream, frame, settings_payload};
+use istok_h3::{H3Config, H3Engine};
+use istok_transport::{StreamId, StreamKind};
+
+const SESSION: StreamId = StreamId(0);
+
+fn engine_with(settings: Settings) -> H3Engine {
+    H3Engine::new(
+        H3Config::builder()
+            .settings(settings)
+            .build()
+            .expect("valid config"),
+    )
+}
+
+fn wt_settings() -> Settings {
+    Settings {
+        enable_connect_protocol: Some(1),
+        h3_datagram: Some(1),
+        webtransport_max_sessions: Some(1),
+        ..Settings::default()
+    }
+}
+
+fn request_headers(method: &[u8], protocol: &[u8]) -> Vec<u8> {
+    let fields = [
+        HeaderField {
+            name: b":method",
+            value: method,
+        },
+        HeaderField {
+            name: b":protocol",
+            value: protocol,
+        },
+        HeaderField {
+            name: b":scheme",
+            value: b"https",
+        },
+        HeaderField {
+            name: b":authority",
+            value: b"example.com",
+        },
+        HeaderField {
+            name: b":path",
+            value: b"/game",
+        },
+    ];
+    let mut block = [0u8; 128];
+    let len = qpack::encode(&fields, &mut block).expect("qpack encodes");
+    frame(consts::FRAME_TYPE_HEADERS, &block[..len])
+}
+
+/// HEADERS frame with `:status 200` as a single static-table reference.
+fn status_200() -> Vec<u8> {
+    frame(consts::FRAME_TYPE_HEADERS, &[0x00, 0x00, 0xd9])
+}
+
+fn capsule_frame(ty: u64, value: &[u8]) -> Vec<u8> {
+    let mut buf = [0u8; 64];
+    let header_len = capsule::encode_capsule_header(
+        capsule::CapsuleHeader {
+            ty,
+            len: value.len() as u64,
+        },
+        &mut buf,
+    )
+    .expect("capsule header encodes");
+    buf[header_len..header_len + value.len()].copy_from_slice(value);
+    frame(consts::FRAME_TYPE_DATA, &buf[..header_len + value.len()])
+}
+
+fn open_peer_control(h: &mut MockHarness<H3Engine>, settings: &Settings) {
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: StreamId(3),
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: StreamId(3),
+            data: control_stream(&settings_payload(settings)),
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+/// Engine with WebTransport enabled on both sides and a session on stream 0.
+fn established() -> MockHarness<H3Engine> {
+    let mut h = MockHarness::new(engine_with(wt_settings()));
+    open_peer_control(&mut h, &wt_settings());
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: SESSION,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: SESSION,
+            data: request_headers(b"CONNECT", b"webtransport"),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: SESSION,
+            data_prefix: status_200(),
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::AppWebTransportSession { id: SESSION }),
+        ScriptStep::ExpectNone,
+    ]);
+    h
+}
+
+#[test]
+fn extended_connect_establishes_session() {
+    let mut h = established();
+    let has_session = h.apply(|engine, _| engine.webtransport_session(SESSION).is_some());
+    assert!(has_session);
+}
+
+#[test]
+fn peer_uni_stream_joins_session_and_is_accepted() {
+    let mut h = established();
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: StreamId(7),
+            kind: StreamKind::Uni,
+        },
+        ScriptStep::ExpectNone,
+        // Stream type 0x54 (2-byte varint), session id 0, then payload.
+        ScriptStep::InQuicData {
+            id: StreamId(7),
+            data: alloc::vec![0x40, 0x54, 0x00, b'h', b'i'],
+            fin: true,
+        },
+        ScriptStep::Expect(ExpectCommand::AppWebTransportStreamData {
+            session: SESSION,
+            id: StreamId(7),
+            data: b"hi".to_vec(),
+            fin: true,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+
+    let accepted = h.apply(|engine, _| {
+        let mut session = engine.webtransport_session(SESSION).expect("session");
+        (
+            session.accept_uni(),
+            session.accept_uni(),
+            session.accept_bi(),
+        )
+    });
+    assert_eq!(accepted, (Some(StreamId(7)), None, None));
+}
+
+#[test]
+fn peer_bidi_stream_with_split_header_is_accepted_and_answered() {
+    let mut h = established();
+    let stream = StreamId(4);
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: stream,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: stream,
+            data: alloc::vec![0x40],
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: stream,
+            data: alloc::vec![0x41, 0x00, 0xaa],
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::AppWebTransportStreamData {
+            session: SESSION,
+            id: stream,
+            data: alloc::vec![0xaa],
+            fin: false,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+
+    let accepted = h.apply(|engine, out| {
+        let mut session = engine.webtransport_session(SESSION).expect("session");
+        let id = session.accept_bi();
+        session
+            .send(stream, b"ok", true, out)
+            .expect("bidi is writable");
+        id
+    });
+    assert_eq!(accepted, Some(stream));
+    h.run_script(&[
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: stream,
+            data_prefix: b"ok".to_vec(),
+            fin: true,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn stream_for_unknown_session_is_rejected() {
+    let mut h = established();
+    let app_error = consts::H3_WEBTRANSPORT_BUFFERED_STREAM_REJECTED;
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: StreamId(4),
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: StreamId(4),
+            data: alloc::vec![0x40, 0x41, 0x08, 0xaa],
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStopSending {
+            id: StreamId(4),
+            app_error,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicResetStream {
+            id: StreamId(4),
+            app_error,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn local_streams_carry_session_headers() {
+    let mut h = established();
+    let ids = h.apply(|engine, out| {
+        let mut session = engine.webtransport_session(SESSION).expect("session");
+        (session.open_uni(out), session.open_bi(out))
+    });
+    assert_eq!(ids, (StreamId(6), StreamId(1)));
+
+    h.run_script(&[
+        ScriptStep::Expect(ExpectCommand::QuicOpenUni),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: StreamId(6),
+            data_prefix: alloc::vec![0x40, 0x54, 0x00],
+            fin: false,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicOpenBidi),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: StreamId(1),
+            data_prefix: alloc::vec![0x40, 0x41, 0x00],
+            fin: false,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn session_datagrams_use_connect_stream_quarter_id() {
+    let mut h = established();
+    h.run_script(&[
+        ScriptStep::InQuicDatagram {
+            data: alloc::vec![0x00, 0x01, 0x02],
+        },
+        ScriptStep::Expect(ExpectCommand::AppDatagram {
+            id: SESSION,
+            payload: alloc::vec![0x01, 0x02],
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+
+    h.apply(|engine, out| {
+        let mut session = engine.webtransport_session(SESSION).expect("session");
+        session.send_datagram(&[0x03], out);
+    });
+    h.run_script(&[
+        ScriptStep::Expect(ExpectCommand::QuicSendDatagram {
+            data: alloc::vec![0x00, 0x03],
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn datagram_capsules_reach_the_application() {
+    let mut h = established();
+    let datagram = capsule_frame(consts::CAPSULE_TYPE_DATAGRAM, &[0x01, 0x02]);
+    let (head, tail) = datagram.split_at(3);
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: SESSION,
+            data: head.to_vec(),
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: SESSION,
+            data: tail.to_vec(),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::AppDatagram {
+            id: SESSION,
+            payload: alloc::vec![0x01, 0x02],
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn close_capsule_ends_session_and_aborts_streams() {
+    let mut h = established();
+    let app_error = consts::H3_WEBTRANSPORT_SESSION_GONE;
+    let mut close = [0u8; 32];
+    let close_len = capsule::encode_close_webtransport_session(7, b"bye", &mut close)
+        .expect("close capsule encodes");
+
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: StreamId(4),
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: StreamId(4),
+            data: alloc::vec![0x40, 0x41, 0x00],
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: SESSION,
+            data: frame(consts::FRAME_TYPE_DATA, &close[..close_len]),
+            fin: true,
+        },
+        ScriptStep::Expect(ExpectCommand::AppWebTransportSessionClosed {
+            id: SESSION,
+            code: 7,
+            reason: b"bye".to_vec(),
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: SESSION,
+            data_prefix: Vec::new(),
+            fin: true,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStopSending {
+            id: StreamId(4),
+            app_error,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicResetStream {
+            id: StreamId(4),
+            app_error,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+
+    let has_session = h.apply(|engine, _| engine.webtransport_session(SESSION).is_some());
+    assert!(!has_session);
+}
+
+#[test]
+fn drain_capsule_and_clean_fin() {
+    let mut h = established();
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: SESSION,
+            data: capsule_frame(consts::CAPSULE_TYPE_DRAIN_WEBTRANSPORT_SESSION, &[]),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::AppWebTransportSessionDraining { id: SESSION }),
+        ScriptStep::ExpectNone,
+        // Unknown capsule types are skipped.
+        ScriptStep::InQuicData {
+            id: SESSION,
+            data: capsule_frame(0x29, &[1, 2, 3]),
+            fin: false,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: SESSION,
+            data: Vec::new(),
+            fin: true,
+        },
+        ScriptStep::Expect(ExpectCommand::AppWebTransportSessionClosed {
+            id: SESSION,
+            code: 0,
+            reason: Vec::new(),
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: SESSION,
+            data_prefix: Vec::new(),
+            fin: true,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn local_close_sends_close_capsule_with_fin() {
+    let mut h = established();
+    h.apply(|engine, out| {
+        let session = engine.webtransport_session(SESSION).expect("session");
+        session.close(9, b"done", out).expect("close encodes");
+    });
+
+    let mut close = [0u8; 32];
+    let close_len = capsule::encode_close_webtransport_session(9, b"done", &mut close)
+        .expect("close capsule encodes");
+    h.run_script(&[
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: SESSION,
+            data_prefix: frame(consts::FRAME_TYPE_DATA, &close[..close_len]),
+            fin: true,
+        }),
+        ScriptStep::ExpectNone,
+        // Late peer data on the finished CONNECT stream is ignored.
+        ScriptStep::InQuicData {
+            id: SESSION,
+            data: alloc::vec![0x00, 0x00],
+            fin: true,
+        },
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn headers_frame_on_session_stream_is_unexpected() {
+    let mut h = established();
+    h.run_script(&[
+        ScriptStep::InQuicData {
+            id: SESSION,
+            data: status_200(),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicCloseConnection {
+            app_error: consts::H3_FRAME_UNEXPECTED,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn protocol_without_extended_connect_is_message_error() {
+    let mut h = MockHarness::new(H3Engine::default());
+    open_peer_control(&mut h, &Settings::new());
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: SESSION,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: SESSION,
+            data: request_headers(b"CONNECT", b"webtransport"),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStopSending {
+            id: SESSION,
+            app_error: consts::H3_MESSAGE_ERROR,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicResetStream {
+            id: SESSION,
+            app_error: consts::H3_MESSAGE_ERROR,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn unsupported_protocol_is_answered_with_501() {
+    let mut h = MockHarness::new(engine_with(wt_settings()));
+    open_peer_control(&mut h, &wt_settings());
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: SESSION,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::ExpectNone,
+        ScriptStep::InQuicData {
+            id: SESSION,
+            data: request_headers(b"CONNECT", b"websocket"),
+            fin: false,
+        },
+        // `:status` name reference (static index 24) with literal "501".
+        ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+            id: SESSION,
+            data_prefix: frame(
+                consts::FRAME_TYPE_HEADERS,
+                &[0x00, 0x00, 0x5f, 0x09, 0x03, b'5', b'0', b'1'],
+            ),
+            fin: true,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicStopSending {
+            id: SESSION,
+            app_error: consts::H3_NO_ERROR,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+}
+
+#[test]
+fn sessions_are_limited_by_max_sessions() {
+    let settings = Settings {
+        webtransport_max_sessions: Some(2),
+        ..wt_settings()
+    };
+    let second = StreamId(4);
+    let third = StreamId(8);
+    let mut h = MockHarness::new(engine_with(settings));
+    open_peer_control(&mut h, &wt_settings());
+    for id in [SESSION, second] {
+        h.run_script(&[
+            ScriptStep::InQuicOpen {
+                id,
+                kind: StreamKind::Bidi,
+            },
+            ScriptStep::InQuicData {
+                id,
+                data: request_headers(b"CONNECT", b"webtransport"),
+                fin: false,
+            },
+            ScriptStep::Expect(ExpectCommand::QuicStreamWrite {
+                id,
+                data_prefix: status_200(),
+                fin: false,
+            }),
+            ScriptStep::Expect(ExpectCommand::AppWebTransportSession { id }),
+            ScriptStep::ExpectNone,
+        ]);
+    }
+    h.run_script(&[
+        ScriptStep::InQuicOpen {
+            id: third,
+            kind: StreamKind::Bidi,
+        },
+        ScriptStep::InQuicData {
+            id: third,
+            data: request_headers(b"CONNECT", b"webtransport"),
+            fin: false,
+        },
+        ScriptStep::Expect(ExpectCommand::QuicStopSending {
+            id: third,
+            app_error: consts::H3_REQUEST_REJECTED,
+        }),
+        ScriptStep::Expect(ExpectCommand::QuicResetStream {
+            id: third,
+            app_error: consts::H3_REQUEST_REJECTED,
+        }),
+        ScriptStep::ExpectNone,
+    ]);
+    let sessions = h.apply(|engine, _| {
+        [SESSION, second, third].map(|id| engine.webtransport_session(id).is_some())
+    });
+    assert_eq!(sessions, [true, true, false]);
+}
@@ -0,0 +1,199 @@
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use istok_core::bytes::Bytes;
+use istok_core::codec::priority::Priority;
+use istok_h3::WriteScheduler;
+use istok_h3::scheduler::PendingWrite;
+use istok_transport::{InlineHeader, StreamId};
+
+fn priority(urgency: u8, incremental: bool) -> Priority {
+    Priority {
+        urgency,
+        incremental,
+    }
+}
+
+/// Drain the scheduler into `(stream, first data byte, fin)` tuples.
+fn drain(s: &mut WriteScheduler) -> Vec<(u64, u8, bool)> {
+    let mut order = Vec::new();
+    while let Some(write) = s.pop() {
+        order.push((write.id.0, write.data[0], write.fin));
+    }
+    order
+}
+
+#[test]
+fn lower_urgency_is_served_first() {
+    let mut s = WriteScheduler::new();
+    s.set_priority(StreamId(0), priority(5, false));
+    s.set_priority(StreamId(4), priority(1, false));
+    s.push(StreamId(0), alloc::vec![0xa0], true);
+    s.push(StreamId(4), alloc::vec![0xb0], false);
+    s.push(StreamId(4), alloc::vec![0xb1], true);
+
+    assert_eq!(
+        drain(&mut s),
+        [(4, 0xb0, false), (4, 0xb1, true), (0, 0xa0, true)]
+    );
+    assert!(s.is_empty());
+    assert_eq!(s.len(), 0, "finished streams are forgotten");
+}
+
+#[test]
+fn non_incremental_streams_complete_in_stream_order() {
+    let mut s = WriteScheduler::new();
+    for id in [8, 0, 4] {
+        s.set_priority(StreamId(id), Priority::default());
+        s.push(StreamId(id), alloc::vec![id as u8], false);
+        s.push(StreamId(id), alloc::vec![id as u8 + 1], true);
+    }
+
+    assert_eq!(
+        drain(&mut s),
+        [
+            (0, 0, false),
+            (0, 1, true),
+            (4, 4, false),
+            (4, 5, true),
+            (8, 8, false),
+            (8, 9, true)
+        ]
+    );
+}
+
+#[test]
+fn incremental_streams_share_round_robin() {
+    let mut s = WriteScheduler::new();
+    for id in [0, 4, 8] {
+        s.set_priority(StreamId(id), priority(3, true));
+    }
+    s.push(StreamId(0), alloc::vec![0x00], false);
+    s.push(StreamId(0), alloc::vec![0x01], false);
+    s.push(StreamId(0), alloc::vec![0x02], true);
+    s.push(StreamId(4), alloc::vec![0x40], false);
+    s.push(StreamId(4), alloc::vec![0x41], true);
+    s.push(StreamId(8), alloc::vec![0x80], true);
+
+    assert_eq!(
+        drain(&mut s),
+        [
+            (0, 0x00, false),
+            (4, 0x40, false),
+            (8, 0x80, true),
+            (0, 0x01, false),
+            (4, 0x41, true),
+            (0, 0x02, true)
+        ]
+    );
+}
+
+#[test]
+fn non_incremental_goes_before_incremental_of_same_urgency() {
+    let mut s = WriteScheduler::new();
+    s.set_priority(StreamId(0), priority(2, true));
+    s.set_priority(StreamId(4), priority(2, false));
+    s.push(StreamId(0), alloc::vec![0x00], true);
+    s.push(StreamId(4), alloc::vec![0x40], true);
+
+    assert_eq!(drain(&mut s), [(4, 0x40, true), (0, 0x00, true)]);
+}
+
+#[test]
+fn reprioritized_stream_moves_ahead() {
+    let mut s = WriteScheduler::new();
+    s.push(StreamId(0), alloc::vec![0x00], false);
+    s.push(StreamId(0), alloc::vec![0x01], true);
+    s.push(StreamId(4), alloc::vec![0x40], true);
+    assert_eq!(s.pop().map(|w| w.id), Some(StreamId(0)));
+
+    s.set_priority(StreamId(4), priority(0, false));
+    assert_eq!(s.priority(StreamId(4)), priority(0, false));
+    assert_eq!(drain(&mut s), [(4, 0x40, true), (0, 0x01, true)]);
+}
+
+#[test]
+fn removed_stream_is_dropped_and_urgency_is_clamped() {
+    let mut s = WriteScheduler::new();
+    s.set_priority(StreamId(0), priority(200, false));
+    assert_eq!(s.priority(StreamId(0)).urgency, 7);
+    s.push(StreamId(0), alloc::vec![0x00], true);
+    s.remove(StreamId(0));
+
+    assert!(!s.contains(StreamId(0)));
+    assert_eq!(s.pop(), None);
+    assert_eq!(s.priority(StreamId(0)), Priority::default());
+}
+
+#[test]
+fn writes_are_cut_to_stream_credit() {
+    let mut s = WriteScheduler::new();
+    s.set_capacity(StreamId(0), 3);
+    s.push(StreamId(0), alloc::vec![1, 2], false);
+    s.push(StreamId(0), alloc::vec![3, 4, 5], true);
+    s.push(StreamId(4), alloc::vec![0x40], true);
+    assert_eq!(s.queued_bytes(StreamId(0)), 5);
+
+    let write = |id, data: &[u8], fin| PendingWrite {
+        id: StreamId(id),
+        header: InlineHeader::default(),
+        data: Bytes::copy_from_slice(data),
+        fin,
+    };
+    assert_eq!(s.pop(), Some(write(0, &[1, 2], false)));
+    assert_eq!(s.pop(), Some(write(0, &[3], false)), "split, FIN held back");
+    assert_eq!(s.pop(), Some(write(4, &[0x40], true)), "unbounded stream");
+    assert_eq!(s.pop(), None);
+    assert_eq!(s.queued_bytes(StreamId(0)), 2);
+
+    s.set_capacity(StreamId(0), 8);
+    assert_eq!(s.pop(), Some(write(0, &[4, 5], true)));
+    assert!(!s.contains(StreamId(0)));
+}
+
+#[test]
+fn bare_fin_needs_no_credit() {
+    let mut s = WriteScheduler::new();
+    s.set_capacity(StreamId(0), 0);
+    s.push(StreamId(0), Vec::new(), true);
+    assert_eq!(
+        s.pop(),
+        Some(PendingWrite {
+            id: StreamId(0),
+            header: InlineHeader::default(),
+            data: Bytes::new(),
+            fin: true,
+        })
+    );
+}
+
+#[test]
+fn framed_writes_split_without_copying_the_body() {
+    let mut s = WriteScheduler::new();
+    let header = InlineHeader::new(&[0x00, 0x05]).expect("fits");
+    let body = Bytes::from(alloc::vec![1, 2, 3, 4, 5]);
+    s.set_capacity(StreamId(0), 1);
+    s.push_framed(StreamId(0), header, body.clone(), true);
+    assert_eq!(s.queued_bytes(StreamId(0)), 7);
+
+    // Credit ends inside the header, then inside the body.
+    let first = s.pop().expect("first part");
+    assert_eq!(
+        (first.header.as_slice(), first.data.len()),
+        (&[0x00][..], 0)
+    );
+    s.set_capacity(StreamId(0), 3);
+    let second = s.pop().expect("second part");
+    assert_eq!(second.header.as_slice(), &[0x05]);
+    assert_eq!(second.data, [1, 2][..]);
+    assert_eq!(second.data.as_ptr(), body.as_ptr());
+    assert!(!second.fin);
+
+    s.set_capacity(StreamId(0), 8);
+    let last = s.pop().expect("rest");
+    assert!(last.header.is_empty());
+    assert_eq!(last.data, [3, 4, 5][..]);
+    assert_eq!(last.data.as_ptr(), body[2..].as_ptr());
+    assert!(last.fin);
+}
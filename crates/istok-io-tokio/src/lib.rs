@@ -1 +1,3 @@
+//! Tokio adapters for the `istok-transport` boundaries.
 
+pub mod udp;
@@ -0,0 +1,124 @@
+//! UDP datagram transport on a tokio `UdpSocket`.
+//!
+//! `TokioUdp` implements `DatagramTransport` with the socket's non-blocking
+//! `try_recv_from`/`try_send_to`; an event loop awaits `readable` or
+//! `writable` and then drains the socket with `try_recv` or retries `send`.
+//!
+//! With the `tracing` feature every receive and send runs in a `udp_recv`
+//! or `udp_send` span carrying the local address, with an event for each
+//! datagram and each socket error. Without it nothing is traced.
+
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+
+use istok_transport::{DatagramRef, DatagramTransport, Endpoint};
+use tokio::net::{ToSocketAddrs, UdpSocket};
+
+/// Largest datagram sent by default: the smallest size every QUIC path
+/// must carry (RFC 9000 §14).
+pub const DEFAULT_MAX_DATAGRAM_SIZE: usize = 1200;
+
+/// Remote UDP address.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UdpEndpoint(pub SocketAddr);
+
+impl fmt::Debug for UdpEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl Endpoint for UdpEndpoint {}
+
+/// Datagram transport over a tokio UDP socket.
+pub struct TokioUdp {
+    socket: UdpSocket,
+    local: SocketAddr,
+    max_datagram_size: usize,
+}
+
+impl TokioUdp {
+    /// Bind a socket to `addr`.
+    pub async fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Self::from_socket(UdpSocket::bind(addr).await?)
+    }
+
+    /// Use an already bound socket.
+    pub fn from_socket(socket: UdpSocket) -> io::Result<Self> {
+        let local = socket.local_addr()?;
+        Ok(Self {
+            socket,
+            local,
+            max_datagram_size: DEFAULT_MAX_DATAGRAM_SIZE,
+        })
+    }
+
+    /// Send datagrams of up to `size` bytes instead of
+    /// `DEFAULT_MAX_DATAGRAM_SIZE`.
+    pub fn with_max_datagram_size(mut self, size: usize) -> Self {
+        self.max_datagram_size = size;
+        self
+    }
+
+    /// Address the socket is bound to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local
+    }
+
+    /// Wait until a datagram may be waiting for `try_recv`.
+    pub async fn readable(&self) -> io::Result<()> {
+        self.socket.readable().await
+    }
+
+    /// Wait until `send` may succeed after it returned `WouldBlock`.
+    pub async fn writable(&self) -> io::Result<()> {
+        self.socket.writable().await
+    }
+}
+
+impl DatagramTransport for TokioUdp {
+    type Endpoint = UdpEndpoint;
+    type Error = io::Error;
+
+    fn try_recv(&mut self, buf: &mut [u8]) -> io::Result<Option<(usize, UdpEndpoint)>> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("udp_recv", local = %self.local).entered();
+        match self.socket.try_recv_from(buf) {
+            Ok((len, from)) => {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(len, %from, "received datagram");
+                Ok(Some((len, UdpEndpoint(from))))
+            }
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(err) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(error = %err, "receive failed");
+                Err(err)
+            }
+        }
+    }
+
+    /// Send one datagram without waiting. `WouldBlock` means the socket
+    /// buffer is full: await `writable` and send again.
+    fn send(&mut self, to: &UdpEndpoint, datagram: DatagramRef<'_>) -> io::Result<()> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("udp_send", local = %self.local).entered();
+        match self.socket.try_send_to(datagram.bytes, to.0) {
+            Ok(_len) => {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(len = _len, to = %to.0, "sent datagram");
+                Ok(())
+            }
+            Err(err) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(error = %err, to = %to.0, "send failed");
+                Err(err)
+            }
+        }
+    }
+
+    fn max_datagram_size(&self) -> usize {
+        self.max_datagram_size
+    }
+}
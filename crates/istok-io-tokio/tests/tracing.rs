@@ -0,0 +1,138 @@
+//! UDP receive and send spans under the `tracing` feature, collected by a
+//! minimal subscriber.
+#![cfg(feature = "tracing")]
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use istok_io_tokio::udp::{TokioUdp, UdpEndpoint};
+use istok_transport::{DatagramRef, DatagramTransport};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+type Fields = BTreeMap<String, String>;
+
+/// An event with the span it was emitted in.
+#[derive(Debug)]
+struct Recorded {
+    fields: Fields,
+    span: &'static str,
+    span_fields: Fields,
+}
+
+#[derive(Default)]
+struct State {
+    spans: BTreeMap<u64, (&'static str, Fields)>,
+    stack: Vec<u64>,
+    events: Vec<Recorded>,
+}
+
+#[derive(Clone, Default)]
+struct Collector {
+    next_id: Arc<AtomicU64>,
+    state: Arc<Mutex<State>>,
+}
+
+struct FieldVisitor<'f>(&'f mut Fields);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}"));
+    }
+}
+
+impl Subscriber for Collector {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut fields = Fields::new();
+        attrs.record(&mut FieldVisitor(&mut fields));
+        let mut state = self.state.lock().expect("not poisoned");
+        state.spans.insert(id, (attrs.metadata().name(), fields));
+        Id::from_u64(id)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::new();
+        event.record(&mut FieldVisitor(&mut fields));
+        let mut state = self.state.lock().expect("not poisoned");
+        let Some(id) = state.stack.last() else {
+            return;
+        };
+        let (span, span_fields) = state.spans[id].clone();
+        state.events.push(Recorded {
+            fields,
+            span,
+            span_fields,
+        });
+    }
+
+    fn enter(&self, span: &Id) {
+        let mut state = self.state.lock().expect("not poisoned");
+        state.stack.push(span.into_u64());
+    }
+
+    fn exit(&self, _span: &Id) {
+        let mut state = self.state.lock().expect("not poisoned");
+        state.stack.pop();
+    }
+}
+
+#[tokio::test]
+async fn datagrams_are_traced_in_receive_and_send_spans() {
+    let collector = Collector::default();
+    let _default = tracing::subscriber::set_default(collector.clone());
+
+    let mut a = TokioUdp::bind("127.0.0.1:0").await.expect("a binds");
+    let mut b = TokioUdp::bind("127.0.0.1:0").await.expect("b binds");
+    let to = UdpEndpoint(b.local_addr());
+    loop {
+        match a.send(&to, DatagramRef { bytes: b"ping" }) {
+            Ok(()) => break,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                a.writable().await.expect("a writable");
+            }
+            Err(err) => panic!("a cannot send: {err}"),
+        }
+    }
+    let mut buf = [0u8; 16];
+    loop {
+        b.readable().await.expect("b readable");
+        if b.try_recv(&mut buf).expect("b receives").is_some() {
+            break;
+        }
+    }
+
+    let state = collector.state.lock().expect("not poisoned");
+    let sent = state
+        .events
+        .iter()
+        .find(|event| event.fields["message"] == "sent datagram")
+        .expect("send traced");
+    assert_eq!(sent.span, "udp_send");
+    assert_eq!(sent.span_fields["local"], a.local_addr().to_string());
+    assert_eq!(sent.fields["len"], "4");
+    assert_eq!(sent.fields["to"], to.0.to_string());
+
+    let received = state
+        .events
+        .iter()
+        .find(|event| event.fields["message"] == "received datagram")
+        .expect("receive traced");
+    assert_eq!(received.span, "udp_recv");
+    assert_eq!(received.span_fields["local"], b.local_addr().to_string());
+    assert_eq!(received.fields["len"], "4");
+    assert_eq!(received.fields["from"], a.local_addr().to_string());
+}
@@ -0,0 +1,39 @@
+use std::io;
+
+use istok_io_tokio::udp::{DEFAULT_MAX_DATAGRAM_SIZE, TokioUdp, UdpEndpoint};
+use istok_transport::{DatagramRef, DatagramTransport};
+
+#[tokio::test]
+async fn datagrams_round_trip_over_loopback() {
+    let mut a = TokioUdp::bind("127.0.0.1:0").await.expect("a binds");
+    let mut b = TokioUdp::bind("127.0.0.1:0")
+        .await
+        .expect("b binds")
+        .with_max_datagram_size(1452);
+    assert_eq!(a.max_datagram_size(), DEFAULT_MAX_DATAGRAM_SIZE);
+    assert_eq!(b.max_datagram_size(), 1452);
+
+    let mut buf = [0u8; 64];
+    assert_eq!(b.try_recv(&mut buf).expect("nothing queued"), None);
+
+    let to = UdpEndpoint(b.local_addr());
+    // A send that would block is retried once the socket is writable.
+    loop {
+        match a.send(&to, DatagramRef { bytes: b"initial" }) {
+            Ok(()) => break,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                a.writable().await.expect("a writable");
+            }
+            Err(err) => panic!("a cannot send: {err}"),
+        }
+    }
+
+    let (len, from) = loop {
+        b.readable().await.expect("b readable");
+        if let Some(received) = b.try_recv(&mut buf).expect("b receives") {
+            break received;
+        }
+    };
+    assert_eq!(&buf[..len], b"initial");
+    assert_eq!(from, UdpEndpoint(a.local_addr()));
+}
@@ -0,0 +1,48 @@
+//! Replay a recording made with `istok_h3::Recorder` through a fresh
+//! `H3Engine` and print every event whose commands differ.
+//!
+//! Usage: `istok-replay <recording>`
+//!
+//! The engine is built from the configuration stored in the recording.
+//! Exits with 1 when the replay differs and 2 when the recording cannot be
+//! read.
+
+use std::process::ExitCode;
+
+use istok_h3::record;
+
+const USAGE: &str = "usage: istok-replay <recording>";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(err) => {
+            eprintln!("istok-replay: {err}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// `Ok(true)` when the recording replays as recorded.
+fn run() -> Result<bool, String> {
+    let mut args = std::env::args().skip(1);
+    let path = match (args.next(), args.next()) {
+        (Some(path), None) if !path.starts_with('-') => path,
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let bytes = std::fs::read(&path).map_err(|err| format!("{path}: {err}"))?;
+    let recording = record::decode(&bytes).map_err(|err| format!("{path}: {err}"))?;
+    let script = &recording.script;
+    let events = script.iter().filter(|step| step.event().is_some()).count();
+    let diffs = record::replay(recording.engine(), script);
+
+    for diff in &diffs {
+        println!("event {}:", diff.event);
+        println!("  recorded: {:?}", diff.expected);
+        println!("  replayed: {:?}", diff.actual);
+    }
+    println!("{events} events replayed, {} differ", diffs.len());
+    Ok(diffs.is_empty())
+}
@@ -0,0 +1,451 @@
+//! Classic CONNECT tunnels to TCP targets (RFC 9114 §4.4).
+//!
+//! `TcpTunnels` consumes the engine's tunnel application events and splices
+//! each CONNECT stream to a non-blocking `TcpStream`. Like `masque::UdpProxy`
+//! it never touches the engine directly: every step yields a `TunnelAction`
+//! for the caller to hand back via `TunnelAction::as_app_action`.
+//!
+//! Targets are resolved and checked by a `target::TargetPolicy`, then
+//! connected without blocking: `poll` watches each connect in progress,
+//! moves on to the next allowed address after the connect timeout, and
+//! answers with `Accept` once one succeeds. A refused target is answered
+//! with 403 and an unreachable one with 502.
+//!
+//! Peer bytes wait in a buffer of at most `MAX_OUTBOUND` bytes until the
+//! target takes them; the caller stops reading a stream while
+//! `wants_peer_data` is false, so QUIC flow control holds the peer back.
+//! Reads from a target stop while the engine reports the stream's writes
+//! as paused.
+//!
+//! Invariants:
+//! - `poll` hands out every action that is ready, never blocks, and reads
+//!   at most `MAX_READS_PER_POLL` chunks per tunnel.
+//! - A tunnel never buffers more than `MAX_OUTBOUND` peer bytes; more
+//!   aborts the stream with `H3_EXCESSIVE_LOAD`.
+//! - A target that fails once connected aborts the stream with
+//!   `H3_CONNECT_ERROR`; a stream reset by the peer drops the TCP connection.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+use std::vec;
+
+use istok_core::bytes::Bytes;
+use istok_core::h3::consts;
+use istok_h3::{AppAction, AppEvent};
+use istok_transport::StreamId;
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::target::{PublicTargets, TargetPolicy};
+use crate::trace::{self, TunnelSpan};
+
+/// Largest chunk read from a target at once.
+const MAX_READ_CHUNK: usize = 16 * 1024;
+
+/// Chunks read from one target per `poll`, so a busy target cannot starve
+/// the others.
+const MAX_READS_PER_POLL: usize = 4;
+
+/// Most peer bytes a tunnel buffers for a target that does not keep up.
+pub const MAX_OUTBOUND: usize = 64 * 1024;
+
+/// How long connecting to one target address may take by default.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// What the engine should do next for a tunnel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TunnelAction {
+    /// The target is connected: send the 200 response.
+    Accept { id: StreamId },
+    /// The target is refused or unreachable: answer with `status`.
+    Reject { id: StreamId, status: u16 },
+    /// Bytes (and possibly EOF) read from the target.
+    Send {
+        id: StreamId,
+        data: Bytes,
+        fin: bool,
+    },
+    /// Abort the stream; the tunnel is gone.
+    Reset { id: StreamId, app_error: u64 },
+}
+
+impl TunnelAction {
+    /// The engine action that carries this out.
+    pub fn as_app_action(&self) -> AppAction<'_> {
+        match self {
+            TunnelAction::Accept { id } => AppAction::AcceptTunnel { id: *id },
+            TunnelAction::Reject { id, status } => AppAction::RejectTunnel {
+                id: *id,
+                status: *status,
+            },
+            TunnelAction::Send { id, data, fin } => AppAction::TunnelSend {
+                id: *id,
+                data: data.clone(),
+                fin: *fin,
+            },
+            TunnelAction::Reset { id, app_error } => AppAction::ResetStream {
+                id: *id,
+                app_error: *app_error,
+            },
+        }
+    }
+}
+
+/// A connect in progress.
+struct Connecting {
+    /// When to give up on the address being connected.
+    deadline: Instant,
+    /// Allowed addresses still to try.
+    rest: vec::IntoIter<SocketAddr>,
+}
+
+struct Tunnel {
+    stream: TcpStream,
+    /// `Some` until the target is connected.
+    connecting: Option<Connecting>,
+    /// Peer bytes the target has not accepted yet.
+    outbound: Vec<u8>,
+    peer_fin: bool,
+    write_shut: bool,
+    target_eof: bool,
+    /// The engine paused writes on the stream: leave the target unread.
+    paused: bool,
+    span: TunnelSpan,
+}
+
+impl Tunnel {
+    /// Both directions finished.
+    fn is_done(&self) -> bool {
+        self.write_shut && self.target_eof
+    }
+}
+
+/// Splices CONNECT tunnels to TCP targets.
+pub struct TcpTunnels {
+    tunnels: BTreeMap<StreamId, Tunnel>,
+    read_buf: Vec<u8>,
+    policy: Box<dyn TargetPolicy>,
+    connect_timeout: Duration,
+}
+
+impl TcpTunnels {
+    /// Tunnels to public IP literals only (see `target::PublicTargets`).
+    pub fn new() -> Self {
+        Self::with_policy(PublicTargets)
+    }
+
+    /// Tunnels to the targets `policy` resolves and allows.
+    pub fn with_policy(policy: impl TargetPolicy + 'static) -> Self {
+        Self {
+            tunnels: BTreeMap::new(),
+            read_buf: vec![0; MAX_READ_CHUNK],
+            policy: Box::new(policy),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+        }
+    }
+
+    /// Give up on a target address after `timeout` instead of
+    /// `DEFAULT_CONNECT_TIMEOUT`.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Number of open tunnels, connected or not.
+    pub fn tunnel_count(&self) -> usize {
+        self.tunnels.len()
+    }
+
+    /// Whether the caller should keep reading CONNECT stream `id`: false
+    /// while `MAX_OUTBOUND` peer bytes wait for the target. `true` for
+    /// streams that are not tunnels here.
+    pub fn wants_peer_data(&self, id: StreamId) -> bool {
+        self.tunnels
+            .get(&id)
+            .is_none_or(|tunnel| tunnel.outbound.len() < MAX_OUTBOUND)
+    }
+
+    /// Apply one engine application event.
+    ///
+    /// `ConnectTunnel` starts connecting to the first allowed address and
+    /// leaves the answer to `poll`; a refused target is answered with
+    /// `Reject` (403) at once, one that cannot be connected to with 502.
+    pub fn on_app_event(&mut self, ev: &AppEvent<'_>) -> Option<TunnelAction> {
+        match ev {
+            AppEvent::ConnectTunnel { id, authority } => {
+                let span = TunnelSpan::tcp(*id, authority);
+                let _entered = span.enter();
+                let now = Instant::now();
+                match connect_target(self.policy.as_mut(), authority, now, self.connect_timeout) {
+                    Ok((stream, connecting)) => {
+                        self.tunnels.insert(
+                            *id,
+                            Tunnel {
+                                stream,
+                                connecting: Some(connecting),
+                                outbound: Vec::new(),
+                                peer_fin: false,
+                                write_shut: false,
+                                target_eof: false,
+                                paused: false,
+                                span,
+                            },
+                        );
+                        None
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+                        Some(reject(*id, 403, "target not allowed"))
+                    }
+                    Err(_) => Some(reject(*id, 502, "target unreachable")),
+                }
+            }
+            AppEvent::TunnelData { id, data, fin } => {
+                let tunnel = self.tunnels.get_mut(id)?;
+                let _entered = tunnel.span.enter();
+                if tunnel.outbound.len() + data.len() > MAX_OUTBOUND {
+                    self.tunnels.remove(id);
+                    return Some(reset(
+                        *id,
+                        consts::H3_EXCESSIVE_LOAD,
+                        "target does not keep up",
+                    ));
+                }
+                tunnel.outbound.extend_from_slice(data);
+                tunnel.peer_fin |= *fin;
+                match flush(tunnel) {
+                    Ok(()) => {
+                        if tunnel.is_done() {
+                            self.tunnels.remove(id);
+                        }
+                        None
+                    }
+                    Err(_) => {
+                        self.tunnels.remove(id);
+                        Some(connect_error(*id, "write to target failed"))
+                    }
+                }
+            }
+            AppEvent::TunnelReset { id, .. } => {
+                self.tunnels.remove(id);
+                None
+            }
+            AppEvent::WritePaused { id } | AppEvent::WriteResumed { id } => {
+                if let Some(tunnel) = self.tunnels.get_mut(id) {
+                    tunnel.paused = matches!(ev, AppEvent::WritePaused { .. });
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Drop the tunnel on stream `id`, e.g. after aborting it.
+    pub fn close(&mut self, id: StreamId) {
+        self.tunnels.remove(&id);
+    }
+
+    /// Make progress on every tunnel without blocking and return every
+    /// resulting action: a connected or unreachable target, bytes read from
+    /// a target, its EOF, or a reset. Empty when idle.
+    pub fn poll(&mut self) -> Vec<TunnelAction> {
+        let now = Instant::now();
+        let mut actions = Vec::new();
+        let mut failed = Vec::new();
+        for (&id, tunnel) in &mut self.tunnels {
+            let _entered = tunnel.span.enter();
+            if tunnel.connecting.is_some() {
+                match poll_connect(tunnel, now, self.connect_timeout) {
+                    Ok(false) => continue,
+                    Ok(true) => actions.push(TunnelAction::Accept { id }),
+                    Err(_) => {
+                        actions.push(reject(id, 502, "target unreachable"));
+                        failed.push(id);
+                        continue;
+                    }
+                }
+            }
+            if flush(tunnel).is_err() {
+                actions.push(connect_error(id, "write to target failed"));
+                failed.push(id);
+                continue;
+            }
+
+            for _ in 0..MAX_READS_PER_POLL {
+                if tunnel.target_eof || tunnel.paused {
+                    break;
+                }
+                match tunnel.stream.read(&mut self.read_buf) {
+                    Ok(0) => {
+                        tunnel.target_eof = true;
+                        actions.push(TunnelAction::Send {
+                            id,
+                            data: Bytes::new(),
+                            fin: true,
+                        });
+                    }
+                    Ok(n) => actions.push(TunnelAction::Send {
+                        id,
+                        data: Bytes::copy_from_slice(&self.read_buf[..n]),
+                        fin: false,
+                    }),
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                    // ECONNRESET and every other error abort the stream.
+                    Err(_) => {
+                        actions.push(connect_error(id, "read from target failed"));
+                        failed.push(id);
+                        break;
+                    }
+                }
+            }
+        }
+        for id in failed {
+            self.tunnels.remove(&id);
+        }
+        self.tunnels.retain(|_, tunnel| !tunnel.is_done());
+        actions
+    }
+}
+
+impl Default for TcpTunnels {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn reject(id: StreamId, status: u16, reason: &'static str) -> TunnelAction {
+    trace::reject(id, status, reason);
+    TunnelAction::Reject { id, status }
+}
+
+fn reset(id: StreamId, app_error: u64, reason: &'static str) -> TunnelAction {
+    trace::reset(id, app_error, reason);
+    TunnelAction::Reset { id, app_error }
+}
+
+fn connect_error(id: StreamId, reason: &'static str) -> TunnelAction {
+    reset(id, consts::H3_CONNECT_ERROR, reason)
+}
+
+/// Write as much buffered peer data as the target accepts, then forward the
+/// peer's FIN once everything was written. Waits for the connect first.
+fn flush(tunnel: &mut Tunnel) -> io::Result<()> {
+    if tunnel.connecting.is_some() {
+        return Ok(());
+    }
+    while !tunnel.outbound.is_empty() {
+        match tunnel.stream.write(&tunnel.outbound) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => {
+                tunnel.outbound.drain(..n);
+            }
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    if tunnel.peer_fin && !tunnel.write_shut {
+        tunnel.stream.shutdown(Shutdown::Write)?;
+        tunnel.write_shut = true;
+    }
+    Ok(())
+}
+
+/// Check on the connect of `tunnel`: `Ok(true)` once the target is
+/// connected, `Ok(false)` while it is pending. A failed or timed out address
+/// is replaced by the next allowed one; `Err` when none is left.
+fn poll_connect(tunnel: &mut Tunnel, now: Instant, timeout: Duration) -> io::Result<bool> {
+    let Some(connecting) = tunnel.connecting.as_mut() else {
+        return Ok(true);
+    };
+    let failed = match tunnel.stream.take_error() {
+        Ok(None) => match tunnel.stream.peer_addr() {
+            Ok(_) => {
+                tunnel.connecting = None;
+                return Ok(true);
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotConnected => now >= connecting.deadline,
+            Err(_) => true,
+        },
+        Ok(Some(_)) | Err(_) => true,
+    };
+    if failed {
+        let (stream, deadline) = connect_next(&mut connecting.rest, now, timeout)?;
+        tunnel.stream = stream;
+        connecting.deadline = deadline;
+    }
+    Ok(false)
+}
+
+/// Start connecting to the first allowed address `authority` resolves to.
+fn connect_target(
+    policy: &mut dyn TargetPolicy,
+    authority: &[u8],
+    now: Instant,
+    timeout: Duration,
+) -> io::Result<(TcpStream, Connecting)> {
+    let invalid = |msg| io::Error::new(io::ErrorKind::InvalidInput, msg);
+    let authority =
+        std::str::from_utf8(authority).map_err(|_| invalid("authority is not UTF-8"))?;
+    let (host, port) = authority
+        .rsplit_once(':')
+        .ok_or_else(|| invalid("authority has no port"))?;
+    let port = port
+        .parse()
+        .map_err(|_| invalid("authority has an invalid port"))?;
+
+    let allowed: Vec<SocketAddr> = policy
+        .resolve(host, port)?
+        .into_iter()
+        .filter(|addr| policy.allow(addr))
+        .collect();
+    if allowed.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "target not allowed",
+        ));
+    }
+    let mut rest = allowed.into_iter();
+    let (stream, deadline) = connect_next(&mut rest, now, timeout)?;
+    Ok((stream, Connecting { deadline, rest }))
+}
+
+/// Start a non-blocking connect to the next address in `rest` that accepts
+/// one, returning the stream and when to give up on it.
+fn connect_next(
+    rest: &mut vec::IntoIter<SocketAddr>,
+    now: Instant,
+    timeout: Duration,
+) -> io::Result<(TcpStream, Instant)> {
+    let mut last = io::Error::new(io::ErrorKind::NotConnected, "target unreachable");
+    for addr in rest {
+        match start_connect(addr) {
+            Ok(stream) => return Ok((stream, now + timeout)),
+            Err(err) => last = err,
+        }
+    }
+    Err(last)
+}
+
+fn start_connect(addr: SocketAddr) -> io::Result<TcpStream> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_nonblocking(true)?;
+    match socket.connect(&addr.into()) {
+        Ok(()) => {}
+        Err(err) if connect_pending(&err) => {}
+        Err(err) => return Err(err),
+    }
+    let stream = TcpStream::from(socket);
+    stream.set_nodelay(true)?;
+    Ok(stream)
+}
+
+/// The error a non-blocking connect returns while it is still in progress.
+fn connect_pending(err: &io::Error) -> bool {
+    #[cfg(unix)]
+    if err.raw_os_error() == Some(libc::EINPROGRESS) {
+        return true;
+    }
+    err.kind() == io::ErrorKind::WouldBlock
+}
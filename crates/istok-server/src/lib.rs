@@ -1 +1,6 @@
-
+pub mod connect;
+pub mod masque;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod target;
+mod trace;
@@ -0,0 +1,174 @@
+//! MASQUE UDP proxy (RFC 9298).
+//!
+//! `UdpProxy` consumes the engine's CONNECT-UDP application events and relays
+//! Context ID 0 datagrams between each tunnel and a connected, non-blocking
+//! `UdpSocket`. It never touches the engine directly: a request is answered
+//! with a `connect::TunnelAction` for the caller to hand back, and received
+//! UDP payloads as HTTP Datagram payloads for `AppAction::SendDatagram`.
+//!
+//! Targets are resolved and checked by a `target::TargetPolicy` before a
+//! socket is bound, so a request never waits on DNS and cannot reach
+//! addresses the policy refuses. A refused target is answered with 403, one
+//! that cannot be resolved or bound with 502.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+
+use istok_core::codec::connect_udp;
+use istok_h3::AppEvent;
+use istok_transport::StreamId;
+
+use crate::connect::TunnelAction;
+use crate::target::{PublicTargets, TargetPolicy};
+use crate::trace::{self, TunnelSpan};
+
+/// Largest UDP payload the proxy receives in one datagram.
+const MAX_UDP_PAYLOAD: usize = 65_527;
+
+struct UdpTunnel {
+    socket: UdpSocket,
+    span: TunnelSpan,
+}
+
+/// Relays CONNECT-UDP tunnels to UDP targets.
+pub struct UdpProxy {
+    tunnels: BTreeMap<StreamId, UdpTunnel>,
+    recv_buf: Vec<u8>,
+    policy: Box<dyn TargetPolicy>,
+}
+
+impl UdpProxy {
+    /// Relays to public IP literals only (see `target::PublicTargets`).
+    pub fn new() -> Self {
+        Self::with_policy(PublicTargets)
+    }
+
+    /// Relays to the targets `policy` resolves and allows.
+    pub fn with_policy(policy: impl TargetPolicy + 'static) -> Self {
+        Self {
+            tunnels: BTreeMap::new(),
+            recv_buf: vec![0; MAX_UDP_PAYLOAD],
+            policy: Box::new(policy),
+        }
+    }
+
+    /// Number of open tunnels.
+    pub fn tunnel_count(&self) -> usize {
+        self.tunnels.len()
+    }
+
+    /// Apply one engine application event.
+    ///
+    /// `ConnectUdp` binds a socket to the target and answers with `Accept`,
+    /// or with `Reject` when the target is refused (403) or cannot be
+    /// resolved or bound (502).
+    pub fn on_app_event(&mut self, ev: &AppEvent<'_>) -> Option<TunnelAction> {
+        match ev {
+            AppEvent::ConnectUdp { id, host, port } => {
+                let span = TunnelSpan::udp(*id, host, *port);
+                let _entered = span.enter();
+                let socket = match connect_target(self.policy.as_mut(), host, *port) {
+                    Ok(socket) => socket,
+                    Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+                        trace::reject(*id, 403, "target not allowed");
+                        return Some(TunnelAction::Reject {
+                            id: *id,
+                            status: 403,
+                        });
+                    }
+                    Err(_) => {
+                        trace::reject(*id, 502, "target unavailable");
+                        return Some(TunnelAction::Reject {
+                            id: *id,
+                            status: 502,
+                        });
+                    }
+                };
+                self.tunnels.insert(*id, UdpTunnel { socket, span });
+                return Some(TunnelAction::Accept { id: *id });
+            }
+            AppEvent::Datagram { id, payload } => {
+                let tunnel = self.tunnels.get(id)?;
+                let _entered = tunnel.span.enter();
+                // Datagrams with other Context IDs or a malformed prefix are
+                // dropped (RFC 9298 §4).
+                if let Ok((connect_udp::CONTEXT_ID_UDP, udp_payload)) =
+                    connect_udp::decode_payload(payload)
+                {
+                    // UDP is unreliable anyway: a full send buffer or an
+                    // ICMP-induced error only loses this datagram.
+                    let _ = tunnel.socket.send(udp_payload);
+                }
+            }
+            AppEvent::ConnectUdpClosed { id } => {
+                self.tunnels.remove(id);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Drop the tunnel on stream `id`, e.g. after aborting it.
+    pub fn close(&mut self, id: StreamId) {
+        self.tunnels.remove(&id);
+    }
+
+    /// Receive one pending UDP datagram from any tunnel.
+    ///
+    /// Returns the tunnel's stream id and an HTTP Datagram payload (Context
+    /// ID 0 + UDP payload) ready for `AppAction::SendDatagram`, or `None`
+    /// when no socket has data.
+    pub fn poll_recv(&mut self) -> io::Result<Option<(StreamId, Vec<u8>)>> {
+        for (id, tunnel) in &self.tunnels {
+            let _entered = tunnel.span.enter();
+            let n = match tunnel.socket.recv(&mut self.recv_buf) {
+                Ok(n) => n,
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::ConnectionRefused
+                    ) =>
+                {
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+
+            let mut prefix = [0u8; 8];
+            let prefix_len = connect_udp::encode_udp_payload_header(&mut prefix)
+                .map_err(|err| io::Error::other(err.to_string()))?;
+            let mut payload = Vec::with_capacity(prefix_len + n);
+            payload.extend_from_slice(&prefix[..prefix_len]);
+            payload.extend_from_slice(&self.recv_buf[..n]);
+            return Ok(Some((*id, payload)));
+        }
+        Ok(None)
+    }
+}
+
+impl Default for UdpProxy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A socket connected to the first allowed address `host` resolves to.
+fn connect_target(policy: &mut dyn TargetPolicy, host: &[u8], port: u16) -> io::Result<UdpSocket> {
+    let host = std::str::from_utf8(host)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "target host is not UTF-8"))?;
+    let target = policy
+        .resolve(host, port)?
+        .into_iter()
+        .find(|addr| policy.allow(addr))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::PermissionDenied, "target not allowed"))?;
+
+    let local = match target {
+        SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.connect(target)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
@@ -0,0 +1,81 @@
+//! Server-wide totals of `H3Engine::stats` across connections.
+//!
+//! `Metrics` is a cheap, cloneable handle: each connection task reports
+//! its engine's stats under a key of the caller's choosing, and a
+//! monitoring task reads `Metrics::snapshot` whenever it likes. Like the
+//! other modules here it never touches an engine itself.
+//!
+//! Invariants:
+//! - A live connection contributes its latest report; reporting again
+//!   replaces it rather than adding to it.
+//! - `close` folds a connection into the closed totals exactly once; a
+//!   key reported after closing counts as a new connection.
+//! - A panic while the lock is held does not disable metrics: the
+//!   counters are plain sums, so a poisoned lock is used as is.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use istok_h3::EngineStats;
+
+/// Totals at one point in time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// Connections reported and not closed yet.
+    pub open_connections: usize,
+    /// Connections closed so far.
+    pub closed_connections: u64,
+    /// Stats of all connections, open and closed, merged.
+    pub totals: EngineStats,
+}
+
+#[derive(Default)]
+struct Inner {
+    live: BTreeMap<u64, EngineStats>,
+    closed: EngineStats,
+    closed_connections: u64,
+}
+
+/// Shared aggregator of per-connection engine stats.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Metrics {
+    /// An aggregator with no connection recorded.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the current stats of live connection `conn`.
+    pub fn report(&self, conn: u64, stats: &EngineStats) {
+        self.lock().live.insert(conn, stats.clone());
+    }
+
+    /// Connection `conn` ended with `stats`.
+    pub fn close(&self, conn: u64, stats: &EngineStats) {
+        let mut inner = self.lock();
+        inner.live.remove(&conn);
+        inner.closed.merge(stats);
+        inner.closed_connections += 1;
+    }
+
+    /// Totals over closed and live connections.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let inner = self.lock();
+        let mut totals = inner.closed.clone();
+        for stats in inner.live.values() {
+            totals.merge(stats);
+        }
+        MetricsSnapshot {
+            open_connections: inner.live.len(),
+            closed_connections: inner.closed_connections,
+            totals,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -0,0 +1,94 @@
+//! Which targets CONNECT and CONNECT-UDP tunnels may reach.
+//!
+//! A proxy that connects wherever a client asks relays into every network
+//! behind it. `TargetPolicy` decides how a requested host resolves and which
+//! of the resulting addresses are allowed; `connect::TcpTunnels` and
+//! `masque::UdpProxy` consult it before opening a socket.
+//!
+//! Invariants:
+//! - Policies run on the connection loop: `resolve` must not block, so the
+//!   default performs no DNS lookup and only accepts IP literals.
+//! - The default policy only allows globally routable unicast addresses;
+//!   loopback, private, link-local and documentation ranges are refused.
+//! - Addresses are checked after resolution, so a name cannot smuggle in a
+//!   refused address.
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// Resolves tunnel targets and decides which addresses they may reach.
+pub trait TargetPolicy {
+    /// Addresses for `host` and `port`, tried in order.
+    ///
+    /// `host` is an IP literal, possibly in brackets, or a name. The default
+    /// accepts IP literals only; override it to resolve names without
+    /// blocking, e.g. from a cache.
+    fn resolve(&mut self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        resolve_literal(host, port)
+    }
+
+    /// Whether a tunnel may reach `addr`. The default allows public
+    /// addresses only (see [`is_public`]).
+    fn allow(&mut self, addr: &SocketAddr) -> bool {
+        is_public(addr.ip())
+    }
+}
+
+/// The default policy: IP literals of public addresses.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PublicTargets;
+
+impl TargetPolicy for PublicTargets {}
+
+/// `host:port` for an IP literal `host`, with or without brackets.
+pub fn resolve_literal(host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+    let host = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+    let ip: IpAddr = host
+        .parse()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "target is not an IP address"))?;
+    Ok(vec![SocketAddr::new(ip, port)])
+}
+
+/// Whether `ip` is a globally routable unicast address.
+///
+/// IPv4-mapped IPv6 addresses are judged by their IPv4 address.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // "This network" 0.0.0.0/8, shared address space 100.64.0.0/10 and
+        // the reserved 240.0.0.0/4.
+        || a == 0
+        || (a == 100 && b & 0xc0 == 0x40)
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let [a, b, ..] = ip.segments();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local fc00::/7, link-local fe80::/10, documentation
+        // 2001:db8::/32.
+        || a & 0xfe00 == 0xfc00
+        || a & 0xffc0 == 0xfe80
+        || (a == 0x2001 && b == 0x0db8))
+}
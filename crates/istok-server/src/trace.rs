@@ -0,0 +1,96 @@
+//! `tracing` spans and events for tunnels.
+//!
+//! The connection and stream spans come from `istok-h3`, whose `tracing`
+//! feature this crate's feature turns on. On top of them every CONNECT or
+//! CONNECT-UDP tunnel gets a span of its own, opened as a child of whatever
+//! span is current when the tunnel is requested, so a server loop that
+//! handles a connection inside a span sees its tunnels nested under it.
+//! Each decision to refuse or abort a tunnel emits a `debug` event with its
+//! reason inside that span. Without the feature `TunnelSpan` is zero-sized
+//! and every call compiles to nothing.
+//!
+//! Invariants:
+//! - A tunnel span lives as long as its tunnel; refusals are reported in
+//!   the span of the refused request.
+//! - Tracing never changes what the tunnels do.
+
+use istok_transport::StreamId;
+
+#[cfg(feature = "tracing")]
+pub(crate) use on::TunnelSpan;
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) use off::TunnelSpan;
+
+/// Tunnel `id` is refused or aborted with `app_error`.
+#[inline]
+pub(crate) fn reset(id: StreamId, app_error: u64, reason: &'static str) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(stream = id.0, app_error, reason, "resetting tunnel");
+    #[cfg(not(feature = "tracing"))]
+    let _ = (id, app_error, reason);
+}
+
+/// Tunnel `id` is refused with `status` before it was established.
+#[inline]
+pub(crate) fn reject(id: StreamId, status: u16, reason: &'static str) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(stream = id.0, status, reason, "rejecting tunnel");
+    #[cfg(not(feature = "tracing"))]
+    let _ = (id, status, reason);
+}
+
+#[cfg(feature = "tracing")]
+mod on {
+    use istok_transport::StreamId;
+    use tracing::Span;
+
+    pub(crate) type Entered = tracing::span::EnteredSpan;
+
+    pub(crate) struct TunnelSpan(Span);
+
+    impl TunnelSpan {
+        /// Span for a CONNECT tunnel on stream `id` to `authority`.
+        pub(crate) fn tcp(id: StreamId, authority: &[u8]) -> Self {
+            let target = String::from_utf8_lossy(authority);
+            Self(tracing::debug_span!("connect_tunnel", stream = id.0, %target))
+        }
+
+        /// Span for a CONNECT-UDP tunnel on stream `id` to `host:port`.
+        pub(crate) fn udp(id: StreamId, host: &[u8], port: u16) -> Self {
+            let host = String::from_utf8_lossy(host);
+            Self(tracing::debug_span!("connect_udp_tunnel", stream = id.0, %host, port))
+        }
+
+        /// Enter the span until the guard is dropped.
+        pub(crate) fn enter(&self) -> Entered {
+            self.0.clone().entered()
+        }
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+mod off {
+    use istok_transport::StreamId;
+
+    pub(crate) struct Entered;
+
+    pub(crate) struct TunnelSpan;
+
+    impl TunnelSpan {
+        #[inline]
+        pub(crate) fn tcp(_id: StreamId, _authority: &[u8]) -> Self {
+            Self
+        }
+
+        #[inline]
+        pub(crate) fn udp(_id: StreamId, _host: &[u8], _port: u16) -> Self {
+            Self
+        }
+
+        #[inline]
+        pub(crate) fn enter(&self) -> Entered {
+            Entered
+        }
+    }
+}
@@ -0,0 +1,449 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use istok_core::h3::consts;
+use istok_core::h3::settings::Settings;
+use istok_core::qpack::HeaderField;
+use istok_h3::mock::Collect;
+use istok_h3::mock::{control_stream, frame, headers, settings_payload, written};
+use istok_h3::{AppEvent, Engine, EngineCommand, EngineEvent, H3Engine};
+use istok_server::connect::{MAX_OUTBOUND, TcpTunnels, TunnelAction};
+use istok_server::target::TargetPolicy;
+use istok_transport::{QuicCommand, QuicEvent, StreamId, StreamKind};
+
+const TUNNEL: StreamId = StreamId(0);
+
+const REFUSED: TunnelAction = TunnelAction::Reject {
+    id: TUNNEL,
+    status: 403,
+};
+
+const UNREACHABLE: TunnelAction = TunnelAction::Reject {
+    id: TUNNEL,
+    status: 502,
+};
+
+/// Allows the loopback targets these tests listen on.
+struct Loopback;
+
+impl TargetPolicy for Loopback {
+    fn allow(&mut self, addr: &SocketAddr) -> bool {
+        addr.ip().is_loopback()
+    }
+}
+
+/// What one engine step produced.
+#[derive(Default)]
+struct Step {
+    /// `(data, fin)` of every stream write.
+    writes: Vec<(Vec<u8>, bool)>,
+    /// Tunnel actions the application events led to.
+    actions: Vec<TunnelAction>,
+    /// Stream reset codes.
+    resets: Vec<u64>,
+}
+
+/// Run one engine step and hand every application event to the tunnels.
+fn drive(engine: &mut H3Engine, tunnels: &mut TcpTunnels, ev: EngineEvent<'_>) -> Step {
+    let mut sink = Collect::default();
+    engine.on_event(ev, &mut sink);
+    let mut step = Step::default();
+    for cmd in &sink.0 {
+        if let Some((_, data, fin)) = written(cmd) {
+            step.writes.push((data, fin));
+        }
+        match cmd {
+            EngineCommand::App(app) => step.actions.extend(tunnels.on_app_event(app)),
+            EngineCommand::Quic(QuicCommand::ResetStream { app_error, .. }) => {
+                step.resets.push(*app_error);
+            }
+            _ => {}
+        }
+    }
+    step
+}
+
+/// Bring up the peer control stream.
+fn open_control(engine: &mut H3Engine, tunnels: &mut TcpTunnels) {
+    let control = control_stream(&settings_payload(&Settings::default()));
+    let control_id = StreamId(3);
+    let events = [
+        QuicEvent::StreamOpened {
+            id: control_id,
+            kind: StreamKind::Uni,
+        },
+        QuicEvent::StreamReadable {
+            id: control_id,
+            data: &control,
+            fin: false,
+        },
+    ];
+    for ev in events {
+        drive(engine, tunnels, EngineEvent::Quic(ev));
+    }
+}
+
+/// Send a CONNECT for `authority` on stream `id`.
+fn connect(
+    engine: &mut H3Engine,
+    tunnels: &mut TcpTunnels,
+    id: StreamId,
+    authority: &str,
+) -> Vec<TunnelAction> {
+    let fields = [
+        HeaderField {
+            name: b":method",
+            value: b"CONNECT",
+        },
+        HeaderField {
+            name: b":authority",
+            value: authority.as_bytes(),
+        },
+    ];
+    let request = headers(&fields);
+
+    let events = [
+        QuicEvent::StreamOpened {
+            id,
+            kind: StreamKind::Bidi,
+        },
+        QuicEvent::StreamReadable {
+            id,
+            data: &request,
+            fin: false,
+        },
+    ];
+    let mut actions = Vec::new();
+    for ev in events {
+        actions.extend(drive(engine, tunnels, EngineEvent::Quic(ev)).actions);
+    }
+    actions
+}
+
+/// Bring up the peer control stream and send a CONNECT for `authority`.
+fn open_tunnel(
+    engine: &mut H3Engine,
+    tunnels: &mut TcpTunnels,
+    authority: &str,
+) -> Vec<TunnelAction> {
+    open_control(engine, tunnels);
+    connect(engine, tunnels, TUNNEL, authority)
+}
+
+/// Poll until the tunnels have something to do.
+fn poll_until(tunnels: &mut TcpTunnels) -> Vec<TunnelAction> {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let actions = tunnels.poll();
+        if !actions.is_empty() {
+            return actions;
+        }
+        assert!(Instant::now() < deadline, "tunnel stayed idle");
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+/// The answer to a CONNECT: given right away for a refused target, or by
+/// `poll` once the connect finished.
+fn answer(tunnels: &mut TcpTunnels, actions: Vec<TunnelAction>) -> Vec<TunnelAction> {
+    if actions.is_empty() {
+        poll_until(tunnels)
+    } else {
+        actions
+    }
+}
+
+/// An echo server on a loopback port.
+fn echo_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("echo binds");
+    let addr = listener.local_addr().expect("echo addr");
+    thread::spawn(move || {
+        let (mut socket, _) = listener.accept().expect("echo accepts");
+        let mut buf = [0u8; 1024];
+        while let Ok(n) = socket.read(&mut buf) {
+            if n == 0 || socket.write_all(&buf[..n]).is_err() {
+                break;
+            }
+        }
+    });
+    addr
+}
+
+/// Hand `actions` to the engine and collect what the targets echoed per
+/// stream until each of `ids` sent its EOF.
+fn relay_until_eof(
+    engine: &mut H3Engine,
+    tunnels: &mut TcpTunnels,
+    ids: &[StreamId],
+) -> BTreeMap<StreamId, Vec<u8>> {
+    let mut echoed = BTreeMap::new();
+    let mut open = ids.len();
+    while open > 0 {
+        for action in poll_until(tunnels) {
+            let writes = drive(engine, tunnels, EngineEvent::App(action.as_app_action())).writes;
+            let TunnelAction::Send { id, data, fin } = action else {
+                panic!("unexpected tunnel action {action:?}");
+            };
+            echoed
+                .entry(id)
+                .or_insert_with(Vec::new)
+                .extend_from_slice(&data);
+            if fin {
+                assert_eq!(writes, [(Vec::new(), true)]);
+                open -= 1;
+            } else {
+                assert_eq!(writes, [(frame(consts::FRAME_TYPE_DATA, &data), false)]);
+            }
+        }
+    }
+    echoed
+}
+
+#[test]
+fn connect_splices_stream_to_tcp_echo_server() {
+    let addr = echo_server();
+
+    let mut engine = H3Engine::default();
+    let mut tunnels = TcpTunnels::with_policy(Loopback);
+    // The connect runs in the background; `poll` reports it.
+    let actions = open_tunnel(&mut engine, &mut tunnels, &addr.to_string());
+    assert_eq!(actions, []);
+    assert_eq!(tunnels.tunnel_count(), 1);
+    let actions = poll_until(&mut tunnels);
+    assert_eq!(actions, [TunnelAction::Accept { id: TUNNEL }]);
+
+    let writes = drive(
+        &mut engine,
+        &mut tunnels,
+        EngineEvent::App(actions[0].as_app_action()),
+    )
+    .writes;
+    assert_eq!(writes, [(vec![0x01, 0x03, 0x00, 0x00, 0xd9], false)]);
+
+    // Peer sends "ping" and half-closes; the echo server answers and closes.
+    let data = frame(consts::FRAME_TYPE_DATA, b"ping");
+    drive(
+        &mut engine,
+        &mut tunnels,
+        EngineEvent::Quic(QuicEvent::StreamReadable {
+            id: TUNNEL,
+            data: &data,
+            fin: true,
+        }),
+    );
+
+    let echoed = relay_until_eof(&mut engine, &mut tunnels, &[TUNNEL]);
+    assert_eq!(echoed[&TUNNEL], b"ping");
+    assert_eq!(tunnels.tunnel_count(), 0);
+}
+
+#[test]
+fn tunnels_run_side_by_side() {
+    let ids = [StreamId(0), StreamId(4), StreamId(8)];
+    let mut engine = H3Engine::default();
+    let mut tunnels = TcpTunnels::with_policy(Loopback);
+    open_control(&mut engine, &mut tunnels);
+    for id in ids {
+        let addr = echo_server();
+        assert_eq!(
+            connect(&mut engine, &mut tunnels, id, &addr.to_string()),
+            []
+        );
+    }
+    assert_eq!(tunnels.tunnel_count(), ids.len());
+
+    let mut accepted = Vec::new();
+    while accepted.len() < ids.len() {
+        for action in poll_until(&mut tunnels) {
+            let TunnelAction::Accept { id } = action else {
+                panic!("unexpected tunnel action {action:?}");
+            };
+            drive(
+                &mut engine,
+                &mut tunnels,
+                EngineEvent::App(action.as_app_action()),
+            );
+            accepted.push(id);
+        }
+    }
+    accepted.sort();
+    assert_eq!(accepted, ids);
+
+    for id in ids {
+        let data = frame(consts::FRAME_TYPE_DATA, format!("ping {}", id.0).as_bytes());
+        drive(
+            &mut engine,
+            &mut tunnels,
+            EngineEvent::Quic(QuicEvent::StreamReadable {
+                id,
+                data: &data,
+                fin: true,
+            }),
+        );
+    }
+
+    let echoed = relay_until_eof(&mut engine, &mut tunnels, &ids);
+    for id in ids {
+        assert_eq!(echoed[&id], format!("ping {}", id.0).as_bytes());
+    }
+    assert_eq!(tunnels.tunnel_count(), 0);
+}
+
+#[test]
+fn peer_data_beyond_the_buffer_cap_aborts_the_tunnel() {
+    let addr = echo_server();
+    let mut tunnels = TcpTunnels::with_policy(Loopback);
+    let connect = AppEvent::ConnectTunnel {
+        id: TUNNEL,
+        authority: addr.to_string().into_bytes(),
+    };
+    assert_eq!(tunnels.on_app_event(&connect), None);
+
+    // Until the target is connected, peer bytes wait in the buffer.
+    let fill = AppEvent::TunnelData {
+        id: TUNNEL,
+        data: vec![0; MAX_OUTBOUND],
+        fin: false,
+    };
+    assert_eq!(tunnels.on_app_event(&fill), None);
+    assert!(!tunnels.wants_peer_data(TUNNEL));
+
+    let more = AppEvent::TunnelData {
+        id: TUNNEL,
+        data: vec![0],
+        fin: false,
+    };
+    assert_eq!(
+        tunnels.on_app_event(&more),
+        Some(TunnelAction::Reset {
+            id: TUNNEL,
+            app_error: consts::H3_EXCESSIVE_LOAD,
+        })
+    );
+    assert_eq!(tunnels.tunnel_count(), 0);
+}
+
+#[test]
+fn buffered_peer_data_drains_once_connected() {
+    let addr = echo_server();
+    let mut tunnels = TcpTunnels::with_policy(Loopback);
+    let connect = AppEvent::ConnectTunnel {
+        id: TUNNEL,
+        authority: addr.to_string().into_bytes(),
+    };
+    assert_eq!(tunnels.on_app_event(&connect), None);
+
+    let data = AppEvent::TunnelData {
+        id: TUNNEL,
+        data: vec![7; MAX_OUTBOUND / 2],
+        fin: false,
+    };
+    assert_eq!(tunnels.on_app_event(&data), None);
+    assert_eq!(tunnels.on_app_event(&data), None);
+    assert!(!tunnels.wants_peer_data(TUNNEL));
+
+    assert_eq!(
+        poll_until(&mut tunnels)[0],
+        TunnelAction::Accept { id: TUNNEL }
+    );
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !tunnels.wants_peer_data(TUNNEL) {
+        assert!(Instant::now() < deadline, "buffer never drained");
+        tunnels.poll();
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn tcp_reset_aborts_stream_with_connect_error() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("target binds");
+    let addr = listener.local_addr().expect("target addr");
+    thread::spawn(move || {
+        let (socket, _) = listener.accept().expect("target accepts");
+        // Closing with unread bytes makes the kernel answer with a RST.
+        let mut buf = [0u8; 1];
+        let _ = socket.peek(&mut buf);
+        drop(socket);
+    });
+
+    let mut engine = H3Engine::default();
+    let mut tunnels = TcpTunnels::with_policy(Loopback);
+    let actions = open_tunnel(&mut engine, &mut tunnels, &addr.to_string());
+    let actions = answer(&mut tunnels, actions);
+    assert_eq!(actions, [TunnelAction::Accept { id: TUNNEL }]);
+    drive(
+        &mut engine,
+        &mut tunnels,
+        EngineEvent::App(actions[0].as_app_action()),
+    );
+
+    let data = frame(consts::FRAME_TYPE_DATA, b"unread");
+    drive(
+        &mut engine,
+        &mut tunnels,
+        EngineEvent::Quic(QuicEvent::StreamReadable {
+            id: TUNNEL,
+            data: &data,
+            fin: false,
+        }),
+    );
+
+    let [action] = poll_until(&mut tunnels).try_into().expect("one action");
+    assert_eq!(
+        action,
+        TunnelAction::Reset {
+            id: TUNNEL,
+            app_error: consts::H3_CONNECT_ERROR,
+        }
+    );
+    assert_eq!(tunnels.tunnel_count(), 0);
+    let resets = drive(
+        &mut engine,
+        &mut tunnels,
+        EngineEvent::App(action.as_app_action()),
+    )
+    .resets;
+    assert_eq!(resets, [consts::H3_CONNECT_ERROR]);
+}
+
+#[test]
+fn unreachable_target_is_answered_with_502() {
+    // Grab a free port and release it so nothing listens there.
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("free port");
+
+    let mut engine = H3Engine::default();
+    let mut tunnels = TcpTunnels::with_policy(Loopback);
+    let actions = open_tunnel(&mut engine, &mut tunnels, &addr.to_string());
+    assert_eq!(answer(&mut tunnels, actions), [UNREACHABLE]);
+    assert_eq!(tunnels.tunnel_count(), 0);
+}
+
+#[test]
+fn internal_targets_are_refused_by_default() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("target binds");
+    let addr = listener.local_addr().expect("target addr");
+    listener
+        .set_nonblocking(true)
+        .expect("listener is non-blocking");
+
+    let mut engine = H3Engine::default();
+    let mut tunnels = TcpTunnels::new();
+    let actions = open_tunnel(&mut engine, &mut tunnels, &addr.to_string());
+    assert_eq!(actions, [REFUSED]);
+    assert_eq!(tunnels.tunnel_count(), 0);
+    // The refused target was never connected to.
+    assert!(listener.accept().is_err());
+}
+
+#[test]
+fn names_are_not_resolved_by_default() {
+    let mut engine = H3Engine::default();
+    let mut tunnels = TcpTunnels::with_policy(Loopback);
+    let actions = open_tunnel(&mut engine, &mut tunnels, "localhost:443");
+    assert_eq!(actions, [UNREACHABLE]);
+}
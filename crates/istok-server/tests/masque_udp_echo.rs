@@ -0,0 +1,220 @@
+use std::net::{SocketAddr, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use istok_core::h3::settings::Settings;
+use istok_core::qpack::HeaderField;
+use istok_h3::mock::Collect;
+use istok_h3::mock::{control_stream, headers, settings_payload};
+use istok_h3::{AppAction, AppEvent, Engine, EngineCommand, EngineEvent, H3Config, H3Engine};
+use istok_server::connect::TunnelAction;
+use istok_server::masque::UdpProxy;
+use istok_server::target::TargetPolicy;
+use istok_transport::{QuicCommand, QuicEvent, StreamId, StreamKind};
+
+const TUNNEL: StreamId = StreamId(0);
+
+/// Allows the loopback echo server.
+struct Loopback;
+
+impl TargetPolicy for Loopback {
+    fn allow(&mut self, addr: &SocketAddr) -> bool {
+        addr.ip().is_loopback()
+    }
+}
+
+fn engine_with(settings: Settings) -> H3Engine {
+    H3Engine::new(
+        H3Config::builder()
+            .settings(settings)
+            .build()
+            .expect("valid config"),
+    )
+}
+
+/// Run one engine step and hand every application event to the proxy.
+/// Returns the engine's commands and the proxy's answers.
+fn drive<'a>(
+    engine: &mut H3Engine,
+    proxy: &mut UdpProxy,
+    ev: EngineEvent<'a>,
+) -> (Vec<EngineCommand<'a>>, Vec<TunnelAction>) {
+    let mut sink = Collect::default();
+    engine.on_event(ev, &mut sink);
+    let actions = sink
+        .0
+        .iter()
+        .filter_map(|cmd| match cmd {
+            EngineCommand::App(app) => proxy.on_app_event(app),
+            _ => None,
+        })
+        .collect();
+    (sink.0, actions)
+}
+
+fn spawn_echo_server() -> u16 {
+    let socket = UdpSocket::bind("127.0.0.1:0").expect("echo binds");
+    let port = socket.local_addr().expect("echo addr").port();
+    thread::spawn(move || {
+        let mut buf = [0u8; 1500];
+        while let Ok((n, from)) = socket.recv_from(&mut buf) {
+            let _ = socket.send_to(&buf[..n], from);
+        }
+    });
+    port
+}
+
+#[test]
+fn connect_udp_relays_datagrams_through_udp_echo_server() {
+    let echo_port = spawn_echo_server();
+    let settings = Settings {
+        enable_connect_protocol: Some(1),
+        h3_datagram: Some(1),
+        ..Settings::default()
+    };
+    let mut engine = engine_with(settings.clone());
+    let mut proxy = UdpProxy::with_policy(Loopback);
+
+    let control = control_stream(&settings_payload(&settings));
+
+    let path = format!("/.well-known/masque/udp/127.0.0.1/{echo_port}/");
+    let fields = [
+        HeaderField {
+            name: b":method",
+            value: b"CONNECT",
+        },
+        HeaderField {
+            name: b":protocol",
+            value: b"connect-udp",
+        },
+        HeaderField {
+            name: b":scheme",
+            value: b"https",
+        },
+        HeaderField {
+            name: b":authority",
+            value: b"localhost",
+        },
+        HeaderField {
+            name: b":path",
+            value: path.as_bytes(),
+        },
+    ];
+    let request = headers(&fields);
+
+    let control_id = StreamId(3);
+    drive(
+        &mut engine,
+        &mut proxy,
+        EngineEvent::Quic(QuicEvent::StreamOpened {
+            id: control_id,
+            kind: StreamKind::Uni,
+        }),
+    );
+    drive(
+        &mut engine,
+        &mut proxy,
+        EngineEvent::Quic(QuicEvent::StreamReadable {
+            id: control_id,
+            data: &control,
+            fin: false,
+        }),
+    );
+    drive(
+        &mut engine,
+        &mut proxy,
+        EngineEvent::Quic(QuicEvent::StreamOpened {
+            id: TUNNEL,
+            kind: StreamKind::Bidi,
+        }),
+    );
+    let (cmds, actions) = drive(
+        &mut engine,
+        &mut proxy,
+        EngineEvent::Quic(QuicEvent::StreamReadable {
+            id: TUNNEL,
+            data: &request,
+            fin: false,
+        }),
+    );
+    assert!(matches!(
+        cmds.as_slice(),
+        [EngineCommand::App(AppEvent::ConnectUdp { port, .. })] if *port == echo_port
+    ));
+    assert_eq!(actions, [TunnelAction::Accept { id: TUNNEL }]);
+    assert_eq!(proxy.tunnel_count(), 1);
+    let (cmds, _) = drive(
+        &mut engine,
+        &mut proxy,
+        EngineEvent::App(actions[0].as_app_action()),
+    );
+    assert!(matches!(
+        cmds.as_slice(),
+        [EngineCommand::Quic(QuicCommand::StreamWriteOwned {
+            id: TUNNEL,
+            ..
+        })]
+    ));
+
+    // Quarter stream id 0, Context ID 0, UDP payload.
+    let datagram = [0x00, 0x00, b'p', b'i', b'n', b'g'];
+    drive(
+        &mut engine,
+        &mut proxy,
+        EngineEvent::Quic(QuicEvent::Datagram { data: &datagram }),
+    );
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let (id, payload) = loop {
+        if let Some(received) = proxy.poll_recv().expect("proxy receives") {
+            break received;
+        }
+        assert!(Instant::now() < deadline, "no echo received");
+        thread::sleep(Duration::from_millis(5));
+    };
+    assert_eq!(id, TUNNEL);
+
+    let (cmds, _) = drive(
+        &mut engine,
+        &mut proxy,
+        EngineEvent::App(AppAction::SendDatagram {
+            id,
+            payload: &payload,
+        }),
+    );
+    match cmds.as_slice() {
+        [EngineCommand::Quic(QuicCommand::SendDatagram { data })] => {
+            assert_eq!(data.as_slice(), &datagram);
+        }
+        _ => panic!("expected one SendDatagram command"),
+    }
+
+    drive(
+        &mut engine,
+        &mut proxy,
+        EngineEvent::Quic(QuicEvent::StreamReadable {
+            id: TUNNEL,
+            data: &[],
+            fin: true,
+        }),
+    );
+    assert_eq!(proxy.tunnel_count(), 0);
+}
+
+#[test]
+fn internal_and_named_targets_are_refused_by_default() {
+    let mut proxy = UdpProxy::new();
+    for host in [&b"127.0.0.1"[..], b"10.0.0.1", b"::1", b"localhost"] {
+        let event = AppEvent::ConnectUdp {
+            id: TUNNEL,
+            host: host.to_vec(),
+            port: 53,
+        };
+        let status = if host == b"localhost" { 502 } else { 403 };
+        assert_eq!(
+            proxy.on_app_event(&event),
+            Some(TunnelAction::Reject { id: TUNNEL, status })
+        );
+    }
+    assert_eq!(proxy.tunnel_count(), 0);
+}
@@ -0,0 +1,114 @@
+#![cfg(feature = "metrics")]
+
+use std::thread;
+
+use istok_core::codec::varint;
+use istok_core::h3::consts;
+use istok_h3::mock::Discard;
+use istok_h3::mock::frame;
+use istok_h3::{Engine, EngineEvent, H3Engine};
+use istok_server::metrics::Metrics;
+use istok_transport::{QuicEvent, StreamId, StreamKind};
+
+/// An engine that served one GET, or closed on a bad control stream.
+fn connection(serve: bool) -> H3Engine {
+    let mut control = vec![0u8; 8];
+    let type_len = varint::encode(consts::STREAM_TYPE_CONTROL, &mut control).expect("encodes");
+    control.truncate(type_len);
+    let first = if serve {
+        consts::FRAME_TYPE_SETTINGS
+    } else {
+        consts::FRAME_TYPE_DATA
+    };
+    control.extend_from_slice(&frame(first, &[]));
+    let request = frame(
+        consts::FRAME_TYPE_HEADERS,
+        &[0x00, 0x00, 0xd1, 0xd7, 0xc1, 0x50, 0x01, b'a'],
+    );
+
+    let mut engine = H3Engine::default();
+    engine.on_event(EngineEvent::Boot, &mut Discard);
+    for ev in [
+        QuicEvent::StreamOpened {
+            id: StreamId(3),
+            kind: StreamKind::Uni,
+        },
+        QuicEvent::StreamReadable {
+            id: StreamId(3),
+            data: &control,
+            fin: false,
+        },
+        QuicEvent::StreamOpened {
+            id: StreamId(0),
+            kind: StreamKind::Bidi,
+        },
+        QuicEvent::StreamReadable {
+            id: StreamId(0),
+            data: &request,
+            fin: true,
+        },
+    ] {
+        engine.on_event(EngineEvent::Quic(ev), &mut Discard);
+    }
+    engine
+}
+
+#[test]
+fn totals_cover_live_and_closed_connections() {
+    let metrics = Metrics::new();
+    let served = connection(true);
+    let failed = connection(false);
+
+    metrics.report(1, served.stats());
+    // A second report replaces the first.
+    metrics.report(1, served.stats());
+    metrics.report(2, failed.stats());
+    let snapshot = metrics.snapshot();
+    assert_eq!(
+        (snapshot.open_connections, snapshot.closed_connections),
+        (2, 0)
+    );
+    assert_eq!(snapshot.totals.streams_completed, 2);
+    assert_eq!(snapshot.totals.frames_out.data, 1);
+
+    metrics.close(2, failed.stats());
+    let snapshot = metrics.snapshot();
+    assert_eq!(
+        (snapshot.open_connections, snapshot.closed_connections),
+        (1, 1)
+    );
+    assert_eq!(
+        snapshot
+            .totals
+            .close_codes
+            .get(&consts::H3_FRAME_UNEXPECTED),
+        Some(&1)
+    );
+    assert_eq!(snapshot.totals.frames_in.headers, 1);
+}
+
+#[test]
+fn connection_tasks_report_concurrently() {
+    let metrics = Metrics::new();
+    let tasks: Vec<_> = (0..4)
+        .map(|conn| {
+            let metrics = metrics.clone();
+            thread::spawn(move || {
+                let engine = connection(true);
+                metrics.report(conn, engine.stats());
+                metrics.close(conn, engine.stats());
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.join().expect("task finished");
+    }
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(
+        (snapshot.open_connections, snapshot.closed_connections),
+        (0, 4)
+    );
+    assert_eq!(snapshot.totals.frames_out.headers, 4);
+    assert_eq!(snapshot.totals.qpack_in.blocks, 4);
+}
@@ -0,0 +1,70 @@
+use std::net::{IpAddr, SocketAddr};
+
+use istok_server::target::{PublicTargets, TargetPolicy, is_public, resolve_literal};
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().expect("valid IP literal")
+}
+
+#[test]
+fn internal_ranges_are_not_public() {
+    for internal in [
+        "0.0.0.0",
+        "0.1.2.3",
+        "10.0.0.1",
+        "100.64.0.1",
+        "127.0.0.1",
+        "169.254.169.254",
+        "172.16.0.1",
+        "192.0.2.1",
+        "192.168.1.1",
+        "224.0.0.1",
+        "240.0.0.1",
+        "255.255.255.255",
+        "::",
+        "::1",
+        "::ffff:127.0.0.1",
+        "::ffff:10.0.0.1",
+        "2001:db8::1",
+        "fc00::1",
+        "fd12:3456::1",
+        "fe80::1",
+        "ff02::1",
+    ] {
+        assert!(!is_public(ip(internal)), "{internal} is internal");
+    }
+}
+
+#[test]
+fn global_unicast_is_public() {
+    for public in [
+        "1.1.1.1",
+        "93.184.216.34",
+        "100.128.0.1",
+        "::ffff:8.8.8.8",
+        "2606:4700::1111",
+    ] {
+        assert!(is_public(ip(public)), "{public} is public");
+    }
+}
+
+#[test]
+fn only_ip_literals_resolve_by_default() {
+    let mut policy = PublicTargets;
+    assert_eq!(
+        policy
+            .resolve("[2001:db8::1]", 443)
+            .expect("bracketed IPv6"),
+        [SocketAddr::new(ip("2001:db8::1"), 443)]
+    );
+    assert_eq!(
+        policy.resolve("2001:db8::1", 443).expect("bare IPv6"),
+        [SocketAddr::new(ip("2001:db8::1"), 443)]
+    );
+    assert_eq!(
+        resolve_literal("192.0.2.1", 80).expect("IPv4"),
+        [SocketAddr::new(ip("192.0.2.1"), 80)]
+    );
+    assert!(policy.resolve("localhost", 443).is_err());
+    assert!(policy.resolve("[localhost]", 443).is_err());
+}
@@ -0,0 +1,177 @@
+//! Tunnel spans and refusal events under the `tracing` feature, collected
+//! by a minimal subscriber.
+#![cfg(feature = "tracing")]
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use istok_h3::AppEvent;
+use istok_server::connect::{TcpTunnels, TunnelAction};
+use istok_server::masque::UdpProxy;
+use istok_transport::StreamId;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+const TUNNEL: StreamId = StreamId(4);
+
+type Fields = BTreeMap<String, String>;
+
+#[derive(Debug, Clone)]
+struct Span {
+    name: &'static str,
+    fields: Fields,
+    parent: Option<u64>,
+}
+
+/// An event with the spans it was emitted in, innermost first.
+#[derive(Debug)]
+struct Recorded {
+    fields: Fields,
+    spans: Vec<Span>,
+}
+
+#[derive(Default)]
+struct State {
+    spans: BTreeMap<u64, Span>,
+    stack: Vec<u64>,
+    events: Vec<Recorded>,
+}
+
+#[derive(Clone, Default)]
+struct Collector {
+    next_id: Arc<AtomicU64>,
+    state: Arc<Mutex<State>>,
+}
+
+struct FieldVisitor<'f>(&'f mut Fields);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}"));
+    }
+}
+
+impl Subscriber for Collector {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut fields = Fields::new();
+        attrs.record(&mut FieldVisitor(&mut fields));
+        let mut state = self.state.lock().expect("not poisoned");
+        let parent = match attrs.parent() {
+            Some(parent) => Some(parent.into_u64()),
+            None if attrs.is_contextual() => state.stack.last().copied(),
+            None => None,
+        };
+        state.spans.insert(
+            id,
+            Span {
+                name: attrs.metadata().name(),
+                fields,
+                parent,
+            },
+        );
+        Id::from_u64(id)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::new();
+        event.record(&mut FieldVisitor(&mut fields));
+        let mut state = self.state.lock().expect("not poisoned");
+        let mut spans = Vec::new();
+        let mut next = state.stack.last().copied();
+        while let Some(id) = next {
+            let span = state.spans[&id].clone();
+            next = span.parent;
+            spans.push(span);
+        }
+        state.events.push(Recorded { fields, spans });
+    }
+
+    fn enter(&self, span: &Id) {
+        let mut state = self.state.lock().expect("not poisoned");
+        state.stack.push(span.into_u64());
+    }
+
+    fn exit(&self, _span: &Id) {
+        let mut state = self.state.lock().expect("not poisoned");
+        state.stack.pop();
+    }
+}
+
+/// Run `f` inside a `connection` span and return what was traced.
+fn traced(f: impl FnOnce()) -> Vec<Recorded> {
+    let collector = Collector::default();
+    tracing::subscriber::with_default(collector.clone(), || {
+        tracing::debug_span!("connection").in_scope(f);
+    });
+    let mut state = collector.state.lock().expect("not poisoned");
+    std::mem::take(&mut state.events)
+}
+
+fn span_names(event: &Recorded) -> Vec<&'static str> {
+    event.spans.iter().map(|span| span.name).collect()
+}
+
+#[test]
+fn refused_connect_is_traced_in_the_tunnel_span() {
+    let events = traced(|| {
+        TcpTunnels::new().on_app_event(&AppEvent::ConnectTunnel {
+            id: TUNNEL,
+            authority: b"127.0.0.1:22".to_vec(),
+        });
+    });
+
+    let [event] = events.as_slice() else {
+        panic!("expected one event, got {events:?}");
+    };
+    assert_eq!(event.fields["message"], "rejecting tunnel");
+    assert_eq!(event.fields["reason"], "target not allowed");
+    assert_eq!(event.fields["status"], "403");
+    assert_eq!(event.fields["stream"], "4");
+
+    assert_eq!(span_names(event), ["connect_tunnel", "connection"]);
+    assert_eq!(event.spans[0].fields["stream"], "4");
+    assert_eq!(event.spans[0].fields["target"], "127.0.0.1:22");
+}
+
+#[test]
+fn refused_connect_udp_is_traced_in_the_tunnel_span() {
+    let events = traced(|| {
+        let action = UdpProxy::new().on_app_event(&AppEvent::ConnectUdp {
+            id: TUNNEL,
+            host: b"10.0.0.1".to_vec(),
+            port: 53,
+        });
+        assert_eq!(
+            action,
+            Some(TunnelAction::Reject {
+                id: TUNNEL,
+                status: 403
+            })
+        );
+    });
+
+    let [event] = events.as_slice() else {
+        panic!("expected one event, got {events:?}");
+    };
+    assert_eq!(event.fields["reason"], "target not allowed");
+    assert_eq!(span_names(event), ["connect_udp_tunnel", "connection"]);
+    assert_eq!(event.spans[0].fields["host"], "10.0.0.1");
+    assert_eq!(event.spans[0].fields["port"], "53");
+}
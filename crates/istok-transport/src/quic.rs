@@ -4,6 +4,9 @@ extern crate alloc;
 #[cfg(feature = "alloc")]
 use alloc::vec::Vec;
 
+#[cfg(feature = "alloc")]
+use istok_core::bytes::Bytes;
+
 use core::fmt;
 
 /// QUIC stream id is a QUIC varint in the wire, but we keep it as u64.
@@ -17,6 +20,60 @@ pub enum StreamKind {
     Bidi,
 }
 
+/// Room for two QUIC varints, e.g. an HTTP/3 frame type and length.
+pub const INLINE_HEADER_LEN: usize = 16;
+
+/// A few bytes written ahead of a shared body, kept inline so that framing
+/// a payload does not copy it.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct InlineHeader {
+    bytes: [u8; INLINE_HEADER_LEN],
+    len: u8,
+}
+
+impl InlineHeader {
+    /// `None` if `bytes` is longer than `INLINE_HEADER_LEN`.
+    pub fn new(bytes: &[u8]) -> Option<Self> {
+        let mut header = Self::default();
+        header.bytes.get_mut(..bytes.len())?.copy_from_slice(bytes);
+        header.len = bytes.len() as u8;
+        Some(header)
+    }
+
+    /// The header bytes.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.bytes[..usize::from(self.len)]
+    }
+
+    /// Number of header bytes, at most `INLINE_HEADER_LEN`.
+    pub fn len(&self) -> usize {
+        usize::from(self.len)
+    }
+
+    /// Whether there are no header bytes at all.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Split off and return the first `at` bytes (at most all of them);
+    /// `self` keeps the rest.
+    pub fn split_to(&mut self, at: usize) -> Self {
+        let at = at.min(self.len());
+        let mut rest = Self::default();
+        rest.bytes[..self.len() - at].copy_from_slice(&self.bytes[at..self.len()]);
+        rest.len = (self.len() - at) as u8;
+        let mut front = core::mem::replace(self, rest);
+        front.len = at as u8;
+        front
+    }
+}
+
+impl fmt::Debug for InlineHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_slice(), f)
+    }
+}
+
 /// Why a stream was reset/closed (subset; expand later).
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum StreamError {
@@ -40,6 +97,14 @@ pub enum QuicEvent<'a> {
     /// Peer reset/stop-sending.
     StreamError { id: StreamId, err: StreamError },
 
+    /// Stream `id` can take `capacity` more bytes, counted from the end of
+    /// every write issued so far. Each report replaces the previous one;
+    /// until the first, the stream is treated as unbounded.
+    StreamWritable { id: StreamId, capacity: u64 },
+
+    /// QUIC DATAGRAM frame payload received (RFC 9221; unordered, unreliable).
+    Datagram { data: &'a [u8] },
+
     /// Connection-level close.
     ConnectionClosed { app_error: Option<u64> },
 }
@@ -50,6 +115,9 @@ pub enum QuicCommand<'a> {
     /// Open a uni stream initiated by us (needed for H3 control/QPACK streams).
     OpenUni { id_hint: Option<StreamId> },
 
+    /// Open a bidi stream initiated by us (e.g. WebTransport bidi streams).
+    OpenBidi { id_hint: Option<StreamId> },
+
     /// Write borrowed bytes to stream (may be partial at runtime; mock can enforce full).
     StreamWrite {
         id: StreamId,
@@ -65,7 +133,8 @@ pub enum QuicCommand<'a> {
         fin: bool,
     },
 
-    /// Write bytes to stream (owned; mock can enforce full).
+    /// Write bytes to stream (owned; mock can enforce full). Writes within
+    /// the capacity last reported by `StreamWritable` are accepted in full.
     #[cfg(feature = "alloc")]
     StreamWriteOwned {
         id: StreamId,
@@ -73,6 +142,22 @@ pub enum QuicCommand<'a> {
         fin: bool,
     },
 
+    /// Write `header` followed by `body` to stream, as one write. The body
+    /// is shared with the producer rather than copied; the runtime may
+    /// gather both into a single send.
+    #[cfg(feature = "alloc")]
+    StreamWriteVectored {
+        id: StreamId,
+        header: InlineHeader,
+        body: Bytes,
+        fin: bool,
+    },
+
+    /// Send one QUIC DATAGRAM frame (RFC 9221). The runtime may drop it, e.g.
+    /// when it exceeds the peer's `max_datagram_frame_size`.
+    #[cfg(feature = "alloc")]
+    SendDatagram { data: Vec<u8> },
+
     /// Reset stream (sender side).
     ResetStream { id: StreamId, app_error: u64 },
 